@@ -2,7 +2,7 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use anyhow::{anyhow, Result};
-use aptos_backup_service::start_backup_service;
+use aptos_backup_service::start_backup_service_with_max_bytes_per_sec;
 use aptos_config::{config::NodeConfig, utils::get_genesis_txn};
 use aptos_db::{fast_sync_storage_wrapper::FastSyncStorageWrapper, AptosDB};
 use aptos_db_indexer::db_indexer::InternalIndexerDB;
@@ -67,8 +67,11 @@ pub(crate) fn bootstrap_db(
     )? {
         Either::Left(db) => {
             let (db_arc, db_rw) = DbReaderWriter::wrap(db);
-            let db_backup_service =
-                start_backup_service(node_config.storage.backup_service_address, db_arc.clone());
+            let db_backup_service = start_backup_service_with_max_bytes_per_sec(
+                node_config.storage.backup_service_address,
+                db_arc.clone(),
+                node_config.storage.backup_service_max_bytes_per_sec,
+            );
             maybe_apply_genesis(&db_rw, node_config)?;
             (db_arc as Arc<dyn DbReader>, db_rw, Some(db_backup_service))
         },
@@ -92,8 +95,11 @@ pub(crate) fn bootstrap_db(
                 // commit the genesis ledger info to the DB.
                 fast_sync_db.commit_genesis_ledger_info(&ledger_info)?;
             }
-            let db_backup_service =
-                start_backup_service(node_config.storage.backup_service_address, fast_sync_db);
+            let db_backup_service = start_backup_service_with_max_bytes_per_sec(
+                node_config.storage.backup_service_address,
+                fast_sync_db,
+                node_config.storage.backup_service_max_bytes_per_sec,
+            );
             (db_arc as Arc<dyn DbReader>, db_rw, Some(db_backup_service))
         },
     };
@@ -139,6 +145,7 @@ fn create_rocksdb_checkpoint_and_change_working_dir(
 ) {
     // Update the source and checkpoint directories
     let source_dir = node_config.storage.dir();
+    let source_dir_paths = node_config.storage.get_dir_paths();
     node_config.set_data_dir(working_dir.as_ref().to_path_buf());
     let checkpoint_dir = node_config.storage.dir();
     assert!(source_dir != checkpoint_dir);
@@ -147,8 +154,9 @@ fn create_rocksdb_checkpoint_and_change_working_dir(
     fs::create_dir_all(&checkpoint_dir).unwrap();
 
     // Open the database and create a checkpoint
-    AptosDB::create_checkpoint(
+    AptosDB::create_checkpoint_with_wal_path(
         &source_dir,
+        source_dir_paths.wal_root_path(),
         &checkpoint_dir,
         node_config.storage.rocksdb_configs.enable_storage_sharding,
     )
@@ -17,8 +17,10 @@ use serde_yaml::Value;
 use std::{
     collections::HashMap,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    num::NonZeroU64,
     path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 
 // Lru cache will consume about 2G RAM based on this default value.
@@ -112,6 +114,23 @@ pub enum RocksDBStatsLevel {
     All,
 }
 
+/// How RocksDB replays the write ahead log when a DB is opened, see `WALRecoveryMode` in
+/// <https://github.com/facebook/rocksdb/blob/main/include/rocksdb/options.h>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalRecoveryMode {
+    /// Ignores incomplete records at the tail of the WAL, as left by a crash in the middle of a
+    /// write, and fails on any other corruption.
+    TolerateCorruptedTailRecords,
+    /// Fails on any corruption of the WAL, including at the tail.
+    AbsoluteConsistency,
+    /// Replays the WAL up to the first corrupted record and drops the rest, e.g. after an abrupt
+    /// shutdown left a truncated or garbled WAL behind. The DB is consistent, as of that point.
+    /// RocksDB's default, i.e. what `None` means.
+    PointInTimeRecovery,
+    /// Skips all the corrupted records and replays the rest, which can leave the DB inconsistent.
+    SkipAnyCorruptedRecords,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum IndexType {
     BinarySearch,
@@ -119,6 +138,62 @@ pub enum IndexType {
     TwoLevelIndexSearch,
 }
 
+/// Filter policy of the merkle node column family, see `RocksdbConfig::node_filter_policy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterPolicy {
+    /// Bloom filters, or hybrid ribbon ones if `bloom_before_level` is set, like all the other
+    /// column families.
+    Bloom,
+    /// Ribbon filters on all levels.
+    Ribbon,
+}
+
+/// Zstd compression with a trained dictionary, for column families with large values.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZstdCompressionConfig {
+    /// Zstd compression level.
+    pub level: i32,
+    /// Max size of the dictionary built for each SST file. 0 disables the dictionary.
+    pub max_dict_bytes: i32,
+    /// Max size of the samples the dictionary is trained on (`zstd_max_train_bytes`). 0 means
+    /// using the raw samples as the dictionary without training.
+    pub max_train_bytes: i32,
+}
+
+impl Default for ZstdCompressionConfig {
+    fn default() -> Self {
+        Self {
+            level: 3,
+            max_dict_bytes: 16 * (1 << 10),
+            // Zstd recommends training on about 100 times the dictionary size.
+            max_train_bytes: 100 * 16 * (1 << 10),
+        }
+    }
+}
+
+/// The compression a column family ends up with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColumnFamilyCompression {
+    Lz4,
+    Zstd(ZstdCompressionConfig),
+}
+
+/// The filter a column family ends up with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnFamilyFilter {
+    Bloom {
+        bits_per_key: f64,
+    },
+    HybridRibbon {
+        bits_per_key: f64,
+        bloom_before_level: i32,
+    },
+    Ribbon {
+        bits_per_key: f64,
+    },
+}
+
 /// Port selected RocksDB options for tuning underlying rocksdb instance of AptosDB.
 /// see <https://github.com/facebook/rocksdb/blob/master/include/rocksdb/options.h>
 /// for detailed explanations.
@@ -127,6 +202,10 @@ pub enum IndexType {
 pub struct RocksdbConfig {
     /// Maximum number of files open by RocksDB at one time
     pub max_open_files: i32,
+    /// If set, caps the files open by all the RocksDB instances of the sub-DB together, e.g. the
+    /// metadata DB and the shards of the sharded state DBs, in place of `max_open_files`, which is
+    /// per instance. It's split evenly among the instances when the DBs are opened.
+    pub max_open_files_per_sub_db: Option<i32>,
     /// Maximum size of the RocksDB write ahead log (WAL)
     pub max_total_wal_size: u64,
     /// Maximum number of background jobs for Rocks DB
@@ -136,30 +215,106 @@ pub struct RocksdbConfig {
     pub block_cache_size: u64,
     /// Block size for Rocks DB
     pub block_size: u64,
-    /// Index type used for tables.
+    /// Index type used for tables. `TwoLevelIndexSearch` partitions the index of each SST file,
+    /// so only a small top level index and the partitions being used need to be in memory,
+    /// instead of the whole index of every open file.
     pub index_type: IndexType,
-    /// Use partitioned filters.
+    /// Use partitioned filters, the filter counterpart of the `TwoLevelIndexSearch` index type,
+    /// which it requires.
     pub partition_filters: bool,
-    /// Whether to cache index and filter blocks into block cache.
+    /// Whether to cache index and filter blocks into block cache. If so, they compete with the
+    /// data blocks for the cache and are bounded by its size, otherwise they are held by each
+    /// open SST file outside of the cache, and the memory grows with `max_open_files`.
     pub cache_index_and_filter_blocks: bool,
     /// Whether to pin L0 filters and indexes in memory. Only makes sense if
     /// `cache_index_and_filter_blocks` is `true`.
     pub pin_l0_filter_and_index_blocks_in_cache: bool,
+    /// Whether to pin the top level index and filter blocks of partitioned indexes and filters in
+    /// the block cache, for all levels. Only makes sense if `cache_index_and_filter_blocks` is
+    /// `true`. It saves a cache lookup (and a potential miss) on each read at the cost of keeping
+    /// a small block per open SST file in the cache, so memory grows with `max_open_files`.
+    pub pin_top_level_index_and_filter: bool,
     /// The level of details for statistics. Higher level might cause more overhead. `None` means
     /// disabling everything.
     pub stats_level: Option<RocksDBStatsLevel>,
     /// If not zero, dump stats to LOG every this many seconds. `None` means using RocksDB's
     /// default.
     pub stats_dump_period_sec: Option<u32>,
+    /// How the DB replays its write ahead log on open, e.g. `AbsoluteConsistency` to refuse to
+    /// open after the WAL got corrupted. `None` means using RocksDB's default,
+    /// `PointInTimeRecovery`.
+    pub wal_recovery_mode: Option<WalRecoveryMode>,
     /// Enable bloom filters with given space per key.
     pub bloom_filter_bits: Option<f64>,
     /// If not `None`, use hybrid ribbon filter policy.
     pub bloom_before_level: Option<i32>,
+    /// Filter policy of the merkle node column family, which only has a filter if
+    /// `bloom_filter_bits` is set. With `Ribbon`, `bloom_filter_bits` is taken as the bits per key
+    /// of the bloom filter with the same false positive rate, and `bloom_before_level` is ignored.
+    /// Per RocksDB's documentation, ribbon filters take about 30% less memory than bloom filters
+    /// for the same false positive rate, but 3-4x the CPU to build, i.e. in flushes and
+    /// compactions, while queries cost about the same. Nodes being most of the keys of the state
+    /// merkle DB, it trades compaction CPU for the memory of most of its filters. These figures
+    /// are RocksDB's, the sizes and lookup costs of the filters of the node column family haven't
+    /// been measured, so `Bloom` stays the default.
+    pub node_filter_policy: FilterPolicy,
+    /// If set, overrides whether the filters of the state value column families also cover the
+    /// whole keys, on top of the state key prefixes. These are the only column families with a
+    /// prefix extractor, all the others always filter on whole keys. By default
+    /// `state_value_by_key_hash` and `hot_state_value_by_key_hash` only filter on prefixes:
+    /// reads seek to the latest version of a key at or before the one asked for, which only the
+    /// prefixes help with, so whole keys would just double the size of the filters. The legacy
+    /// `state_value` column family of the unsharded DB keeps both. Whole key filters only pay off
+    /// for point lookups of exact key and version pairs.
+    pub state_value_whole_key_filtering: Option<bool>,
+    /// Target size of the SST files in level 1, files in the levels below are larger by
+    /// `target_file_size_multiplier` per level.
+    pub target_file_size_base: u64,
+    /// Max total size of level 1, the levels below are larger by
+    /// `max_bytes_for_level_multiplier` per level.
+    pub max_bytes_for_level_base: u64,
+    /// Size of a single memtable. Larger memtables suit large sequential writes, at the cost of
+    /// memory and of longer recovery from the WAL.
+    pub write_buffer_size: usize,
+    /// Max number of memtables, including the one being written to, before writes stall waiting
+    /// for flushes.
+    pub max_write_buffer_number: i32,
+    /// Min number of immutable memtables to merge together when flushing them.
+    pub min_write_buffer_number_to_merge: i32,
+    /// If set, the state value column families are compressed with zstd and a trained
+    /// dictionary instead of LZ4. All the other column families (e.g. the merkle nodes) stay on
+    /// LZ4. RocksDB compresses each block separately, so zstd's long distance matching window
+    /// can't apply, the dictionary is what captures the redundancy across values; raising
+    /// `block_size` helps further with large values.
+    pub state_value_zstd: Option<ZstdCompressionConfig>,
+    /// If set, the memtables are flushed at the end of every commit, so that the WAL has nothing
+    /// to replay for the commits done when reopening after a crash, at the cost of commit
+    /// throughput and of many small L0 files for compactions to merge. Only honored by the state
+    /// merkle DB, and by the state KV DB when sharded, otherwise it lives in the ledger DB.
+    pub flush_on_commit: bool,
 }
 
 impl RocksdbConfig {
     /// Default block size is 4KB,
     const DEFAULT_BLOCK_SIZE: u64 = 4 * (1 << 10);
+    /// Default max bytes for level base is 256MB, same as RocksDB's default.
+    pub const DEFAULT_MAX_BYTES_FOR_LEVEL_BASE: u64 = 256 * (1 << 20);
+    /// Default max write buffer number is 2, same as RocksDB's default.
+    pub const DEFAULT_MAX_WRITE_BUFFER_NUMBER: i32 = 2;
+    /// Default min write buffer number to merge is 1, same as RocksDB's default.
+    pub const DEFAULT_MIN_WRITE_BUFFER_NUMBER_TO_MERGE: i32 = 1;
+    /// Default target file size base is 64MB, same as RocksDB's default.
+    pub const DEFAULT_TARGET_FILE_SIZE_BASE: u64 = 64 * (1 << 20);
+    /// Default write buffer size is 64MB, same as RocksDB's default.
+    pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 64 * (1 << 20);
+
+    /// Returns the effective compression of the state value column families.
+    pub fn state_value_compression(&self) -> ColumnFamilyCompression {
+        match self.state_value_zstd {
+            Some(zstd_config) => ColumnFamilyCompression::Zstd(zstd_config),
+            None => ColumnFamilyCompression::Lz4,
+        }
+    }
 }
 
 impl Default for RocksdbConfig {
@@ -167,6 +322,7 @@ impl Default for RocksdbConfig {
         Self {
             // Allow db to close old sst files, saving memory.
             max_open_files: 5000,
+            max_open_files_per_sub_db: None,
             // For now we set the max total WAL size to be 1G. This config can be useful when column
             // families are updated at non-uniform frequencies.
             max_total_wal_size: 1u64 << 30,
@@ -181,12 +337,24 @@ impl Default for RocksdbConfig {
             cache_index_and_filter_blocks: true,
             // L0 index/filter blocks are usually small and used frequently.
             pin_l0_filter_and_index_blocks_in_cache: true,
+            // Same as RocksDB's default.
+            pin_top_level_index_and_filter: true,
             // Enable but use a less detailed option by default since there might be some overhead.
             stats_level: Some(RocksDBStatsLevel::ExceptHistogramOrTimers),
             // Use RocksDB's default if not specified.
             stats_dump_period_sec: None,
+            wal_recovery_mode: None,
             bloom_filter_bits: None,
             bloom_before_level: None,
+            node_filter_policy: FilterPolicy::Bloom,
+            state_value_whole_key_filtering: None,
+            target_file_size_base: Self::DEFAULT_TARGET_FILE_SIZE_BASE,
+            max_bytes_for_level_base: Self::DEFAULT_MAX_BYTES_FOR_LEVEL_BASE,
+            write_buffer_size: Self::DEFAULT_WRITE_BUFFER_SIZE,
+            max_write_buffer_number: Self::DEFAULT_MAX_WRITE_BUFFER_NUMBER,
+            min_write_buffer_number_to_merge: Self::DEFAULT_MIN_WRITE_BUFFER_NUMBER_TO_MERGE,
+            state_value_zstd: None,
+            flush_on_commit: false,
         }
     }
 }
@@ -205,6 +373,34 @@ pub struct RocksdbConfigs {
     pub low_priority_background_threads: i32,
     /// The size of the single block cache shared by all the DB instances in `AptosDB`.
     pub shared_block_cache_size: usize,
+    /// Number of threads merklizing the buffered state when it's flushed to the state merkle DB.
+    /// If set, the flush gets its own thread pool instead of sharing the non-execution CPU pool
+    /// with the commit path, so that large flushes don't stall commits.
+    pub state_snapshot_commit_concurrency: Option<usize>,
+    /// If set, the LRU node cache of the state merkle DB is bounded by the total encoded bytes of
+    /// the nodes rather than by `max_num_nodes_per_lru_cache_shard`, which makes its memory usage
+    /// predictable. Setting `max_num_nodes_per_lru_cache_shard` to 0 still disables the cache.
+    pub lru_node_cache_max_bytes: Option<usize>,
+    /// If set, the Jellyfish Merkle nodes written are not put into the LRU node cache, which is
+    /// then only filled by reads, e.g. for a bulk restore, whose nodes won't be read again soon.
+    /// The nodes of the versions being committed are still kept in memory until persisted. Can
+    /// be toggled at runtime with `AptosDB::set_cache_jmt_nodes_on_read_only`.
+    pub cache_jmt_nodes_on_read_only: bool,
+    /// Max number of versions, i.e. state snapshots, whose Jellyfish Merkle nodes are kept in
+    /// memory once written, for the snapshots still being committed. Beyond it the oldest
+    /// versions are evicted into the LRU node cache. At least 1, `None` means 2. Lowering it
+    /// bounds the memory of large snapshots, e.g. during catch-up, independently of the LRU
+    /// node cache.
+    pub versioned_node_cache_max_versions: Option<usize>,
+    /// If set, the memtables of all the DB instances in `AptosDB` share a RocksDB write buffer
+    /// manager with this budget in bytes, which bounds their total memory: the memtables are
+    /// flushed early as the budget is approached, and writes stall if flushes can't keep up.
+    pub write_buffer_manager_size: Option<usize>,
+    /// If set, the checksums of the data blocks are verified on every read, rather than only when
+    /// a block is read from disk into the block cache, for machines suspected of flaky memory. The
+    /// reads bypass the block cache for it, so they are slower. Compactions always verify the
+    /// checksums of the blocks they read.
+    pub verify_checksums_on_every_read: bool,
 }
 
 impl RocksdbConfigs {
@@ -234,6 +430,12 @@ impl Default for RocksdbConfigs {
             high_priority_background_threads: 4,
             low_priority_background_threads: 2,
             shared_block_cache_size: Self::DEFAULT_BLOCK_CACHE_SIZE,
+            state_snapshot_commit_concurrency: None,
+            lru_node_cache_max_bytes: None,
+            cache_jmt_nodes_on_read_only: false,
+            versioned_node_cache_max_versions: None,
+            write_buffer_manager_size: None,
+            verify_checksums_on_every_read: false,
         }
     }
 }
@@ -268,6 +470,9 @@ impl Default for HotStateConfig {
 #[serde(default, deny_unknown_fields)]
 pub struct StorageConfig {
     pub backup_service_address: SocketAddr,
+    /// If set, caps the bytes per second the backup service reads and sends, so that running a
+    /// backup doesn't steal IO from the node.
+    pub backup_service_max_bytes_per_sec: Option<NonZeroU64>,
     /// Top level directory to store the RocksDB
     pub dir: PathBuf,
     /// Hot state configuration
@@ -297,10 +502,20 @@ pub struct StorageConfig {
     /// If not specificed, will use `dir` as default.
     /// Only allowed when sharding is enabled.
     db_path_overrides: Option<DbPathConfig>,
+    /// Root of the WALs of all the DBs, each DB puts its WAL in a sub directory named after it.
+    /// Useful to put the WALs on a faster device than the one holding the SST files. Unlike
+    /// `db_path_overrides`, allowed whether sharding is enabled or not.
+    pub wal_path: Option<PathBuf>,
     /// ensure `ulimit -n`, set to 0 to not ensure.
     pub ensure_rlimit_nofile: u64,
     /// panic if failed to ensure `ulimit -n`
     pub assert_rlimit_nofile: bool,
+    /// Recompute the transaction accumulator root in memory after each commit and check it against
+    /// the one persisted, failing the commit on divergence. Costs extra CPU on the commit path.
+    pub verify_ledger_root_on_commit: bool,
+    /// If set, the ledger pruner copies the data it's about to delete into an archive DB in this
+    /// directory, where it remains queryable, instead of only deleting it.
+    pub ledger_archive_dir: Option<PathBuf>,
 }
 
 pub const NO_OP_STORAGE_PRUNER_CONFIG: PrunerConfig = PrunerConfig {
@@ -320,6 +535,8 @@ pub const NO_OP_STORAGE_PRUNER_CONFIG: PrunerConfig = PrunerConfig {
         prune_window: 0,
         batch_size: 0,
     },
+    enable_deletion_log: false,
+    deletion_log_retention_secs: 0,
 };
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -376,12 +593,39 @@ impl From<EpochSnapshotPrunerConfig> for StateMerklePrunerConfig {
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, Default)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct PrunerConfig {
     pub ledger_pruner_config: LedgerPrunerConfig,
     pub state_merkle_pruner_config: StateMerklePrunerConfig,
     pub epoch_snapshot_pruner_config: EpochSnapshotPrunerConfig,
+    /// Record what the pruners delete (version range, number of deletes per column family) in the
+    /// DBs they delete from, for auditing. Off by default.
+    pub enable_deletion_log: bool,
+    /// How long the deletion log records are kept. Older records are deleted by the pruners as
+    /// they write new ones.
+    pub deletion_log_retention_secs: u64,
+}
+
+impl PrunerConfig {
+    /// How long to keep the deletion log records, or `None` if the deletion log is disabled.
+    pub fn deletion_log_retention(&self) -> Option<Duration> {
+        self.enable_deletion_log
+            .then(|| Duration::from_secs(self.deletion_log_retention_secs))
+    }
+}
+
+impl Default for PrunerConfig {
+    fn default() -> Self {
+        PrunerConfig {
+            ledger_pruner_config: LedgerPrunerConfig::default(),
+            state_merkle_pruner_config: StateMerklePrunerConfig::default(),
+            epoch_snapshot_pruner_config: EpochSnapshotPrunerConfig::default(),
+            enable_deletion_log: false,
+            // A week.
+            deletion_log_retention_secs: 7 * 24 * 3600,
+        }
+    }
 }
 
 impl Default for LedgerPrunerConfig {
@@ -434,6 +678,7 @@ impl Default for StorageConfig {
     fn default() -> StorageConfig {
         StorageConfig {
             backup_service_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 6186),
+            backup_service_max_bytes_per_sec: None,
             dir: PathBuf::from("db"),
             hot_state_config: HotStateConfig::default(),
             // The prune window must at least out live a RPC request because its sub requests are
@@ -447,10 +692,13 @@ impl Default for StorageConfig {
             rocksdb_configs: RocksdbConfigs::default(),
             enable_indexer: false,
             db_path_overrides: None,
+            wal_path: None,
             buffered_state_target_items: BUFFERED_STATE_TARGET_ITEMS,
             max_num_nodes_per_lru_cache_shard: DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
             ensure_rlimit_nofile: 0,
             assert_rlimit_nofile: false,
+            verify_ledger_root_on_commit: false,
+            ledger_archive_dir: None,
         }
     }
 }
@@ -503,6 +751,7 @@ impl StorageConfig {
             state_merkle_db_paths,
             hot_state_kv_db_paths,
             hot_state_merkle_db_paths,
+            self.wal_path.clone(),
         )
     }
 
@@ -524,6 +773,7 @@ pub struct StorageDirPaths {
     state_merkle_db_paths: ShardedDbPaths,
     hot_state_kv_db_paths: ShardedDbPaths,
     hot_state_merkle_db_paths: ShardedDbPaths,
+    wal_path: Option<PathBuf>,
 }
 
 impl StorageDirPaths {
@@ -581,6 +831,35 @@ impl StorageDirPaths {
             .unwrap_or(&self.default_path)
     }
 
+    /// Root of the WAL directories, `None` means each DB keeps its WAL along with its data.
+    pub fn wal_root_path(&self) -> Option<&Path> {
+        self.wal_path.as_deref()
+    }
+
+    pub fn with_wal_path<P: AsRef<Path>>(mut self, wal_path: P) -> Self {
+        self.wal_path = Some(wal_path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Puts the ledger DB under `path` instead of the default path.
+    pub fn with_ledger_db_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.ledger_db_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Puts the state KV metadata DB and all its shards under `path` instead of the default path.
+    pub fn with_state_kv_db_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.state_kv_db_paths = ShardedDbPaths::all_at(path.as_ref());
+        self
+    }
+
+    /// Puts the state merkle metadata DB and all its shards under `path` instead of the default
+    /// path.
+    pub fn with_state_merkle_db_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.state_merkle_db_paths = ShardedDbPaths::all_at(path.as_ref());
+        self
+    }
+
     pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
         Self {
             default_path: path.as_ref().to_path_buf(),
@@ -589,6 +868,7 @@ impl StorageDirPaths {
             state_merkle_db_paths: Default::default(),
             hot_state_kv_db_paths: Default::default(),
             hot_state_merkle_db_paths: Default::default(),
+            wal_path: None,
         }
     }
 
@@ -599,6 +879,7 @@ impl StorageDirPaths {
         state_merkle_db_paths: ShardedDbPaths,
         hot_state_kv_db_paths: ShardedDbPaths,
         hot_state_merkle_db_paths: ShardedDbPaths,
+        wal_path: Option<PathBuf>,
     ) -> Self {
         Self {
             default_path,
@@ -607,6 +888,7 @@ impl StorageDirPaths {
             state_merkle_db_paths,
             hot_state_kv_db_paths,
             hot_state_merkle_db_paths,
+            wal_path,
         }
     }
 }
@@ -630,6 +912,13 @@ impl ShardedDbPaths {
         }
     }
 
+    fn all_at(path: &Path) -> Self {
+        Self {
+            metadata_path: Some(path.to_path_buf()),
+            shard_paths: arr![Some(path.to_path_buf()); 16],
+        }
+    }
+
     fn metadata_path(&self) -> Option<&PathBuf> {
         self.metadata_path.as_ref()
     }
@@ -727,6 +1016,58 @@ impl ConfigSanitizer for StorageConfig {
             ));
         }
 
+        if config.rocksdb_configs.state_snapshot_commit_concurrency == Some(0) {
+            return Err(Error::ConfigSanitizerFailed(
+                sanitizer_name,
+                "state_snapshot_commit_concurrency must be positive if set.".to_string(),
+            ));
+        }
+
+        if config.rocksdb_configs.lru_node_cache_max_bytes == Some(0) {
+            return Err(Error::ConfigSanitizerFailed(
+                sanitizer_name,
+                "lru_node_cache_max_bytes must be positive if set.".to_string(),
+            ));
+        }
+
+        if config.rocksdb_configs.write_buffer_manager_size == Some(0) {
+            return Err(Error::ConfigSanitizerFailed(
+                sanitizer_name,
+                "write_buffer_manager_size must be positive if set.".to_string(),
+            ));
+        }
+
+        for (db_name, rocksdb_config) in [
+            ("ledger_db", &config.rocksdb_configs.ledger_db_config),
+            (
+                "state_merkle_db",
+                &config.rocksdb_configs.state_merkle_db_config,
+            ),
+            ("state_kv_db", &config.rocksdb_configs.state_kv_db_config),
+            ("index_db", &config.rocksdb_configs.index_db_config),
+        ] {
+            if rocksdb_config.min_write_buffer_number_to_merge < 1
+                || rocksdb_config.min_write_buffer_number_to_merge
+                    >= rocksdb_config.max_write_buffer_number
+            {
+                return Err(Error::ConfigSanitizerFailed(
+                    sanitizer_name,
+                    format!(
+                        "{db_name}: min_write_buffer_number_to_merge must be positive and less than max_write_buffer_number, otherwise writes stall until all the memtables are full."
+                    ),
+                ));
+            }
+        }
+
+        if let Some(wal_path) = config.wal_path.as_ref() {
+            if !wal_path.is_absolute() {
+                return Err(Error::ConfigSanitizerFailed(
+                    sanitizer_name,
+                    format!("wal_path {wal_path:?} is not an absolute path."),
+                ));
+            }
+        }
+
         if let Some(db_path_overrides) = config.db_path_overrides.as_ref() {
             if !config.rocksdb_configs.enable_storage_sharding {
                 return Err(Error::ConfigSanitizerFailed(
@@ -800,9 +1141,13 @@ impl ConfigSanitizer for StorageConfig {
 
 #[cfg(test)]
 mod test {
-    use super::{ShardPathConfig, ShardedDbPathConfig, StorageConfig};
-    use crate::config::{config_optimizer::ConfigOptimizer, NodeConfig, NodeType, PrunerConfig};
+    use super::{ShardPathConfig, ShardedDbPathConfig, StorageConfig, StorageDirPaths};
+    use crate::config::{
+        config_optimizer::ConfigOptimizer, config_sanitizer::ConfigSanitizer, NodeConfig, NodeType,
+        PrunerConfig,
+    };
     use aptos_types::chain_id::ChainId;
+    use std::path::PathBuf;
 
     #[test]
     pub fn test_default_prune_window() {
@@ -840,6 +1185,39 @@ mod test {
         assert_eq!(shard_path_map.get(&11), Some(&"/disk2/db".into()));
     }
 
+    #[test]
+    pub fn test_storage_dir_paths_with_db_paths() {
+        let db_paths = StorageDirPaths::from_path("/disk0/db")
+            .with_ledger_db_path("/disk1/db")
+            .with_state_kv_db_path("/disk2/db")
+            .with_state_merkle_db_path("/disk3/db");
+
+        assert_eq!(db_paths.default_root_path(), &PathBuf::from("/disk0/db"));
+        assert_eq!(db_paths.ledger_db_root_path(), &PathBuf::from("/disk1/db"));
+        assert_eq!(
+            db_paths.state_kv_db_metadata_root_path(),
+            &PathBuf::from("/disk2/db")
+        );
+        assert_eq!(
+            db_paths.state_merkle_db_metadata_root_path(),
+            &PathBuf::from("/disk3/db")
+        );
+        for shard_id in 0..16 {
+            assert_eq!(
+                db_paths.state_kv_db_shard_root_path(shard_id),
+                &PathBuf::from("/disk2/db")
+            );
+            assert_eq!(
+                db_paths.state_merkle_db_shard_root_path(shard_id),
+                &PathBuf::from("/disk3/db")
+            );
+            assert_eq!(
+                db_paths.hot_state_kv_db_shard_root_path(shard_id),
+                &PathBuf::from("/disk0/db")
+            );
+        }
+    }
+
     #[test]
     pub fn test_invalid_sharded_db_path_config() {
         let path_overrides = ShardedDbPathConfig {
@@ -879,6 +1257,21 @@ mod test {
         assert!(path_overrides.get_shard_paths().is_err());
     }
 
+    #[test]
+    fn test_sanitize_wal_path() {
+        let mut node_config = NodeConfig::default();
+        node_config.storage.rocksdb_configs.enable_storage_sharding = false;
+        node_config.storage.wal_path = Some("/disk1/wal".into());
+        StorageConfig::sanitize(&node_config, NodeType::Validator, None).unwrap();
+        assert_eq!(
+            node_config.storage.get_dir_paths().wal_root_path(),
+            Some(PathBuf::from("/disk1/wal").as_path())
+        );
+
+        node_config.storage.wal_path = Some("wal".into());
+        StorageConfig::sanitize(&node_config, NodeType::Validator, None).unwrap_err();
+    }
+
     #[test]
     fn test_optimize_ensure_rlimit_nofile() {
         let mut node_config = NodeConfig::default();
@@ -18,6 +18,13 @@ pub enum Cmd {
 }
 
 impl Cmd {
+    pub fn writes_to_stdout(&self) -> bool {
+        match self {
+            Cmd::AptosDb(cmd) => cmd.writes_to_stdout(),
+            Cmd::Decode(_) | Cmd::DumpPendingTxns(_) | Cmd::Move(_) => false,
+        }
+    }
+
     pub async fn run(self) -> Result<()> {
         match self {
             Cmd::AptosDb(cmd) => cmd.run().await,
@@ -3,7 +3,7 @@
 
 use anyhow::Result;
 use aptos_debugger::Cmd;
-use aptos_logger::{Level, Logger};
+use aptos_logger::{aptos_logger::StderrWriter, Level, Logger};
 use aptos_push_metrics::MetricsPusher;
 use clap::Parser;
 
@@ -12,8 +12,15 @@ aptos_jemalloc::setup_jemalloc!();
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    Logger::new().level(Level::Info).init();
+    let cmd = Cmd::parse();
+
+    let mut logger = Logger::new();
+    logger.level(Level::Info);
+    if cmd.writes_to_stdout() {
+        logger.printer(Box::new(StderrWriter::new()));
+    }
+    logger.init();
     let _mp = MetricsPusher::start(vec![]);
 
-    Cmd::parse().run().await
+    cmd.run().await
 }
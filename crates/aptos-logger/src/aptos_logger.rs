@@ -27,7 +27,7 @@ use std::{
     collections::BTreeMap,
     env,
     fmt::{self, Debug},
-    io::{Stdout, Write},
+    io::{Stderr, Stdout, Write},
     ops::{Deref, DerefMut},
     str::FromStr,
     sync::{self, Arc},
@@ -730,6 +730,37 @@ impl Writer for StdoutWriter {
     }
 }
 
+/// A struct for writing logs to stderr, e.g. for tools writing their output to stdout
+pub struct StderrWriter {
+    buffer: std::io::BufWriter<Stderr>,
+}
+
+impl StderrWriter {
+    pub fn new() -> Self {
+        let buffer = std::io::BufWriter::new(std::io::stderr());
+        Self { buffer }
+    }
+}
+
+impl Default for StderrWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Writer for StderrWriter {
+    /// Write log to stderr
+    fn write(&self, log: String) {
+        eprintln!("{}", log);
+    }
+
+    fn write_buferred(&mut self, log: String) {
+        self.buffer
+            .write_fmt(format_args!("{}\n", log))
+            .unwrap_or_default();
+    }
+}
+
 /// A struct for writing logs to a file
 pub struct FileWriter {
     log_file: RwLock<std::fs::File>,
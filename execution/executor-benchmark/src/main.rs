@@ -105,6 +105,8 @@ impl PrunerOpt {
                 batch_size: self.ledger_pruning_batch_size,
                 user_pruning_window_offset: 0,
             },
+            enable_deletion_log: false,
+            deletion_log_retention_secs: 0,
         }
     }
 }
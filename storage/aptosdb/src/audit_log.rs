@@ -0,0 +1,147 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! A tamper-evident audit log of the committed write sets, kept outside of the DB.
+//!
+//! With an [`AuditSink`] passed to `AptosDB::open_with_audit_sink`, every write set is handed to
+//! it in version order once the commit progress covering it is persisted, so the log never has
+//! versions the DB could truncate on restart. The DB catches the sink up from
+//! [`AuditSink::last_version`] to the committed version on open and after every commit, so a
+//! crash or a sink failure after a commit only delays the versions of that commit. A sink failure
+//! doesn't fail the commit, which is durable by then, it's logged and retried on the next one.
+
+use aptos_crypto::{hash::CryptoHash, HashValue};
+use aptos_infallible::Mutex;
+use aptos_types::{transaction::Version, write_set::WriteSet};
+use std::io::Write;
+
+pub trait AuditSink: Send + Sync {
+    /// Appends the write set committed at `version` to the log, `version` being the one after
+    /// `Self::last_version`. Called from the commit path, so it shouldn't block for long.
+    fn append(&self, version: Version, write_set: &WriteSet) -> anyhow::Result<()>;
+
+    /// The version of the last write set appended, `None` if the log is empty.
+    fn last_version(&self) -> Option<Version>;
+
+    /// The head of the hash chain, covering all the write sets appended so far.
+    fn chain_head(&self) -> HashValue;
+}
+
+/// Writes a line per write set to `W`: the version, the hash of the write set, and the head of
+/// the hash chain after it, which is the hash of the previous head followed by the version and
+/// the hash of the write set. Tampering with any write set in the log breaks all the heads after
+/// it.
+pub struct HashChainAuditSink<W> {
+    inner: Mutex<HashChain<W>>,
+}
+
+struct HashChain<W> {
+    writer: W,
+    chain_head: HashValue,
+    last_version: Option<Version>,
+}
+
+impl<W: Write + Send> HashChainAuditSink<W> {
+    /// Starts appending to `writer` after `chain_head` and `last_version`, e.g.
+    /// `HashValue::zero()` and `None` for a new log, or the last head and version of an existing
+    /// one.
+    pub fn new(writer: W, chain_head: HashValue, last_version: Option<Version>) -> Self {
+        Self {
+            inner: Mutex::new(HashChain {
+                writer,
+                chain_head,
+                last_version,
+            }),
+        }
+    }
+
+    pub fn next_chain_head(
+        chain_head: HashValue,
+        version: Version,
+        write_set_hash: HashValue,
+    ) -> HashValue {
+        HashValue::sha3_256_of(
+            &[
+                chain_head.as_ref(),
+                &version.to_be_bytes(),
+                write_set_hash.as_ref(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+impl<W: Write + Send> AuditSink for HashChainAuditSink<W> {
+    fn append(&self, version: Version, write_set: &WriteSet) -> anyhow::Result<()> {
+        let write_set_hash = write_set.hash();
+        let mut inner = self.inner.lock();
+        let expected_version = inner.last_version.map_or(0, |v| v + 1);
+        anyhow::ensure!(
+            version == expected_version,
+            "Appending version {} to the audit log, expected {}.",
+            version,
+            expected_version,
+        );
+        let next_chain_head = Self::next_chain_head(inner.chain_head, version, write_set_hash);
+        writeln!(
+            inner.writer,
+            "{} {} {}",
+            version,
+            write_set_hash.to_hex(),
+            next_chain_head.to_hex(),
+        )?;
+        inner.writer.flush()?;
+        // Only advanced once the record is written.
+        inner.chain_head = next_chain_head;
+        inner.last_version = Some(version);
+        Ok(())
+    }
+
+    fn last_version(&self) -> Option<Version> {
+        self.inner.lock().last_version
+    }
+
+    fn chain_head(&self) -> HashValue {
+        self.inner.lock().chain_head
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_types::state_store::state_key::StateKey;
+
+    #[test]
+    fn test_hash_chain_audit_sink() {
+        let sink = HashChainAuditSink::new(Vec::new(), HashValue::zero(), None);
+        let write_sets = [
+            WriteSet::new_for_test([(StateKey::raw(b"key"), None)]),
+            WriteSet::default(),
+        ];
+        let mut chain_head = HashValue::zero();
+        for (version, write_set) in write_sets.iter().enumerate() {
+            sink.append(version as Version, write_set).unwrap();
+            chain_head = HashChainAuditSink::<Vec<u8>>::next_chain_head(
+                chain_head,
+                version as Version,
+                write_set.hash(),
+            );
+            assert_eq!(sink.chain_head(), chain_head);
+            assert_eq!(sink.last_version(), Some(version as Version));
+        }
+        // Out of order.
+        assert!(sink.append(3, &write_sets[0]).is_err());
+
+        let log = String::from_utf8(sink.inner.lock().writer.clone()).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            format!(
+                "1 {} {}",
+                write_sets[1].hash().to_hex(),
+                chain_head.to_hex()
+            )
+        );
+    }
+}
@@ -5,21 +5,27 @@ use crate::{
     backup::restore_utils,
     ledger_db::LedgerDb,
     schema::db_metadata::{DbMetadataKey, DbMetadataSchema},
-    state_restore::{StateSnapshotRestore, StateSnapshotRestoreMode},
-    state_store::StateStore,
+    state_restore::{
+        StateSnapshotRestore, StateSnapshotRestoreMode, StateValueBatch, StateValueWriter,
+    },
+    state_store::{sst_ingest::SstIngestStateValueWriter, StateStore},
     AptosDB,
 };
 use aptos_crypto::HashValue;
+use aptos_db_indexer_schemas::metadata::StateSnapshotProgress;
 use aptos_storage_interface::{DbReader, Result};
 use aptos_types::{
     contract_event::ContractEvent,
     ledger_info::LedgerInfoWithSignatures,
     proof::definition::LeafCount,
-    state_store::{state_key::StateKey, state_value::StateValue},
+    state_store::{
+        state_key::StateKey, state_storage_usage::StateStorageUsage, state_value::StateValue,
+    },
     transaction::{PersistedAuxiliaryInfo, Transaction, TransactionInfo, Version},
     write_set::WriteSet,
 };
-use std::sync::Arc;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::{num::NonZeroUsize, path::PathBuf, sync::Arc};
 
 /// Provides functionalities for AptosDB data restore.
 #[derive(Clone)]
@@ -27,14 +33,33 @@ pub struct RestoreHandler {
     pub aptosdb: Arc<AptosDB>,
     state_store: Arc<StateStore>,
     ledger_db: Arc<LedgerDb>,
+    /// Pool to write the state KV shards in during a state snapshot restore, the IO pool if `None`.
+    shard_pool: Option<Arc<ThreadPool>>,
 }
 
 impl RestoreHandler {
-    pub(crate) fn new(aptosdb: Arc<AptosDB>, state_store: Arc<StateStore>) -> Self {
+    /// With `max_concurrent_shards` set, a state snapshot restore writes at most that many state
+    /// KV shards at a time, instead of all of them at once, e.g. to stay within the IO limits of
+    /// constrained hardware.
+    pub(crate) fn new(
+        aptosdb: Arc<AptosDB>,
+        state_store: Arc<StateStore>,
+        max_concurrent_shards: Option<NonZeroUsize>,
+    ) -> Self {
+        let shard_pool = max_concurrent_shards.map(|num_threads| {
+            Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(num_threads.get())
+                    .thread_name(|index| format!("restore-shard-{}", index))
+                    .build()
+                    .expect("Failed to create restore shard thread pool."),
+            )
+        });
         Self {
             ledger_db: Arc::clone(&aptosdb.ledger_db),
             aptosdb,
             state_store,
+            shard_pool,
         }
     }
 
@@ -43,10 +68,49 @@ impl RestoreHandler {
         version: Version,
         expected_root_hash: HashValue,
         restore_mode: StateSnapshotRestoreMode,
+    ) -> Result<StateSnapshotRestore<StateKey, StateValue>> {
+        match &self.shard_pool {
+            Some(shard_pool) => StateSnapshotRestore::new(
+                &self.state_store.state_merkle_db,
+                &Arc::new(ShardPoolStateValueWriter {
+                    state_store: Arc::clone(&self.state_store),
+                    shard_pool: Arc::clone(shard_pool),
+                }),
+                version,
+                expected_root_hash,
+                true, /* async_commit */
+                restore_mode,
+            ),
+            None => StateSnapshotRestore::new(
+                &self.state_store.state_merkle_db,
+                &self.state_store,
+                version,
+                expected_root_hash,
+                true, /* async_commit */
+                restore_mode,
+            ),
+        }
+    }
+
+    /// Same as `get_state_restore_receiver`, except that the state values are restored by building
+    /// SST files under `sst_dir` and ingesting them into the state KV shards, which is much faster
+    /// than writing through the memtable and WAL for an initial bulk load. This requires storage
+    /// sharding, and requires the keys to arrive sorted per shard, which the state snapshot backup
+    /// format already guarantees.
+    pub fn get_state_restore_receiver_by_ingestion(
+        &self,
+        version: Version,
+        expected_root_hash: HashValue,
+        restore_mode: StateSnapshotRestoreMode,
+        sst_dir: PathBuf,
     ) -> Result<StateSnapshotRestore<StateKey, StateValue>> {
         StateSnapshotRestore::new(
             &self.state_store.state_merkle_db,
-            &self.state_store,
+            &Arc::new(SstIngestStateValueWriter::new(
+                Arc::clone(&self.state_store),
+                sst_dir,
+                self.shard_pool.clone(),
+            )?),
             version,
             expected_root_hash,
             true, /* async_commit */
@@ -148,3 +212,30 @@ impl RestoreHandler {
         Ok(None)
     }
 }
+
+/// Writes the restored state values like the state store does, but commits the shards in a pool
+/// of its own to bound how many are written concurrently.
+struct ShardPoolStateValueWriter {
+    state_store: Arc<StateStore>,
+    shard_pool: Arc<ThreadPool>,
+}
+
+impl StateValueWriter<StateKey, StateValue> for ShardPoolStateValueWriter {
+    fn write_kv_batch(
+        &self,
+        version: Version,
+        node_batch: &StateValueBatch<StateKey, Option<StateValue>>,
+        progress: StateSnapshotProgress,
+    ) -> Result<()> {
+        self.state_store
+            .write_kv_batch_in_pool(version, node_batch, progress, &self.shard_pool)
+    }
+
+    fn kv_finish(&self, version: Version, usage: StateStorageUsage) -> Result<()> {
+        self.state_store.kv_finish(version, usage)
+    }
+
+    fn get_progress(&self, version: Version) -> Result<Option<StateSnapshotProgress>> {
+        self.state_store.get_progress(version)
+    }
+}
@@ -2,15 +2,17 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
+    backup::backup_handler::BackupHandler,
     db::AptosDB,
     event_store::EventStore,
     ledger_db::LedgerDb,
     metrics::{API_LATENCY_SECONDS, CONCURRENCY_GAUGE},
-    pruner::{LedgerPrunerManager, PrunerManager},
+    pruner::{LedgerArchiver, LedgerPrunerManager, PrunerManager},
     rocksdb_property_reporter::RocksdbPropertyReporter,
+    schema::{STATE_VALUE_BY_KEY_HASH_CF_NAME, STATE_VALUE_CF_NAME, WRITE_SET_CF_NAME},
     state_kv_db::StateKvDb,
     state_merkle_db::StateMerkleDb,
-    state_store::{StatePruner, StateStore},
+    state_store::{StatePruner, StateStore, SubDbVersions},
     transaction_store::TransactionStore,
 };
 use aptos_config::config::{
@@ -19,9 +21,9 @@ use aptos_config::config::{
 };
 use aptos_db_indexer::{db_indexer::InternalIndexerDB, Indexer};
 use aptos_logger::prelude::*;
-use aptos_metrics_core::{IntGaugeVecHelper, TimerHelper};
+use aptos_metrics_core::TimerHelper;
 use aptos_resource_viewer::AptosValueAnnotator;
-use aptos_schemadb::{Cache, Env};
+use aptos_schemadb::{value_cipher::ValueCipher, Cache, Env};
 use aptos_storage_interface::{
     block_info::BlockInfo, db_ensure as ensure, db_other_bail as bail, AptosDbError, DbReader,
     Order, Result,
@@ -34,6 +36,7 @@ use std::{
     cell::Cell,
     fmt::{Debug, Formatter},
     iter::Iterator,
+    ops::Range,
     path::Path,
     sync::Arc,
     time::Instant,
@@ -47,11 +50,14 @@ impl AptosDB {
         state_kv_db: StateKvDb,
         pruner_config: PrunerConfig,
         buffered_state_target_items: usize,
+        state_snapshot_commit_concurrency: Option<usize>,
         hack_for_tests: bool,
         empty_buffered_state_for_restore: bool,
         skip_index_and_usage: bool,
         internal_indexer_db: Option<InternalIndexerDB>,
         hot_state_config: HotStateConfig,
+        ledger_archive_db: Option<Arc<AptosDB>>,
+        sub_db_versions_on_open: SubDbVersions,
     ) -> Self {
         let ledger_db = Arc::new(ledger_db);
         let hot_state_merkle_db = hot_state_merkle_db.map(Arc::new);
@@ -70,6 +76,7 @@ impl AptosDB {
             Arc::clone(&state_kv_db),
             state_pruner,
             buffered_state_target_items,
+            state_snapshot_commit_concurrency,
             hack_for_tests,
             empty_buffered_state_for_restore,
             skip_index_and_usage,
@@ -77,10 +84,19 @@ impl AptosDB {
             hot_state_config,
         ));
 
+        let ledger_archiver = ledger_archive_db.as_ref().map(|archive_db| {
+            LedgerArchiver::new(
+                BackupHandler::new(Arc::clone(&state_store), Arc::clone(&ledger_db)),
+                Arc::clone(&ledger_db),
+                Arc::clone(archive_db),
+            )
+        });
         let ledger_pruner = LedgerPrunerManager::new(
             Arc::clone(&ledger_db),
             pruner_config.ledger_pruner_config,
             internal_indexer_db,
+            ledger_archiver,
+            pruner_config.deletion_log_retention(),
         );
 
         AptosDB {
@@ -90,6 +106,7 @@ impl AptosDB {
             state_store,
             transaction_store: Arc::new(TransactionStore::new(Arc::clone(&ledger_db))),
             ledger_pruner,
+            ledger_archive_db,
             _rocksdb_property_reporter: RocksdbPropertyReporter::new(
                 ledger_db,
                 state_merkle_db,
@@ -97,9 +114,13 @@ impl AptosDB {
             ),
             pre_commit_lock: std::sync::Mutex::new(()),
             commit_lock: std::sync::Mutex::new(()),
+            isolated: std::sync::RwLock::new(false),
             indexer: None,
             skip_index_and_usage,
             update_subscriber: None,
+            verify_ledger_root_on_commit: false,
+            audit_sink: None,
+            sub_db_versions_on_open,
         }
     }
 
@@ -114,6 +135,8 @@ impl AptosDB {
         empty_buffered_state_for_restore: bool,
         internal_indexer_db: Option<InternalIndexerDB>,
         hot_state_config: HotStateConfig,
+        ledger_archive_db: Option<Arc<AptosDB>>,
+        value_cipher: Option<Arc<dyn ValueCipher>>,
     ) -> Result<Self> {
         ensure!(
             pruner_config.eq(&NO_OP_STORAGE_PRUNER_CONFIG) || !readonly,
@@ -138,6 +161,31 @@ impl AptosDB {
             max_num_nodes_per_lru_cache_shard,
             hot_state_config.delete_on_restart,
         )?;
+        ledger_db
+            .metadata_db()
+            .check_value_encryption(value_cipher.is_some(), readonly)?;
+        if let Some(value_cipher) = value_cipher {
+            Self::set_value_cipher(&ledger_db, &state_kv_db, value_cipher)?;
+        }
+        if rocksdb_configs.verify_checksums_on_every_read {
+            // Before any reads, so that the block cache holds no data blocks to begin with.
+            ledger_db
+                .all_dbs()
+                .into_iter()
+                .chain(state_kv_db.all_dbs_with_hot_state())
+                .chain(state_merkle_db.all_dbs())
+                .chain(hot_state_merkle_db.iter().flat_map(StateMerkleDb::all_dbs))
+                .for_each(|db| db.set_verify_checksums_on_every_read(true));
+        }
+        // Before the sub-DBs are truncated to the overall commit progress.
+        let sub_db_versions = SubDbVersions::read(&ledger_db, &state_kv_db, &state_merkle_db)?;
+        if !sub_db_versions.is_consistent() {
+            warn!(
+                sub_db_versions = ?sub_db_versions,
+                "The sub-DBs disagree on the latest committed version beyond an interrupted \
+                 commit, a commit may have been torn."
+            );
+        }
 
         let mut myself = Self::new_with_dbs(
             ledger_db,
@@ -146,11 +194,14 @@ impl AptosDB {
             state_kv_db,
             pruner_config,
             buffered_state_target_items,
+            rocksdb_configs.state_snapshot_commit_concurrency,
             readonly,
             empty_buffered_state_for_restore,
             rocksdb_configs.enable_storage_sharding,
             internal_indexer_db,
             hot_state_config,
+            ledger_archive_db,
+            sub_db_versions,
         );
 
         if !readonly {
@@ -194,6 +245,29 @@ impl AptosDB {
         Ok(myself)
     }
 
+    /// Encrypts the state values at rest with `value_cipher` from now on, both those in the state
+    /// K/V DB and those in the write sets. Without sharding, they're all in the same DB.
+    fn set_value_cipher(
+        ledger_db: &LedgerDb,
+        state_kv_db: &StateKvDb,
+        value_cipher: Arc<dyn ValueCipher>,
+    ) -> Result<()> {
+        if state_kv_db.enabled_sharding() {
+            ledger_db
+                .write_set_db_raw()
+                .set_value_cipher(value_cipher.clone(), &[WRITE_SET_CF_NAME])?;
+            state_kv_db.set_value_cipher(value_cipher)
+        } else {
+            ledger_db
+                .write_set_db_raw()
+                .set_value_cipher(value_cipher, &[
+                    WRITE_SET_CF_NAME,
+                    STATE_VALUE_CF_NAME,
+                    STATE_VALUE_BY_KEY_HASH_CF_NAME,
+                ])
+        }
+    }
+
     fn open_indexer(
         &mut self,
         db_root_path: impl AsRef<Path>,
@@ -273,6 +347,21 @@ impl AptosDB {
         Ok(())
     }
 
+    /// Returns the ledger archive DB to read `versions` from instead, if they are pruned here and
+    /// all in the archive.
+    pub(super) fn ledger_archive_for(&self, versions: Range<Version>) -> Result<Option<&AptosDB>> {
+        let Some(archive_db) = &self.ledger_archive_db else {
+            return Ok(None);
+        };
+        if versions.start >= self.ledger_pruner.get_min_readable_version() {
+            return Ok(None);
+        }
+        let archived = archive_db
+            .get_synced_version()?
+            .is_some_and(|synced_version| synced_version >= versions.end.saturating_sub(1));
+        Ok(archived.then_some(archive_db.as_ref()))
+    }
+
     pub(super) fn error_if_state_merkle_pruned(
         &self,
         data_type: &str,
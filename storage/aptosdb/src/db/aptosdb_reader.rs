@@ -48,7 +48,7 @@ use aptos_types::{
     write_set::WriteSet,
 };
 use itertools::Itertools;
-use std::{iter::Iterator, sync::Arc};
+use std::{collections::BTreeMap, iter::Iterator, sync::Arc};
 
 impl DbReader for AptosDB {
     fn get_persisted_state(&self) -> Result<(Arc<dyn HotStateView>, State)> {
@@ -96,11 +96,26 @@ impl DbReader for AptosDB {
         })
     }
 
+    fn get_account_state(
+        &self,
+        address: AccountAddress,
+        version: Version,
+    ) -> Result<BTreeMap<StateKey, StateValue>> {
+        gauged_api("get_account_state", || {
+            self.error_if_state_kv_pruned("StateValue", version)?;
+
+            self.state_store.get_account_state(address, version)
+        })
+    }
+
     fn get_transaction_auxiliary_data_by_version(
         &self,
         version: Version,
     ) -> Result<Option<TransactionAuxiliaryData>> {
         gauged_api("get_transaction_auxiliary_data_by_version", || {
+            if let Some(archive_db) = self.ledger_archive_for(version..version + 1)? {
+                return archive_db.get_transaction_auxiliary_data_by_version(version);
+            }
             self.error_if_ledger_pruned("Transaction", version)?;
             self.ledger_db
                 .transaction_auxiliary_data_db()
@@ -108,11 +123,24 @@ impl DbReader for AptosDB {
         })
     }
 
+    fn get_transaction_output(&self, version: Version) -> Result<TransactionOutput> {
+        gauged_api("get_transaction_output", || {
+            if let Some(archive_db) = self.ledger_archive_for(version..version + 1)? {
+                return archive_db.get_transaction_output(version);
+            }
+            self.error_if_ledger_pruned("Transaction", version)?;
+            self.transaction_store.get_transaction_output(version)
+        })
+    }
+
     fn get_persisted_auxiliary_info_by_version(
         &self,
         version: Version,
     ) -> Result<PersistedAuxiliaryInfo> {
         gauged_api("get_persisted_auxiliary_info_by_version", || {
+            if let Some(archive_db) = self.ledger_archive_for(version..version + 1)? {
+                return archive_db.get_persisted_auxiliary_info_by_version(version);
+            }
             self.error_if_ledger_pruned("PersistedAuxiliaryInfo", version)?;
             Ok(self
                 .ledger_db
@@ -277,6 +305,14 @@ impl DbReader for AptosDB {
             if start_version > ledger_version || limit == 0 {
                 return Ok(TransactionListWithProofV2::new_empty());
             }
+            if let Some(archive_db) = self.ledger_archive_for(start_version..ledger_version + 1)? {
+                return archive_db.get_transactions(
+                    start_version,
+                    limit,
+                    ledger_version,
+                    fetch_events,
+                );
+            }
             self.error_if_ledger_pruned("Transaction", start_version)?;
 
             let limit = std::cmp::min(limit, ledger_version - start_version + 1);
@@ -384,6 +420,9 @@ impl DbReader for AptosDB {
                 return Ok(TransactionOutputListWithProofV2::new_empty());
             }
 
+            if let Some(archive_db) = self.ledger_archive_for(start_version..ledger_version + 1)? {
+                return archive_db.get_transaction_outputs(start_version, limit, ledger_version);
+            }
             self.error_if_ledger_pruned("Transaction", start_version)?;
 
             let limit = std::cmp::min(limit, ledger_version - start_version + 1);
@@ -395,21 +434,10 @@ impl DbReader for AptosDB {
                         .ledger_db
                         .transaction_info_db()
                         .get_transaction_info(version)?;
-                    let events = self.ledger_db.event_db().get_events_by_version(version)?;
-                    let write_set = self.ledger_db.write_set_db().get_write_set(version)?;
                     let txn = self.ledger_db.transaction_db().get_transaction(version)?;
-                    let auxiliary_data = self
-                        .ledger_db
-                        .transaction_auxiliary_data_db()
-                        .get_transaction_auxiliary_data(version)?
-                        .unwrap_or_default();
-                    let txn_output = TransactionOutput::new(
-                        write_set,
-                        events,
-                        txn_info.gas_used(),
-                        txn_info.status().clone().into(),
-                        auxiliary_data,
-                    );
+                    let txn_output = self
+                        .transaction_store
+                        .get_transaction_output_with_info(version, &txn_info)?;
                     let persisted_aux_info = self
                         .ledger_db
                         .persisted_auxiliary_info_db()
@@ -481,6 +509,11 @@ impl DbReader for AptosDB {
     ) -> Result<Box<dyn Iterator<Item = Result<Transaction>> + '_>> {
         gauged_api("get_transaction_iterator", || {
             error_if_too_many_requested(limit, MAX_REQUEST_LIMIT)?;
+            if let Some(archive_db) =
+                self.ledger_archive_for(start_version..start_version + limit)?
+            {
+                return archive_db.get_transaction_iterator(start_version, limit);
+            }
             self.error_if_ledger_pruned("Transaction", start_version)?;
 
             let iter = self
@@ -498,6 +531,11 @@ impl DbReader for AptosDB {
     ) -> Result<Box<dyn Iterator<Item = Result<TransactionInfo>> + '_>> {
         gauged_api("get_transaction_info_iterator", || {
             error_if_too_many_requested(limit, MAX_REQUEST_LIMIT)?;
+            if let Some(archive_db) =
+                self.ledger_archive_for(start_version..start_version + limit)?
+            {
+                return archive_db.get_transaction_info_iterator(start_version, limit);
+            }
             self.error_if_ledger_pruned("Transaction", start_version)?;
 
             let iter = self
@@ -515,7 +553,13 @@ impl DbReader for AptosDB {
     ) -> Result<Box<dyn Iterator<Item = Result<Vec<ContractEvent>>> + '_>> {
         gauged_api("get_events_iterator", || {
             error_if_too_many_requested(limit, MAX_REQUEST_LIMIT)?;
+            if let Some(archive_db) =
+                self.ledger_archive_for(start_version..start_version + limit)?
+            {
+                return archive_db.get_events_iterator(start_version, limit);
+            }
             self.error_if_ledger_pruned("Transaction", start_version)?;
+            self.event_store.error_if_events_pruned(start_version)?;
 
             let iter = self
                 .ledger_db
@@ -528,6 +572,26 @@ impl DbReader for AptosDB {
         })
     }
 
+    fn get_events_grouped_by_version(
+        &self,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<BTreeMap<Version, Vec<ContractEvent>>> {
+        gauged_api("get_events_grouped_by_version", || {
+            error_if_too_many_requested(
+                end_version.saturating_sub(start_version),
+                MAX_REQUEST_LIMIT,
+            )?;
+            if let Some(archive_db) = self.ledger_archive_for(start_version..end_version)? {
+                return archive_db.get_events_grouped_by_version(start_version, end_version);
+            }
+            self.error_if_ledger_pruned("Transaction", start_version)?;
+
+            self.event_store
+                .get_events_grouped(start_version, end_version)
+        })
+    }
+
     fn get_write_set_iterator(
         &self,
         start_version: Version,
@@ -535,6 +599,11 @@ impl DbReader for AptosDB {
     ) -> Result<Box<dyn Iterator<Item = Result<WriteSet>> + '_>> {
         gauged_api("get_write_set_iterator", || {
             error_if_too_many_requested(limit, MAX_REQUEST_LIMIT)?;
+            if let Some(archive_db) =
+                self.ledger_archive_for(start_version..start_version + limit)?
+            {
+                return archive_db.get_write_set_iterator(start_version, limit);
+            }
             self.error_if_ledger_pruned("Transaction", start_version)?;
 
             let iter = self
@@ -552,6 +621,13 @@ impl DbReader for AptosDB {
         ledger_version: Version,
     ) -> Result<TransactionAccumulatorRangeProof> {
         gauged_api("get_transaction_accumulator_range_proof", || {
+            if let Some(archive_db) = self.ledger_archive_for(first_version..ledger_version + 1)? {
+                return archive_db.get_transaction_accumulator_range_proof(
+                    first_version,
+                    limit,
+                    ledger_version,
+                );
+            }
             self.error_if_ledger_pruned("Transaction", first_version)?;
 
             self.ledger_db
@@ -575,7 +651,8 @@ impl DbReader for AptosDB {
         end_epoch: u64,
     ) -> Result<Box<dyn Iterator<Item = Result<LedgerInfoWithSignatures>> + '_>> {
         gauged_api("get_epoch_ending_ledger_info_iterator", || {
-            self.check_epoch_ending_ledger_infos_request(start_epoch, end_epoch)?;
+            self.ledger_db
+                .check_epoch_ending_ledger_infos_request(start_epoch, end_epoch)?;
             let limit = std::cmp::min(
                 end_epoch.saturating_sub(start_epoch),
                 MAX_NUM_EPOCH_ENDING_LEDGER_INFO as u64,
@@ -831,6 +908,9 @@ impl DbReader for AptosDB {
 
     fn get_accumulator_root_hash(&self, version: Version) -> Result<HashValue> {
         gauged_api("get_accumulator_root_hash", || {
+            if let Some(archive_db) = self.ledger_archive_for(version..version + 1)? {
+                return archive_db.get_accumulator_root_hash(version);
+            }
             self.error_if_ledger_pruned("Transaction accumulator", version)?;
             self.ledger_db
                 .transaction_accumulator_db()
@@ -982,6 +1062,9 @@ impl DbReader for AptosDB {
         index: u64,
     ) -> Result<ContractEvent> {
         gauged_api("get_event_by_version_and_index", || {
+            if let Some(archive_db) = self.ledger_archive_for(version..version + 1)? {
+                return archive_db.get_event_by_version_and_index(version, index);
+            }
             self.error_if_ledger_pruned("Event", version)?;
             self.event_store
                 .get_event_by_version_and_index(version, index)
@@ -1006,63 +1089,14 @@ impl AptosDB {
         )
     }
 
-    fn check_epoch_ending_ledger_infos_request(
-        &self,
-        start_epoch: u64,
-        end_epoch: u64,
-    ) -> Result<()> {
-        ensure!(
-            start_epoch <= end_epoch,
-            "Bad epoch range [{}, {})",
-            start_epoch,
-            end_epoch,
-        );
-        // Note that the latest epoch can be the same with the current epoch (in most cases), or
-        // current_epoch + 1 (when the latest ledger_info carries next validator set)
-
-        let latest_epoch = self
-            .ledger_db
-            .metadata_db()
-            .get_latest_ledger_info()?
-            .ledger_info()
-            .next_block_epoch();
-        ensure!(
-            end_epoch <= latest_epoch,
-            "Unable to provide epoch change ledger info for still open epoch. asked upper bound: {}, last sealed epoch: {}",
-            end_epoch,
-            latest_epoch - 1,  // okay to -1 because genesis LedgerInfo has .next_block_epoch() == 1
-        );
-        Ok(())
-    }
-
     pub(super) fn get_epoch_ending_ledger_infos_impl(
         &self,
         start_epoch: u64,
         end_epoch: u64,
         limit: usize,
     ) -> Result<(Vec<LedgerInfoWithSignatures>, bool)> {
-        self.check_epoch_ending_ledger_infos_request(start_epoch, end_epoch)?;
-
-        let (paging_epoch, more) = if end_epoch - start_epoch > limit as u64 {
-            (start_epoch + limit as u64, true)
-        } else {
-            (end_epoch, false)
-        };
-
-        let lis = self
-            .ledger_db
-            .metadata_db()
-            .get_epoch_ending_ledger_info_iter(start_epoch, paging_epoch)?
-            .collect::<Result<Vec<_>>>()?;
-
-        ensure!(
-            lis.len() == (paging_epoch - start_epoch) as usize,
-            "DB corruption: missing epoch ending ledger info for epoch {}",
-            lis.last()
-                .map(|li| li.ledger_info().next_block_epoch() - 1)
-                .unwrap_or(start_epoch),
-        );
-        Ok((lis, more))
+        self.ledger_db
+            .get_epoch_ending_ledger_infos(start_epoch, end_epoch, limit)
     }
 
     /// Returns the transaction with proof for a given version, or error if the transaction is not
@@ -1073,6 +1107,9 @@ impl AptosDB {
         ledger_version: Version,
         fetch_events: bool,
     ) -> Result<TransactionWithProof> {
+        if let Some(archive_db) = self.ledger_archive_for(version..ledger_version + 1)? {
+            return archive_db.get_transaction_by_version(version, ledger_version, fetch_events);
+        }
         self.error_if_ledger_pruned("Transaction", version)?;
 
         let proof = self
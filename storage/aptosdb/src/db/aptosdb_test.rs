@@ -2,38 +2,73 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
+    audit_log::{AuditSink, HashChainAuditSink},
     db::{
         aptosdb_internal::get_first_seq_num_and_limit,
         test_helper::{
             arb_blocks_to_commit, put_transaction_auxiliary_data, test_save_blocks_impl,
-            test_sync_transactions_impl,
+            test_sync_transactions_impl, update_store,
         },
-        AptosDB,
+        AptosDB, LogicalDivergence,
     },
+    db_options::{
+        compression_for_cf, filter_for_cf, hot_state_kv_db_column_families,
+        ledger_db_column_families, state_kv_db_new_key_column_families,
+        state_merkle_db_column_families, whole_key_filtering_for_cf,
+    },
+    get_restore_handler::GetRestoreHandler,
+    ledger_db::LedgerDb,
     pruner::{LedgerPrunerManager, PrunerManager, StateMerklePrunerManager},
-    schema::stale_node_index::StaleNodeIndexSchema,
+    schema::{
+        db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+        stale_node_index::StaleNodeIndexSchema,
+        state_value::StateValueSchema,
+        state_value_by_key_hash::StateValueByKeyHashSchema,
+        DB_METADATA_CF_NAME, JELLYFISH_MERKLE_NODE_CF_NAME, STALE_NODE_INDEX_CF_NAME,
+        STALE_STATE_VALUE_INDEX_BY_KEY_HASH_CF_NAME, STATE_VALUE_BY_KEY_HASH_CF_NAME,
+        TRANSACTION_CF_NAME,
+    },
+    state_restore::StateSnapshotRestoreMode,
+    state_store::{SubDbVersions, MAX_COMMIT_PROGRESS_DIFFERENCE},
 };
 use aptos_config::config::{
-    EpochSnapshotPrunerConfig, HotStateConfig, LedgerPrunerConfig, PrunerConfig, RocksdbConfigs,
-    StateMerklePrunerConfig, StorageDirPaths, BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
-    DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    ColumnFamilyCompression, ColumnFamilyFilter, EpochSnapshotPrunerConfig, FilterPolicy,
+    HotStateConfig, LedgerPrunerConfig, PrunerConfig, RocksdbConfig, RocksdbConfigs,
+    StateMerklePrunerConfig, StorageDirPaths, WalRecoveryMode, ZstdCompressionConfig,
+    BUFFERED_STATE_TARGET_ITEMS_FOR_TEST, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
 };
 use aptos_crypto::{hash::CryptoHash, HashValue};
-use aptos_storage_interface::{DbReader, Order};
+use aptos_schemadb::{batch::SchemaBatch, value_cipher::Aes256GcmValueCipher, WriteBufferManager};
+use aptos_storage_interface::{AptosDbError, DbReader, DbWriter, Order, StateSnapshotReceiver};
 use aptos_temppath::TempPath;
 use aptos_types::{
-    ledger_info::LedgerInfoWithSignatures,
-    proof::SparseMerkleLeafNode,
-    state_store::{state_key::StateKey, state_value::StateValue},
+    aggregate_signature::AggregateSignature,
+    block_info::BlockInfo,
+    epoch_state::EpochState,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    proof::{
+        accumulator::InMemoryTransactionAccumulator, SparseMerkleLeafNode, SparseMerkleRangeProof,
+    },
+    state_store::{state_key::StateKey, state_value::StateValue, NUM_STATE_SHARDS},
     transaction::{
         ExecutionStatus, PersistedAuxiliaryInfo, TransactionAuxiliaryData,
-        TransactionAuxiliaryDataV1, TransactionInfo, TransactionToCommit, VMErrorDetail, Version,
+        TransactionAuxiliaryDataV1, TransactionInfo, TransactionStatus, TransactionToCommit,
+        VMErrorDetail, Version,
     },
     vm_status::StatusCode,
     write_set::WriteSet,
 };
 use proptest::prelude::*;
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::HashSet,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 proptest! {
     #![proptest_config(ProptestConfig::with_cases(10))]
@@ -103,6 +138,7 @@ fn test_pruner_config() {
                 prune_window: 20,
                 batch_size: 1,
             },
+            None, /* deletion_log_retention */
         );
         assert_eq!(state_merkle_pruner.is_pruner_enabled(), enable);
         assert_eq!(state_merkle_pruner.get_prune_window(), 20);
@@ -116,6 +152,8 @@ fn test_pruner_config() {
                 user_pruning_window_offset: 0,
             },
             None,
+            None,
+            None, /* deletion_log_retention */
         );
         assert_eq!(ledger_pruner.is_pruner_enabled(), enable);
         assert_eq!(ledger_pruner.get_prune_window(), 100);
@@ -226,6 +264,1797 @@ fn test_get_latest_ledger_summary() {
     assert_eq!(bootstrapped.state_summary.root_hash(), state_hash);
 }
 
+#[test]
+fn test_open_read_only_shared() {
+    let tmp_dir = TempPath::new();
+    let synced_version = {
+        let db = AptosDB::new_for_test(&tmp_dir);
+        db.get_synced_version().unwrap()
+    };
+
+    let rocksdb_configs = RocksdbConfigs {
+        enable_storage_sharding: false,
+        ..Default::default()
+    };
+    // Multiple readers can hold the same directory open at the same time.
+    let readers = (0..2)
+        .map(|_| {
+            AptosDB::open_read_only_shared(
+                StorageDirPaths::from_path(&tmp_dir),
+                rocksdb_configs,
+                DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            )
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    for reader in &readers {
+        assert_eq!(reader.get_synced_version().unwrap(), synced_version);
+    }
+}
+
+#[test]
+fn test_open_with_shard_config_mismatch() {
+    let open = |tmp_dir: &TempPath, enable_storage_sharding| {
+        AptosDB::open(
+            StorageDirPaths::from_path(tmp_dir),
+            /*readonly=*/ false,
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            RocksdbConfigs {
+                enable_storage_sharding,
+                ..Default::default()
+            },
+            false, /* enable_indexer */
+            BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            None,
+            HotStateConfig::default(),
+        )
+    };
+
+    for enable_storage_sharding in [false, true] {
+        let tmp_dir = TempPath::new();
+        drop(open(&tmp_dir, enable_storage_sharding).unwrap());
+        assert!(matches!(
+            open(&tmp_dir, !enable_storage_sharding),
+            Err(AptosDbError::ShardConfigMismatch { .. })
+        ));
+        // Reopening with the original config still works.
+        open(&tmp_dir, enable_storage_sharding).unwrap();
+    }
+}
+
+#[test]
+fn test_write_stall_status() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+
+    let status = db.write_stall_status().unwrap();
+    assert!(status.dbs.contains_key("ledger_db"));
+    assert!(status.dbs.contains_key("state_merkle_db"));
+    assert!(!status.is_stalled());
+}
+
+#[test]
+fn test_compaction_stats() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    db.save_transactions_for_test(
+        &[txn_to_commit_with_write(b"test_key", b"test_val")],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    db.ledger_db
+        .transaction_db_raw()
+        .flush_cf(TRANSACTION_CF_NAME)
+        .unwrap();
+
+    let stats = db.compaction_stats().unwrap();
+    let transaction_cf_stats = stats
+        .iter()
+        .find(|s| s.db_name == "transaction_db" && s.cf_name == TRANSACTION_CF_NAME)
+        .unwrap();
+    // Flushed to a single SST file.
+    assert_eq!(
+        transaction_cf_stats
+            .level_sizes
+            .iter()
+            .filter(|s| **s > 0)
+            .count(),
+        1
+    );
+    // All the shards are covered.
+    assert_eq!(
+        stats
+            .iter()
+            .filter(|s| s.db_name.starts_with("state_kv_db_shard_")
+                && s.cf_name == STATE_VALUE_BY_KEY_HASH_CF_NAME)
+            .count(),
+        NUM_STATE_SHARDS
+    );
+    assert!(stats.iter().all(|s| s.cf_name != "default"));
+}
+
+fn txn_to_commit_with_write(key: &[u8], value: &[u8]) -> TransactionToCommit {
+    let key = StateKey::raw(key);
+    let value = StateValue::from(value.to_vec());
+    let state_hash = SparseMerkleLeafNode::new(key.hash(), value.hash()).hash();
+    let mut txn_to_commit = TransactionToCommit::dummy();
+    txn_to_commit.transaction_info = TransactionInfo::new(
+        HashValue::zero(),
+        HashValue::zero(),
+        HashValue::zero(),
+        Some(state_hash),
+        0,
+        ExecutionStatus::MiscellaneousError(None),
+        None,
+    );
+    txn_to_commit.write_set = WriteSet::new_for_test([(key, Some(value))]);
+    txn_to_commit
+}
+
+#[test]
+fn test_is_version_committed() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    assert!(!db.is_version_committed(0).unwrap());
+
+    db.save_transactions_for_test(
+        &[txn_to_commit_with_write(b"test_key", b"test_val")],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+
+    assert!(db.is_version_committed(0).unwrap());
+    assert!(!db.is_version_committed(1).unwrap());
+
+    let sub_db_versions = db.sub_db_versions().unwrap();
+    assert_eq!(sub_db_versions.overall_commit_progress, Some(0));
+    assert_eq!(sub_db_versions.ledger_commit_progress, Some(0));
+    assert_eq!(sub_db_versions.state_kv_commit_progress, Some(0));
+}
+
+#[test]
+fn test_verify_ledger_root_on_commit() {
+    let tmp_dir = TempPath::new();
+    let mut db = AptosDB::new_for_test(&tmp_dir);
+    db.set_verify_ledger_root_on_commit(true);
+
+    for version in 0..3 {
+        db.save_transactions_for_test(
+            &[txn_to_commit_with_write(b"test_key", &[version as u8])],
+            version, /* first_version */
+            None,    /* ledger_info_with_sigs */
+            true,    /* sync_commit */
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_get_transaction_output() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let txn_to_commit = txn_to_commit_with_write(b"test_key", b"test_val");
+    db.save_transactions_for_test(
+        &[txn_to_commit.clone()],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+
+    let output = db.get_transaction_output(0).unwrap();
+    assert_eq!(output.write_set(), &txn_to_commit.write_set);
+    assert_eq!(output.events(), txn_to_commit.events.as_slice());
+    assert_eq!(output.gas_used(), txn_to_commit.transaction_info.gas_used());
+    assert_eq!(
+        output.status(),
+        &TransactionStatus::from(txn_to_commit.transaction_info.status().clone())
+    );
+    assert!(db.get_transaction_output(1).is_err());
+}
+
+#[test]
+fn test_open_with_value_cipher() {
+    let tmp_dir = TempPath::new();
+    let open = |value_cipher: Aes256GcmValueCipher| {
+        AptosDB::open_with_value_cipher(
+            StorageDirPaths::from_path(&tmp_dir),
+            false, /* readonly */
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            RocksdbConfigs {
+                enable_storage_sharding: true,
+                ..Default::default()
+            },
+            false, /* indexer */
+            BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            None, /* internal_indexer_db */
+            HotStateConfig::default(),
+            Arc::new(value_cipher),
+        )
+    };
+    let state_key = StateKey::raw(b"test_key");
+    let state_value = StateValue::from(b"test_val".to_vec());
+    let txn_to_commit = txn_to_commit_with_write(b"test_key", b"test_val");
+    {
+        let db = open(Aes256GcmValueCipher::new(1, &[1; 32])).unwrap();
+        db.save_transactions_for_test(
+            &[txn_to_commit.clone()],
+            0,    /* first_version */
+            None, /* ledger_info_with_sigs */
+            true, /* sync_commit */
+        )
+        .unwrap();
+        db.state_store.buffered_state().lock().sync_commit();
+
+        let (value, proof) = db
+            .get_state_value_with_proof_by_version(&state_key, 0)
+            .unwrap();
+        assert_eq!(value.as_ref(), Some(&state_value));
+        // The tree is over the plaintext.
+        proof
+            .verify(
+                txn_to_commit
+                    .transaction_info
+                    .state_checkpoint_hash()
+                    .unwrap(),
+                state_key.hash(),
+                value.as_ref(),
+            )
+            .unwrap();
+        assert_eq!(
+            db.get_transaction_output(0).unwrap().write_set(),
+            &txn_to_commit.write_set
+        );
+        // The size of the plaintext, not of what's stored.
+        assert_eq!(db.transaction_store.write_set_sizes(0, 1).unwrap(), vec![(
+            0,
+            bcs::serialized_size(&txn_to_commit.write_set).unwrap() as u64
+        )]);
+    }
+    {
+        // Rotated key.
+        let db =
+            open(Aes256GcmValueCipher::new(2, &[2; 32]).with_retired_key(1, &[1; 32])).unwrap();
+        assert_eq!(
+            db.get_state_value_by_version(&state_key, 0).unwrap(),
+            Some(state_value)
+        );
+    }
+    assert!(open(Aes256GcmValueCipher::new(1, &[2; 32]))
+        .and_then(|db| db.get_state_value_by_version(&state_key, 0))
+        .is_err());
+    // Opening without a cipher is refused, rather than reading the encrypted values as is.
+    assert!(AptosDB::open(
+        StorageDirPaths::from_path(&tmp_dir),
+        false, /* readonly */
+        NO_OP_STORAGE_PRUNER_CONFIG,
+        RocksdbConfigs {
+            enable_storage_sharding: true,
+            ..Default::default()
+        },
+        false, /* indexer */
+        BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        None, /* internal_indexer_db */
+        HotStateConfig::default(),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_open_plaintext_db_with_value_cipher() {
+    let tmp_dir = TempPath::new();
+    let db =
+        AptosDB::new_for_test_with_sharding(&tmp_dir, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD);
+    db.save_transactions_for_test(
+        &[txn_to_commit_with_write(b"test_key", b"test_val")],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    drop(db);
+
+    assert!(AptosDB::open_with_value_cipher(
+        StorageDirPaths::from_path(&tmp_dir),
+        false, /* readonly */
+        NO_OP_STORAGE_PRUNER_CONFIG,
+        RocksdbConfigs {
+            enable_storage_sharding: true,
+            ..Default::default()
+        },
+        false, /* indexer */
+        BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        None, /* internal_indexer_db */
+        HotStateConfig::default(),
+        Arc::new(Aes256GcmValueCipher::new(1, &[1; 32])),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_audit_sink() {
+    struct FlakyAuditSink {
+        failing: AtomicBool,
+        inner: HashChainAuditSink<Vec<u8>>,
+    }
+
+    impl AuditSink for FlakyAuditSink {
+        fn append(&self, version: Version, write_set: &WriteSet) -> anyhow::Result<()> {
+            anyhow::ensure!(
+                !self.failing.load(Ordering::SeqCst),
+                "Audit log unavailable."
+            );
+            self.inner.append(version, write_set)
+        }
+
+        fn last_version(&self) -> Option<Version> {
+            self.inner.last_version()
+        }
+
+        fn chain_head(&self) -> HashValue {
+            self.inner.chain_head()
+        }
+    }
+
+    let tmp_dir = TempPath::new();
+    assert_eq!(AptosDB::new_for_test(&tmp_dir).audit_chain_head(), None);
+
+    let open = |audit_sink: Arc<dyn AuditSink>| {
+        AptosDB::open_with_audit_sink(
+            StorageDirPaths::from_path(&tmp_dir),
+            false, /* readonly */
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            RocksdbConfigs {
+                enable_storage_sharding: false,
+                ..Default::default()
+            },
+            false, /* indexer */
+            BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            None, /* internal_indexer_db */
+            HotStateConfig::default(),
+            audit_sink,
+        )
+        .unwrap()
+    };
+    let txns =
+        [b"val_0", b"val_1", b"val_2"].map(|value| txn_to_commit_with_write(b"test_key", value));
+    let chain_head = |num_versions: usize| {
+        txns[..num_versions].iter().enumerate().fold(
+            HashValue::zero(),
+            |chain_head, (version, txn)| {
+                HashChainAuditSink::<Vec<u8>>::next_chain_head(
+                    chain_head,
+                    version as Version,
+                    txn.write_set.hash(),
+                )
+            },
+        )
+    };
+
+    let db = open(Arc::new(HashChainAuditSink::new(
+        Vec::new(),
+        HashValue::zero(),
+        None, /* last_version */
+    )));
+    db.save_transactions_for_test(
+        &txns[..1],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    assert_eq!(db.audit_chain_head(), Some(chain_head(1)));
+    drop(db);
+
+    // A new log is caught up on open, or at the next commit if the sink fails, which doesn't fail
+    // the commit.
+    let sink = Arc::new(FlakyAuditSink {
+        failing: AtomicBool::new(true),
+        inner: HashChainAuditSink::new(Vec::new(), HashValue::zero(), None),
+    });
+    let db = open(sink.clone());
+    assert_eq!(sink.last_version(), None);
+    db.save_transactions_for_test(
+        &txns[1..2],
+        1,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    assert_eq!(sink.last_version(), None);
+    sink.failing.store(false, Ordering::SeqCst);
+    db.save_transactions_for_test(
+        &txns[2..],
+        2,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    assert_eq!(sink.last_version(), Some(2));
+    assert_eq!(db.audit_chain_head(), Some(chain_head(3)));
+    drop(db);
+
+    let db = open(Arc::new(HashChainAuditSink::new(
+        Vec::new(),
+        HashValue::zero(),
+        None, /* last_version */
+    )));
+    assert_eq!(db.audit_chain_head(), Some(chain_head(3)));
+}
+
+#[test]
+fn test_open_with_wal_recovery() {
+    let tmp_dir = TempPath::new();
+    {
+        let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+        db.save_transactions_for_test(
+            &[txn_to_commit_with_write(b"test_key", b"test_val")],
+            0,    /* first_version */
+            None, /* ledger_info_with_sigs */
+            true, /* sync_commit */
+        )
+        .unwrap();
+    }
+
+    // Garbage at the tail of every WAL, as if the process died in the middle of a write.
+    fn garble_wals(dir: &Path) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                garble_wals(&path);
+            } else if path.extension().is_some_and(|ext| ext == "log") {
+                let mut file = std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&path)
+                    .unwrap();
+                std::io::Write::write_all(&mut file, &[0xAB; 100]).unwrap();
+            }
+        }
+    }
+    garble_wals(tmp_dir.path());
+
+    let open = |wal_recovery_mode| {
+        AptosDB::open_with_wal_recovery(
+            StorageDirPaths::from_path(&tmp_dir),
+            false, /* readonly */
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            RocksdbConfigs {
+                enable_storage_sharding: true,
+                ..Default::default()
+            },
+            false, /* indexer */
+            BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            None, /* internal_indexer_db */
+            HotStateConfig::default(),
+            wal_recovery_mode,
+            None, /* value_cipher */
+        )
+    };
+    // The corruption is caught without a lenient recovery mode.
+    assert!(open(WalRecoveryMode::AbsoluteConsistency).is_err());
+
+    let db = open(WalRecoveryMode::PointInTimeRecovery).unwrap();
+    assert_eq!(db.get_synced_version().unwrap(), Some(0));
+    assert_eq!(
+        db.get_state_value_by_version(&StateKey::raw(b"test_key"), 0)
+            .unwrap(),
+        Some(StateValue::from(b"test_val".to_vec()))
+    );
+}
+
+#[test]
+fn test_writers_of_key() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let txns = [
+        txn_to_commit_with_write(b"test_key", b"val_0"),
+        txn_to_commit_with_write(b"other_key", b"val_1"),
+        txn_to_commit_with_write(b"test_key", b"val_2"),
+    ];
+    for (version, txn) in txns.iter().enumerate() {
+        db.save_transactions_for_test(
+            std::slice::from_ref(txn),
+            version as Version, /* first_version */
+            None,               /* ledger_info_with_sigs */
+            true,               /* sync_commit */
+        )
+        .unwrap();
+    }
+
+    let writers = |start_version, end_version| {
+        db.state_store
+            .writers_of_key(&StateKey::raw(b"test_key"), start_version, end_version)
+            .unwrap()
+            .into_iter()
+            .map(|(version, txn)| {
+                assert_eq!(txn, txns[version as usize].transaction);
+                version
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(writers(0, 3), vec![0, 2]);
+    assert_eq!(writers(1, 3), vec![2]);
+    assert_eq!(writers(0, 2), vec![0]);
+    assert!(writers(1, 2).is_empty());
+}
+
+#[test]
+fn test_key_count_deltas() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let value = || Some(StateValue::from(b"val".to_vec()));
+    let write_sets = [
+        // Creates two keys.
+        vec![(b"key_0", value()), (b"key_1", value())],
+        // Modifies one, creates another.
+        vec![(b"key_0", value()), (b"key_2", value())],
+        // Deletes one, and one which doesn't exist.
+        vec![(b"key_1", None), (b"key_3", None)],
+        // Recreates the deleted one, deletes another.
+        vec![(b"key_1", value()), (b"key_2", None)],
+    ];
+    for (version, write_set) in write_sets.into_iter().enumerate() {
+        let mut txn_to_commit = txn_to_commit_with_write(b"key_0", b"val");
+        txn_to_commit.write_set = WriteSet::new_for_test(
+            write_set
+                .into_iter()
+                .map(|(key, value)| (StateKey::raw(key), value)),
+        );
+        db.save_transactions_for_test(
+            &[txn_to_commit],
+            version as Version, /* first_version */
+            None,               /* ledger_info_with_sigs */
+            true,               /* sync_commit */
+        )
+        .unwrap();
+    }
+
+    assert_eq!(db.state_store.key_count_deltas(0, 4).unwrap(), vec![
+        (0, 2),
+        (1, 1),
+        (2, -1),
+        (3, 0)
+    ]);
+    assert_eq!(db.state_store.key_count_deltas(1, 3).unwrap(), vec![
+        (1, 1),
+        (2, -1)
+    ]);
+    assert!(db.state_store.key_count_deltas(2, 2).unwrap().is_empty());
+    assert!(db.state_store.key_count_deltas(3, 2).is_err());
+}
+
+#[test]
+fn test_get_transition_proof() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let value = |bytes: &[u8]| Some(StateValue::from(bytes.to_vec()));
+    let write_sets = [
+        vec![(b"key_0", value(b"val_0")), (b"key_1", value(b"val_1"))],
+        vec![(b"key_2", value(b"val_2"))],
+        vec![(b"key_0", value(b"val_3")), (b"key_1", None)],
+    ];
+    for (version, write_set) in write_sets.into_iter().enumerate() {
+        let mut txn_to_commit = txn_to_commit_with_write(b"key_0", b"val");
+        txn_to_commit.write_set = WriteSet::new_for_test(
+            write_set
+                .into_iter()
+                .map(|(key, value)| (StateKey::raw(key), value)),
+        );
+        db.save_transactions_for_test(
+            &[txn_to_commit],
+            version as Version, /* first_version */
+            None,               /* ledger_info_with_sigs */
+            true,               /* sync_commit */
+        )
+        .unwrap();
+        db.state_store.buffered_state().lock().sync_commit();
+    }
+    let root = |version| db.state_store.get_root_hash(version).unwrap();
+
+    let proof = db.state_store.get_transition_proof(0, 2).unwrap();
+    let values_of = |key: &[u8]| {
+        let entry = proof
+            .entries
+            .iter()
+            .find(|entry| entry.state_key == StateKey::raw(key))
+            .unwrap();
+        (entry.old_value.clone(), entry.new_value.clone())
+    };
+    assert_eq!(proof.entries.len(), 3);
+    assert_eq!(values_of(b"key_0"), (value(b"val_0"), value(b"val_3")));
+    assert_eq!(values_of(b"key_1"), (value(b"val_1"), None));
+    assert_eq!(values_of(b"key_2"), (None, value(b"val_2")));
+    proof.verify(root(0), root(2)).unwrap();
+    assert!(proof.verify(root(0), root(1)).is_err());
+
+    // Leaving a change out is caught, even though the proofs left are all valid.
+    let mut partial_proof = proof.clone();
+    partial_proof
+        .entries
+        .retain(|entry| entry.state_key != StateKey::raw(b"key_2"));
+    assert!(partial_proof.verify(root(0), root(2)).is_err());
+
+    let empty_proof = db.state_store.get_transition_proof(1, 1).unwrap();
+    assert!(empty_proof.entries.is_empty());
+    empty_proof.verify(root(1), root(1)).unwrap();
+    assert!(db.state_store.get_transition_proof(2, 1).is_err());
+}
+
+#[test]
+fn test_open_ledger_db_standalone() {
+    for enable_storage_sharding in [false, true] {
+        let tmp_dir = TempPath::new();
+        let txn_to_commit = txn_to_commit_with_write(b"test_key", b"test_val");
+        {
+            let db = if enable_storage_sharding {
+                AptosDB::new_for_test_with_sharding(&tmp_dir, 1000)
+            } else {
+                AptosDB::new_for_test(&tmp_dir)
+            };
+            db.save_transactions_for_test(
+                &[txn_to_commit.clone()],
+                0,    /* first_version */
+                None, /* ledger_info_with_sigs */
+                true, /* sync_commit */
+            )
+            .unwrap();
+        }
+
+        let ledger_db = LedgerDb::open_standalone(&tmp_dir, RocksdbConfigs {
+            enable_storage_sharding,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(ledger_db.get_synced_version().unwrap(), Some(0));
+        assert_eq!(
+            ledger_db.get_transaction(0).unwrap(),
+            txn_to_commit.transaction
+        );
+        assert_eq!(
+            ledger_db.get_transaction_info(0).unwrap(),
+            txn_to_commit.transaction_info
+        );
+        assert_eq!(ledger_db.get_events(0).unwrap(), txn_to_commit.events);
+        assert_eq!(ledger_db.get_write_set(0).unwrap(), txn_to_commit.write_set);
+        assert!(ledger_db.get_transaction(1).is_err());
+    }
+}
+
+#[test]
+fn test_get_accumulator_frozen_subtrees() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let txns_to_commit = (0..5)
+        .map(|i| txn_to_commit_with_write(format!("test_key{i}").as_bytes(), b"test_val"))
+        .collect::<Vec<_>>();
+    db.save_transactions_for_test(
+        &txns_to_commit,
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    let txn_info_hashes = txns_to_commit
+        .iter()
+        .map(|txn_to_commit| txn_to_commit.transaction_info.hash())
+        .collect::<Vec<_>>();
+
+    let ledger_db = &db.ledger_db;
+    let accumulator_db = ledger_db.transaction_accumulator_db();
+    let last_root_hash = accumulator_db.get_root_hash(4).unwrap();
+    for version in 0..5 {
+        let frozen_subtrees = ledger_db.get_accumulator_frozen_subtrees(version).unwrap();
+        let accumulator =
+            InMemoryTransactionAccumulator::new(frozen_subtrees, version + 1).unwrap();
+        assert_eq!(
+            accumulator.root_hash(),
+            accumulator_db.get_root_hash(version).unwrap()
+        );
+        // Resuming from them gives the same roots as the DB.
+        assert_eq!(
+            accumulator
+                .append(&txn_info_hashes[version as usize + 1..])
+                .root_hash(),
+            last_root_hash
+        );
+    }
+    assert!(ledger_db.get_accumulator_frozen_subtrees(5).is_err());
+}
+
+#[test]
+fn test_subdb_version_skew() {
+    let tmp_dir = TempPath::new();
+    {
+        let db = AptosDB::new_for_test(&tmp_dir);
+        assert!(db.subdb_version_skew().is_consistent());
+        db.save_transactions_for_test(
+            &[txn_to_commit_with_write(b"test_key", b"test_val")],
+            0,    /* first_version */
+            None, /* ledger_info_with_sigs */
+            true, /* sync_commit */
+        )
+        .unwrap();
+    }
+    {
+        let db = AptosDB::new_for_test(&tmp_dir);
+        let sub_db_versions = db.subdb_version_skew();
+        assert_eq!(sub_db_versions.overall_commit_progress, Some(0));
+        assert_eq!(sub_db_versions.ledger_commit_progress, Some(0));
+        assert_eq!(sub_db_versions.state_kv_commit_progress, Some(0));
+        assert!(sub_db_versions.is_consistent());
+
+        // An interrupted commit, which only made it into the ledger DB.
+        db.ledger_db
+            .metadata_db_arc()
+            .put::<DbMetadataSchema>(
+                &DbMetadataKey::LedgerCommitProgress,
+                &DbMetadataValue::Version(1),
+            )
+            .unwrap();
+    }
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let sub_db_versions = db.subdb_version_skew();
+    assert_eq!(sub_db_versions.overall_commit_progress, Some(0));
+    assert_eq!(sub_db_versions.ledger_commit_progress, Some(1));
+    assert!(sub_db_versions.is_consistent());
+
+    // Beyond what interrupted commits leave, which opening the DB refuses to truncate.
+    let out_of_window = SubDbVersions {
+        ledger_commit_progress: Some(MAX_COMMIT_PROGRESS_DIFFERENCE + 1),
+        ..sub_db_versions
+    };
+    assert!(!out_of_window.is_consistent());
+    let at_window_end = SubDbVersions {
+        state_kv_commit_progress: Some(MAX_COMMIT_PROGRESS_DIFFERENCE),
+        state_merkle_max_version: Some(MAX_COMMIT_PROGRESS_DIFFERENCE),
+        ..sub_db_versions
+    };
+    assert!(at_window_end.is_consistent());
+    let merkle_out_of_window = SubDbVersions {
+        state_merkle_max_version: Some(MAX_COMMIT_PROGRESS_DIFFERENCE + 1),
+        ..sub_db_versions
+    };
+    assert!(!merkle_out_of_window.is_consistent());
+    let behind = SubDbVersions {
+        overall_commit_progress: Some(2),
+        ..sub_db_versions
+    };
+    assert!(!behind.is_consistent());
+    let nothing_committed = SubDbVersions {
+        overall_commit_progress: None,
+        ..sub_db_versions
+    };
+    assert!(nothing_committed.is_consistent());
+}
+
+#[test]
+fn test_read_txn() {
+    let tmp_dir = TempPath::new();
+    let db = Arc::new(AptosDB::new_for_test(&tmp_dir));
+    let state_key = StateKey::raw(b"test_key");
+    let empty_txn = db.read_txn().unwrap();
+    assert_eq!(empty_txn.version(), None);
+    assert_eq!(empty_txn.get_state_value(&state_key).unwrap(), None);
+
+    let txn_to_commit = txn_to_commit_with_write(b"test_key", b"old_val");
+    db.save_transactions_for_test(
+        &[txn_to_commit.clone()],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    let read_txn = db.read_txn().unwrap();
+    assert_eq!(read_txn.version(), Some(0));
+
+    db.save_transactions_for_test(
+        &[txn_to_commit_with_write(b"test_key", b"new_val")],
+        1,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    assert_eq!(
+        read_txn.get_state_value(&state_key).unwrap(),
+        Some(StateValue::from(b"old_val".to_vec()))
+    );
+    assert_eq!(
+        read_txn.get_transaction(0).unwrap(),
+        txn_to_commit.transaction
+    );
+    assert_eq!(read_txn.get_events(0).unwrap(), txn_to_commit.events);
+    assert!(read_txn.get_transaction(1).is_err());
+    assert!(empty_txn.get_transaction(0).is_err());
+
+    drop(read_txn);
+    // The read transaction unpinned its version.
+    assert!(db.unpin_version(0).is_err());
+}
+
+#[test]
+fn test_snapshot_reader() {
+    let tmp_dir = TempPath::new();
+    let db = Arc::new(AptosDB::new_for_test(&tmp_dir));
+    db.save_transactions_for_test(
+        &[txn_to_commit_with_write(b"test_key", b"old_val")],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+
+    let (reader, guard) = db.snapshot_reader().unwrap();
+    assert_eq!(guard.pinned_version(), Some(0));
+
+    db.save_transactions_for_test(
+        &[txn_to_commit_with_write(b"test_key", b"new_val")],
+        1,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    assert_eq!(db.get_synced_version().unwrap(), Some(1));
+    assert_eq!(reader.get_synced_version().unwrap(), Some(0));
+    assert_eq!(
+        reader.get_latest_state_checkpoint_version().unwrap(),
+        Some(0)
+    );
+    assert_eq!(
+        reader
+            .get_state_value_by_version(&StateKey::raw(b"test_key"), 0)
+            .unwrap(),
+        Some(StateValue::from(b"old_val".to_vec()))
+    );
+    assert!(reader.get_persisted_state().is_err());
+
+    drop(guard);
+    // The guard unpinned the version.
+    assert!(db.unpin_version(0).is_err());
+}
+
+#[test]
+fn test_isolated_handle() {
+    let tmp_dir = TempPath::new();
+    let db = Arc::new(AptosDB::new_for_test(&tmp_dir));
+    let handle = db.isolated_handle().unwrap();
+    assert!(db.isolated_handle().is_err());
+
+    db.save_transactions_for_test_through(
+        handle.writer.as_ref(),
+        &[txn_to_commit_with_write(b"test_key", b"val")],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+    assert_eq!(handle.reader.get_synced_version().unwrap(), Some(0));
+    assert_eq!(
+        handle
+            .reader
+            .get_state_value_by_version(&StateKey::raw(b"test_key"), 0)
+            .unwrap(),
+        Some(StateValue::from(b"val".to_vec()))
+    );
+
+    // Committing other than through the handle fails while it's alive.
+    let other_txn = txn_to_commit_with_write(b"test_key", b"other_val");
+    assert!(db
+        .save_transactions_for_test(&[other_txn.clone()], 1, None, true)
+        .is_err());
+    assert_eq!(handle.reader.get_synced_version().unwrap(), Some(0));
+    // As does restoring a state snapshot, through the handle too.
+    assert!(db
+        .get_state_snapshot_receiver(1, HashValue::random())
+        .is_err());
+    assert!(handle
+        .writer
+        .get_state_snapshot_receiver(1, HashValue::random())
+        .is_err());
+
+    drop(handle);
+    db.save_transactions_for_test(&[other_txn], 1, None, true)
+        .unwrap();
+    assert_eq!(db.get_synced_version().unwrap(), Some(1));
+
+    // Creating a handle waits for the writes in flight through the DB directly.
+    let not_isolated = db.ensure_not_isolated().unwrap();
+    let create_handle = std::thread::spawn({
+        let db = Arc::clone(&db);
+        move || db.isolated_handle().map(|_handle| ())
+    });
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert!(!create_handle.is_finished());
+    drop(not_isolated);
+    create_handle.join().unwrap().unwrap();
+}
+
+#[test]
+fn test_logical_equals() {
+    let tmp_dirs = [TempPath::new(), TempPath::new(), TempPath::new()];
+    let dbs = tmp_dirs
+        .iter()
+        .zip([b"test_val", b"test_val", b"diff_val"])
+        .map(|(tmp_dir, value)| {
+            let db = AptosDB::new_for_test(tmp_dir);
+            db.save_transactions_for_test(
+                &[txn_to_commit_with_write(b"test_key", value)],
+                0,    /* first_version */
+                None, /* ledger_info_with_sigs */
+                true, /* sync_commit */
+            )
+            .unwrap();
+            db
+        })
+        .collect::<Vec<_>>();
+
+    for compare_contents in [false, true] {
+        assert!(dbs[0].logical_equals(&dbs[1], 0, compare_contents).unwrap());
+        assert!(!dbs[0].logical_equals(&dbs[2], 0, compare_contents).unwrap());
+    }
+}
+
+#[test]
+fn test_logical_divergence() {
+    let tmp_dirs = [TempPath::new(), TempPath::new(), TempPath::new()];
+    let dbs = tmp_dirs
+        .iter()
+        .zip([b"test_val", b"test_val", b"diff_val"])
+        .map(|(tmp_dir, value)| {
+            let db = AptosDB::new_for_test(tmp_dir);
+            db.save_transactions_for_test(
+                &[txn_to_commit_with_write(b"test_key", value)],
+                0,    /* first_version */
+                None, /* ledger_info_with_sigs */
+                true, /* sync_commit */
+            )
+            .unwrap();
+            db
+        })
+        .collect::<Vec<_>>();
+
+    for compare_contents in [false, true] {
+        assert_eq!(
+            dbs[0]
+                .logical_divergence(&dbs[1], 0, compare_contents)
+                .unwrap(),
+            None
+        );
+        assert!(matches!(
+            dbs[0]
+                .logical_divergence(&dbs[2], 0, compare_contents)
+                .unwrap(),
+            Some(LogicalDivergence::LedgerRootHash(..))
+        ));
+    }
+
+    // A value that doesn't match the hashes of the ledger is only found comparing the contents.
+    let key = StateKey::raw(b"test_key");
+    let corrupted_value = StateValue::from(b"corrupted".to_vec());
+    dbs[1]
+        .state_kv_db
+        .metadata_db()
+        .put::<StateValueSchema>(&(key.clone(), 0), &Some(corrupted_value.clone()))
+        .unwrap();
+    assert_eq!(dbs[0].logical_divergence(&dbs[1], 0, false).unwrap(), None);
+    assert_eq!(
+        dbs[0].logical_divergence(&dbs[1], 0, true).unwrap(),
+        Some(LogicalDivergence::StateValue {
+            state_key: key,
+            version: 0,
+            values: (
+                Some(StateValue::from(b"test_val".to_vec())),
+                Some(corrupted_value)
+            ),
+        })
+    );
+}
+
+#[test]
+fn test_content_fingerprint() {
+    let tmp_dirs = [TempPath::new(), TempPath::new(), TempPath::new()];
+    let dbs = tmp_dirs
+        .iter()
+        .zip([b"test_val", b"test_val", b"diff_val"])
+        .map(|(tmp_dir, value)| {
+            let db = AptosDB::new_for_test(tmp_dir);
+            db.save_transactions_for_test(
+                &[
+                    txn_to_commit_with_write(b"test_key", value),
+                    txn_to_commit_with_write(b"other_key", b"other_val"),
+                ],
+                0,    /* first_version */
+                None, /* ledger_info_with_sigs */
+                true, /* sync_commit */
+            )
+            .unwrap();
+            db
+        })
+        .collect::<Vec<_>>();
+
+    for version in 0..2 {
+        let fingerprint = dbs[0].content_fingerprint(version).unwrap();
+        assert_eq!(fingerprint, dbs[1].content_fingerprint(version).unwrap());
+        assert_ne!(fingerprint, dbs[2].content_fingerprint(version).unwrap());
+    }
+    assert_ne!(
+        dbs[0].content_fingerprint(0).unwrap(),
+        dbs[0].content_fingerprint(1).unwrap()
+    );
+    assert!(dbs[0].content_fingerprint(2).is_err());
+}
+
+#[test]
+fn test_pending_buffered_by_shard() {
+    let tmp_dir = TempPath::new();
+    // Large enough for nothing to be flushed.
+    let db = AptosDB::new_for_test_with_buffered_state_target_items(&tmp_dir, 1000);
+    assert_eq!(
+        db.state_store.pending_buffered_by_shard(),
+        [0; NUM_STATE_SHARDS]
+    );
+
+    db.save_transactions_for_test(
+        &[
+            txn_to_commit_with_write(b"test_key", b"test_val1"),
+            txn_to_commit_with_write(b"test_key", b"test_val2"),
+            txn_to_commit_with_write(b"other_key", b"other_val"),
+        ],
+        0,     /* first_version */
+        None,  /* ledger_info_with_sigs */
+        false, /* sync_commit */
+    )
+    .unwrap();
+
+    let mut expected = [0; NUM_STATE_SHARDS];
+    for key in [b"test_key".as_slice(), b"other_key".as_slice()] {
+        expected[StateKey::raw(key).get_shard_id()] += 1;
+    }
+    assert_eq!(db.state_store.pending_buffered_by_shard(), expected);
+}
+
+#[test]
+fn test_schema_registry() {
+    let schemas = AptosDB::schema_registry();
+    let all_cf_names = [
+        ledger_db_column_families(),
+        state_merkle_db_column_families(),
+        state_kv_db_new_key_column_families(),
+        hot_state_kv_db_column_families(),
+    ]
+    .concat()
+    .into_iter()
+    .collect::<HashSet<_>>();
+    for schema in &schemas {
+        assert!(
+            all_cf_names.contains(schema.cf_name),
+            "{} is not opened.",
+            schema.cf_name
+        );
+    }
+    assert_eq!(
+        schemas
+            .iter()
+            .map(|schema| schema.cf_name)
+            .collect::<HashSet<_>>()
+            .len(),
+        schemas.len()
+    );
+
+    let transaction_schema = schemas
+        .iter()
+        .find(|schema| schema.name == "TransactionSchema")
+        .unwrap();
+    assert_eq!(transaction_schema.cf_name, TRANSACTION_CF_NAME);
+    assert_eq!(transaction_schema.key_type, "u64");
+    assert!(transaction_schema.value_type.ends_with("Transaction"));
+}
+
+#[test]
+fn test_open_dbs_with_wal_path() {
+    fn wal_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(wal_files(&path));
+            } else if path.extension().is_some_and(|ext| ext == "log") {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    let tmp_dir = TempPath::new();
+    let wal_dir = TempPath::new();
+    let db_paths = StorageDirPaths::from_path(&tmp_dir).with_wal_path(&wal_dir);
+    let rocksdb_configs = RocksdbConfigs {
+        enable_storage_sharding: true,
+        ..Default::default()
+    };
+    let _dbs = AptosDB::open_dbs(
+        &db_paths,
+        rocksdb_configs,
+        /* env = */ None,
+        /* block_cache = */ None,
+        /* readonly = */ false,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        /* reset_hot_state = */ false,
+    )
+    .unwrap();
+
+    assert!(wal_files(tmp_dir.path()).is_empty());
+    for db_name in [
+        "ledger_metadata_db",
+        "state_kv_db_shard_0",
+        "state_merkle_db_shard_0",
+    ] {
+        assert!(!wal_files(&wal_dir.path().join(db_name)).is_empty());
+    }
+}
+
+#[test]
+fn test_create_checkpoint_with_wal_path() {
+    let tmp_dir = TempPath::new();
+    let wal_dir = TempPath::new();
+    {
+        let db = AptosDB::open(
+            StorageDirPaths::from_path(&tmp_dir).with_wal_path(&wal_dir),
+            false, /* readonly */
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            RocksdbConfigs {
+                enable_storage_sharding: true,
+                ..Default::default()
+            },
+            false, /* indexer */
+            BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            None, /* internal_indexer_db */
+            HotStateConfig::default(),
+        )
+        .unwrap();
+        // Only in the WALs, nothing is flushed.
+        db.save_transactions_for_test(
+            &[txn_to_commit_with_write(b"test_key", b"test_val")],
+            0,    /* first_version */
+            None, /* ledger_info_with_sigs */
+            true, /* sync_commit */
+        )
+        .unwrap();
+    }
+
+    let cp_dir = TempPath::new();
+    cp_dir.create_as_dir().unwrap();
+    AptosDB::create_checkpoint_with_wal_path(
+        &tmp_dir,
+        Some(wal_dir.path()),
+        &cp_dir,
+        /* sharding = */ true,
+    )
+    .unwrap();
+    let db =
+        AptosDB::new_for_test_with_sharding(&cp_dir, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD);
+    assert_eq!(
+        db.get_state_value_by_version(&StateKey::raw(b"test_key"), 0)
+            .unwrap(),
+        Some(StateValue::from(b"test_val".to_vec()))
+    );
+}
+
+#[test]
+fn test_open_dbs_with_write_buffer_manager() {
+    let tmp_dir = TempPath::new();
+    let rocksdb_configs = RocksdbConfigs {
+        enable_storage_sharding: true,
+        ..Default::default()
+    };
+    let write_buffer_manager =
+        WriteBufferManager::new_write_buffer_manager(64 << 20, /* allow_stall = */ true);
+    let (_ledger_db, _hot_state_merkle_db, _state_merkle_db, state_kv_db) =
+        AptosDB::open_dbs_with_write_buffer_manager(
+            &StorageDirPaths::from_path(&tmp_dir),
+            rocksdb_configs,
+            /* env = */ None,
+            /* block_cache = */ None,
+            Some(&write_buffer_manager),
+            /* readonly = */ false,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            /* reset_hot_state = */ false,
+        )
+        .unwrap();
+
+    // The memtables of each of the shards, which are separate DBs, are charged to the manager.
+    for shard_id in 0..2 {
+        let usage_before = write_buffer_manager.get_usage();
+        let mut batch = SchemaBatch::new();
+        for i in 0..64u8 {
+            batch
+                .put::<StateValueByKeyHashSchema>(
+                    &(HashValue::sha3_256_of(&[shard_id, i]), 0),
+                    &Some(StateValue::from(vec![i; 1024])),
+                )
+                .unwrap();
+        }
+        state_kv_db
+            .db_shard(shard_id as usize)
+            .write_schemas(batch)
+            .unwrap();
+        assert!(write_buffer_manager.get_usage() > usage_before);
+    }
+}
+
+#[test]
+fn test_open_dbs_with_db_paths() {
+    for enable_storage_sharding in [false, true] {
+        let tmp_dir = TempPath::new();
+        let ledger_db_dir = TempPath::new();
+        let state_kv_db_dir = TempPath::new();
+        let state_merkle_db_dir = TempPath::new();
+        let db_paths = StorageDirPaths::from_path(&tmp_dir)
+            .with_ledger_db_path(&ledger_db_dir)
+            .with_state_kv_db_path(&state_kv_db_dir)
+            .with_state_merkle_db_path(&state_merkle_db_dir);
+        let rocksdb_configs = RocksdbConfigs {
+            enable_storage_sharding,
+            ..Default::default()
+        };
+        let _dbs = AptosDB::open_dbs(
+            &db_paths,
+            rocksdb_configs,
+            /* env = */ None,
+            /* block_cache = */ None,
+            /* readonly = */ false,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            /* reset_hot_state = */ false,
+        )
+        .unwrap();
+
+        assert!(ledger_db_dir.path().join("ledger_db").exists());
+        assert!(state_merkle_db_dir.path().join("state_merkle_db").exists());
+        assert!(!tmp_dir.path().join("ledger_db").exists());
+        assert!(!tmp_dir.path().join("state_merkle_db").exists());
+        if enable_storage_sharding {
+            assert!(state_kv_db_dir.path().join("state_kv_db").exists());
+            assert!(!tmp_dir.path().join("state_kv_db").exists());
+        }
+    }
+}
+
+#[test]
+fn test_open_dbs_with_state_value_zstd() {
+    let zstd_config = ZstdCompressionConfig::default();
+    let rocksdb_configs = RocksdbConfigs {
+        state_kv_db_config: RocksdbConfig {
+            state_value_zstd: Some(zstd_config),
+            ..Default::default()
+        },
+        enable_storage_sharding: true,
+        ..Default::default()
+    };
+    let state_kv_db_config = &rocksdb_configs.state_kv_db_config;
+    assert_eq!(
+        compression_for_cf(state_kv_db_config, STATE_VALUE_BY_KEY_HASH_CF_NAME),
+        ColumnFamilyCompression::Zstd(zstd_config)
+    );
+    assert_eq!(
+        compression_for_cf(
+            state_kv_db_config,
+            STALE_STATE_VALUE_INDEX_BY_KEY_HASH_CF_NAME
+        ),
+        ColumnFamilyCompression::Lz4
+    );
+    assert_eq!(
+        compression_for_cf(state_kv_db_config, JELLYFISH_MERKLE_NODE_CF_NAME),
+        ColumnFamilyCompression::Lz4
+    );
+
+    let tmp_dir = TempPath::new();
+    AptosDB::open_dbs(
+        &StorageDirPaths::from_path(&tmp_dir),
+        rocksdb_configs,
+        /* env = */ None,
+        /* block_cache = */ None,
+        /* readonly = */ false,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        /* reset_hot_state = */ false,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_open_dbs_with_ribbon_node_filter() {
+    let rocksdb_configs = RocksdbConfigs {
+        state_merkle_db_config: RocksdbConfig {
+            bloom_filter_bits: Some(10.0),
+            bloom_before_level: Some(2),
+            node_filter_policy: FilterPolicy::Ribbon,
+            ..Default::default()
+        },
+        enable_storage_sharding: true,
+        ..Default::default()
+    };
+    let state_merkle_db_config = &rocksdb_configs.state_merkle_db_config;
+    assert_eq!(
+        filter_for_cf(state_merkle_db_config, JELLYFISH_MERKLE_NODE_CF_NAME),
+        Some(ColumnFamilyFilter::Ribbon { bits_per_key: 10.0 })
+    );
+    assert_eq!(
+        filter_for_cf(state_merkle_db_config, STALE_NODE_INDEX_CF_NAME),
+        Some(ColumnFamilyFilter::HybridRibbon {
+            bits_per_key: 10.0,
+            bloom_before_level: 2,
+        })
+    );
+    assert_eq!(
+        filter_for_cf(
+            &RocksdbConfig {
+                node_filter_policy: FilterPolicy::Ribbon,
+                ..Default::default()
+            },
+            JELLYFISH_MERKLE_NODE_CF_NAME
+        ),
+        None
+    );
+
+    let tmp_dir = TempPath::new();
+    AptosDB::open_dbs(
+        &StorageDirPaths::from_path(&tmp_dir),
+        rocksdb_configs,
+        /* env = */ None,
+        /* block_cache = */ None,
+        /* readonly = */ false,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        /* reset_hot_state = */ false,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_open_dbs_with_state_value_whole_key_filtering() {
+    let default_config = RocksdbConfig::default();
+    assert!(!whole_key_filtering_for_cf(
+        &default_config,
+        STATE_VALUE_BY_KEY_HASH_CF_NAME
+    ));
+    assert!(whole_key_filtering_for_cf(
+        &default_config,
+        TRANSACTION_CF_NAME
+    ));
+
+    for whole_key_filtering in [false, true] {
+        let rocksdb_configs = RocksdbConfigs {
+            state_kv_db_config: RocksdbConfig {
+                state_value_whole_key_filtering: Some(whole_key_filtering),
+                ..Default::default()
+            },
+            enable_storage_sharding: true,
+            ..Default::default()
+        };
+        let state_kv_db_config = &rocksdb_configs.state_kv_db_config;
+        assert_eq!(
+            whole_key_filtering_for_cf(state_kv_db_config, STATE_VALUE_BY_KEY_HASH_CF_NAME),
+            whole_key_filtering
+        );
+        assert!(whole_key_filtering_for_cf(
+            state_kv_db_config,
+            STALE_STATE_VALUE_INDEX_BY_KEY_HASH_CF_NAME
+        ));
+
+        let tmp_dir = TempPath::new();
+        AptosDB::open_dbs(
+            &StorageDirPaths::from_path(&tmp_dir),
+            rocksdb_configs,
+            /* env = */ None,
+            /* block_cache = */ None,
+            /* readonly = */ false,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            /* reset_hot_state = */ false,
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_flush_on_commit() {
+    let num_memtable_entries = |db: &aptos_schemadb::DB| {
+        db.get_property(DB_METADATA_CF_NAME, "rocksdb.num-entries-active-mem-table")
+            .unwrap()
+    };
+    for flush_on_commit in [false, true] {
+        let rocksdb_configs = RocksdbConfigs {
+            state_kv_db_config: RocksdbConfig {
+                flush_on_commit,
+                ..Default::default()
+            },
+            enable_storage_sharding: true,
+            ..Default::default()
+        };
+        let tmp_dir = TempPath::new();
+        let (_ledger_db, _hot_state_merkle_db, _state_merkle_db, state_kv_db) = AptosDB::open_dbs(
+            &StorageDirPaths::from_path(&tmp_dir),
+            rocksdb_configs,
+            /* env = */ None,
+            /* block_cache = */ None,
+            /* readonly = */ false,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            /* reset_hot_state = */ false,
+        )
+        .unwrap();
+
+        // Each commit writes the progress of every shard and the overall one.
+        state_kv_db
+            .commit(0, None, state_kv_db.new_sharded_native_batches())
+            .unwrap();
+        for db in state_kv_db.all_dbs() {
+            assert_eq!(num_memtable_entries(db) == 0, flush_on_commit);
+        }
+    }
+}
+
+#[test]
+fn test_diff_options() {
+    let open_dbs = |tmp_dir: &TempPath, write_buffer_size| {
+        AptosDB::open_dbs(
+            &StorageDirPaths::from_path(tmp_dir),
+            RocksdbConfigs {
+                state_kv_db_config: RocksdbConfig {
+                    write_buffer_size,
+                    ..Default::default()
+                },
+                enable_storage_sharding: true,
+                ..Default::default()
+            },
+            /* env = */ None,
+            /* block_cache = */ None,
+            /* readonly = */ false,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            /* reset_hot_state = */ false,
+        )
+        .unwrap();
+    };
+    let tmp_dir = TempPath::new();
+    open_dbs(&tmp_dir, 1 << 20);
+    let other_tmp_dir = TempPath::new();
+    open_dbs(&other_tmp_dir, 2 << 20);
+
+    assert!(AptosDB::diff_options(&tmp_dir, &tmp_dir)
+        .unwrap()
+        .is_empty());
+    let diffs = AptosDB::diff_options(&tmp_dir, &other_tmp_dir).unwrap();
+    let write_buffer_size_diffs = diffs
+        .iter()
+        .filter(|diff| diff.option == "write_buffer_size")
+        .collect::<Vec<_>>();
+    assert!(!write_buffer_size_diffs.is_empty());
+    for diff in write_buffer_size_diffs {
+        assert!(diff.db_dir.starts_with("state_kv_db"));
+        assert_eq!(diff.value.as_deref(), Some("1048576"));
+        assert_eq!(diff.other_value.as_deref(), Some("2097152"));
+    }
+}
+
+#[test]
+fn test_split_max_open_files() {
+    let rocksdb_configs = RocksdbConfigs {
+        ledger_db_config: RocksdbConfig {
+            max_open_files_per_sub_db: Some(800),
+            ..Default::default()
+        },
+        state_kv_db_config: RocksdbConfig {
+            max_open_files_per_sub_db: Some(3300),
+            ..Default::default()
+        },
+        enable_storage_sharding: true,
+        ..Default::default()
+    };
+
+    let split = AptosDB::split_max_open_files(rocksdb_configs, /* readonly = */ false);
+    assert_eq!(split.ledger_db_config.max_open_files, 100);
+    assert_eq!(split.state_kv_db_config.max_open_files, 100);
+    // Not set, left as is.
+    assert_eq!(
+        split.state_merkle_db_config.max_open_files,
+        RocksdbConfig::default().max_open_files
+    );
+
+    // The hot state KV DB is not opened readonly.
+    let split = AptosDB::split_max_open_files(rocksdb_configs, /* readonly = */ true);
+    assert_eq!(split.state_kv_db_config.max_open_files, 194);
+
+    let split = AptosDB::split_max_open_files(
+        RocksdbConfigs {
+            enable_storage_sharding: false,
+            ..rocksdb_configs
+        },
+        /* readonly = */ false,
+    );
+    assert_eq!(split.ledger_db_config.max_open_files, 800);
+
+    let tmp_dir = TempPath::new();
+    AptosDB::open_dbs(
+        &StorageDirPaths::from_path(&tmp_dir),
+        rocksdb_configs,
+        /* env = */ None,
+        /* block_cache = */ None,
+        /* readonly = */ false,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        /* reset_hot_state = */ false,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_state_snapshot_commit_with_dedicated_pool() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::open(
+        StorageDirPaths::from_path(&tmp_dir),
+        /*readonly=*/ false,
+        NO_OP_STORAGE_PRUNER_CONFIG,
+        RocksdbConfigs {
+            state_snapshot_commit_concurrency: Some(2),
+            ..Default::default()
+        },
+        false, /* enable_indexer */
+        BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        None,
+        HotStateConfig::default(),
+    )
+    .unwrap();
+    let txn_to_commit = txn_to_commit_with_write(b"test_key", b"test_val");
+    db.save_transactions_for_test(
+        &[txn_to_commit.clone()],
+        0,    /* first_version */
+        None, /* ledger_info_with_sigs */
+        true, /* sync_commit */
+    )
+    .unwrap();
+
+    assert_eq!(
+        db.get_state_snapshot_before(1).unwrap(),
+        Some((
+            0,
+            txn_to_commit
+                .transaction_info
+                .state_checkpoint_hash()
+                .unwrap()
+        ))
+    );
+}
+
+#[test]
+fn test_restore_with_max_concurrent_shards() {
+    let tmp_dir = TempPath::new();
+    let db = Arc::new(AptosDB::new_for_test_with_sharding(
+        &tmp_dir,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    ));
+    let restore_handler =
+        db.get_restore_handler_with_max_concurrent_shards(NonZeroUsize::new(2).unwrap());
+
+    let mut chunk = (0..100u8)
+        .map(|i| (StateKey::raw(&[i]), StateValue::from(vec![i])))
+        .collect::<Vec<_>>();
+    // Restored chunks are sorted by key hash.
+    chunk.sort_by_key(|(key, _value)| key.hash());
+    let mut receiver = restore_handler
+        .get_state_restore_receiver(
+            0, /* version */
+            HashValue::zero(),
+            StateSnapshotRestoreMode::KvOnly,
+        )
+        .unwrap();
+    receiver
+        .add_chunk(chunk.clone(), SparseMerkleRangeProof::new(vec![]))
+        .unwrap();
+    receiver.finish().unwrap();
+
+    for (key, value) in chunk {
+        assert_eq!(db.get_state_value_by_version(&key, 0).unwrap(), Some(value));
+    }
+}
+
+#[test]
+fn test_restore_by_ingestion() {
+    let tmp_dir1 = TempPath::new();
+    let db1 =
+        AptosDB::new_for_test_with_sharding(&tmp_dir1, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD);
+    let input = (0..100u8)
+        .map(|i| (StateKey::raw(&[i]), StateValue::from(vec![i])))
+        .collect::<Vec<_>>();
+    update_store(
+        &db1.state_store,
+        input
+            .iter()
+            .map(|(key, value)| (key.clone(), Some(value.clone()))),
+        0, /* first_version */
+    );
+    let version = (input.len() - 1) as Version;
+    let expected_root_hash = db1.state_store.get_root_hash(version).unwrap();
+
+    let tmp_dir2 = TempPath::new();
+    let db2 = Arc::new(AptosDB::new_for_test_with_sharding(
+        &tmp_dir2,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    ));
+    let sst_dir = TempPath::new();
+    let mut receiver = db2
+        .get_restore_handler()
+        .get_state_restore_receiver_by_ingestion(
+            version,
+            expected_root_hash,
+            StateSnapshotRestoreMode::Default,
+            sst_dir.path().to_path_buf(),
+        )
+        .unwrap();
+    let chunk_size = 30;
+    for start_idx in (0..input.len()).step_by(chunk_size) {
+        let chunk = db1
+            .state_store
+            .get_value_chunk_with_proof(version, start_idx, chunk_size)
+            .unwrap();
+        receiver.add_chunk(chunk.raw_values, chunk.proof).unwrap();
+    }
+    receiver.finish().unwrap();
+
+    assert_eq!(
+        db2.state_store.get_root_hash(version).unwrap(),
+        expected_root_hash
+    );
+    assert_eq!(
+        db2.state_store.get_value_count(version).unwrap(),
+        input.len()
+    );
+    for (key, value) in input {
+        assert_eq!(
+            db2.get_state_value_by_version(&key, version).unwrap(),
+            Some(value)
+        );
+    }
+}
+
+#[test]
+fn test_ledger_pruner_archive() {
+    let archive_tmp_dir = TempPath::new();
+    let archive_db = Arc::new(
+        AptosDB::open_kv_only(
+            StorageDirPaths::from_path(&archive_tmp_dir),
+            /*readonly=*/ false,
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            RocksdbConfigs::default(),
+            false, /* enable_indexer */
+            BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+            DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+            None,
+        )
+        .unwrap(),
+    );
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::open_with_ledger_archive(
+        StorageDirPaths::from_path(&tmp_dir),
+        /*readonly=*/ false,
+        PrunerConfig {
+            ledger_pruner_config: LedgerPrunerConfig {
+                enable: true,
+                prune_window: 1,
+                batch_size: 1,
+                user_pruning_window_offset: 0,
+            },
+            ..NO_OP_STORAGE_PRUNER_CONFIG
+        },
+        RocksdbConfigs::default(),
+        false, /* enable_indexer */
+        BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+        None,
+        HotStateConfig::default(),
+        Arc::clone(&archive_db),
+    )
+    .unwrap();
+
+    let txns_to_commit = (0..4u8)
+        .map(|i| txn_to_commit_with_write(b"test_key", &[i]))
+        .collect::<Vec<_>>();
+    for (version, txn_to_commit) in txns_to_commit.iter().enumerate() {
+        db.save_transactions_for_test(
+            std::slice::from_ref(txn_to_commit),
+            version as Version, /* first_version */
+            None,               /* ledger_info_with_sigs */
+            true,               /* sync_commit */
+        )
+        .unwrap();
+    }
+    // Version 0 ends epoch 0.
+    let epoch_ending_ledger_info = LedgerInfoWithSignatures::new(
+        LedgerInfo::new(
+            BlockInfo::new(
+                0, /* epoch */
+                0, /* round */
+                HashValue::zero(),
+                db.get_accumulator_root_hash(0).unwrap(),
+                0, /* version */
+                0, /* timestamp_usecs */
+                Some(EpochState::empty()),
+            ),
+            HashValue::zero(),
+        ),
+        AggregateSignature::empty(),
+    );
+    db.get_restore_handler()
+        .save_ledger_infos(std::slice::from_ref(&epoch_ending_ledger_info))
+        .unwrap();
+    db.ledger_pruner.wake_and_wait_pruner(3).unwrap();
+    assert_eq!(db.ledger_pruner.get_min_readable_version(), 2);
+    assert_eq!(
+        archive_db.get_epoch_ending_ledger_info(0).unwrap(),
+        epoch_ending_ledger_info
+    );
+
+    assert_eq!(archive_db.get_synced_version().unwrap(), Some(1));
+    for (version, txn_to_commit) in txns_to_commit.iter().enumerate().take(2) {
+        let version = version as Version;
+        assert!(db.ledger_db.write_set_db().get_write_set(version).is_err());
+        assert_eq!(
+            archive_db
+                .ledger_db
+                .write_set_db()
+                .get_write_set(version)
+                .unwrap(),
+            txn_to_commit.write_set
+        );
+        assert_eq!(
+            archive_db
+                .ledger_db
+                .transaction_info_db()
+                .get_transaction_info(version)
+                .unwrap(),
+            txn_to_commit.transaction_info
+        );
+    }
+    let txn_info_hashes = txns_to_commit
+        .iter()
+        .take(2)
+        .map(|txn_to_commit| txn_to_commit.transaction_info.hash())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        archive_db
+            .ledger_db
+            .transaction_accumulator_db()
+            .get_root_hash(1)
+            .unwrap(),
+        InMemoryTransactionAccumulator::new_empty()
+            .append(&txn_info_hashes)
+            .root_hash()
+    );
+
+    // The reads of the pruned versions are served from the archive, as long as it has them all.
+    assert_eq!(
+        db.get_write_set_iterator(0, 2)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        txns_to_commit
+            .iter()
+            .take(2)
+            .map(|txn_to_commit| txn_to_commit.write_set.clone())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        db.get_accumulator_root_hash(1).unwrap(),
+        archive_db.get_accumulator_root_hash(1).unwrap()
+    );
+    assert!(db.get_write_set_iterator(1, 2).is_err());
+}
+
 pub fn test_state_merkle_pruning_impl(
     input: Vec<(Vec<TransactionToCommit>, LedgerInfoWithSignatures)>,
 ) {
@@ -251,6 +2080,8 @@ pub fn test_state_merkle_pruning_impl(
                 prune_window: 10,
                 batch_size: 1,
             },
+            enable_deletion_log: false,
+            deletion_log_retention_secs: 0,
         },
         RocksdbConfigs::default(),
         false, /* enable_indexer */
@@ -130,6 +130,24 @@ impl AptosDB {
         first_version: Version,
         ledger_info_with_sigs: Option<&LedgerInfoWithSignatures>,
         sync_commit: bool,
+    ) -> Result<()> {
+        self.save_transactions_for_test_through(
+            self,
+            txns_to_commit,
+            first_version,
+            ledger_info_with_sigs,
+            sync_commit,
+        )
+    }
+
+    /// Like `save_transactions_for_test`, but commits through `writer`, e.g. an isolated handle.
+    pub fn save_transactions_for_test_through(
+        &self,
+        writer: &dyn DbWriter,
+        txns_to_commit: &[TransactionToCommit],
+        first_version: Version,
+        ledger_info_with_sigs: Option<&LedgerInfoWithSignatures>,
+        sync_commit: bool,
     ) -> Result<()> {
         let (transactions, transaction_outputs, transaction_infos) =
             Self::disassemble_txns_to_commit(txns_to_commit);
@@ -192,7 +210,7 @@ impl AptosDB {
             is_reconfig: transactions_to_keep.is_reconfig(),
         };
 
-        self.save_transactions(chunk, ledger_info_with_sigs, sync_commit)
+        writer.save_transactions(chunk, ledger_info_with_sigs, sync_commit)
     }
 
     fn disassemble_txns_to_commit(
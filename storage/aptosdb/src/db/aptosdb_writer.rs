@@ -2,6 +2,7 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
+    audit_log::AuditSink,
     backup::restore_utils,
     db::{aptosdb_internal::gauged_api, AptosDB},
     ledger_db::{
@@ -18,8 +19,9 @@ use crate::{
         transaction_accumulator_root_hash::TransactionAccumulatorRootHashSchema,
     },
 };
-use aptos_crypto::HashValue;
+use aptos_crypto::{hash::CryptoHash, HashValue};
 use aptos_experimental_runtimes::thread_manager::THREAD_MANAGER;
+use aptos_logger::prelude::*;
 use aptos_metrics_core::TimerHelper;
 use aptos_schemadb::batch::SchemaBatch;
 use aptos_storage_interface::{
@@ -29,6 +31,7 @@ use aptos_storage_interface::{
 use aptos_types::{
     account_config::new_block_event_key,
     ledger_info::LedgerInfoWithSignatures,
+    proof::accumulator::InMemoryTransactionAccumulator,
     state_store::{state_key::StateKey, state_value::StateValue},
     transaction::{
         Transaction, TransactionAuxiliaryData, TransactionInfo, TransactionOutput,
@@ -42,37 +45,8 @@ use std::{iter::Iterator, time::Instant};
 
 impl DbWriter for AptosDB {
     fn pre_commit_ledger(&self, chunk: ChunkToCommit, sync_commit: bool) -> Result<()> {
-        gauged_api("pre_commit_ledger", || {
-            // Pre-committing and committing in concurrency is allowed but not pre-committing at the
-            // same time from multiple threads, the same for committing.
-            // Consensus and state sync must hand over to each other after all pending execution and
-            // committing complete.
-            let _lock = self
-                .pre_commit_lock
-                .try_lock()
-                .expect("Concurrent committing detected.");
-            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["pre_commit_ledger"]);
-
-            chunk
-                .state_summary
-                .latest()
-                .global_state_summary
-                .log_generation("db_save");
-
-            self.pre_commit_validation(&chunk)?;
-            let _new_root_hash =
-                self.calculate_and_commit_ledger_and_state_kv(&chunk, self.skip_index_and_usage)?;
-
-            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["save_transactions__others"]);
-
-            self.state_store.buffered_state().lock().update(
-                chunk.result_ledger_state_with_summary(),
-                chunk.estimated_total_state_updates(),
-                sync_commit || chunk.is_reconfig,
-            )?;
-
-            Ok(())
-        })
+        let _not_isolated = self.ensure_not_isolated()?;
+        self.pre_commit_ledger_impl(chunk, sync_commit)
     }
 
     fn commit_ledger(
@@ -81,34 +55,8 @@ impl DbWriter for AptosDB {
         ledger_info_with_sigs: Option<&LedgerInfoWithSignatures>,
         chunk_opt: Option<ChunkToCommit>,
     ) -> Result<()> {
-        gauged_api("commit_ledger", || {
-            // Pre-committing and committing in concurrency is allowed but not pre-committing at the
-            // same time from multiple threads, the same for committing.
-            // Consensus and state sync must hand over to each other after all pending execution and
-            // committing complete.
-            let _lock = self
-                .commit_lock
-                .try_lock()
-                .expect("Concurrent committing detected.");
-            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["commit_ledger"]);
-
-            let old_committed_ver = self.get_and_check_commit_range(version)?;
-
-            let mut ledger_batch = SchemaBatch::new();
-            // Write down LedgerInfo if provided.
-            if let Some(li) = ledger_info_with_sigs {
-                self.check_and_put_ledger_info(version, li, &mut ledger_batch)?;
-            }
-            // Write down commit progress
-            ledger_batch.put::<DbMetadataSchema>(
-                &DbMetadataKey::OverallCommitProgress,
-                &DbMetadataValue::Version(version),
-            )?;
-            self.ledger_db.metadata_db().write_schemas(ledger_batch)?;
-
-            // Notify the pruners, invoke the indexer, and update in-memory ledger info.
-            self.post_commit(old_committed_ver, version, ledger_info_with_sigs, chunk_opt)
-        })
+        let _not_isolated = self.ensure_not_isolated()?;
+        self.commit_ledger_impl(version, ledger_info_with_sigs, chunk_opt)
     }
 
     fn get_state_snapshot_receiver(
@@ -116,6 +64,7 @@ impl DbWriter for AptosDB {
         version: Version,
         expected_root_hash: HashValue,
     ) -> Result<Box<dyn StateSnapshotReceiver<StateKey, StateValue>>> {
+        let _not_isolated = self.ensure_not_isolated()?;
         gauged_api("get_state_snapshot_receiver", || {
             self.state_store
                 .get_snapshot_receiver(version, expected_root_hash)
@@ -128,6 +77,7 @@ impl DbWriter for AptosDB {
         output_with_proof: TransactionOutputListWithProofV2,
         ledger_infos: &[LedgerInfoWithSignatures],
     ) -> Result<()> {
+        let _not_isolated = self.ensure_not_isolated()?;
         let (output_with_proof, persisted_aux_info) = output_with_proof.into_parts();
         gauged_api("finalize_state_snapshot", || {
             // Ensure the output with proof only contains a single transaction output and info
@@ -245,6 +195,88 @@ impl DbWriter for AptosDB {
 }
 
 impl AptosDB {
+    pub(super) fn pre_commit_ledger_impl(
+        &self,
+        chunk: ChunkToCommit,
+        sync_commit: bool,
+    ) -> Result<()> {
+        gauged_api("pre_commit_ledger", || {
+            // Pre-committing and committing in concurrency is allowed but not pre-committing at the
+            // same time from multiple threads, the same for committing.
+            // Consensus and state sync must hand over to each other after all pending execution and
+            // committing complete.
+            let _lock = self
+                .pre_commit_lock
+                .try_lock()
+                .expect("Concurrent committing detected.");
+            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["pre_commit_ledger"]);
+
+            chunk
+                .state_summary
+                .latest()
+                .global_state_summary
+                .log_generation("db_save");
+
+            self.pre_commit_validation(&chunk)?;
+            let _new_root_hash =
+                self.calculate_and_commit_ledger_and_state_kv(&chunk, self.skip_index_and_usage)?;
+            if self.verify_ledger_root_on_commit {
+                self.verify_transaction_accumulator_root(
+                    chunk.first_version,
+                    chunk.transaction_infos,
+                )?;
+            }
+
+            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["save_transactions__others"]);
+
+            self.state_store.buffered_state().lock().update(
+                chunk.result_ledger_state_with_summary(),
+                chunk.estimated_total_state_updates(),
+                sync_commit || chunk.is_reconfig,
+            )?;
+
+            Ok(())
+        })
+    }
+
+    pub(super) fn commit_ledger_impl(
+        &self,
+        version: Version,
+        ledger_info_with_sigs: Option<&LedgerInfoWithSignatures>,
+        chunk_opt: Option<ChunkToCommit>,
+    ) -> Result<()> {
+        gauged_api("commit_ledger", || {
+            // Pre-committing and committing in concurrency is allowed but not pre-committing at the
+            // same time from multiple threads, the same for committing.
+            // Consensus and state sync must hand over to each other after all pending execution and
+            // committing complete.
+            let _lock = self
+                .commit_lock
+                .try_lock()
+                .expect("Concurrent committing detected.");
+            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["commit_ledger"]);
+
+            let old_committed_ver = self.get_and_check_commit_range(version)?;
+
+            let mut ledger_batch = SchemaBatch::new();
+            // Write down LedgerInfo if provided.
+            if let Some(li) = ledger_info_with_sigs {
+                self.check_and_put_ledger_info(version, li, &mut ledger_batch)?;
+            }
+            // Write down commit progress
+            ledger_batch.put::<DbMetadataSchema>(
+                &DbMetadataKey::OverallCommitProgress,
+                &DbMetadataValue::Version(version),
+            )?;
+            self.ledger_db.metadata_db().write_schemas(ledger_batch)?;
+
+            // Notify the pruners, invoke the indexer, and update in-memory ledger info.
+            self.post_commit(old_committed_ver, version, ledger_info_with_sigs, chunk_opt)?;
+            self.catch_up_audit_log(version);
+            Ok(())
+        })
+    }
+
     fn pre_commit_validation(&self, chunk: &ChunkToCommit) -> Result<()> {
         let _timer = OTHER_TIMERS_SECONDS.timer_with(&["save_transactions_validation"]);
 
@@ -263,6 +295,49 @@ impl AptosDB {
         Ok(())
     }
 
+    /// Catches the audit sink, if any, up to `committed_version`, from the version after the last
+    /// one it appended. A failure is logged rather than returned, as the versions are committed
+    /// by then, and the next call retries from where the sink stopped.
+    pub(super) fn catch_up_audit_log(&self, committed_version: Version) {
+        let Some(audit_sink) = &self.audit_sink else {
+            return;
+        };
+        if let Err(err) = self.catch_up_audit_log_impl(audit_sink.as_ref(), committed_version) {
+            warn!(
+                committed_version = committed_version,
+                last_version = ?audit_sink.last_version(),
+                error = ?err,
+                "Failed to catch the audit log up, retrying on the next commit."
+            );
+        }
+    }
+
+    fn catch_up_audit_log_impl(
+        &self,
+        audit_sink: &dyn AuditSink,
+        committed_version: Version,
+    ) -> Result<()> {
+        let first_version = audit_sink.last_version().map_or(0, |v| v + 1);
+        if committed_version < first_version {
+            return Ok(());
+        }
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["catch_up_audit_log"]);
+
+        let write_sets = self.ledger_db.write_set_db().get_write_set_iter(
+            first_version,
+            (committed_version + 1 - first_version) as usize,
+        )?;
+        for (version, write_set) in (first_version..).zip(write_sets) {
+            audit_sink.append(version, &write_set?).map_err(|err| {
+                AptosDbError::Other(format!(
+                    "Failed to append the write set at version {} to the audit log: {}",
+                    version, err
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
     fn calculate_and_commit_ledger_and_state_kv(
         &self,
         chunk: &ChunkToCommit,
@@ -471,6 +546,38 @@ impl AptosDB {
         Ok(root_hash)
     }
 
+    /// Recomputes the accumulator root after `transaction_infos` from the frozen subtrees before
+    /// them, and checks it against the root persisted by `commit_transaction_accumulator()`.
+    fn verify_transaction_accumulator_root(
+        &self,
+        first_version: Version,
+        transaction_infos: &[TransactionInfo],
+    ) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["verify_transaction_accumulator_root"]);
+
+        let accumulator_db = self.ledger_db.transaction_accumulator_db();
+        let frozen_subtrees = accumulator_db.get_frozen_subtree_hashes(first_version)?;
+        let txn_info_hashes = transaction_infos
+            .iter()
+            .map(CryptoHash::hash)
+            .collect::<Vec<_>>();
+        let expected_root_hash =
+            InMemoryTransactionAccumulator::new(frozen_subtrees, first_version)?
+                .append(&txn_info_hashes)
+                .root_hash();
+
+        let last_version = first_version + transaction_infos.len() as Version - 1;
+        let root_hash = accumulator_db.get_root_hash(last_version)?;
+        ensure!(
+            root_hash == expected_root_hash,
+            "Transaction accumulator root hash at version {} diverges, persisted: {}, recomputed: {}.",
+            last_version,
+            root_hash,
+            expected_root_hash,
+        );
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub(super) fn commit_transaction_auxiliary_data<'a>(
         &self,
@@ -0,0 +1,110 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! A reader and writer pair which has the commit path of the DB to itself, for tests and
+//! simulations that need their reads to see exactly what they committed.
+//!
+//! While the handle is alive, committing through the DB directly fails instead of landing between
+//! the handle's writes and reads, and creating the handle waits for the commits in flight. The
+//! handle's own commits are serialized by a lock rather than detected as concurrent, so it can be
+//! shared by threads.
+
+use crate::AptosDB;
+use aptos_crypto::HashValue;
+use aptos_storage_interface::{
+    chunk_to_commit::ChunkToCommit, db_ensure as ensure, AptosDbError, DbReader, DbReaderWriter,
+    DbWriter, Result, StateSnapshotReceiver,
+};
+use aptos_types::{
+    ledger_info::LedgerInfoWithSignatures,
+    state_store::{state_key::StateKey, state_value::StateValue},
+    transaction::{TransactionOutputListWithProofV2, Version},
+};
+use std::sync::{Arc, Mutex, RwLockReadGuard};
+
+/// See [`AptosDB::isolated_handle`].
+struct IsolatedDb {
+    db: Arc<AptosDB>,
+    commit_lock: Mutex<()>,
+}
+
+impl DbReader for IsolatedDb {
+    fn get_read_delegatee(&self) -> &dyn DbReader {
+        self.db.as_ref()
+    }
+}
+
+impl DbWriter for IsolatedDb {
+    fn get_state_snapshot_receiver(
+        &self,
+        _version: Version,
+        _expected_root_hash: HashValue,
+    ) -> Result<Box<dyn StateSnapshotReceiver<StateKey, StateValue>>> {
+        Err(AptosDbError::Other(
+            "State snapshots can't be restored through an isolated handle.".to_string(),
+        ))
+    }
+
+    fn finalize_state_snapshot(
+        &self,
+        _version: Version,
+        _output_with_proof: TransactionOutputListWithProofV2,
+        _ledger_infos: &[LedgerInfoWithSignatures],
+    ) -> Result<()> {
+        Err(AptosDbError::Other(
+            "State snapshots can't be restored through an isolated handle.".to_string(),
+        ))
+    }
+
+    fn pre_commit_ledger(&self, chunk: ChunkToCommit, sync_commit: bool) -> Result<()> {
+        let _lock = self.commit_lock.lock().expect("Lock poisoned.");
+        self.db.pre_commit_ledger_impl(chunk, sync_commit)
+    }
+
+    fn commit_ledger(
+        &self,
+        version: Version,
+        ledger_info_with_sigs: Option<&LedgerInfoWithSignatures>,
+        chunk_opt: Option<ChunkToCommit>,
+    ) -> Result<()> {
+        let _lock = self.commit_lock.lock().expect("Lock poisoned.");
+        self.db
+            .commit_ledger_impl(version, ledger_info_with_sigs, chunk_opt)
+    }
+}
+
+impl Drop for IsolatedDb {
+    fn drop(&mut self) {
+        *self.db.isolated.write().expect("Lock poisoned.") = false;
+    }
+}
+
+impl AptosDB {
+    /// Returns a reader and writer pair through which alone the DB can be committed to until it's
+    /// dropped, so that reads through it see what was committed through it and nothing else. Fails
+    /// if another isolated handle is alive.
+    ///
+    /// Only committing transactions goes through the handle, i.e. `pre_commit_ledger()`,
+    /// `commit_ledger()` and `save_transactions()`, restoring a state snapshot through it fails.
+    pub fn isolated_handle(self: &Arc<Self>) -> Result<DbReaderWriter> {
+        // Waits for the writes in flight through the DB directly, which hold the lock for read.
+        let mut isolated = self.isolated.write().expect("Lock poisoned.");
+        ensure!(!*isolated, "Another isolated handle to the DB is alive.");
+        *isolated = true;
+        Ok(DbReaderWriter::new(IsolatedDb {
+            db: Arc::clone(self),
+            commit_lock: Mutex::new(()),
+        }))
+    }
+
+    /// Fails if an isolated handle is alive, otherwise keeps one from being created until the
+    /// returned guard is dropped. Every write through the DB directly holds it.
+    pub(super) fn ensure_not_isolated(&self) -> Result<RwLockReadGuard<'_, bool>> {
+        let isolated = self.isolated.read().expect("Lock poisoned.");
+        ensure!(
+            !*isolated,
+            "The DB can only be written to through its isolated handle."
+        );
+        Ok(isolated)
+    }
+}
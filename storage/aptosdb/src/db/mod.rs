@@ -1,19 +1,52 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
+pub use crate::rocksdb_property_reporter::{
+    CfCompactionStats, DbWriteStallStatus, WriteStallStatus,
+};
 use crate::{
-    backup::backup_handler::BackupHandler, event_store::EventStore, ledger_db::LedgerDb,
-    pruner::LedgerPrunerManager, rocksdb_property_reporter::RocksdbPropertyReporter,
-    state_kv_db::StateKvDb, state_merkle_db::StateMerkleDb, state_store::StateStore,
+    audit_log::AuditSink,
+    backup::backup_handler::BackupHandler,
+    event_store::EventStore,
+    ledger_db::LedgerDb,
+    metrics::ROCKSDB_CONFIGURED_OPTIONS,
+    pruner::{
+        deletion_log::get_deletion_records, DeletionRecord, LedgerPrunerManager, PrunerManager,
+    },
+    rocksdb_property_reporter::{
+        get_compaction_stats, get_write_stall_status, RocksdbPropertyReporter,
+    },
+    schema::{self, db_metadata::ShardConfig, SchemaInfo},
+    state_kv_db::StateKvDb,
+    state_merkle_db::StateMerkleDb,
+    state_store::{StateStore, SubDbVersions},
     transaction_store::TransactionStore,
+    utils::options_diff::{self, OptionDiff},
+};
+use aptos_config::config::{
+    HotStateConfig, PrunerConfig, RocksdbConfigs, StorageDirPaths, WalRecoveryMode,
+    BUFFERED_STATE_TARGET_ITEMS, NO_OP_STORAGE_PRUNER_CONFIG,
 };
-use aptos_config::config::{HotStateConfig, PrunerConfig, RocksdbConfigs, StorageDirPaths};
+use aptos_crypto::{hash::DefaultHasher, HashValue};
 use aptos_db_indexer::{db_indexer::InternalIndexerDB, Indexer};
 use aptos_logger::prelude::*;
-use aptos_schemadb::{batch::SchemaBatch, Cache, Env};
-use aptos_storage_interface::{db_ensure as ensure, AptosDbError, Result};
-use aptos_types::{ledger_info::LedgerInfoWithSignatures, transaction::Version};
-use std::{path::Path, sync::Arc, time::Instant};
+pub use aptos_schemadb::io_accounting::{IoAccountingHandle, IoAccountingReport};
+use aptos_schemadb::{
+    batch::SchemaBatch, value_cipher::ValueCipher, Cache, Env, WriteBufferManager, DB,
+};
+use aptos_storage_interface::{db_ensure as ensure, AptosDbError, DbReader, Result};
+use aptos_types::{
+    ledger_info::LedgerInfoWithSignatures,
+    state_store::{state_key::StateKey, state_value::StateValue, NUM_STATE_SHARDS},
+    transaction::{Transaction, Version},
+};
+use itertools::{EitherOrBoth, Itertools};
+use std::{
+    ops::Range,
+    path::Path,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
 use tokio::sync::watch::Sender;
 
 #[cfg(test)]
@@ -30,14 +63,25 @@ pub struct AptosDB {
     pub(crate) state_store: Arc<StateStore>,
     pub(crate) transaction_store: Arc<TransactionStore>,
     ledger_pruner: LedgerPrunerManager,
+    /// Where the ledger pruner archives what it prunes, see `AptosDB::open_with_ledger_archive`.
+    ledger_archive_db: Option<Arc<AptosDB>>,
     _rocksdb_property_reporter: RocksdbPropertyReporter,
     /// This is just to detect concurrent calls to `pre_commit_ledger()`
     pre_commit_lock: std::sync::Mutex<()>,
     /// This is just to detect concurrent calls to `commit_ledger()`
     commit_lock: std::sync::Mutex<()>,
+    /// Set while an isolated handle has the commit path to itself, see `AptosDB::isolated_handle`.
+    /// The writes through the DB directly hold it for read, so creating the handle waits for them.
+    isolated: std::sync::RwLock<bool>,
     indexer: Option<Indexer>,
     skip_index_and_usage: bool,
     update_subscriber: Option<Sender<(Instant, Version)>>,
+    /// See `StorageConfig::verify_ledger_root_on_commit`.
+    verify_ledger_root_on_commit: bool,
+    /// See `AptosDB::open_with_audit_sink`.
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    /// See `AptosDB::subdb_version_skew`.
+    sub_db_versions_on_open: SubDbVersions,
 }
 
 // DbReader implementations and private functions used by them.
@@ -46,6 +90,10 @@ mod aptosdb_reader;
 mod aptosdb_writer;
 // Other private methods.
 mod aptosdb_internal;
+// Point-in-time reader over the DB.
+mod snapshot_reader;
+// Reader and writer pair with the commit path to itself.
+mod isolated_handle;
 // Testonly methods.
 #[cfg(any(test, feature = "fuzzing", feature = "consensus-only-perf-test"))]
 mod aptosdb_testonly;
@@ -53,6 +101,29 @@ mod aptosdb_testonly;
 #[cfg(feature = "consensus-only-perf-test")]
 pub mod fake_aptosdb;
 
+pub use snapshot_reader::{ReadTxn, SnapshotGuard};
+
+/// The first difference found by [`AptosDB::logical_divergence`] between two DBs, with the
+/// values in `self` and in `other` in this order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogicalDivergence {
+    /// The transaction accumulator root hashes differ.
+    LedgerRootHash(HashValue, HashValue),
+    /// The hashes of the latest state checkpoints differ.
+    StateCheckpointHash(HashValue, HashValue),
+    /// The transactions at `version` differ, `None` meaning that it's missing.
+    Transaction {
+        version: Version,
+        transactions: (Option<Transaction>, Option<Transaction>),
+    },
+    /// The values of `state_key` at `version` differ, `None` meaning that it doesn't exist.
+    StateValue {
+        state_key: StateKey,
+        version: Version,
+        values: (Option<StateValue>, Option<StateValue>),
+    },
+}
+
 impl AptosDB {
     pub fn open(
         db_paths: StorageDirPaths,
@@ -76,9 +147,163 @@ impl AptosDB {
             false,
             internal_indexer_db,
             hot_state_config,
+            /* ledger_archive_db = */ None,
+            /* value_cipher = */ None,
+        )
+    }
+
+    /// Same as `open`, except that before the ledger pruner deletes any data, it copies it into
+    /// `ledger_archive_db`, which keeps it queryable there: the ledger reads of pruned versions
+    /// are served from the archive DB when it has them. The archive DB is expected to be opened
+    /// with `open_kv_only`, as only the ledger data is written into it.
+    pub fn open_with_ledger_archive(
+        db_paths: StorageDirPaths,
+        readonly: bool,
+        pruner_config: PrunerConfig,
+        rocksdb_configs: RocksdbConfigs,
+        enable_indexer: bool,
+        buffered_state_target_items: usize,
+        max_num_nodes_per_lru_cache_shard: usize,
+        internal_indexer_db: Option<InternalIndexerDB>,
+        hot_state_config: HotStateConfig,
+        ledger_archive_db: Arc<AptosDB>,
+    ) -> Result<Self> {
+        Self::open_internal(
+            &db_paths,
+            readonly,
+            pruner_config,
+            rocksdb_configs,
+            enable_indexer,
+            buffered_state_target_items,
+            max_num_nodes_per_lru_cache_shard,
+            false,
+            internal_indexer_db,
+            hot_state_config,
+            Some(ledger_archive_db),
+            /* value_cipher = */ None,
+        )
+    }
+
+    /// Same as `open`, except that the state values are encrypted at rest with `value_cipher`,
+    /// e.g. an `Aes256GcmValueCipher` keyed from a KMS, both in the state K/V DB and in the write
+    /// sets. Reads and writes through the DB see the plaintext, so the Jellyfish Merkle tree
+    /// hashes and the proofs are the same as without encryption. The DB must be opened with a
+    /// cipher holding the keys the values were encrypted with every time, and a DB created
+    /// without encryption can't be opened with one, nor the other way around.
+    pub fn open_with_value_cipher(
+        db_paths: StorageDirPaths,
+        readonly: bool,
+        pruner_config: PrunerConfig,
+        rocksdb_configs: RocksdbConfigs,
+        enable_indexer: bool,
+        buffered_state_target_items: usize,
+        max_num_nodes_per_lru_cache_shard: usize,
+        internal_indexer_db: Option<InternalIndexerDB>,
+        hot_state_config: HotStateConfig,
+        value_cipher: Arc<dyn ValueCipher>,
+    ) -> Result<Self> {
+        Self::open_internal(
+            &db_paths,
+            readonly,
+            pruner_config,
+            rocksdb_configs,
+            enable_indexer,
+            buffered_state_target_items,
+            max_num_nodes_per_lru_cache_shard,
+            false,
+            internal_indexer_db,
+            hot_state_config,
+            /* ledger_archive_db = */ None,
+            Some(value_cipher),
         )
     }
 
+    /// Same as `open`, except that all the RocksDB instances replay their write ahead logs with
+    /// `wal_recovery_mode`, overriding the `wal_recovery_mode` of the config of each sub-DB, and
+    /// the synced version recovered is logged. E.g. `PointInTimeRecovery` salvages a DB configured
+    /// with `AbsoluteConsistency` whose WALs were truncated or corrupted by an abrupt shutdown: the
+    /// writes up to the corruption are kept, then the sub-DBs are truncated to their common commit
+    /// progress as on every open. `value_cipher` is the one the DB was opened with, if any, see
+    /// `open_with_value_cipher`.
+    pub fn open_with_wal_recovery(
+        db_paths: StorageDirPaths,
+        readonly: bool,
+        pruner_config: PrunerConfig,
+        mut rocksdb_configs: RocksdbConfigs,
+        enable_indexer: bool,
+        buffered_state_target_items: usize,
+        max_num_nodes_per_lru_cache_shard: usize,
+        internal_indexer_db: Option<InternalIndexerDB>,
+        hot_state_config: HotStateConfig,
+        wal_recovery_mode: WalRecoveryMode,
+        value_cipher: Option<Arc<dyn ValueCipher>>,
+    ) -> Result<Self> {
+        for config in [
+            &mut rocksdb_configs.ledger_db_config,
+            &mut rocksdb_configs.state_merkle_db_config,
+            &mut rocksdb_configs.state_kv_db_config,
+        ] {
+            config.wal_recovery_mode = Some(wal_recovery_mode);
+        }
+        let db = Self::open_internal(
+            &db_paths,
+            readonly,
+            pruner_config,
+            rocksdb_configs,
+            enable_indexer,
+            buffered_state_target_items,
+            max_num_nodes_per_lru_cache_shard,
+            false,
+            internal_indexer_db,
+            hot_state_config,
+            /* ledger_archive_db = */ None,
+            value_cipher,
+        )?;
+        let synced_version = db.get_synced_version()?;
+        warn!(
+            wal_recovery_mode = ?wal_recovery_mode,
+            synced_version = ?synced_version,
+            "Opened AptosDB with WAL recovery."
+        );
+        Ok(db)
+    }
+
+    /// Same as `open`, except that `commit_ledger()` appends every write set committed to
+    /// `audit_sink` once the commit is persisted, see [`crate::audit_log`]. The sink is caught up
+    /// to the committed version here first.
+    pub fn open_with_audit_sink(
+        db_paths: StorageDirPaths,
+        readonly: bool,
+        pruner_config: PrunerConfig,
+        rocksdb_configs: RocksdbConfigs,
+        enable_indexer: bool,
+        buffered_state_target_items: usize,
+        max_num_nodes_per_lru_cache_shard: usize,
+        internal_indexer_db: Option<InternalIndexerDB>,
+        hot_state_config: HotStateConfig,
+        audit_sink: Arc<dyn AuditSink>,
+    ) -> Result<Self> {
+        let mut db = Self::open_internal(
+            &db_paths,
+            readonly,
+            pruner_config,
+            rocksdb_configs,
+            enable_indexer,
+            buffered_state_target_items,
+            max_num_nodes_per_lru_cache_shard,
+            false,
+            internal_indexer_db,
+            hot_state_config,
+            /* ledger_archive_db = */ None,
+            /* value_cipher = */ None,
+        )?;
+        db.audit_sink = Some(audit_sink);
+        if let Some(synced_version) = db.get_synced_version()? {
+            db.catch_up_audit_log(synced_version);
+        }
+        Ok(db)
+    }
+
     pub fn open_kv_only(
         db_paths: StorageDirPaths,
         readonly: bool,
@@ -100,6 +325,39 @@ impl AptosDB {
             true,
             internal_indexer_db,
             HotStateConfig::default(),
+            /* ledger_archive_db = */ None,
+            /* value_cipher = */ None,
+        )
+    }
+
+    /// Opens the DB for reading only, in a way that allows multiple processes to open the same
+    /// directory at the same time (e.g. read replicas fanning out on a shared volume).
+    ///
+    /// Each sub-DB is opened with RocksDB's read-only mode, which doesn't acquire the DB `LOCK`
+    /// file, and nothing is written on open: no pruner, no indexer, no hot state and no truncation
+    /// of uncommitted data. The caller MUST make sure no writer mutates the files while they are
+    /// open, otherwise reads can fail or observe inconsistent data.
+    pub fn open_read_only_shared(
+        db_paths: StorageDirPaths,
+        rocksdb_configs: RocksdbConfigs,
+        max_num_nodes_per_lru_cache_shard: usize,
+    ) -> Result<Self> {
+        Self::open_internal(
+            &db_paths,
+            /* readonly = */ true,
+            NO_OP_STORAGE_PRUNER_CONFIG,
+            rocksdb_configs,
+            /* enable_indexer = */ false,
+            BUFFERED_STATE_TARGET_ITEMS,
+            max_num_nodes_per_lru_cache_shard,
+            /* empty_buffered_state_for_restore = */ false,
+            /* internal_indexer_db = */ None,
+            HotStateConfig {
+                delete_on_restart: false,
+                ..Default::default()
+            },
+            /* ledger_archive_db = */ None,
+            /* value_cipher = */ None,
         )
     }
 
@@ -112,18 +370,65 @@ impl AptosDB {
         max_num_nodes_per_lru_cache_shard: usize,
         reset_hot_state: bool,
     ) -> Result<(LedgerDb, Option<StateMerkleDb>, StateMerkleDb, StateKvDb)> {
+        // Shared by all the DB instances, so that their memtables are bounded as a whole.
+        let write_buffer_manager = rocksdb_configs.write_buffer_manager_size.map(|size| {
+            WriteBufferManager::new_write_buffer_manager(size, /* allow_stall = */ true)
+        });
+        Self::open_dbs_with_write_buffer_manager(
+            db_paths,
+            rocksdb_configs,
+            env,
+            block_cache,
+            write_buffer_manager.as_ref(),
+            readonly,
+            max_num_nodes_per_lru_cache_shard,
+            reset_hot_state,
+        )
+    }
+
+    pub(crate) fn open_dbs_with_write_buffer_manager(
+        db_paths: &StorageDirPaths,
+        rocksdb_configs: RocksdbConfigs,
+        env: Option<&Env>,
+        block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
+        readonly: bool,
+        max_num_nodes_per_lru_cache_shard: usize,
+        reset_hot_state: bool,
+    ) -> Result<(LedgerDb, Option<StateMerkleDb>, StateMerkleDb, StateKvDb)> {
+        let rocksdb_configs = Self::split_max_open_files(rocksdb_configs, readonly);
+        Self::report_configured_options(&rocksdb_configs);
+        // The shard config is recorded in the ledger metadata DB, whose path depends on the
+        // sharding flag, so a flipped flag would open a new metadata DB. It's caught by the layout
+        // on disk instead.
+        let shard_config = ShardConfig::new(rocksdb_configs.enable_storage_sharding);
+        if let Some(sharding_on_disk) = LedgerDb::sharding_on_disk(db_paths.ledger_db_root_path()) {
+            if sharding_on_disk != rocksdb_configs.enable_storage_sharding {
+                return Err(AptosDbError::ShardConfigMismatch {
+                    stored: format!("{:?}", ShardConfig::new(sharding_on_disk)),
+                    opening: format!("{shard_config:?}"),
+                });
+            }
+        }
         let ledger_db = LedgerDb::new(
             db_paths.ledger_db_root_path(),
+            db_paths.wal_root_path(),
             rocksdb_configs,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
         )?;
+        // Before opening the sharded DBs, whose data would be misrouted.
+        ledger_db
+            .metadata_db()
+            .check_shard_config(shard_config, readonly)?;
         let state_kv_db = StateKvDb::new(
             db_paths,
             rocksdb_configs,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
             ledger_db.metadata_db_arc(),
         )?;
@@ -133,6 +438,7 @@ impl AptosDB {
                 rocksdb_configs,
                 env,
                 block_cache,
+                write_buffer_manager,
                 readonly,
                 max_num_nodes_per_lru_cache_shard,
                 /* is_hot = */ true,
@@ -146,6 +452,7 @@ impl AptosDB {
             rocksdb_configs,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
             max_num_nodes_per_lru_cache_shard,
             /* is_hot = */ false,
@@ -155,6 +462,76 @@ impl AptosDB {
         Ok((ledger_db, hot_state_merkle_db, state_merkle_db, state_kv_db))
     }
 
+    /// Sets `max_open_files` of the sub-DBs with `max_open_files_per_sub_db` set to their share of
+    /// it, i.e. split evenly among the RocksDB instances opened with the sub-DB's config.
+    fn split_max_open_files(mut rocksdb_configs: RocksdbConfigs, readonly: bool) -> RocksdbConfigs {
+        let sharding = rocksdb_configs.enable_storage_sharding;
+        let (num_ledger_dbs, num_state_kv_dbs, num_state_merkle_dbs) = if !sharding {
+            // The state KV DB lives in the ledger DB.
+            (1, 1, 1)
+        } else if readonly {
+            // The metadata DBs and the shards, the hot state DBs are not opened.
+            (8, NUM_STATE_SHARDS + 1, NUM_STATE_SHARDS + 1)
+        } else {
+            // The hot state KV DB has only the shards, the hot state merkle DB has its metadata DB.
+            (8, 2 * NUM_STATE_SHARDS + 1, 2 * (NUM_STATE_SHARDS + 1))
+        };
+        for (config, num_dbs) in [
+            (&mut rocksdb_configs.ledger_db_config, num_ledger_dbs),
+            (&mut rocksdb_configs.state_kv_db_config, num_state_kv_dbs),
+            (
+                &mut rocksdb_configs.state_merkle_db_config,
+                num_state_merkle_dbs,
+            ),
+        ] {
+            if let Some(max_open_files) = config.max_open_files_per_sub_db {
+                config.max_open_files = (max_open_files / num_dbs as i32).max(1);
+            }
+        }
+        rocksdb_configs
+    }
+
+    /// Logs and exports as gauges the effective values of the per sub-DB tunable options, so
+    /// tuning can be verified in production.
+    fn report_configured_options(rocksdb_configs: &RocksdbConfigs) {
+        for (db_name, config) in [
+            ("ledger_db", &rocksdb_configs.ledger_db_config),
+            ("state_merkle_db", &rocksdb_configs.state_merkle_db_config),
+            ("state_kv_db", &rocksdb_configs.state_kv_db_config),
+        ] {
+            info!(
+                db_name = db_name,
+                target_file_size_base = config.target_file_size_base,
+                max_bytes_for_level_base = config.max_bytes_for_level_base,
+                write_buffer_size = config.write_buffer_size,
+                max_write_buffer_number = config.max_write_buffer_number,
+                min_write_buffer_number_to_merge = config.min_write_buffer_number_to_merge,
+                node_filter_policy = ?config.node_filter_policy,
+                "RocksDB level compaction, memtable and filter options."
+            );
+            for (option_name, value) in [
+                ("target_file_size_base", config.target_file_size_base as i64),
+                (
+                    "max_bytes_for_level_base",
+                    config.max_bytes_for_level_base as i64,
+                ),
+                ("write_buffer_size", config.write_buffer_size as i64),
+                (
+                    "max_write_buffer_number",
+                    config.max_write_buffer_number as i64,
+                ),
+                (
+                    "min_write_buffer_number_to_merge",
+                    config.min_write_buffer_number_to_merge as i64,
+                ),
+            ] {
+                ROCKSDB_CONFIGURED_OPTIONS
+                    .with_label_values(&[db_name, option_name])
+                    .set(value);
+            }
+        }
+    }
+
     pub fn add_version_update_subscriber(
         &mut self,
         sender: Sender<(Instant, Version)>,
@@ -163,6 +540,29 @@ impl AptosDB {
         Ok(())
     }
 
+    /// When set, each `pre_commit_ledger()` recomputes the transaction accumulator root in memory
+    /// and fails if it diverges from the one just persisted.
+    pub fn set_verify_ledger_root_on_commit(&mut self, verify_ledger_root_on_commit: bool) {
+        self.verify_ledger_root_on_commit = verify_ledger_root_on_commit;
+    }
+
+    /// Stops, or resumes, putting the Jellyfish Merkle nodes written into the node cache, which
+    /// is then only filled by reads, e.g. around a bulk restore so that it doesn't evict the
+    /// nodes serving reads. Overrides `RocksdbConfigs::cache_jmt_nodes_on_read_only`.
+    pub fn set_cache_jmt_nodes_on_read_only(&self, enabled: bool) {
+        self.state_store
+            .state_merkle_db
+            .set_cache_on_read_only(enabled);
+        if let Some(hot_state_merkle_db) = &self.state_store.hot_state_merkle_db {
+            hot_state_merkle_db.set_cache_on_read_only(enabled);
+        }
+    }
+
+    /// The head of the hash chain of the audit sink, `None` if there's no audit sink.
+    pub fn audit_chain_head(&self) -> Option<HashValue> {
+        self.audit_sink.as_ref().map(|sink| sink.chain_head())
+    }
+
     /// Gets an instance of `BackupHandler` for data backup purpose.
     pub fn get_backup_handler(&self) -> BackupHandler {
         BackupHandler::new(Arc::clone(&self.state_store), Arc::clone(&self.ledger_db))
@@ -173,16 +573,28 @@ impl AptosDB {
         db_path: impl AsRef<Path>,
         cp_path: impl AsRef<Path>,
         sharding: bool,
+    ) -> Result<()> {
+        Self::create_checkpoint_with_wal_path(db_path, None, cp_path, sharding)
+    }
+
+    /// Same as `create_checkpoint`, for a DB whose WALs are under `wal_root_path` if set, see
+    /// `StorageDirPaths::wal_root_path`. The checkpoint has its WALs with the rest.
+    pub fn create_checkpoint_with_wal_path(
+        db_path: impl AsRef<Path>,
+        wal_root_path: Option<&Path>,
+        cp_path: impl AsRef<Path>,
+        sharding: bool,
     ) -> Result<()> {
         let start = Instant::now();
 
         info!(sharding = sharding, "Creating checkpoint for AptosDB.");
 
-        LedgerDb::create_checkpoint(db_path.as_ref(), cp_path.as_ref(), sharding)?;
+        LedgerDb::create_checkpoint(db_path.as_ref(), wal_root_path, cp_path.as_ref(), sharding)?;
         if sharding {
-            StateKvDb::create_checkpoint(db_path.as_ref(), cp_path.as_ref())?;
+            StateKvDb::create_checkpoint(db_path.as_ref(), wal_root_path, cp_path.as_ref())?;
             StateMerkleDb::create_checkpoint(
                 db_path.as_ref(),
+                wal_root_path,
                 cp_path.as_ref(),
                 sharding,
                 /* is_hot = */ true,
@@ -190,6 +602,7 @@ impl AptosDB {
         }
         StateMerkleDb::create_checkpoint(
             db_path.as_ref(),
+            wal_root_path,
             cp_path.as_ref(),
             sharding,
             /* is_hot = */ false,
@@ -204,6 +617,287 @@ impl AptosDB {
         Ok(())
     }
 
+    /// Compares the RocksDB options of the DBs under `db_path` and `other_path`, e.g. a restored
+    /// DB and its source, as of when each DB instance was last opened. Both are expected to have
+    /// the default layout, with all the DBs under the same root.
+    pub fn diff_options(
+        db_path: impl AsRef<Path>,
+        other_path: impl AsRef<Path>,
+    ) -> Result<Vec<OptionDiff>> {
+        options_diff::diff_options(db_path, other_path)
+    }
+
+    /// Returns the latest versions of the sub-DBs as found when the DB was opened, before they
+    /// were truncated to their common commit progress. A warning is logged on open if they
+    /// disagree beyond what an interrupted commit leaves, see `SubDbVersions::is_consistent`.
+    pub fn subdb_version_skew(&self) -> SubDbVersions {
+        self.sub_db_versions_on_open
+    }
+
+    /// Returns the current write stall state of each underlying RocksDB instance, which tells
+    /// whether commit latency is caused by RocksDB delaying or stopping writes. The
+    /// corresponding gauges are updated as well.
+    pub fn write_stall_status(&self) -> Result<WriteStallStatus> {
+        get_write_stall_status(&self.all_dbs())
+    }
+
+    /// Returns the compaction state of each column family of the ledger DB, the state KV DB and
+    /// the state merkle DB, including those of each shard: the pending compaction bytes, the
+    /// running compactions and the size of each level. The hot state DBs are not included.
+    pub fn compaction_stats(&self) -> Result<Vec<CfCompactionStats>> {
+        get_compaction_stats(
+            &self.ledger_db,
+            &self.state_store.state_merkle_db,
+            &self.state_kv_db,
+        )
+    }
+
+    /// Returns the latest versions of the sub-DBs as of now, judging by the commit progress
+    /// recorded in the metadata of each of them, see `Self::is_version_committed`.
+    pub fn sub_db_versions(&self) -> Result<SubDbVersions> {
+        SubDbVersions::read(
+            &self.ledger_db,
+            &self.state_kv_db,
+            &self.state_store.state_merkle_db,
+        )
+    }
+
+    /// Returns whether `version` is committed to the ledger DB and the state KV DB, as well as to
+    /// the overall commit progress, see `SubDbVersions::is_version_committed`.
+    pub fn is_version_committed(&self, version: Version) -> Result<bool> {
+        Ok(self.sub_db_versions()?.is_version_committed(version))
+    }
+
+    /// Returns whether `self` and `other` hold the same ledger and state as of `version`, see
+    /// `Self::logical_divergence`.
+    pub fn logical_equals(
+        &self,
+        other: &AptosDB,
+        version: Version,
+        compare_contents: bool,
+    ) -> Result<bool> {
+        self.logical_divergence(other, version, compare_contents)
+            .map(|divergence| divergence.is_none())
+    }
+
+    /// Returns the first divergence between the ledger and state `self` and `other` hold as of
+    /// `version`, or `None` if they hold the same. Compares the transaction accumulator root
+    /// hashes and the hashes of the latest state checkpoints at or before `version`, which are
+    /// both taken from the ledger, so when the DBs happened to persist state snapshots doesn't
+    /// matter. With `compare_contents`, also compares one by one all the transactions (that are
+    /// not pruned in either DB), and the state values of the latest snapshot at or before
+    /// `version` of each DB against those of the other DB at the same version.
+    pub fn logical_divergence(
+        &self,
+        other: &AptosDB,
+        version: Version,
+        compare_contents: bool,
+    ) -> Result<Option<LogicalDivergence>> {
+        let ledger_root = self.get_accumulator_root_hash(version)?;
+        let other_ledger_root = other.get_accumulator_root_hash(version)?;
+        if ledger_root != other_ledger_root {
+            return Ok(Some(LogicalDivergence::LedgerRootHash(
+                ledger_root,
+                other_ledger_root,
+            )));
+        }
+
+        let state_root = self.latest_state_checkpoint_hash(version)?;
+        let other_state_root = other.latest_state_checkpoint_hash(version)?;
+        if state_root != other_state_root {
+            return Ok(Some(LogicalDivergence::StateCheckpointHash(
+                state_root,
+                other_state_root,
+            )));
+        }
+
+        if !compare_contents {
+            return Ok(None);
+        }
+
+        let next_version = version
+            .checked_add(1)
+            .ok_or_else(|| AptosDbError::Other("Version overflow.".to_string()))?;
+        let first_version = self
+            .get_first_txn_version()?
+            .max(other.get_first_txn_version()?)
+            .unwrap_or(0);
+        if first_version <= version {
+            let num_transactions = (next_version - first_version) as usize;
+            let transactions = self
+                .ledger_db
+                .transaction_db()
+                .get_transaction_iter(first_version, num_transactions)?;
+            let other_transactions = other
+                .ledger_db
+                .transaction_db()
+                .get_transaction_iter(first_version, num_transactions)?;
+            if let Some((idx, transaction, other_transaction)) =
+                Self::first_difference(transactions, other_transactions)?
+            {
+                return Ok(Some(LogicalDivergence::Transaction {
+                    version: first_version + idx as Version,
+                    transactions: (transaction, other_transaction),
+                }));
+            }
+        }
+
+        for (db, other_db, is_self) in [(self, other, true), (other, self, false)] {
+            let Some((snapshot_version, _root_hash)) =
+                db.get_state_snapshot_before(next_version)?
+            else {
+                continue;
+            };
+            for item in db
+                .state_store
+                .get_state_key_and_value_iter(snapshot_version, 0)?
+            {
+                let (state_key, value) = item?;
+                let other_value = other_db
+                    .state_store
+                    .get_state_value_by_version(&state_key, snapshot_version)?;
+                if other_value.as_ref() != Some(&value) {
+                    let values = if is_self {
+                        (Some(value), other_value)
+                    } else {
+                        (other_value, Some(value))
+                    };
+                    return Ok(Some(LogicalDivergence::StateValue {
+                        state_key,
+                        version: snapshot_version,
+                        values,
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns a fingerprint of the logical content of the DB as of `version`, identical on every
+    /// node that processed the same history. It hashes, in this order, the version, the
+    /// transaction accumulator root hash, the hash of the latest state checkpoint at or before
+    /// `version` and the event root hash of the transaction at `version`. All of them are taken
+    /// from the ledger, so the fingerprint doesn't depend on when a node happened to persist a
+    /// state snapshot. Fails if the transaction infos needed are pruned.
+    pub fn content_fingerprint(&self, version: Version) -> Result<HashValue> {
+        let ledger_root = self.get_accumulator_root_hash(version)?;
+        let event_root = self
+            .ledger_db
+            .transaction_info_db()
+            .get_transaction_info(version)?
+            .event_root_hash();
+        let state_root = self.latest_state_checkpoint_hash(version)?;
+
+        let mut hasher = DefaultHasher::new(b"AptosDbContentFingerprint");
+        hasher.update(&version.to_be_bytes());
+        for root in [ledger_root, state_root, event_root] {
+            hasher.update(root.as_ref());
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Returns the hash of the latest state checkpoint at or before `version`, as recorded in the
+    /// transaction infos.
+    fn latest_state_checkpoint_hash(&self, version: Version) -> Result<HashValue> {
+        let transaction_info_db = self.ledger_db.transaction_info_db();
+        let mut checkpoint_version = version;
+        loop {
+            let txn_info = transaction_info_db.get_transaction_info(checkpoint_version)?;
+            if let Some(state_checkpoint_hash) = txn_info.state_checkpoint_hash() {
+                return Ok(state_checkpoint_hash);
+            }
+            checkpoint_version = checkpoint_version.checked_sub(1).ok_or_else(|| {
+                AptosDbError::NotFound(format!("State checkpoint at or before version {version}"))
+            })?;
+        }
+    }
+
+    /// Returns every schema the DB uses, with its column family and the types of its keys and
+    /// values, e.g. for tooling decoding the raw DB.
+    pub fn schema_registry() -> Vec<SchemaInfo> {
+        schema::schema_registry()
+    }
+
+    /// Returns the index of the first pair of items that differ, along with them, `None`
+    /// meaning that one of the iterators ended first.
+    fn first_difference<T: PartialEq>(
+        items: impl Iterator<Item = Result<T>>,
+        other_items: impl Iterator<Item = Result<T>>,
+    ) -> Result<Option<(usize, Option<T>, Option<T>)>> {
+        for (idx, pair) in items.zip_longest(other_items).enumerate() {
+            let (item, other_item) = match pair {
+                EitherOrBoth::Both(item, other_item) => (Some(item?), Some(other_item?)),
+                EitherOrBoth::Left(item) => (Some(item?), None),
+                EitherOrBoth::Right(other_item) => (None, Some(other_item?)),
+            };
+            if item != other_item {
+                return Ok(Some((idx, item, other_item)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pins `version` so that none of the pruners prunes it (or anything after it) until it's
+    /// unpinned, while the versions before it keep being pruned. A version can be pinned multiple
+    /// times, in which case it needs to be unpinned as many times. Fails if `version` is already
+    /// pruned. Pins are kept in memory only, so they don't survive a restart.
+    pub fn pin_version(&self, version: Version) -> Result<()> {
+        self.ledger_pruner.pin_version(version)?;
+        if let Err(err) = self.state_store.state_pruner.pin_version(version) {
+            self.ledger_pruner.unpin_version(version)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Undoes one [`Self::pin_version`] of `version`.
+    pub fn unpin_version(&self, version: Version) -> Result<()> {
+        self.ledger_pruner.unpin_version(version)?;
+        self.state_store.state_pruner.unpin_version(version)
+    }
+
+    /// Starts accounting the bytes read from and written to the underlying DBs, until the returned
+    /// handle is dropped. IO of other DBs in the process isn't included, but that of concurrent
+    /// workloads on this DB is.
+    pub fn begin_io_accounting(&self) -> IoAccountingHandle {
+        IoAccountingHandle::begin(self.all_dbs())
+    }
+
+    /// Returns what the pruners deleted in `time_range`, oldest first, read from all the
+    /// underlying DBs. Nothing is recorded unless `enable_deletion_log` is set in the pruner
+    /// config, and records are kept for `deletion_log_retention_secs`.
+    pub fn pruner_deletion_log(
+        &self,
+        time_range: Range<SystemTime>,
+    ) -> Result<Vec<DeletionRecord>> {
+        let to_usecs =
+            |time: &SystemTime| aptos_infallible::duration_since_epoch_at(time).as_micros() as u64;
+        let time_range_usecs = to_usecs(&time_range.start)..to_usecs(&time_range.end);
+        let mut records = Vec::new();
+        for db in self.all_dbs().into_iter().chain(
+            self.state_store
+                .hot_state_merkle_db
+                .iter()
+                .flat_map(|db| db.all_dbs()),
+        ) {
+            records.extend(get_deletion_records(db, time_range_usecs.clone())?);
+        }
+        records.sort_by_key(|record| record.timestamp_usecs);
+        Ok(records)
+    }
+
+    /// Returns all the underlying RocksDB instances (excluding the hot state merkle DB), each of
+    /// them exactly once.
+    pub(crate) fn all_dbs(&self) -> Vec<&DB> {
+        self.ledger_db
+            .all_dbs()
+            .into_iter()
+            .chain(self.state_kv_db.all_dbs())
+            .chain(self.state_store.state_merkle_db.all_dbs())
+            .collect()
+    }
+
     pub fn commit_genesis_ledger_info(&self, genesis_li: &LedgerInfoWithSignatures) -> Result<()> {
         let ledger_metadata_db = self.ledger_db.metadata_db();
         let current_epoch = ledger_metadata_db
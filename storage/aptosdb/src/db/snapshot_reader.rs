@@ -0,0 +1,230 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! A reader which sees the DB as of the moment it was created, for long-running queries that need
+//! repeatable reads.
+//!
+//! Rather than holding a RocksDB snapshot, which every read path would have to be threaded
+//! through, the reader fixes the "latest" ledger info and versions at creation and pins them so
+//! that the pruners leave them alone. Everything stored at or below a committed version is never
+//! rewritten, so reads at those versions are stable while writes go on undisturbed.
+//!
+//! A [`ReadTxn`] does the same for a handful of point reads bound to a single version, without
+//! going through the `DbReader` APIs.
+
+use crate::AptosDB;
+use aptos_logger::warn;
+use aptos_storage_interface::{
+    db_ensure as ensure, db_other_bail as bail,
+    state_store::{
+        state::State, state_summary::StateSummary, state_view::hot_state_view::HotStateView,
+    },
+    DbReader, LedgerSummary, Result,
+};
+use aptos_types::{
+    contract_event::{ContractEvent, EventWithVersion},
+    epoch_state::EpochState,
+    ledger_info::LedgerInfoWithSignatures,
+    state_store::{state_key::StateKey, state_value::StateValue},
+    transaction::{Transaction, Version},
+};
+use std::sync::Arc;
+
+/// Serves reads as of the moment it was created, see [`AptosDB::snapshot_reader`].
+struct SnapshotDbReader {
+    db: Arc<AptosDB>,
+    ledger_info: Option<LedgerInfoWithSignatures>,
+    synced_version: Option<Version>,
+    state_checkpoint_version: Option<Version>,
+    epoch_state: Option<EpochState>,
+}
+
+impl DbReader for SnapshotDbReader {
+    fn get_read_delegatee(&self) -> &dyn DbReader {
+        self.db.as_ref()
+    }
+
+    fn get_latest_ledger_info_option(&self) -> Result<Option<LedgerInfoWithSignatures>> {
+        Ok(self.ledger_info.clone())
+    }
+
+    fn get_synced_version(&self) -> Result<Option<Version>> {
+        Ok(self.synced_version)
+    }
+
+    fn get_pre_committed_version(&self) -> Result<Option<Version>> {
+        Ok(self.synced_version)
+    }
+
+    fn get_latest_state_checkpoint_version(&self) -> Result<Option<Version>> {
+        Ok(self.state_checkpoint_version)
+    }
+
+    fn get_latest_epoch_state(&self) -> Result<EpochState> {
+        match &self.epoch_state {
+            Some(epoch_state) => Ok(epoch_state.clone()),
+            None => bail!("No epoch state in the snapshot, the DB was empty."),
+        }
+    }
+
+    fn get_latest_block_events(&self, _num_events: usize) -> Result<Vec<EventWithVersion>> {
+        bail!("get_latest_block_events is not supported by a snapshot reader.")
+    }
+
+    fn get_pre_committed_ledger_summary(&self) -> Result<LedgerSummary> {
+        bail!("get_pre_committed_ledger_summary is not supported by a snapshot reader.")
+    }
+
+    fn get_persisted_state(&self) -> Result<(Arc<dyn HotStateView>, State)> {
+        bail!("get_persisted_state is not supported by a snapshot reader.")
+    }
+
+    fn get_persisted_state_summary(&self) -> Result<StateSummary> {
+        bail!("get_persisted_state_summary is not supported by a snapshot reader.")
+    }
+}
+
+/// Keeps the versions of a snapshot reader from being pruned, until dropped.
+pub struct SnapshotGuard {
+    db: Arc<AptosDB>,
+    pinned_version: Option<Version>,
+}
+
+impl SnapshotGuard {
+    /// The version pinned for the snapshot, `None` if the DB was empty.
+    pub fn pinned_version(&self) -> Option<Version> {
+        self.pinned_version
+    }
+}
+
+impl Drop for SnapshotGuard {
+    fn drop(&mut self) {
+        if let Some(version) = self.pinned_version {
+            if let Err(err) = self.db.unpin_version(version) {
+                warn!(
+                    version = version,
+                    error = ?err,
+                    "Failed to unpin the version of a snapshot reader."
+                );
+            }
+        }
+    }
+}
+
+/// A sequence of reads isolated from concurrent commits, see [`AptosDB::read_txn`]. Dropping it
+/// unpins its version.
+pub struct ReadTxn {
+    db: Arc<AptosDB>,
+    version: Option<Version>,
+}
+
+impl ReadTxn {
+    /// The synced version the reads are bound to, `None` if the DB was empty.
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// Returns the value of `state_key` as of the version of the transaction.
+    pub fn get_state_value(&self, state_key: &StateKey) -> Result<Option<StateValue>> {
+        match self.version {
+            Some(version) => self.db.get_state_value_by_version(state_key, version),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the transaction at `version`, which must be at or below the version of the
+    /// transaction, as the ones after it are not visible.
+    pub fn get_transaction(&self, version: Version) -> Result<Transaction> {
+        self.ensure_visible(version)?;
+        self.db.ledger_db.transaction_db().get_transaction(version)
+    }
+
+    /// Returns the events emitted by the transaction at `version`, which must be at or below the
+    /// version of the transaction.
+    pub fn get_events(&self, version: Version) -> Result<Vec<ContractEvent>> {
+        self.ensure_visible(version)?;
+        self.db.ledger_db.event_db().get_events_by_version(version)
+    }
+
+    fn ensure_visible(&self, version: Version) -> Result<()> {
+        ensure!(
+            self.version.is_some_and(|v| version <= v),
+            "Version {} is not visible to the read transaction at version {:?}.",
+            version,
+            self.version,
+        );
+        Ok(())
+    }
+}
+
+impl Drop for ReadTxn {
+    fn drop(&mut self) {
+        if let Some(version) = self.version {
+            if let Err(err) = self.db.unpin_version(version) {
+                warn!(
+                    version = version,
+                    error = ?err,
+                    "Failed to unpin the version of a read transaction."
+                );
+            }
+        }
+    }
+}
+
+impl AptosDB {
+    /// Starts a read transaction bound to the current synced version: all its reads see the DB
+    /// as of that version, however many commits land in between. Like
+    /// [`Self::snapshot_reader`], it relies on committed data never being rewritten rather than
+    /// on a RocksDB snapshot, and pins the version against pruning until it's dropped.
+    pub fn read_txn(self: &Arc<Self>) -> Result<ReadTxn> {
+        let version = self.get_synced_version()?;
+        if let Some(version) = version {
+            self.pin_version(version)?;
+        }
+        Ok(ReadTxn {
+            db: Arc::clone(self),
+            version,
+        })
+    }
+
+    /// Returns a reader which sees the DB as of now: its latest ledger info, synced version,
+    /// state checkpoint and epoch state stay as they are now while writes go on. Reads at a
+    /// version up to those are repeatable, since committed data is never rewritten and the
+    /// versions are pinned against pruning until the returned guard is dropped.
+    ///
+    /// This is not a RocksDB snapshot: reads at versions the reader doesn't advertise see the
+    /// current DB, and APIs about in-memory or pre-committed state are not supported.
+    pub fn snapshot_reader(self: &Arc<Self>) -> Result<(Arc<dyn DbReader>, SnapshotGuard)> {
+        let ledger_info = self.get_latest_ledger_info_option()?;
+        let synced_version = self.get_synced_version()?;
+        let state_checkpoint_version = self.get_latest_state_checkpoint_version()?;
+        let epoch_state = if ledger_info.is_some() {
+            Some(self.get_latest_epoch_state()?)
+        } else {
+            None
+        };
+
+        let pinned_version = ledger_info
+            .as_ref()
+            .map(|li| li.ledger_info().version())
+            .into_iter()
+            .chain(state_checkpoint_version)
+            .min();
+        if let Some(version) = pinned_version {
+            self.pin_version(version)?;
+        }
+        let guard = SnapshotGuard {
+            db: Arc::clone(self),
+            pinned_version,
+        };
+
+        let reader = SnapshotDbReader {
+            db: Arc::clone(self),
+            ledger_info,
+            synced_version,
+            state_checkpoint_version,
+            epoch_state,
+        };
+        Ok((Arc::new(reader), guard))
+    }
+}
@@ -20,11 +20,11 @@ impl Cmd {
     pub fn run(self) -> Result<()> {
         ensure!(!self.output_dir.exists(), "Output dir already exists.");
         fs::create_dir_all(&self.output_dir)?;
-        let sharding_config = self.db_dir.sharding_config.clone();
-        AptosDB::create_checkpoint(
-            self.db_dir,
+        AptosDB::create_checkpoint_with_wal_path(
+            &self.db_dir,
+            self.db_dir.wal_dir(),
             self.output_dir,
-            sharding_config.enable_storage_sharding,
+            self.db_dir.sharding_config.enable_storage_sharding,
         )
     }
 }
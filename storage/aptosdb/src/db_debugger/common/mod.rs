@@ -3,9 +3,9 @@
 
 use crate::{
     db_debugger::ShardingConfig, ledger_db::LedgerDb, state_kv_db::StateKvDb,
-    state_merkle_db::StateMerkleDb,
+    state_merkle_db::StateMerkleDb, utils::dir_paths_with_wal,
 };
-use aptos_config::config::{RocksdbConfigs, StorageDirPaths};
+use aptos_config::config::RocksdbConfigs;
 use aptos_storage_interface::Result;
 use aptos_types::nibble::{nibble_path::NibblePath, Nibble};
 use clap::Parser;
@@ -20,22 +20,32 @@ pub struct DbDir {
     #[clap(long, value_parser)]
     db_dir: PathBuf,
 
+    /// Where the WALs of the DBs are, if not with the rest, see `StorageDirPaths::wal_root_path`.
+    #[clap(long, value_parser)]
+    wal_dir: Option<PathBuf>,
+
     #[clap(flatten)]
     pub sharding_config: ShardingConfig,
 }
 
 impl DbDir {
+    pub fn wal_dir(&self) -> Option<&Path> {
+        self.wal_dir.as_deref()
+    }
+
     pub fn open_state_merkle_db(&self) -> Result<StateMerkleDb> {
         let env = None;
         let block_cache = None;
+        let write_buffer_manager = None;
         StateMerkleDb::new(
-            &StorageDirPaths::from_path(&self.db_dir),
+            &dir_paths_with_wal(&self.db_dir, self.wal_dir()),
             RocksdbConfigs {
                 enable_storage_sharding: self.sharding_config.enable_storage_sharding,
                 ..Default::default()
             },
             env,
             block_cache,
+            write_buffer_manager,
             /* read_only = */ false,
             /* max_nodes_per_lru_cache_shard = */ 0,
             /* is_hot = */ false,
@@ -47,14 +57,16 @@ impl DbDir {
         let leger_db = self.open_ledger_db()?;
         let env = None;
         let block_cache = None;
+        let write_buffer_manager = None;
         StateKvDb::new(
-            &StorageDirPaths::from_path(&self.db_dir),
+            &dir_paths_with_wal(&self.db_dir, self.wal_dir()),
             RocksdbConfigs {
                 enable_storage_sharding: self.sharding_config.enable_storage_sharding,
                 ..Default::default()
             },
             env,
             block_cache,
+            write_buffer_manager,
             true,
             leger_db.metadata_db_arc(),
         )
@@ -63,14 +75,17 @@ impl DbDir {
     pub fn open_ledger_db(&self) -> Result<LedgerDb> {
         let env = None;
         let block_cache = None;
+        let write_buffer_manager = None;
         LedgerDb::new(
             self.db_dir.as_path(),
+            self.wal_dir(),
             RocksdbConfigs {
                 enable_storage_sharding: self.sharding_config.enable_storage_sharding,
                 ..Default::default()
             },
             env,
             block_cache,
+            write_buffer_manager,
             true,
         )
     }
@@ -5,6 +5,7 @@ mod check_stale_nodes;
 mod get_leaf;
 mod get_path;
 mod get_snapshots;
+mod verify_no_dangling;
 
 use aptos_storage_interface::Result;
 
@@ -14,6 +15,7 @@ pub enum Cmd {
     GetPath(get_path::Cmd),
     GetLeaf(get_leaf::Cmd),
     CheckStaleNodes(check_stale_nodes::Cmd),
+    VerifyNoDangling(verify_no_dangling::Cmd),
 }
 
 impl Cmd {
@@ -23,6 +25,7 @@ impl Cmd {
             Self::GetPath(cmd) => cmd.run(),
             Self::GetLeaf(cmd) => cmd.run(),
             Self::CheckStaleNodes(cmd) => cmd.run(),
+            Self::VerifyNoDangling(cmd) => cmd.run(),
         }
     }
 }
@@ -0,0 +1,39 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+use crate::{db_debugger::common::DbDir, schema::db_metadata::DbMetadataKey, utils::get_progress};
+use aptos_storage_interface::Result;
+use aptos_types::transaction::Version;
+use clap::Parser;
+
+#[derive(Parser)]
+#[clap(about = "Check that pruning left no JMT node referenced by a retained version missing.")]
+pub struct Cmd {
+    #[clap(flatten)]
+    db_dir: DbDir,
+
+    /// Defaults to the state merkle pruner progress.
+    #[clap(long)]
+    min_readable_version: Option<Version>,
+}
+
+impl Cmd {
+    pub fn run(self) -> Result<()> {
+        let state_merkle_db = self.db_dir.open_state_merkle_db()?;
+
+        let min_readable_version = match self.min_readable_version {
+            Some(version) => version,
+            None => get_progress(
+                state_merkle_db.metadata_db(),
+                &DbMetadataKey::StateMerklePrunerProgress,
+            )?
+            .unwrap_or(0),
+        };
+        println!("Verifying the trees at versions >= {min_readable_version}...");
+
+        state_merkle_db.verify_no_dangling_after_prune(min_readable_version)?;
+        println!("No dangling node found.");
+
+        Ok(())
+    }
+}
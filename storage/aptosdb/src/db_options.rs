@@ -2,7 +2,9 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::schema::*;
-use aptos_config::config::{IndexType, RocksdbConfig};
+use aptos_config::config::{
+    ColumnFamilyCompression, ColumnFamilyFilter, FilterPolicy, IndexType, RocksdbConfig,
+};
 use aptos_schemadb::{
     BlockBasedIndexType, BlockBasedOptions, Cache, ColumnFamilyDescriptor, ColumnFamilyName,
     DBCompressionType, Options, SliceTransform, DEFAULT_COLUMN_FAMILY_NAME,
@@ -166,18 +168,109 @@ where
 {
     let mut cfds = Vec::with_capacity(cfs.len());
     for cf_name in cfs {
-        let table_options = gen_table_options(rocksdb_config, block_cache, cf_name);
-
-        let mut cf_opts = Options::default();
-        cf_opts.set_compression_type(DBCompressionType::Lz4);
-        cf_opts.set_block_based_table_factory(&table_options);
-        cf_opts.add_compact_on_deletion_collector_factory(0, 0, 0.4);
-        cf_opts_post_processor(cf_name, &mut cf_opts);
+        let cf_opts = gen_cf_opts(
+            rocksdb_config,
+            block_cache,
+            cf_name,
+            &cf_opts_post_processor,
+        );
         cfds.push(ColumnFamilyDescriptor::new((*cf_name).to_string(), cf_opts));
     }
     cfds
 }
 
+fn gen_cf_opts<F>(
+    rocksdb_config: &RocksdbConfig,
+    block_cache: Option<&Cache>,
+    cf_name: ColumnFamilyName,
+    cf_opts_post_processor: F,
+) -> Options
+where
+    F: Fn(ColumnFamilyName, &mut Options),
+{
+    let table_options = gen_table_options(rocksdb_config, block_cache, cf_name);
+
+    let mut cf_opts = Options::default();
+    set_compression(&mut cf_opts, compression_for_cf(rocksdb_config, cf_name));
+    cf_opts.set_block_based_table_factory(&table_options);
+    cf_opts.add_compact_on_deletion_collector_factory(0, 0, 0.4);
+    cf_opts.set_target_file_size_base(rocksdb_config.target_file_size_base);
+    cf_opts.set_max_bytes_for_level_base(rocksdb_config.max_bytes_for_level_base);
+    cf_opts.set_write_buffer_size(rocksdb_config.write_buffer_size);
+    cf_opts.set_max_write_buffer_number(rocksdb_config.max_write_buffer_number);
+    cf_opts.set_min_write_buffer_number_to_merge(rocksdb_config.min_write_buffer_number_to_merge);
+    cf_opts_post_processor(cf_name, &mut cf_opts);
+    cf_opts
+}
+
+/// Returns the effective compression of the column family `cf_name`.
+pub(crate) fn compression_for_cf(
+    rocksdb_config: &RocksdbConfig,
+    cf_name: ColumnFamilyName,
+) -> ColumnFamilyCompression {
+    if cf_name == STATE_VALUE_CF_NAME || cf_name == STATE_VALUE_BY_KEY_HASH_CF_NAME {
+        rocksdb_config.state_value_compression()
+    } else {
+        ColumnFamilyCompression::Lz4
+    }
+}
+
+/// Returns the effective filter of the column family `cf_name`, `None` if it has no filter.
+pub(crate) fn filter_for_cf(
+    rocksdb_config: &RocksdbConfig,
+    cf_name: ColumnFamilyName,
+) -> Option<ColumnFamilyFilter> {
+    let bits_per_key = rocksdb_config.bloom_filter_bits?;
+    if cf_name == JELLYFISH_MERKLE_NODE_CF_NAME
+        && rocksdb_config.node_filter_policy == FilterPolicy::Ribbon
+    {
+        return Some(ColumnFamilyFilter::Ribbon { bits_per_key });
+    }
+    Some(match rocksdb_config.bloom_before_level {
+        Some(bloom_before_level) => ColumnFamilyFilter::HybridRibbon {
+            bits_per_key,
+            bloom_before_level,
+        },
+        None => ColumnFamilyFilter::Bloom { bits_per_key },
+    })
+}
+
+/// Returns whether the filter of the column family `cf_name` covers the whole keys, see
+/// `RocksdbConfig::state_value_whole_key_filtering`. Only matters for the column families with a
+/// prefix extractor, i.e. the state value ones.
+pub(crate) fn whole_key_filtering_for_cf(
+    rocksdb_config: &RocksdbConfig,
+    cf_name: ColumnFamilyName,
+) -> bool {
+    if cf_name == STATE_VALUE_CF_NAME
+        || cf_name == STATE_VALUE_BY_KEY_HASH_CF_NAME
+        || cf_name == HOT_STATE_VALUE_BY_KEY_HASH_CF_NAME
+    {
+        if let Some(whole_key_filtering) = rocksdb_config.state_value_whole_key_filtering {
+            return whole_key_filtering;
+        }
+    }
+    // We do not generally perform point queries on these tables.
+    cf_name != STATE_VALUE_BY_KEY_HASH_CF_NAME && cf_name != HOT_STATE_VALUE_BY_KEY_HASH_CF_NAME
+}
+
+fn set_compression(cf_opts: &mut Options, compression: ColumnFamilyCompression) {
+    match compression {
+        ColumnFamilyCompression::Lz4 => cf_opts.set_compression_type(DBCompressionType::Lz4),
+        ColumnFamilyCompression::Zstd(zstd_config) => {
+            cf_opts.set_compression_type(DBCompressionType::Zstd);
+            // Window bits and strategy are RocksDB's defaults, zstd ignores them.
+            cf_opts.set_compression_options(
+                -14,
+                zstd_config.level,
+                /* strategy = */ 0,
+                zstd_config.max_dict_bytes,
+            );
+            cf_opts.set_zstd_max_train_bytes(zstd_config.max_train_bytes);
+        },
+    }
+}
+
 fn gen_table_options(
     rocksdb_config: &RocksdbConfig,
     block_cache: Option<&Cache>,
@@ -193,23 +286,27 @@ fn gen_table_options(
     table_options.set_pin_l0_filter_and_index_blocks_in_cache(
         rocksdb_config.pin_l0_filter_and_index_blocks_in_cache,
     );
+    table_options.set_pin_top_level_index_and_filter(rocksdb_config.pin_top_level_index_and_filter);
 
     if let Some(cache) = block_cache {
         table_options.set_block_cache(cache);
     }
 
-    if let Some(bits) = rocksdb_config.bloom_filter_bits {
-        match rocksdb_config.bloom_before_level {
-            Some(level) => table_options.set_hybrid_ribbon_filter(bits, level),
-            None => table_options.set_bloom_filter(bits, /* block_based = */ false),
-        }
+    match filter_for_cf(rocksdb_config, cf_name) {
+        Some(ColumnFamilyFilter::Bloom { bits_per_key }) => {
+            table_options.set_bloom_filter(bits_per_key, /* block_based = */ false)
+        },
+        Some(ColumnFamilyFilter::HybridRibbon {
+            bits_per_key,
+            bloom_before_level,
+        }) => table_options.set_hybrid_ribbon_filter(bits_per_key, bloom_before_level),
+        Some(ColumnFamilyFilter::Ribbon { bits_per_key }) => {
+            table_options.set_ribbon_filter(bits_per_key)
+        },
+        None => {},
     }
 
-    if cf_name == STATE_VALUE_BY_KEY_HASH_CF_NAME || cf_name == HOT_STATE_VALUE_BY_KEY_HASH_CF_NAME
-    {
-        // We do not generally perform point queries on these tables.
-        table_options.set_whole_key_filtering(false);
-    }
+    table_options.set_whole_key_filtering(whole_key_filtering_for_cf(rocksdb_config, cf_name));
 
     table_options
 }
@@ -334,6 +431,22 @@ pub(super) fn gen_state_kv_shard_cfds(
     )
 }
 
+/// Returns the options of the state K/V column family `cf_name`, the same in the shards, see
+/// `gen_state_kv_shard_cfds`, and in the ledger DB when sharding is disabled, see
+/// `gen_ledger_cfds`. Meant for the SST files written to be ingested into it, so the block cache,
+/// which only matters to reads, is left out.
+pub(super) fn gen_state_kv_cf_opts(
+    rocksdb_config: &RocksdbConfig,
+    cf_name: ColumnFamilyName,
+) -> Options {
+    gen_cf_opts(
+        rocksdb_config,
+        /* block_cache = */ None,
+        cf_name,
+        with_state_key_extractor_processor,
+    )
+}
+
 pub(super) fn gen_hot_state_kv_shard_cfds(
     rocksdb_config: &RocksdbConfig,
     block_cache: Option<&Cache>,
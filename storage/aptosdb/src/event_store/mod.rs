@@ -6,10 +6,17 @@
 #![allow(unused)]
 
 use super::AptosDB;
-use crate::schema::{event::EventSchema, event_accumulator::EventAccumulatorSchema};
+use crate::schema::{
+    db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+    event::EventSchema,
+    event_accumulator::EventAccumulatorSchema,
+};
 use anyhow::anyhow;
-use aptos_accumulator::HashReader;
-use aptos_crypto::{hash::CryptoHash, HashValue};
+use aptos_accumulator::{HashReader, MerkleAccumulator};
+use aptos_crypto::{
+    hash::{CryptoHash, EventAccumulatorHasher},
+    HashValue,
+};
 use aptos_db_indexer_schemas::schema::{
     event_by_key::EventByKeySchema, event_by_version::EventByVersionSchema,
 };
@@ -20,11 +27,11 @@ use aptos_types::{
     account_config::{new_block_event_key, NewBlockEvent},
     contract_event::ContractEvent,
     event::EventKey,
-    proof::position::Position,
+    proof::{position::Position, EventAccumulatorProof},
     transaction::Version,
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     convert::{TryFrom, TryInto},
     sync::Arc,
 };
@@ -49,6 +56,94 @@ impl EventStore {
             .ok_or_else(|| AptosDbError::NotFound(format!("Event {} of Txn {}", index, version)))
     }
 
+    /// Returns the events emitted by the transactions in `[start_version, end_version)`, grouped
+    /// by version, read in one ordered scan. Versions whose transaction emitted no event are not
+    /// in the map.
+    pub fn get_events_grouped(
+        &self,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<BTreeMap<Version, Vec<ContractEvent>>> {
+        ensure!(
+            start_version <= end_version,
+            "Bad version range [{}, {})",
+            start_version,
+            end_version,
+        );
+
+        self.error_if_events_pruned(start_version)?;
+
+        let mut iter = self.event_db.iter::<EventSchema>()?;
+        iter.seek(&start_version)?;
+        let mut events_by_version: BTreeMap<Version, Vec<ContractEvent>> = BTreeMap::new();
+        for res in iter {
+            let ((version, _index), event) = res?;
+            if version >= end_version {
+                break;
+            }
+            events_by_version.entry(version).or_default().push(event);
+        }
+        Ok(events_by_version)
+    }
+
+    /// Returns the event at `event_index` of the transaction at `version`, with its proof against
+    /// the event root hash in the transaction info.
+    ///
+    /// The event accumulator isn't written when the indices are skipped (i.e. they live in the
+    /// internal indexer DB, as on sharded DBs), in which case it's rebuilt from the events of the
+    /// transaction.
+    pub fn get_event_with_proof(
+        &self,
+        version: Version,
+        event_index: u64,
+    ) -> Result<(ContractEvent, EventAccumulatorProof)> {
+        self.error_if_events_pruned(version)?;
+
+        let mut iter = self.event_db.iter::<EventSchema>()?;
+        iter.seek(&(version, 0))?;
+        let mut events = Vec::new();
+        for res in iter {
+            let ((ver, _index), event) = res?;
+            if ver != version {
+                break;
+            }
+            events.push(event);
+        }
+        let num_events = events.len() as u64;
+        let event = events.get(event_index as usize).cloned().ok_or_else(|| {
+            AptosDbError::NotFound(format!("Event {} of Txn {}", event_index, version))
+        })?;
+
+        let accumulator_persisted = self
+            .event_db
+            .get::<EventAccumulatorSchema>(&(version, Position::from_inorder_index(0)))?
+            .is_some();
+        let proof = if accumulator_persisted {
+            MerkleAccumulator::<_, EventAccumulatorHasher>::get_proof(
+                &EventHashReader::new(self, version),
+                num_events,
+                event_index,
+            )
+        } else {
+            let event_hashes: Vec<HashValue> = events.iter().map(ContractEvent::hash).collect();
+            let (_root_hash, writes) =
+                MerkleAccumulator::<EmptyReader, EventAccumulatorHasher>::append(
+                    &EmptyReader,
+                    0,
+                    &event_hashes,
+                )?;
+            MerkleAccumulator::<_, EventAccumulatorHasher>::get_proof(
+                &InMemoryHashReader(writes.into_iter().collect()),
+                num_events,
+                event_index,
+            )
+        }
+        .map_err(|err| {
+            AptosDbError::NotFound(format!("Event accumulator of Txn {version}: {err}"))
+        })?;
+        Ok((event, proof))
+    }
+
     pub fn get_txn_ver_by_seq_num(&self, event_key: &EventKey, seq_num: u64) -> Result<u64> {
         let (ver, _) = self
             .event_db
@@ -316,6 +411,101 @@ impl EventStore {
         })
     }
 
+    /// Deletes all the events before `up_to_version`, along with their accumulators and their
+    /// indices in the event DB (not those in the internal indexer DB, if any), returning the number
+    /// of events deleted. Meant for a retention of events shorter than that of the rest of the
+    /// ledger, so it's independent of the ledger pruner: its progress is left as is, and the
+    /// pruner skips what's already gone when it catches up.
+    ///
+    /// The `NewBlockEvent`s are kept, since the block info lookups rely on them. The event APIs
+    /// fail on the versions pruned from then on, see `Self::error_if_events_pruned`, while the
+    /// transaction and output reads return the `NewBlockEvent` alone for them, which is why
+    /// `Self::get_first_event_version` is advertised.
+    ///
+    /// The events are deleted in batches of `PRUNE_EVENTS_BATCH_SIZE` versions, each recording
+    /// the progress, so a failure can leave the oldest events deleted and the newer ones not,
+    /// which a retry finishes. The versions before the first event left, e.g. the ones the ledger
+    /// pruner is done with, are skipped rather than walked in batches.
+    pub fn prune_events(&self, up_to_version: Version) -> Result<u64> {
+        const PRUNE_EVENTS_BATCH_SIZE: Version = 10_000;
+
+        let progress = self.get_first_event_version()?;
+        if progress >= up_to_version {
+            return Ok(0);
+        }
+        let mut iter = self.event_db.iter::<EventSchema>()?;
+        iter.seek(&progress)?;
+        let mut begin = match iter.next().transpose()? {
+            Some(((version, _index), _event)) => version.min(up_to_version),
+            None => up_to_version,
+        };
+        if begin > progress {
+            self.event_db.put::<DbMetadataSchema>(
+                &DbMetadataKey::EventRetentionProgress,
+                &DbMetadataValue::Version(begin),
+            )?;
+        }
+
+        let mut num_deleted = 0;
+        while begin < up_to_version {
+            let end = begin
+                .saturating_add(PRUNE_EVENTS_BATCH_SIZE)
+                .min(up_to_version);
+            let mut batch = SchemaBatch::new();
+            let mut iter = self.event_db.iter::<EventSchema>()?;
+            iter.seek(&begin)?;
+            while let Some(((version, index), event)) = iter.next().transpose()? {
+                if version >= end {
+                    break;
+                }
+                if let ContractEvent::V1(v1) = event {
+                    if *v1.key() == new_block_event_key() {
+                        continue;
+                    }
+                    batch.delete::<EventByKeySchema>(&(*v1.key(), v1.sequence_number()))?;
+                    batch.delete::<EventByVersionSchema>(&(
+                        *v1.key(),
+                        version,
+                        v1.sequence_number(),
+                    ))?;
+                }
+                batch.delete::<EventSchema>(&(version, index))?;
+                num_deleted += 1;
+            }
+            self.prune_event_accumulator(begin, end, &mut batch)?;
+            batch.put::<DbMetadataSchema>(
+                &DbMetadataKey::EventRetentionProgress,
+                &DbMetadataValue::Version(end),
+            )?;
+            self.event_db.write_schemas(batch)?;
+            begin = end;
+        }
+
+        Ok(num_deleted)
+    }
+
+    /// Returns the first version whose events are all readable, the ones before it having been
+    /// deleted by `Self::prune_events` except for the `NewBlockEvent`s.
+    pub fn get_first_event_version(&self) -> Result<Version> {
+        Ok(self
+            .event_db
+            .get::<DbMetadataSchema>(&DbMetadataKey::EventRetentionProgress)?
+            .map_or(0, |value| value.expect_version()))
+    }
+
+    /// Fails if the events at `version` have been deleted by `Self::prune_events`, like
+    /// `AptosDB::error_if_ledger_pruned` does for the ledger pruner.
+    pub(crate) fn error_if_events_pruned(&self, version: Version) -> Result<()> {
+        let min_readable_version = self.get_first_event_version()?;
+        ensure!(
+            version >= min_readable_version,
+            "Events at version {} are pruned, min available version is {}.",
+            version,
+            min_readable_version
+        );
+        Ok(())
+    }
+
     /// Prunes events by accumulator store for a range of version in [begin, end)
     pub(crate) fn prune_event_accumulator(
         &self,
@@ -355,6 +545,18 @@ impl HashReader for EventHashReader<'_> {
     }
 }
 
+/// The nodes of an accumulator built in memory.
+struct InMemoryHashReader(HashMap<Position, HashValue>);
+
+impl HashReader for InMemoryHashReader {
+    fn get(&self, position: Position) -> Result<HashValue, anyhow::Error> {
+        self.0
+            .get(&position)
+            .copied()
+            .ok_or_else(|| anyhow!("Hash at position {:?} not found.", position))
+    }
+}
+
 pub(crate) struct EmptyReader;
 
 // Asserts `get()` is never called.
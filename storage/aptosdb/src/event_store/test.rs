@@ -10,6 +10,7 @@ use aptos_types::{
     account_address::AccountAddress,
     contract_event::ContractEvent,
     event::EventKey,
+    proof::accumulator::InMemoryEventAccumulator,
     proptest_types::{AccountInfoUniverse, ContractEventGen},
 };
 use itertools::Itertools;
@@ -20,7 +21,7 @@ use proptest::{
     strategy::Union,
 };
 use rand::Rng;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[test]
 fn test_error_on_get_from_empty() {
@@ -183,6 +184,242 @@ fn test_index_get_impl(event_batches: Vec<Vec<ContractEvent>>) {
         });
 }
 
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_get_events_grouped(
+        mut universe in any_with::<AccountInfoUniverse>(3),
+        gen_batches in vec(vec((any::<Index>(), any::<ContractEventGen>()), 0..=2), 0..100),
+    ) {
+        let event_batches = gen_batches
+            .into_iter()
+            .map(|gens| {
+                gens.into_iter()
+                    .map(|(index, r#gen)| r#gen.materialize(*index, &mut universe))
+                    .collect()
+            })
+            .collect();
+
+        test_get_events_grouped_impl(event_batches);
+    }
+}
+
+fn test_get_events_grouped_impl(event_batches: Vec<Vec<ContractEvent>>) {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.event_store;
+    let event_db = &db.ledger_db.event_db();
+
+    let mut batch = SchemaBatch::new();
+    event_batches.iter().enumerate().for_each(|(ver, events)| {
+        event_db
+            .put_events(ver as u64, events, /*skip_index=*/ false, &mut batch)
+            .unwrap();
+    });
+    event_db.write_schemas(batch).unwrap();
+    let ledger_version_plus_one = event_batches.len() as u64;
+
+    // Grouped by version, leaving out the versions without events.
+    let start_version = ledger_version_plus_one / 3;
+    let end_version = ledger_version_plus_one * 2 / 3;
+    let expected_grouped = (start_version..end_version)
+        .filter(|ver| !event_batches[*ver as usize].is_empty())
+        .map(|ver| (ver, event_batches[ver as usize].clone()))
+        .collect::<BTreeMap<_, _>>();
+    assert_eq!(
+        store
+            .get_events_grouped(start_version, end_version)
+            .unwrap(),
+        expected_grouped,
+    );
+    assert!(store
+        .get_events_grouped(end_version + 1, end_version)
+        .is_err());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_get_event_with_proof(
+        mut universe in any_with::<AccountInfoUniverse>(3),
+        gen_batches in vec(vec((any::<Index>(), any::<ContractEventGen>()), 0..=3), 0..50),
+    ) {
+        let event_batches: Vec<Vec<ContractEvent>> = gen_batches
+            .into_iter()
+            .map(|gens| {
+                gens.into_iter()
+                    .map(|(index, r#gen)| r#gen.materialize(*index, &mut universe))
+                    .collect()
+            })
+            .collect();
+
+        // Without the indices, as on sharded DBs, the event accumulator isn't persisted.
+        for skip_index in [false, true] {
+            test_get_event_with_proof_impl(&event_batches, skip_index);
+        }
+    }
+}
+
+fn test_get_event_with_proof_impl(event_batches: &[Vec<ContractEvent>], skip_index: bool) {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.event_store;
+    let event_db = &db.ledger_db.event_db();
+
+    let mut batch = SchemaBatch::new();
+    event_batches.iter().enumerate().for_each(|(ver, events)| {
+        event_db
+            .put_events(ver as u64, events, skip_index, &mut batch)
+            .unwrap();
+    });
+    event_db.write_schemas(batch).unwrap();
+
+    // Check the event proofs against the event root hashes.
+    event_batches.iter().enumerate().for_each(|(ver, events)| {
+        let event_hashes = events.iter().map(CryptoHash::hash).collect::<Vec<_>>();
+        let event_root_hash = InMemoryEventAccumulator::from_leaves(&event_hashes).root_hash();
+        events.iter().enumerate().for_each(|(idx, event)| {
+            let (fetched_event, proof) = store
+                .get_event_with_proof(ver as Version, idx as u64)
+                .unwrap();
+            assert_eq!(&fetched_event, event);
+            proof
+                .verify(event_root_hash, event.hash(), idx as u64)
+                .unwrap();
+        });
+        assert!(store
+            .get_event_with_proof(ver as Version, events.len() as u64)
+            .is_err());
+    });
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_prune_events(
+        mut universe in any_with::<AccountInfoUniverse>(3),
+        gen_batches in vec(vec((any::<Index>(), any::<ContractEventGen>()), 0..=2), 1..50),
+        prune_index in any::<Index>(),
+    ) {
+        let event_batches: Vec<Vec<ContractEvent>> = gen_batches
+            .into_iter()
+            .map(|gens| {
+                gens.into_iter()
+                    .map(|(index, r#gen)| r#gen.materialize(*index, &mut universe))
+                    .collect()
+            })
+            .collect();
+        test_prune_events_impl(event_batches, prune_index);
+    }
+}
+
+fn test_prune_events_impl(mut event_batches: Vec<Vec<ContractEvent>>, prune_index: Index) {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.event_store;
+    let event_db = &db.ledger_db.event_db();
+
+    // Every version starts a block, whose event survives the pruning. It's emitted last, so the
+    // event left isn't the first of its version.
+    for (ver, events) in event_batches.iter_mut().enumerate() {
+        let new_block_event = ContractEvent::new_v1(
+            new_block_event_key(),
+            ver as u64,
+            TypeTag::Struct(Box::new(NewBlockEvent::struct_tag())),
+            bcs::to_bytes(&NewBlockEvent::new(
+                AccountAddress::ZERO,
+                0,                   // epoch
+                ver as u64,          // round
+                ver as u64,          // height
+                vec![],              // prev block voters
+                AccountAddress::ONE, // proposer
+                Vec::new(),          // failed_proposers
+                ver as u64,          // timestamp
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        events.push(new_block_event);
+    }
+
+    let mut batch = SchemaBatch::new();
+    event_batches.iter().enumerate().for_each(|(ver, events)| {
+        event_db
+            .put_events(ver as u64, events, /*skip_index=*/ false, &mut batch)
+            .unwrap();
+    });
+    event_db.write_schemas(batch).unwrap();
+
+    let up_to_version = prune_index.index(event_batches.len() + 1) as Version;
+    let num_expected = event_batches[..up_to_version as usize]
+        .iter()
+        .map(|events| events.len() - 1)
+        .sum::<usize>();
+    assert_eq!(
+        store.prune_events(up_to_version).unwrap(),
+        num_expected as u64
+    );
+    // Nothing left to prune.
+    assert_eq!(store.prune_events(up_to_version).unwrap(), 0);
+
+    for (ver, events) in event_batches.iter().enumerate() {
+        let ver = ver as Version;
+        assert_eq!(
+            &event_db.expect_new_block_event(ver).unwrap(),
+            events.last().unwrap(),
+            "NewBlockEvent at version {ver} pruned.",
+        );
+        let expected_events = if ver < up_to_version {
+            &events[events.len() - 1..]
+        } else {
+            &events[..]
+        };
+        assert_eq!(
+            &event_db.get_events_by_version(ver).unwrap(),
+            expected_events
+        );
+        assert_eq!(
+            store.get_events_grouped(ver, ver + 1).is_err(),
+            ver < up_to_version,
+        );
+        for (idx, event) in events.iter().enumerate().take(events.len() - 1) {
+            let fetched = store.get_event_by_version_and_index(ver, idx as u64);
+            if ver < up_to_version {
+                assert!(fetched.is_err());
+                if let ContractEvent::V1(v1) = event {
+                    assert!(store
+                        .get_txn_ver_by_seq_num(v1.key(), v1.sequence_number())
+                        .is_err());
+                }
+            } else {
+                assert_eq!(&fetched.unwrap(), event);
+                assert!(store.get_event_with_proof(ver, idx as u64).is_ok());
+            }
+        }
+    }
+
+    // The ledger pruner deletes what's left, wherever the NewBlockEvents are.
+    let num_versions = event_batches.len() as Version;
+    let mut batch = SchemaBatch::new();
+    let event_keys = event_db
+        .prune_event_indices(0, num_versions, Some(&mut batch))
+        .unwrap();
+    event_db
+        .prune_events(event_keys, 0, num_versions, &mut batch)
+        .unwrap();
+    event_db.write_schemas(batch).unwrap();
+    assert_eq!(event_db.latest_version().unwrap(), None);
+    assert_eq!(
+        store.prune_events(num_versions + 1).unwrap(),
+        0,
+        "Nothing left to prune.",
+    );
+    assert_eq!(store.get_first_event_version().unwrap(), num_versions + 1);
+}
+
 prop_compose! {
     fn arb_new_block_events()(
         hash in any::<AccountAddress>(),
@@ -3,7 +3,7 @@
 
 use crate::AptosDB;
 use anyhow::anyhow;
-use aptos_config::config::{NodeConfig, StorageDirPaths};
+use aptos_config::config::{NodeConfig, StorageDirPaths, NO_OP_STORAGE_PRUNER_CONFIG};
 use aptos_crypto::HashValue;
 use aptos_db_indexer::db_indexer::InternalIndexerDB;
 use aptos_infallible::RwLock;
@@ -45,21 +45,49 @@ impl FastSyncStorageWrapper {
         internal_indexer_db: Option<InternalIndexerDB>,
         update_sender: Option<Sender<(Instant, Version)>>,
     ) -> Result<Either<AptosDB, Self>> {
-        let mut db_main = AptosDB::open(
-            config.storage.get_dir_paths(),
-            /*readonly=*/ false,
-            config.storage.storage_pruner_config,
-            config.storage.rocksdb_configs,
-            config.storage.enable_indexer,
-            config.storage.buffered_state_target_items,
-            config.storage.max_num_nodes_per_lru_cache_shard,
-            internal_indexer_db,
-            config.storage.hot_state_config,
-        )
+        let mut db_main = if let Some(ledger_archive_dir) = &config.storage.ledger_archive_dir {
+            // Only ledger data is written to the archive, the same as restoring transactions.
+            let ledger_archive_db = AptosDB::open_kv_only(
+                StorageDirPaths::from_path(ledger_archive_dir),
+                /*readonly=*/ false,
+                NO_OP_STORAGE_PRUNER_CONFIG,
+                config.storage.rocksdb_configs,
+                /*enable_indexer=*/ false,
+                config.storage.buffered_state_target_items,
+                config.storage.max_num_nodes_per_lru_cache_shard,
+                None,
+            )
+            .map_err(|err| anyhow!("Ledger archive DB failed to open {}", err))?;
+            AptosDB::open_with_ledger_archive(
+                config.storage.get_dir_paths(),
+                /*readonly=*/ false,
+                config.storage.storage_pruner_config,
+                config.storage.rocksdb_configs,
+                config.storage.enable_indexer,
+                config.storage.buffered_state_target_items,
+                config.storage.max_num_nodes_per_lru_cache_shard,
+                internal_indexer_db,
+                config.storage.hot_state_config,
+                Arc::new(ledger_archive_db),
+            )
+        } else {
+            AptosDB::open(
+                config.storage.get_dir_paths(),
+                /*readonly=*/ false,
+                config.storage.storage_pruner_config,
+                config.storage.rocksdb_configs,
+                config.storage.enable_indexer,
+                config.storage.buffered_state_target_items,
+                config.storage.max_num_nodes_per_lru_cache_shard,
+                internal_indexer_db,
+                config.storage.hot_state_config,
+            )
+        }
         .map_err(|err| anyhow!("fast sync DB failed to open {}", err))?;
         if let Some(sender) = update_sender {
             db_main.add_version_update_subscriber(sender)?;
         }
+        db_main.set_verify_ledger_root_on_commit(config.storage.verify_ledger_root_on_commit);
 
         let mut db_dir = config.storage.dir();
         // when the db is empty and configured to do fast sync, we will create a second DB
@@ -2,15 +2,33 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{backup::restore_handler::RestoreHandler, db::AptosDB};
-use std::sync::Arc;
+use std::{num::NonZeroUsize, sync::Arc};
 
 pub trait GetRestoreHandler {
     /// Gets an instance of `RestoreHandler` for data restore purpose.
     fn get_restore_handler(&self) -> RestoreHandler;
+
+    /// Same as `get_restore_handler`, except that a state snapshot restore writes at most
+    /// `max_concurrent_shards` state KV shards at a time.
+    fn get_restore_handler_with_max_concurrent_shards(
+        &self,
+        max_concurrent_shards: NonZeroUsize,
+    ) -> RestoreHandler;
 }
 
 impl GetRestoreHandler for Arc<AptosDB> {
     fn get_restore_handler(&self) -> RestoreHandler {
-        RestoreHandler::new(Arc::clone(self), Arc::clone(&self.state_store))
+        RestoreHandler::new(Arc::clone(self), Arc::clone(&self.state_store), None)
+    }
+
+    fn get_restore_handler_with_max_concurrent_shards(
+        &self,
+        max_concurrent_shards: NonZeroUsize,
+    ) -> RestoreHandler {
+        RestoreHandler::new(
+            Arc::clone(self),
+            Arc::clone(&self.state_store),
+            Some(max_concurrent_shards),
+        )
     }
 }
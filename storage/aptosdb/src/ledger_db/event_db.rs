@@ -64,6 +64,9 @@ impl EventDb {
     }
 
     /// Returns all of the events for a given transaction version.
+    ///
+    /// Only the `NewBlockEvent` is left below `EventStore::get_first_event_version`, where the
+    /// events have been deleted by `EventStore::prune_events`.
     pub(crate) fn get_events_by_version(&self, version: Version) -> Result<Vec<ContractEvent>> {
         let mut events = vec![];
 
@@ -95,8 +98,31 @@ impl EventDb {
         )))
     }
 
+    /// Returns the last `NewBlockEvent` at or before the given version, with the version it was
+    /// emitted at, looked up in the event index, which is empty if the indices are skipped.
+    pub(crate) fn get_new_block_event_at_or_before(
+        &self,
+        version: Version,
+    ) -> Result<Option<(Version, ContractEvent)>> {
+        let mut iter = self.db.iter::<EventByVersionSchema>()?;
+        iter.seek_for_prev(&(new_block_event_key(), version, u64::MAX))?;
+        match iter.next().transpose()? {
+            Some(((key, block_version, _seq_num), idx)) if key == new_block_event_key() => {
+                let event = self
+                    .db
+                    .get::<EventSchema>(&(block_version, idx))?
+                    .ok_or_else(|| {
+                        AptosDbError::NotFound(format!("Event {} of Txn {}", idx, block_version))
+                    })?;
+                Ok(Some((block_version, event)))
+            },
+            _ => Ok(None),
+        }
+    }
+
     /// Returns an iterator that yields at most `num_versions` versions' events starting from
-    /// `start_version`.
+    /// `start_version`, see `Self::get_events_by_version` for the versions whose events have been
+    /// deleted by `EventStore::prune_events`.
     pub(crate) fn get_events_by_version_iter(
         &self,
         start_version: Version,
@@ -187,35 +213,37 @@ impl EventDb {
         Ok(())
     }
 
-    /// Deletes event indices, returns number of events per version, so `prune_events` doesn't need
-    /// to iterate through evnets from DB again.
+    /// Deletes event indices, returns the keys of the events, so `prune_events` doesn't need to
+    /// iterate through events from DB again.
+    ///
+    /// The keys are the ones found rather than `0..num_events` per version, as the `NewBlockEvent`
+    /// kept by `EventStore::prune_events` isn't necessarily the first event of its version.
     pub(crate) fn prune_event_indices(
         &self,
         start: Version,
         end: Version,
         mut indices_batch: Option<&mut SchemaBatch>,
-    ) -> Result<Vec<usize>> {
+    ) -> Result<Vec<(Version, u64)>> {
         let mut ret = Vec::new();
 
-        let mut current_version = start;
-
-        for events in self.get_events_by_version_iter(start, (end - start) as usize)? {
-            let events = events?;
-            ret.push(events.len());
+        let mut iter = self.db.iter::<EventSchema>()?;
+        iter.seek(&start)?;
+        while let Some(((version, index), event)) = iter.next().transpose()? {
+            if version >= end {
+                break;
+            }
+            ret.push((version, index));
 
             if let Some(ref mut batch) = indices_batch {
-                for event in events {
-                    if let ContractEvent::V1(v1) = event {
-                        batch.delete::<EventByKeySchema>(&(*v1.key(), v1.sequence_number()))?;
-                        batch.delete::<EventByVersionSchema>(&(
-                            *v1.key(),
-                            current_version,
-                            v1.sequence_number(),
-                        ))?;
-                    }
+                if let ContractEvent::V1(v1) = event {
+                    batch.delete::<EventByKeySchema>(&(*v1.key(), v1.sequence_number()))?;
+                    batch.delete::<EventByVersionSchema>(&(
+                        *v1.key(),
+                        version,
+                        v1.sequence_number(),
+                    ))?;
                 }
             }
-            current_version += 1;
         }
 
         Ok(ret)
@@ -224,18 +252,13 @@ impl EventDb {
     /// Deletes a set of events in the range of version in [begin, end), and all related indices.
     pub(crate) fn prune_events(
         &self,
-        num_events_per_version: Vec<usize>,
+        event_keys: Vec<(Version, u64)>,
         start: Version,
         end: Version,
         db_batch: &mut SchemaBatch,
     ) -> Result<()> {
-        let mut current_version = start;
-
-        for num_events in num_events_per_version {
-            for idx in 0..num_events {
-                db_batch.delete::<EventSchema>(&(current_version, idx as u64))?;
-            }
-            current_version += 1;
+        for key in event_keys {
+            db_batch.delete::<EventSchema>(&key)?;
         }
         self.event_store
             .prune_event_accumulator(start, end, db_batch)?;
@@ -5,7 +5,7 @@ use crate::{
     schema::{
         block_by_version::BlockByVersionSchema,
         block_info::BlockInfoSchema,
-        db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+        db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue, ShardConfig},
         epoch_by_version::EpochByVersionSchema,
         ledger_info::LedgerInfoSchema,
         version_data::VersionDataSchema,
@@ -86,6 +86,67 @@ impl LedgerMetadataDb {
         get_progress(&self.db, &DbMetadataKey::LedgerPrunerProgress)?
             .ok_or_else(|| AptosDbError::NotFound("No LedgerPrunerProgress in db.".to_string()))
     }
+
+    /// Fails with `AptosDbError::ShardConfigMismatch` if the DB was created with a shard config
+    /// other than `shard_config`. A DB without one, i.e. a new DB or one created before the shard
+    /// config was recorded, gets `shard_config` recorded unless `readonly`.
+    pub(crate) fn check_shard_config(
+        &self,
+        shard_config: ShardConfig,
+        readonly: bool,
+    ) -> Result<()> {
+        let stored = self
+            .db
+            .get::<DbMetadataSchema>(&DbMetadataKey::ShardConfig)?;
+        match stored.map(DbMetadataValue::expect_shard_config) {
+            Some(stored) => {
+                if stored != shard_config {
+                    return Err(AptosDbError::ShardConfigMismatch {
+                        stored: format!("{stored:?}"),
+                        opening: format!("{shard_config:?}"),
+                    });
+                }
+            },
+            None if !readonly => self.db.put::<DbMetadataSchema>(
+                &DbMetadataKey::ShardConfig,
+                &DbMetadataValue::ShardConfig(shard_config),
+            )?,
+            None => (),
+        }
+        Ok(())
+    }
+
+    /// Fails if the state values were encrypted at rest but `encrypted` is false, or the other
+    /// way around, since the plaintext and encrypted values can't be told apart once mixed. A DB
+    /// without the setting recorded gets it recorded unless `readonly`, provided it's new or
+    /// `encrypted` is false, i.e. it was created before the setting was recorded, in plaintext.
+    pub(crate) fn check_value_encryption(&self, encrypted: bool, readonly: bool) -> Result<()> {
+        let stored = self
+            .db
+            .get::<DbMetadataSchema>(&DbMetadataKey::ValueEncryption)?
+            .map(DbMetadataValue::expect_value_encryption);
+        match stored {
+            Some(stored) => ensure!(
+                stored == encrypted,
+                "The state values are {}encrypted at rest, but the DB is opened {} a value cipher.",
+                if stored { "" } else { "not " },
+                if encrypted { "with" } else { "without" },
+            ),
+            None => {
+                ensure!(
+                    !encrypted || self.get_synced_version()?.is_none(),
+                    "The DB has state values in plaintext, it can't be opened with a value cipher.",
+                );
+                if !readonly {
+                    self.db.put::<DbMetadataSchema>(
+                        &DbMetadataKey::ValueEncryption,
+                        &DbMetadataValue::ValueEncryption(encrypted),
+                    )?;
+                }
+            },
+        }
+        Ok(())
+    }
 }
 
 /// LedgerInfo APIs.
@@ -279,6 +340,17 @@ impl LedgerMetadataDb {
         Ok(block_height)
     }
 
+    /// Returns the BlockInfo of the block containing the given version, or None if the block index
+    /// doesn't cover it.
+    pub(crate) fn get_block_info_by_version(&self, version: Version) -> Result<Option<BlockInfo>> {
+        let mut iter = self.db.iter::<BlockByVersionSchema>()?;
+        iter.seek_for_prev(&version)?;
+        match iter.next().transpose()? {
+            Some((_, block_height)) => self.get_block_info(block_height),
+            None => Ok(None),
+        }
+    }
+
     pub(crate) fn get_block_height_at_or_after_version(
         &self,
         version: Version,
@@ -1,7 +1,11 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
-use crate::{ledger_db::ledger_metadata_db::LedgerMetadataDb, AptosDB};
+use crate::{
+    ledger_db::ledger_metadata_db::LedgerMetadataDb,
+    schema::db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+    AptosDB,
+};
 use aptos_schemadb::batch::SchemaBatch;
 use aptos_storage_interface::AptosDbError;
 use aptos_temppath::TempPath;
@@ -165,6 +169,96 @@ proptest! {
         }
     }
 
+    #[test]
+    fn test_get_epoch_ending_ledger_info_by_epoch(ledger_infos_with_sigs in arb_ledger_infos_with_sigs()) {
+        let tmp_dir = TempPath::new();
+        let db = set_up(&tmp_dir, &ledger_infos_with_sigs);
+
+        for ledger_info_with_sigs in &ledger_infos_with_sigs {
+            let li = ledger_info_with_sigs.ledger_info();
+            if li.ends_epoch() {
+                prop_assert_eq!(
+                    &db.ledger_db.get_epoch_ending_ledger_info(li.epoch()).unwrap(),
+                    ledger_info_with_sigs
+                );
+            }
+        }
+
+        let last_li = ledger_infos_with_sigs.last().unwrap().ledger_info();
+        if !last_li.ends_epoch() {
+            prop_assert!(db.ledger_db.get_epoch_ending_ledger_info(last_li.epoch()).is_err());
+        }
+        prop_assert!(db.ledger_db.get_epoch_ending_ledger_info(last_li.epoch() + 1).is_err());
+    }
+
+    #[test]
+    fn test_get_epoch_change_proof(ledger_infos_with_sigs in arb_ledger_infos_with_sigs()) {
+        let tmp_dir = TempPath::new();
+        let db = set_up(&tmp_dir, &ledger_infos_with_sigs);
+
+        let epoch_ending_lis: Vec<_> = ledger_infos_with_sigs
+            .iter()
+            .filter(|li| li.ledger_info().ends_epoch())
+            .cloned()
+            .collect();
+        let latest_epoch = ledger_infos_with_sigs.last().unwrap().ledger_info().next_block_epoch();
+
+        let proof = db.ledger_db.get_epoch_change_proof(0, latest_epoch).unwrap();
+        prop_assert_eq!(&proof.ledger_info_with_sigs, &epoch_ending_lis);
+        prop_assert!(!proof.more);
+
+        let proof = db.ledger_db.get_epoch_change_proof(latest_epoch, latest_epoch).unwrap();
+        prop_assert!(proof.ledger_info_with_sigs.is_empty());
+
+        prop_assert!(db.ledger_db.get_epoch_change_proof(0, latest_epoch + 1).is_err());
+    }
+
+    #[test]
+    fn test_transactions_in_epoch(ledger_infos_with_sigs in arb_ledger_infos_with_sigs()) {
+        let tmp_dir = TempPath::new();
+        let db = set_up(&tmp_dir, &ledger_infos_with_sigs);
+
+        let mut first_version = 0;
+        for (i, ledger_info_with_sigs) in ledger_infos_with_sigs.iter().enumerate() {
+            let li = ledger_info_with_sigs.ledger_info();
+            let is_last_in_epoch = ledger_infos_with_sigs
+                .get(i + 1)
+                .is_none_or(|next| next.ledger_info().epoch() != li.epoch());
+            if is_last_in_epoch {
+                prop_assert_eq!(
+                    db.ledger_db.transactions_in_epoch(li.epoch()).unwrap(),
+                    (first_version, li.version(), li.version() - first_version + 1)
+                );
+                first_version = li.version() + 1;
+            }
+        }
+
+        let last_epoch = get_last_epoch(&ledger_infos_with_sigs);
+        prop_assert!(db.ledger_db.transactions_in_epoch(last_epoch + 1).is_err());
+    }
+
+    #[test]
+    fn test_epoch_bounds_of_version(ledger_infos_with_sigs in arb_ledger_infos_with_sigs()) {
+        let tmp_dir = TempPath::new();
+        let db = set_up(&tmp_dir, &ledger_infos_with_sigs);
+
+        let last_epoch = get_last_epoch(&ledger_infos_with_sigs);
+        for epoch in 0..=last_epoch {
+            let (first_version, last_version, _num_txns) =
+                db.ledger_db.transactions_in_epoch(epoch).unwrap();
+            // Around the epoch ending transaction of the previous epoch and of this one.
+            for version in [first_version, (first_version + last_version) / 2, last_version] {
+                prop_assert_eq!(
+                    db.ledger_db.epoch_bounds_of_version(version).unwrap(),
+                    (epoch, first_version, last_version)
+                );
+            }
+        }
+
+        let last_version = get_last_version(&ledger_infos_with_sigs);
+        prop_assert!(db.ledger_db.epoch_bounds_of_version(last_version + 1).is_err());
+    }
+
     #[test]
     fn test_get_epoch_ending_ledger_info(ledger_infos_with_sigs in arb_ledger_infos_with_sigs()) {
         let tmp_dir = TempPath::new();
@@ -321,6 +415,111 @@ fn test_block_api() {
     }
 }
 
+#[test]
+fn test_get_block_info_by_version() {
+    let new_block_event = |height, proposer| {
+        let event = NewBlockEvent::new(
+            AccountAddress::random(),
+            0,
+            height,
+            height,
+            vec![],
+            proposer,
+            vec![],
+            height * 1000,
+        );
+        ContractEvent::new_v1(
+            new_block_event_key(),
+            height,
+            TypeTag::from(NewBlockEvent::struct_tag()),
+            bcs::to_bytes(&event).unwrap(),
+        )
+        .expect("Should always be able to create a new block event")
+    };
+    let proposers = [AccountAddress::random(), AccountAddress::random()];
+    let blocks = [
+        (1, new_block_event(1, proposers[0])),
+        (10, new_block_event(2, proposers[1])),
+    ];
+
+    // By the block index.
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let mut batch = SchemaBatch::new();
+    for (version, event) in &blocks {
+        LedgerMetadataDb::put_block_info(*version, event, &mut batch).unwrap();
+    }
+    put_synced_version(&mut batch, 19);
+    db.ledger_db.metadata_db().write_schemas(batch).unwrap();
+    check_block_info_by_version(&db, &proposers);
+
+    // By the NewBlockEvent index.
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let mut batch = SchemaBatch::new();
+    for (version, event) in &blocks {
+        db.ledger_db
+            .event_db()
+            .put_events(
+                *version,
+                &[event.clone()],
+                /*skip_index=*/ false,
+                &mut batch,
+            )
+            .unwrap();
+    }
+    db.ledger_db.event_db().write_schemas(batch).unwrap();
+    let mut batch = SchemaBatch::new();
+    put_synced_version(&mut batch, 19);
+    db.ledger_db.metadata_db().write_schemas(batch).unwrap();
+    check_block_info_by_version(&db, &proposers);
+}
+
+fn put_synced_version(batch: &mut SchemaBatch, version: Version) {
+    batch
+        .put::<DbMetadataSchema>(
+            &DbMetadataKey::OverallCommitProgress,
+            &DbMetadataValue::Version(version),
+        )
+        .unwrap();
+}
+
+fn check_block_info_by_version(db: &AptosDB, proposers: &[AccountAddress; 2]) {
+    assert!(matches!(
+        db.ledger_db.get_block_info_by_version(0),
+        Err(AptosDbError::NotFound(_))
+    ));
+    assert!(matches!(
+        db.ledger_db.get_timestamp(0),
+        Err(AptosDbError::NotFound(_))
+    ));
+    for version in 1..10 {
+        let block_info = db.ledger_db.get_block_info_by_version(version).unwrap();
+        assert_eq!(block_info.first_version(), 1);
+        assert_eq!(block_info.round(), 1);
+        assert_eq!(block_info.proposer(), proposers[0]);
+        assert_eq!(block_info.timestamp_usecs(), 1000);
+        assert_eq!(db.ledger_db.get_timestamp(version).unwrap(), 1000);
+    }
+    for version in 10..20 {
+        let block_info = db.ledger_db.get_block_info_by_version(version).unwrap();
+        assert_eq!(block_info.first_version(), 10);
+        assert_eq!(block_info.round(), 2);
+        assert_eq!(block_info.proposer(), proposers[1]);
+        assert_eq!(block_info.timestamp_usecs(), 2000);
+        assert_eq!(db.ledger_db.get_timestamp(version).unwrap(), 2000);
+    }
+    // Beyond the synced version.
+    assert!(matches!(
+        db.ledger_db.get_block_info_by_version(20),
+        Err(AptosDbError::NotFound(_))
+    ));
+    assert!(matches!(
+        db.ledger_db.get_timestamp(20),
+        Err(AptosDbError::NotFound(_))
+    ));
+}
+
 #[test]
 fn test_usage() {
     let tmp_dir = TempPath::new();
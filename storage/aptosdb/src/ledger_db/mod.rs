@@ -5,6 +5,7 @@
 #![allow(dead_code)]
 
 use crate::{
+    common::MAX_NUM_EPOCH_ENDING_LEDGER_INFO,
     db_options::{
         event_db_column_families, gen_event_cfds, gen_ledger_cfds, gen_ledger_metadata_cfds,
         gen_persisted_auxiliary_info_cfds, gen_transaction_accumulator_cfds,
@@ -23,16 +24,26 @@ use crate::{
         transaction_info_db::TransactionInfoDb, write_set_db::WriteSetDb,
     },
     schema::db_metadata::{DbMetadataKey, DbMetadataSchema},
+    utils::set_wal_dir,
 };
 use aptos_config::config::{RocksdbConfig, RocksdbConfigs};
+use aptos_crypto::HashValue;
 use aptos_experimental_runtimes::thread_manager::THREAD_MANAGER;
 use aptos_logger::prelude::info;
 use aptos_rocksdb_options::gen_rocksdb_options;
 use aptos_schemadb::{
-    batch::SchemaBatch, Cache, ColumnFamilyDescriptor, ColumnFamilyName, Env, DB,
+    batch::SchemaBatch, Cache, ColumnFamilyDescriptor, ColumnFamilyName, Env, WriteBufferManager,
+    DB,
+};
+use aptos_storage_interface::{block_info::BlockInfo, db_ensure as ensure, AptosDbError, Result};
+use aptos_types::{
+    account_config::NewBlockEvent,
+    contract_event::ContractEvent,
+    epoch_change::EpochChangeProof,
+    ledger_info::LedgerInfoWithSignatures,
+    transaction::{Transaction, TransactionInfo, Version},
+    write_set::WriteSet,
 };
-use aptos_storage_interface::Result;
-use aptos_types::transaction::Version;
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
@@ -121,9 +132,11 @@ pub struct LedgerDb {
 impl LedgerDb {
     pub(crate) fn new<P: AsRef<Path>>(
         db_root_path: P,
+        wal_root_path: Option<&Path>,
         rocksdb_configs: RocksdbConfigs,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
     ) -> Result<Self> {
         let sharding = rocksdb_configs.enable_storage_sharding;
@@ -135,9 +148,11 @@ impl LedgerDb {
             } else {
                 LEDGER_DB_NAME
             },
+            wal_root_path,
             &rocksdb_configs.ledger_db_config,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
         )?);
 
@@ -186,9 +201,11 @@ impl LedgerDb {
                     Self::open_rocksdb(
                         ledger_db_folder.join(EVENT_DB_NAME),
                         EVENT_DB_NAME,
+                        wal_root_path,
                         &rocksdb_configs.ledger_db_config,
                         env,
                         block_cache,
+                        write_buffer_manager,
                         readonly,
                     )
                     .unwrap(),
@@ -203,9 +220,11 @@ impl LedgerDb {
                     Self::open_rocksdb(
                         ledger_db_folder.join(PERSISTED_AUXILIARY_INFO_DB_NAME),
                         PERSISTED_AUXILIARY_INFO_DB_NAME,
+                        wal_root_path,
                         &rocksdb_configs.ledger_db_config,
                         env,
                         block_cache,
+                        write_buffer_manager,
                         readonly,
                     )
                     .unwrap(),
@@ -216,9 +235,11 @@ impl LedgerDb {
                     Self::open_rocksdb(
                         ledger_db_folder.join(TRANSACTION_ACCUMULATOR_DB_NAME),
                         TRANSACTION_ACCUMULATOR_DB_NAME,
+                        wal_root_path,
                         &rocksdb_configs.ledger_db_config,
                         env,
                         block_cache,
+                        write_buffer_manager,
                         readonly,
                     )
                     .unwrap(),
@@ -229,9 +250,11 @@ impl LedgerDb {
                     Self::open_rocksdb(
                         ledger_db_folder.join(TRANSACTION_AUXILIARY_DATA_DB_NAME),
                         TRANSACTION_AUXILIARY_DATA_DB_NAME,
+                        wal_root_path,
                         &rocksdb_configs.ledger_db_config,
                         env,
                         block_cache,
+                        write_buffer_manager,
                         readonly,
                     )
                     .unwrap(),
@@ -242,9 +265,11 @@ impl LedgerDb {
                     Self::open_rocksdb(
                         ledger_db_folder.join(TRANSACTION_DB_NAME),
                         TRANSACTION_DB_NAME,
+                        wal_root_path,
                         &rocksdb_configs.ledger_db_config,
                         env,
                         block_cache,
+                        write_buffer_manager,
                         readonly,
                     )
                     .unwrap(),
@@ -255,9 +280,11 @@ impl LedgerDb {
                     Self::open_rocksdb(
                         ledger_db_folder.join(TRANSACTION_INFO_DB_NAME),
                         TRANSACTION_INFO_DB_NAME,
+                        wal_root_path,
                         &rocksdb_configs.ledger_db_config,
                         env,
                         block_cache,
+                        write_buffer_manager,
                         readonly,
                     )
                     .unwrap(),
@@ -268,9 +295,11 @@ impl LedgerDb {
                     Self::open_rocksdb(
                         ledger_db_folder.join(WRITE_SET_DB_NAME),
                         WRITE_SET_DB_NAME,
+                        wal_root_path,
                         &rocksdb_configs.ledger_db_config,
                         env,
                         block_cache,
+                        write_buffer_manager,
                         readonly,
                     )
                     .unwrap(),
@@ -293,6 +322,64 @@ impl LedgerDb {
         })
     }
 
+    /// Opens the ledger DB under `db_root_path` read only, on its own, for the tools which only
+    /// need the transactions and what comes with them: the state KV and state merkle DBs, with
+    /// their shards, are left alone, so none of the state can be queried from it.
+    pub fn open_standalone<P: AsRef<Path>>(
+        db_root_path: P,
+        rocksdb_configs: RocksdbConfigs,
+    ) -> Result<Self> {
+        Self::new(
+            db_root_path,
+            /* wal_root_path = */ None,
+            rocksdb_configs,
+            /* env = */ None,
+            /* block_cache = */ None,
+            /* write_buffer_manager = */ None,
+            /* readonly = */ true,
+        )
+    }
+
+    /// Returns the latest version whose transaction and output are in the DB, if any.
+    pub fn get_synced_version(&self) -> Result<Option<Version>> {
+        self.ledger_metadata_db.get_synced_version()
+    }
+
+    pub fn get_transaction(&self, version: Version) -> Result<Transaction> {
+        self.transaction_db.get_transaction(version)
+    }
+
+    pub fn get_transaction_info(&self, version: Version) -> Result<TransactionInfo> {
+        self.transaction_info_db.get_transaction_info(version)
+    }
+
+    pub fn get_events(&self, version: Version) -> Result<Vec<ContractEvent>> {
+        self.event_db.get_events_by_version(version)
+    }
+
+    pub fn get_write_set(&self, version: Version) -> Result<WriteSet> {
+        self.write_set_db.get_write_set(version)
+    }
+
+    /// Returns the roots of the frozen subtrees of the transaction accumulator right after the
+    /// transaction at `version`, from the largest to the smallest. They're all that's needed to
+    /// append the following transactions and compute the same root hashes as the DB, e.g. with
+    /// `InMemoryTransactionAccumulator::new(frozen_subtrees, version + 1)`.
+    pub fn get_accumulator_frozen_subtrees(&self, version: Version) -> Result<Vec<HashValue>> {
+        let synced_version = self.get_synced_version()?;
+        ensure!(
+            synced_version.is_some_and(|synced_version| version <= synced_version),
+            "Version {} is beyond the synced version {:?}.",
+            version,
+            synced_version,
+        );
+        let num_leaves = version
+            .checked_add(1)
+            .ok_or_else(|| AptosDbError::Other("Version overflow.".to_string()))?;
+        self.transaction_accumulator_db
+            .get_frozen_subtree_hashes(num_leaves)
+    }
+
     pub(crate) fn enable_storage_sharding(&self) -> bool {
         self.enable_storage_sharding
     }
@@ -308,8 +395,208 @@ impl LedgerDb {
         Ok(None)
     }
 
+    /// Returns the signed ledger info that closed the given epoch, or an error if the epoch hasn't
+    /// ended yet.
+    pub fn get_epoch_ending_ledger_info(
+        &self,
+        epoch: u64,
+    ) -> Result<LedgerInfoWithSignatures> {
+        let li = self
+            .ledger_metadata_db
+            .get_latest_ledger_info_in_epoch(epoch)?;
+        ensure!(
+            li.ledger_info().ends_epoch(),
+            "Epoch {} hasn't ended yet.",
+            epoch
+        );
+        Ok(li)
+    }
+
+    /// Returns the first and the last version of the given epoch, and the number of transactions
+    /// in it. The boundaries come from the ledger infos: the epoch starts right after the epoch
+    /// ending ledger info of the previous epoch, and ends at the latest ledger info of the epoch
+    /// itself, which is the epoch ending one unless the epoch is still ongoing.
+    pub fn transactions_in_epoch(&self, epoch: u64) -> Result<(Version, Version, u64)> {
+        let first_version = if epoch == 0 {
+            0
+        } else {
+            self.get_epoch_ending_ledger_info(epoch - 1)?
+                .ledger_info()
+                .version()
+                .checked_add(1)
+                .ok_or_else(|| AptosDbError::Other("Version overflow.".to_string()))?
+        };
+        let last_version = self
+            .ledger_metadata_db
+            .get_latest_ledger_info_in_epoch(epoch)?
+            .ledger_info()
+            .version();
+        ensure!(
+            last_version >= first_version,
+            "DB corruption: epoch {} ends at version {} before it starts at version {}.",
+            epoch,
+            last_version,
+            first_version,
+        );
+
+        Ok((
+            first_version,
+            last_version,
+            last_version - first_version + 1,
+        ))
+    }
+
+    /// Returns the epoch `version` is in, with the first and the last version of that epoch, see
+    /// `transactions_in_epoch`. An epoch ending transaction belongs to the epoch it ends. For the
+    /// ongoing epoch, the last version is that of the latest ledger info, and versions after it
+    /// are an error.
+    pub fn epoch_bounds_of_version(&self, version: Version) -> Result<(u64, Version, Version)> {
+        let epoch = self.ledger_metadata_db.get_epoch(version)?;
+        let (first_version, last_version, _num_txns) = self.transactions_in_epoch(epoch)?;
+        ensure!(
+            (first_version..=last_version).contains(&version),
+            "Version {} is not covered by a ledger info, epoch {} is known up to version {}.",
+            version,
+            epoch,
+            last_version,
+        );
+        Ok((epoch, first_version, last_version))
+    }
+
+    /// Returns the epoch change proof from `start_epoch` to `end_epoch`, i.e. the epoch ending
+    /// ledger infos of the epochs in `[start_epoch, end_epoch)`, which carry the validator set
+    /// transitions a client trusting `start_epoch` verifies to trust `end_epoch`. Up to
+    /// `MAX_NUM_EPOCH_ENDING_LEDGER_INFO` ledger infos are returned, with `more` set in the proof
+    /// if there are more, in which case the client asks again from where the proof ends.
+    pub fn get_epoch_change_proof(
+        &self,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<EpochChangeProof> {
+        let (ledger_infos_with_sigs, more) = self.get_epoch_ending_ledger_infos(
+            start_epoch,
+            end_epoch,
+            MAX_NUM_EPOCH_ENDING_LEDGER_INFO,
+        )?;
+        Ok(EpochChangeProof::new(ledger_infos_with_sigs, more))
+    }
+
+    /// Returns the epoch ending ledger infos of the epochs in `[start_epoch, end_epoch)`. If there
+    /// are more than `limit` of them, only the first `limit` are returned, along with a flag
+    /// telling there are more.
+    pub(crate) fn get_epoch_ending_ledger_infos(
+        &self,
+        start_epoch: u64,
+        end_epoch: u64,
+        limit: usize,
+    ) -> Result<(Vec<LedgerInfoWithSignatures>, bool)> {
+        self.check_epoch_ending_ledger_infos_request(start_epoch, end_epoch)?;
+
+        let (paging_epoch, more) = if end_epoch - start_epoch > limit as u64 {
+            (start_epoch + limit as u64, true)
+        } else {
+            (end_epoch, false)
+        };
+
+        let lis = self
+            .ledger_metadata_db
+            .get_epoch_ending_ledger_info_iter(start_epoch, paging_epoch)?
+            .collect::<Result<Vec<_>>>()?;
+
+        ensure!(
+            lis.len() == (paging_epoch - start_epoch) as usize,
+            "DB corruption: missing epoch ending ledger info for epoch {}",
+            lis.last()
+                .map(|li| li.ledger_info().next_block_epoch() - 1)
+                .unwrap_or(start_epoch),
+        );
+        Ok((lis, more))
+    }
+
+    pub(crate) fn check_epoch_ending_ledger_infos_request(
+        &self,
+        start_epoch: u64,
+        end_epoch: u64,
+    ) -> Result<()> {
+        ensure!(
+            start_epoch <= end_epoch,
+            "Bad epoch range [{}, {})",
+            start_epoch,
+            end_epoch,
+        );
+        // Note that the latest epoch can be the same with the current epoch (in most cases), or
+        // current_epoch + 1 (when the latest ledger_info carries next validator set)
+
+        let latest_epoch = self
+            .ledger_metadata_db
+            .get_latest_ledger_info()?
+            .ledger_info()
+            .next_block_epoch();
+        ensure!(
+            end_epoch <= latest_epoch,
+            "Unable to provide epoch change ledger info for still open epoch. asked upper bound: {}, last sealed epoch: {}",
+            end_epoch,
+            latest_epoch - 1,  // okay to -1 because genesis LedgerInfo has .next_block_epoch() == 1
+        );
+        Ok(())
+    }
+
+    /// Returns the info of the block containing the given version, which includes the version the
+    /// block starts at. It's looked up in the block index if that's written (i.e. when the event
+    /// indices are skipped), otherwise by the `NewBlockEvent` index. Versions beyond the synced
+    /// version aren't in any block yet, even if it's the last one.
+    pub fn get_block_info_by_version(&self, version: Version) -> Result<BlockInfo> {
+        let synced_version = self.get_synced_version()?;
+        if !synced_version.is_some_and(|synced_version| version <= synced_version) {
+            return Err(AptosDbError::NotFound(format!(
+                "Block containing version {version}, beyond the synced version {synced_version:?}"
+            )));
+        }
+        if let Some(block_info) = self.ledger_metadata_db.get_block_info_by_version(version)? {
+            return Ok(block_info);
+        }
+        let (first_version, event) = self
+            .event_db
+            .get_new_block_event_at_or_before(version)?
+            .ok_or_else(|| AptosDbError::NotFound(format!("Block containing version {version}")))?;
+        let new_block_event = NewBlockEvent::try_from_bytes(event.event_data())?;
+        Ok(BlockInfo::from_new_block_event(
+            first_version,
+            &new_block_event,
+        ))
+    }
+
+    /// Returns the timestamp, in microseconds, of the block containing the given version, see
+    /// [`Self::get_block_info_by_version`].
+    pub fn get_timestamp(&self, version: Version) -> Result<u64> {
+        Ok(self.get_block_info_by_version(version)?.timestamp_usecs())
+    }
+
+    /// Returns an iterator over the transaction infos in `[start_version, end_version)` with their
+    /// versions, which is all that's needed to recompute the transaction accumulator, without
+    /// reading the transactions themselves. Fails on a gap, e.g. from pruning.
+    pub fn iter_transaction_infos(
+        &self,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<impl Iterator<Item = Result<(Version, TransactionInfo)>> + '_> {
+        ensure!(
+            start_version <= end_version,
+            "Invalid version range [{}, {}).",
+            start_version,
+            end_version,
+        );
+        let num_transaction_infos = end_version - start_version;
+        Ok(self
+            .transaction_info_db
+            .get_transaction_info_iter(start_version, num_transaction_infos as usize)?
+            .zip(start_version..end_version)
+            .map(|(res, version)| res.map(|txn_info| (version, txn_info))))
+    }
+
     pub(crate) fn create_checkpoint(
         db_root_path: impl AsRef<Path>,
+        wal_root_path: Option<&Path>,
         cp_root_path: impl AsRef<Path>,
         sharding: bool,
     ) -> Result<()> {
@@ -319,11 +606,14 @@ impl LedgerDb {
         };
         let env = None;
         let block_cache = None;
+        let write_buffer_manager = None;
         let ledger_db = Self::new(
             db_root_path,
+            wal_root_path,
             rocksdb_configs,
             env,
             block_cache,
+            write_buffer_manager,
             /*readonly=*/ false,
         )?;
         let cp_ledger_db_folder = cp_root_path.as_ref().join(LEDGER_DB_FOLDER_NAME);
@@ -387,6 +677,23 @@ impl LedgerDb {
         Ok(())
     }
 
+    /// Returns all the underlying RocksDB instances, each of them exactly once.
+    pub(crate) fn all_dbs(&self) -> Vec<&DB> {
+        if !self.enable_storage_sharding {
+            return vec![self.ledger_metadata_db.db()];
+        }
+        vec![
+            self.ledger_metadata_db.db(),
+            self.event_db.db(),
+            self.persisted_auxiliary_info_db.db(),
+            self.transaction_accumulator_db.db(),
+            self.transaction_auxiliary_data_db.db(),
+            self.transaction_db.db(),
+            self.transaction_info_db.db(),
+            self.write_set_db.db(),
+        ]
+    }
+
     pub(crate) fn metadata_db(&self) -> &LedgerMetadataDb {
         &self.ledger_metadata_db
     }
@@ -457,21 +764,28 @@ impl LedgerDb {
     fn open_rocksdb(
         path: PathBuf,
         name: &str,
+        wal_root_path: Option<&Path>,
         db_config: &RocksdbConfig,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
     ) -> Result<DB> {
+        let mut rocksdb_opts = gen_rocksdb_options(db_config, env, readonly);
+        if let Some(write_buffer_manager) = write_buffer_manager {
+            rocksdb_opts.set_write_buffer_manager(write_buffer_manager);
+        }
+        set_wal_dir(&mut rocksdb_opts, wal_root_path, name, readonly)?;
         let db = if readonly {
             DB::open_cf_readonly(
-                &gen_rocksdb_options(db_config, env, true),
+                &rocksdb_opts,
                 path.clone(),
                 name,
                 Self::gen_cfds_by_name(db_config, block_cache, name),
             )?
         } else {
             DB::open_cf(
-                &gen_rocksdb_options(db_config, env, false),
+                &rocksdb_opts,
                 path.clone(),
                 name,
                 Self::gen_cfds_by_name(db_config, block_cache, name),
@@ -519,6 +833,22 @@ impl LedgerDb {
         }
     }
 
+    /// Returns whether the ledger DB under `db_root_path` was created with storage sharding, by
+    /// which of the two layouts is on disk. `None` if neither is, i.e. for a new DB, or if both
+    /// are, e.g. after a migration, in which case the shard config recorded in the DB decides.
+    pub(crate) fn sharding_on_disk<P: AsRef<Path>>(db_root_path: P) -> Option<bool> {
+        let exists = |sharding| {
+            Self::metadata_db_path(db_root_path.as_ref(), sharding)
+                .join("CURRENT")
+                .exists()
+        };
+        match (exists(true), exists(false)) {
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            _ => None,
+        }
+    }
+
     fn metadata_db_path<P: AsRef<Path>>(db_root_path: P, sharding: bool) -> PathBuf {
         let ledger_db_folder = db_root_path.as_ref().join(LEDGER_DB_FOLDER_NAME);
         if sharding {
@@ -96,4 +96,33 @@ proptest! {
             iter.collect::<Result<Vec<_>, _>>().unwrap()
         );
     }
+
+    #[test]
+    fn test_iter_transaction_infos(
+        (infos, start_version, end_version) in
+            vec(any::<TransactionInfo>(), 1..100)
+                .prop_flat_map(|infos| {
+                    let num_infos = infos.len() as u64;
+                    (Just(infos), 0..=num_infos)
+                })
+                .prop_flat_map(|(infos, start_version)| {
+                    let num_infos = infos.len() as u64;
+                    (Just(infos), Just(start_version), start_version..=num_infos)
+                })
+    ) {
+        let tmp_dir = TempPath::new();
+        let db = AptosDB::new_for_test(&tmp_dir);
+        put_transaction_infos(&db, 0, &infos);
+
+        let iter = db.ledger_db
+            .iter_transaction_infos(start_version, end_version)
+            .unwrap();
+        prop_assert_eq!(
+            (start_version..end_version)
+                .zip(infos[start_version as usize..end_version as usize].iter().cloned())
+                .collect::<Vec<_>>(),
+            iter.collect::<Result<Vec<_>, _>>().unwrap()
+        );
+        prop_assert!(db.ledger_db.iter_transaction_infos(end_version + 1, end_version).is_err());
+    }
 }
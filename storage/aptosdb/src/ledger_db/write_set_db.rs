@@ -71,6 +71,33 @@ impl WriteSetDb {
         iter.expect_continuous_versions(start_version, num_transactions)
     }
 
+    /// Returns the serialized sizes of up to `limit` write sets starting from `start_version`,
+    /// without deserializing them. Fewer are returned if the DB ends before that.
+    pub(crate) fn get_write_set_sizes(
+        &self,
+        start_version: Version,
+        limit: usize,
+    ) -> Result<Vec<(Version, u64)>> {
+        let mut iter = self.db.iter::<WriteSetSchema>()?;
+        iter.seek(&start_version)?;
+
+        let mut ret = Vec::new();
+        for expected_version in (start_version..).take(limit) {
+            let Some((version, size)) = iter.next_with_value_size()? else {
+                break;
+            };
+            ensure!(
+                version == expected_version,
+                "Write set missing for version {}, got version {}",
+                expected_version,
+                version,
+            );
+            ret.push((version, size as u64));
+        }
+
+        Ok(ret)
+    }
+
     /// Returns write sets in `[begin_version, end_version)` half-open range.
     ///
     /// N.b. an empty `Vec` is returned when `begin_version == end_version`.
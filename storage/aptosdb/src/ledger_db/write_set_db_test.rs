@@ -87,6 +87,36 @@ proptest! {
         prop_assert!(write_set_db.get_write_set_iter(10, usize::MAX).is_err());
     }
 
+    #[test]
+    fn test_get_write_set_sizes(
+        write_sets in vec(
+            any::<WriteSet>(),
+            1..10
+        ),
+    ) {
+        let tmp_dir = TempPath::new();
+        let db = AptosDB::new_for_test(&tmp_dir);
+        let write_set_db  = db.ledger_db.write_set_db();
+        init_db(&write_sets, write_set_db);
+
+        let expected = write_sets
+            .iter()
+            .enumerate()
+            .map(|(version, write_set)| {
+                (version as Version, bcs::to_bytes(write_set).unwrap().len() as u64)
+            })
+            .collect::<Vec<_>>();
+        prop_assert_eq!(
+            write_set_db.get_write_set_sizes(0, write_sets.len() + 1).unwrap(),
+            expected.clone()
+        );
+        prop_assert_eq!(
+            write_set_db.get_write_set_sizes(1, 1).unwrap(),
+            expected.into_iter().skip(1).take(1).collect::<Vec<_>>()
+        );
+        prop_assert!(write_set_db.get_write_set_sizes(0, 0).unwrap().is_empty());
+    }
+
     #[test]
     fn test_prune(
         write_sets in vec(
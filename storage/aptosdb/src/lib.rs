@@ -9,10 +9,14 @@
 //! It relays read/write operations on the physical storage via `schemadb` to the underlying
 //! Key-Value storage system, and implements aptos data structures on top of it.
 
-pub use crate::db::AptosDB;
+pub use crate::{
+    db::AptosDB,
+    pruner::{DeletionRecord, PrunerName},
+};
 
 // Used in this and other crates for testing.
 
+pub mod audit_log;
 pub mod backup;
 pub mod common;
 pub mod db;
@@ -20,6 +24,7 @@ pub mod get_restore_handler;
 pub mod event_store;
 pub mod ledger_db;
 pub mod metrics;
+pub mod metrics_sink;
 pub mod pruner;
 pub mod state_kv_db;
 pub mod state_merkle_db;
@@ -11,7 +11,40 @@ use std::{fmt, num::NonZeroUsize};
 const NUM_SHARDS: usize = 256;
 
 pub(crate) struct LruNodeCache {
-    shards: [Mutex<LruCache<NibblePath, (Version, Node)>>; NUM_SHARDS],
+    shards: [Mutex<CacheShard>; NUM_SHARDS],
+}
+
+struct CacheShard {
+    /// Cached nodes, with their encoded sizes if the shard is bounded by bytes.
+    nodes: LruCache<NibblePath, (Version, Node, usize)>,
+    /// Max total encoded bytes of the nodes, `None` if the shard is bounded by number of nodes.
+    max_bytes: Option<usize>,
+    num_bytes: usize,
+}
+
+impl CacheShard {
+    fn put(&mut self, nibble_path: NibblePath, version: Version, node: Node) {
+        let Some(max_bytes) = self.max_bytes else {
+            self.nodes.put(nibble_path, (version, node, 0));
+            return;
+        };
+
+        let node_bytes = node
+            .encoded_len()
+            .expect("Encoding a node in memory can't fail.");
+        if let Some((_, _, replaced_bytes)) =
+            self.nodes.put(nibble_path, (version, node, node_bytes))
+        {
+            self.num_bytes -= replaced_bytes;
+        }
+        self.num_bytes += node_bytes;
+        while self.num_bytes > max_bytes {
+            match self.nodes.pop_lru() {
+                Some((_, (_, _, evicted_bytes))) => self.num_bytes -= evicted_bytes,
+                None => break,
+            }
+        }
+    }
 }
 
 impl fmt::Debug for LruNodeCache {
@@ -24,7 +57,26 @@ impl LruNodeCache {
     pub fn new(max_nodes_per_shard: NonZeroUsize) -> Self {
         Self {
             // `arr!()` doesn't allow a const in place of the integer literal
-            shards: arr_macro::arr![Mutex::new(LruCache::new(max_nodes_per_shard)); 256],
+            shards: arr_macro::arr![Mutex::new(CacheShard {
+                nodes: LruCache::new(max_nodes_per_shard),
+                max_bytes: None,
+                num_bytes: 0,
+            }); 256],
+        }
+    }
+
+    /// Bounds the cache by the total encoded bytes of the nodes instead of their number, since
+    /// the size of a node varies a lot, e.g. with the number of children of an internal node. The
+    /// budget is split evenly among the shards.
+    pub fn new_with_max_bytes(max_bytes: usize) -> Self {
+        // A budget below `NUM_SHARDS` would otherwise round down to 0 bytes per shard.
+        let max_bytes_per_shard = (max_bytes / NUM_SHARDS).max(1);
+        Self {
+            shards: arr_macro::arr![Mutex::new(CacheShard {
+                nodes: LruCache::unbounded(),
+                max_bytes: Some(max_bytes_per_shard),
+                num_bytes: 0,
+            }); 256],
         }
     }
 
@@ -39,19 +91,60 @@ impl LruNodeCache {
 
     pub fn get(&self, node_key: &NodeKey) -> Option<Node> {
         let mut r = self.shards[Self::shard(node_key.nibble_path()) as usize].lock();
-        r.get(node_key.nibble_path()).and_then(|(version, node)| {
-            if *version == node_key.version() {
-                Some(node.clone())
-            } else {
-                None
-            }
-        })
+        r.nodes
+            .get(node_key.nibble_path())
+            .and_then(|(version, node, _)| {
+                if *version == node_key.version() {
+                    Some(node.clone())
+                } else {
+                    None
+                }
+            })
     }
 
     pub fn put(&self, node_key: NodeKey, node: Node) {
         let (version, nibble_path) = node_key.unpack();
         let mut w = self.shards[Self::shard(&nibble_path) as usize].lock();
-        let value = (version, node);
-        w.put(nibble_path, value);
+        w.put(nibble_path, version, node);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aptos_crypto::HashValue;
+    use aptos_types::state_store::state_key::StateKey;
+
+    fn leaf(i: u8) -> Node {
+        Node::new_leaf(
+            HashValue::random(),
+            HashValue::random(),
+            (StateKey::raw(&[i]), 0),
+        )
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let node_bytes = leaf(0).encoded_len().unwrap();
+        // Room for 3 nodes in each shard.
+        let cache = LruNodeCache::new_with_max_bytes(3 * node_bytes * NUM_SHARDS);
+        // All in the same shard.
+        let node_keys = (0..4u8)
+            .map(|i| NodeKey::new(0, NibblePath::new_even(vec![0, i])))
+            .collect::<Vec<_>>();
+        for (i, node_key) in node_keys.iter().enumerate() {
+            cache.put(node_key.clone(), leaf(i as u8));
+        }
+
+        assert!(cache.get(&node_keys[0]).is_none());
+        for node_key in &node_keys[1..] {
+            assert!(cache.get(node_key).is_some());
+        }
+
+        // Replacing a node doesn't count it twice.
+        cache.put(node_keys[1].clone(), leaf(1));
+        for node_key in &node_keys[1..] {
+            assert!(cache.get(node_key).is_some());
+        }
     }
 }
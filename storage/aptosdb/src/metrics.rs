@@ -1,137 +1,137 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
+//! The storage metrics, which are forwarded to the sinks registered with
+//! [`crate::metrics_sink::register_metrics_sink`] on top of Prometheus.
+
+use crate::metrics_sink::{
+    SinkHistogramVec, SinkIntCounter, SinkIntGauge, SinkIntGaugeVec, SinkLocalHistogramVec,
+    SinkLocalIntCounterVec,
+};
 use aptos_metrics_core::{
     exponential_buckets, make_thread_local_histogram_vec, make_thread_local_int_counter_vec,
-    register_histogram_vec, register_int_counter, register_int_gauge, register_int_gauge_vec,
-    HistogramVec, IntCounter, IntGauge, IntGaugeVec,
 };
 use once_cell::sync::Lazy;
 
-pub static LEDGER_COUNTER: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
+pub static LEDGER_COUNTER: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
         // metric name
         "aptos_storage_ledger",
         // metric description
         "Aptos storage ledger counters",
         // metric labels (dimensions)
-        &["type"]
+        &["type"],
     )
-    .unwrap()
 });
 
-pub static COMMITTED_TXNS: Lazy<IntCounter> = Lazy::new(|| {
-    register_int_counter!(
+pub static COMMITTED_TXNS: Lazy<SinkIntCounter> = Lazy::new(|| {
+    SinkIntCounter::register(
         "aptos_storage_committed_txns",
-        "Aptos storage committed transactions"
+        "Aptos storage committed transactions",
     )
-    .unwrap()
 });
 
-pub static LATEST_TXN_VERSION: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub static LATEST_TXN_VERSION: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_storage_latest_transaction_version",
-        "Aptos storage latest transaction version"
+        "Aptos storage latest transaction version",
     )
-    .unwrap()
 });
 
-pub static LEDGER_VERSION: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub static LEDGER_VERSION: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_storage_ledger_version",
-        "Version in the latest saved ledger info."
+        "Version in the latest saved ledger info.",
     )
-    .unwrap()
 });
 
-pub static NEXT_BLOCK_EPOCH: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub static NEXT_BLOCK_EPOCH: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_storage_next_block_epoch",
-        "ledger_info.next_block_epoch() for the latest saved ledger info."
+        "ledger_info.next_block_epoch() for the latest saved ledger info.",
     )
-    .unwrap()
 });
 
-pub static STATE_ITEMS: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!("aptos_storage_state_items", "Total number of state items.").unwrap()
+pub static STATE_ITEMS: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register("aptos_storage_state_items", "Total number of state items.")
 });
 
-pub static TOTAL_STATE_BYTES: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub static TOTAL_STATE_BYTES: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_storage_total_state_bytes",
-        "Total size in bytes of all state items."
+        "Total size in bytes of all state items.",
     )
-    .unwrap()
 });
 
-pub static PRUNER_WINDOW: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
+pub static PRUNER_WINDOW: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
         // metric name
         "aptos_storage_prune_window",
         // metric description
         "Aptos storage prune window",
         // metric labels (dimensions)
-        &["pruner_name",]
+        &["pruner_name"],
     )
-    .unwrap()
 });
 
 /// DB pruner least readable versions
-pub static PRUNER_VERSIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
+pub static PRUNER_VERSIONS: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
         // metric name
         "aptos_pruner_versions",
         // metric description
         "Aptos pruner versions",
         // metric labels (dimensions)
-        &["pruner_name", "tag"]
+        &["pruner_name", "tag"],
     )
-    .unwrap()
 });
 
 /// Pruner batch size. For ledger pruner, this means the number of versions to be pruned at a time.
 /// For state store pruner, this means the number of stale nodes to be pruned at a time.
-pub static PRUNER_BATCH_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
+pub static PRUNER_BATCH_SIZE: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
         // metric name
         "pruner_batch_size",
         // metric description
         "Aptos pruner batch size",
         // metric labels (dimensions)
-        &["pruner_name",]
+        &["pruner_name"],
     )
-    .unwrap()
 });
 
-pub static API_LATENCY_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
-    register_histogram_vec!(
+pub static API_LATENCY_SECONDS: Lazy<SinkHistogramVec> = Lazy::new(|| {
+    SinkHistogramVec::register(
         // metric name
         "aptos_storage_api_latency_seconds",
         // metric description
         "Aptos storage api latency in seconds",
         // metric labels (dimensions)
         &["api_name", "result"],
-        exponential_buckets(/*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 22).unwrap(),
+        exponential_buckets(
+            /*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 22,
+        )
+        .unwrap(),
     )
-    .unwrap()
 });
 
-pub static OTHER_TIMERS_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
-    register_histogram_vec!(
+pub static OTHER_TIMERS_SECONDS: Lazy<SinkHistogramVec> = Lazy::new(|| {
+    SinkHistogramVec::register(
         // metric name
         "aptos_storage_other_timers_seconds",
         // metric description
         "Various timers below public API level.",
         // metric labels (dimensions)
         &["name"],
-        exponential_buckets(/*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 22).unwrap(),
+        exponential_buckets(
+            /*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 22,
+        )
+        .unwrap(),
     )
-    .unwrap()
 });
 
 make_thread_local_histogram_vec!(
-    pub,
-    NODE_CACHE_SECONDS,
+    ,
+    LOCAL_NODE_CACHE_SECONDS,
     // metric name
     "aptos_storage_node_cache_seconds",
     // metric description
@@ -141,109 +141,130 @@ make_thread_local_histogram_vec!(
     exponential_buckets(/*start=*/ 1e-9, /*factor=*/ 2.0, /*count=*/ 30).unwrap(),
 );
 
+pub static NODE_CACHE_SECONDS: SinkLocalHistogramVec = SinkLocalHistogramVec::new(
+    "aptos_storage_node_cache_seconds",
+    &["tag", "name"],
+    &LOCAL_NODE_CACHE_SECONDS,
+);
+
 /// Rocksdb metrics
-pub static ROCKSDB_PROPERTIES: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
+pub static ROCKSDB_PROPERTIES: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
         // metric name
         "aptos_rocksdb_properties",
         // metric description
         "rocksdb integer properties",
         // metric labels (dimensions)
-        &["cf_name", "property_name",]
+        &["cf_name", "property_name"],
     )
-    .unwrap()
 });
 
 /// Rocksdb metrics
-pub static ROCKSDB_SHARD_PROPERTIES: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
+pub static ROCKSDB_SHARD_PROPERTIES: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
         // metric name
         "aptos_rocksdb_shard_properties",
         // metric description
         "sharded rocksdb integer properties",
         // metric labels (dimensions)
-        &["shard_id", "cf_name", "property_name",]
+        &["shard_id", "cf_name", "property_name"],
+    )
+});
+
+pub static ROCKSDB_CONFIGURED_OPTIONS: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
+        // metric name
+        "aptos_rocksdb_configured_options",
+        // metric description
+        "Effective values of the tunable rocksdb options each sub-DB is opened with",
+        // metric labels (dimensions)
+        &["db_name", "option_name"],
+    )
+});
+
+/// Rocksdb metrics
+pub static ROCKSDB_DB_PROPERTIES: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
+        // metric name
+        "aptos_rocksdb_db_properties",
+        // metric description
+        "rocksdb DB-wide integer properties",
+        // metric labels (dimensions)
+        &["db_name", "property_name"],
     )
-    .unwrap()
 });
 
 // Async committer gauges:
-pub(crate) static LATEST_SNAPSHOT_VERSION: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub(crate) static LATEST_SNAPSHOT_VERSION: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_storage_latest_state_snapshot_version",
-        "The version of the most recent snapshot."
+        "The version of the most recent snapshot.",
     )
-    .unwrap()
 });
 
-pub(crate) static LATEST_CHECKPOINT_VERSION: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub(crate) static LATEST_CHECKPOINT_VERSION: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_storage_latest_state_checkpoint_version",
-        "The version of the most recent committed checkpoint."
+        "The version of the most recent committed checkpoint.",
     )
-    .unwrap()
 });
 
 // Backup progress gauges:
 
-pub(crate) static BACKUP_EPOCH_ENDING_EPOCH: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub(crate) static BACKUP_EPOCH_ENDING_EPOCH: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_backup_handler_epoch_ending_epoch",
-        "Current epoch returned in an epoch ending backup."
+        "Current epoch returned in an epoch ending backup.",
     )
-    .unwrap()
 });
 
-pub(crate) static BACKUP_TXN_VERSION: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub(crate) static BACKUP_TXN_VERSION: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_backup_handler_transaction_version",
-        "Current version returned in a transaction backup."
+        "Current version returned in a transaction backup.",
     )
-    .unwrap()
 });
 
-pub(crate) static BACKUP_STATE_SNAPSHOT_VERSION: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub(crate) static BACKUP_STATE_SNAPSHOT_VERSION: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_backup_handler_state_snapshot_version",
-        "Version of requested state snapshot backup."
+        "Version of requested state snapshot backup.",
     )
-    .unwrap()
 });
 
-pub(crate) static BACKUP_STATE_SNAPSHOT_LEAF_IDX: Lazy<IntGauge> = Lazy::new(|| {
-    register_int_gauge!(
+pub(crate) static BACKUP_STATE_SNAPSHOT_LEAF_IDX: Lazy<SinkIntGauge> = Lazy::new(|| {
+    SinkIntGauge::register(
         "aptos_backup_handler_state_snapshot_leaf_index",
-        "Index of current leaf index returned in a state snapshot backup."
+        "Index of current leaf index returned in a state snapshot backup.",
     )
-    .unwrap()
 });
 
-pub static BACKUP_TIMER: Lazy<HistogramVec> = Lazy::new(|| {
-    register_histogram_vec!(
+pub static BACKUP_TIMER: Lazy<SinkHistogramVec> = Lazy::new(|| {
+    SinkHistogramVec::register(
         "aptos_backup_handler_timers_seconds",
         "Various timers for performance analysis.",
         &["name"],
-        exponential_buckets(/*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 32).unwrap(),
+        exponential_buckets(
+            /*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 32,
+        )
+        .unwrap(),
     )
-    .unwrap()
 });
 
-pub static CONCURRENCY_GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!(
+pub static CONCURRENCY_GAUGE: Lazy<SinkIntGaugeVec> = Lazy::new(|| {
+    SinkIntGaugeVec::register(
         "aptos_storage_api_concurrency",
         "Call concurrency by API.",
-        &["name"]
+        &["name"],
     )
-    .unwrap()
 });
 
-pub static GAUGE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!("aptos_storage_gauge", "Various gauges", &["name"]).unwrap()
-});
+pub static GAUGE: Lazy<SinkIntGaugeVec> =
+    Lazy::new(|| SinkIntGaugeVec::register("aptos_storage_gauge", "Various gauges", &["name"]));
 
 make_thread_local_int_counter_vec!(
-    pub,
-    COUNTER,
+    ,
+    LOCAL_COUNTER,
     // metric name
     "aptos_storage_counter",
     // metric description
@@ -251,3 +272,6 @@ make_thread_local_int_counter_vec!(
     // metric labels (dimensions)
     &["name"],
 );
+
+pub static COUNTER: SinkLocalIntCounterVec =
+    SinkLocalIntCounterVec::new("aptos_storage_counter", &["name"], &LOCAL_COUNTER);
@@ -0,0 +1,486 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Sinks for the storage metrics other than Prometheus, e.g. to forward them to a StatsD or
+//! OpenTelemetry backend.
+//!
+//! The metrics in [`crate::metrics`] are wrapped in the types below, which update the Prometheus
+//! metric, the default sink, and then hand the same update to every sink registered with
+//! [`register_metrics_sink`], at the point it's made. Nothing is forwarded while no sink is
+//! registered, beyond checking that there is none.
+
+use aptos_infallible::RwLock;
+use aptos_metrics_core::{
+    register_histogram_vec, register_int_counter, register_int_gauge, register_int_gauge_vec,
+    thread_local::{ThreadLocalHistogramTimer, ThreadLocalHistogramVec, ThreadLocalIntCounterVec},
+    HistogramTimer, HistogramVec, IntCounter, IntCounterVecHelper, IntGauge, IntGaugeVec,
+    TimerHelper,
+};
+use once_cell::sync::Lazy;
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::LocalKey,
+    time::Instant,
+};
+
+/// Receives the updates to the storage metrics, as they are made. The methods are called on the
+/// threads updating the metrics, often on the hot paths of reads and commits, so they shouldn't
+/// block.
+///
+/// `labels` pairs the names of the labels of the metric with their values.
+pub trait MetricsSink: Send + Sync {
+    /// The counter `name` was incremented by `value`.
+    fn inc_counter(&self, name: &'static str, labels: &[(&'static str, &str)], value: u64);
+
+    /// The gauge `name` was set to `value`.
+    fn set_gauge(&self, name: &'static str, labels: &[(&'static str, &str)], value: i64);
+
+    /// `value` was observed by the histogram `name`.
+    fn observe_histogram(&self, name: &'static str, labels: &[(&'static str, &str)], value: f64);
+}
+
+/// The registered sinks, by the ID of their handle.
+type Sinks = Vec<(u64, Arc<dyn MetricsSink>)>;
+
+static SINKS: Lazy<RwLock<Sinks>> = Lazy::new(|| RwLock::new(Vec::new()));
+static NUM_SINKS: AtomicUsize = AtomicUsize::new(0);
+static NEXT_SINK_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps a sink registered, until dropped.
+#[must_use = "The sink is unregistered when the handle is dropped."]
+pub struct MetricsSinkHandle {
+    id: u64,
+}
+
+impl Drop for MetricsSinkHandle {
+    fn drop(&mut self) {
+        let mut sinks = SINKS.write();
+        sinks.retain(|(id, _sink)| *id != self.id);
+        NUM_SINKS.store(sinks.len(), Ordering::Release);
+    }
+}
+
+/// Registers `sink` to receive the updates to the storage metrics, on top of Prometheus, until
+/// the returned handle is dropped.
+pub fn register_metrics_sink(sink: Arc<dyn MetricsSink>) -> MetricsSinkHandle {
+    let id = NEXT_SINK_ID.fetch_add(1, Ordering::Relaxed);
+    let mut sinks = SINKS.write();
+    sinks.push((id, sink));
+    NUM_SINKS.store(sinks.len(), Ordering::Release);
+    MetricsSinkHandle { id }
+}
+
+fn for_each_sink(
+    label_names: &'static [&'static str],
+    label_values: &[&str],
+    f: impl Fn(&dyn MetricsSink, &[(&'static str, &str)]),
+) {
+    if NUM_SINKS.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let labels = label_names
+        .iter()
+        .copied()
+        .zip(label_values.iter().copied())
+        .collect::<Vec<_>>();
+    for (_id, sink) in SINKS.read().iter() {
+        f(sink.as_ref(), &labels);
+    }
+}
+
+/// Copies the label values of an update that completes later, e.g. when a timer is dropped, if
+/// there are sinks to forward it to.
+fn capture_labels(label_values: &[&str]) -> Option<Vec<String>> {
+    (NUM_SINKS.load(Ordering::Acquire) > 0)
+        .then(|| label_values.iter().map(|value| value.to_string()).collect())
+}
+
+pub struct SinkIntCounter {
+    name: &'static str,
+    inner: IntCounter,
+}
+
+impl SinkIntCounter {
+    pub(crate) fn register(name: &'static str, help: &str) -> Self {
+        Self {
+            name,
+            inner: register_int_counter!(name, help).unwrap(),
+        }
+    }
+
+    pub fn inc_by(&self, v: u64) {
+        self.inner.inc_by(v);
+        for_each_sink(&[], &[], |sink, labels| {
+            sink.inc_counter(self.name, labels, v)
+        });
+    }
+
+    pub fn get(&self) -> u64 {
+        self.inner.get()
+    }
+}
+
+pub struct SinkIntGauge {
+    name: &'static str,
+    inner: IntGauge,
+}
+
+impl SinkIntGauge {
+    pub(crate) fn register(name: &'static str, help: &str) -> Self {
+        Self {
+            name,
+            inner: register_int_gauge!(name, help).unwrap(),
+        }
+    }
+
+    pub fn set(&self, v: i64) {
+        self.inner.set(v);
+        for_each_sink(&[], &[], |sink, labels| {
+            sink.set_gauge(self.name, labels, v)
+        });
+    }
+
+    pub fn get(&self) -> i64 {
+        self.inner.get()
+    }
+}
+
+pub struct SinkIntGaugeVec {
+    name: &'static str,
+    label_names: &'static [&'static str],
+    inner: IntGaugeVec,
+}
+
+impl SinkIntGaugeVec {
+    pub(crate) fn register(
+        name: &'static str,
+        help: &str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            label_names,
+            inner: register_int_gauge_vec!(name, help, label_names).unwrap(),
+        }
+    }
+
+    pub fn with_label_values<'a>(&'a self, labels: &'a [&'a str]) -> SinkIntGaugeChild<'a> {
+        SinkIntGaugeChild {
+            parent: self,
+            labels,
+            inner: self.inner.with_label_values(labels),
+        }
+    }
+
+    pub fn set_with(&self, labels: &[&str], v: i64) {
+        self.with_label_values(labels).set(v)
+    }
+
+    /// Increments the gauge until the returned guard is dropped.
+    pub fn concurrency_with(&self, labels: &[&str]) -> SinkConcurrencyGauge<'_> {
+        let gauge = self.inner.with_label_values(labels);
+        gauge.inc();
+        self.forward(labels, gauge.get());
+        SinkConcurrencyGauge {
+            parent: self,
+            gauge,
+            labels: capture_labels(labels),
+        }
+    }
+
+    fn forward(&self, labels: &[&str], v: i64) {
+        for_each_sink(self.label_names, labels, |sink, labels| {
+            sink.set_gauge(self.name, labels, v)
+        });
+    }
+}
+
+pub struct SinkIntGaugeChild<'a> {
+    parent: &'a SinkIntGaugeVec,
+    labels: &'a [&'a str],
+    inner: IntGauge,
+}
+
+impl SinkIntGaugeChild<'_> {
+    pub fn set(&self, v: i64) {
+        self.inner.set(v);
+        self.parent.forward(self.labels, v);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.inner.get()
+    }
+}
+
+pub struct SinkConcurrencyGauge<'a> {
+    parent: &'a SinkIntGaugeVec,
+    gauge: IntGauge,
+    /// The label values to forward the decrement with, if there were sinks at the increment.
+    labels: Option<Vec<String>>,
+}
+
+impl Drop for SinkConcurrencyGauge<'_> {
+    fn drop(&mut self) {
+        self.gauge.dec();
+        if let Some(labels) = &self.labels {
+            let labels = labels.iter().map(String::as_str).collect::<Vec<_>>();
+            self.parent.forward(&labels, self.gauge.get());
+        }
+    }
+}
+
+pub struct SinkHistogramVec {
+    name: &'static str,
+    label_names: &'static [&'static str],
+    inner: HistogramVec,
+}
+
+impl SinkHistogramVec {
+    pub(crate) fn register(
+        name: &'static str,
+        help: &str,
+        label_names: &'static [&'static str],
+        buckets: Vec<f64>,
+    ) -> Self {
+        Self {
+            name,
+            label_names,
+            inner: register_histogram_vec!(name, help, label_names, buckets).unwrap(),
+        }
+    }
+
+    pub fn get_sample_sum_with(&self, labels: &[&str]) -> f64 {
+        self.inner.with_label_values(labels).get_sample_sum()
+    }
+}
+
+impl TimerHelper for SinkHistogramVec {
+    type TimerType<'a> = SinkHistogramTimer<HistogramTimer>;
+
+    fn timer_with<'a>(&'static self, labels: &'a [&str]) -> Self::TimerType<'a> {
+        SinkHistogramTimer::new(
+            self.inner.timer_with(labels),
+            self.name,
+            self.label_names,
+            labels,
+        )
+    }
+
+    fn observe_with(&'static self, labels: &[&str], val: f64) {
+        self.inner.observe_with(labels, val);
+        forward_observation(self.name, self.label_names, labels, val);
+    }
+}
+
+/// A thread local histogram, i.e. one flushed to Prometheus periodically, which is still
+/// forwarded to the sinks on every observation.
+pub struct SinkLocalHistogramVec {
+    name: &'static str,
+    label_names: &'static [&'static str],
+    inner: &'static LocalKey<RefCell<ThreadLocalHistogramVec>>,
+}
+
+impl SinkLocalHistogramVec {
+    pub(crate) const fn new(
+        name: &'static str,
+        label_names: &'static [&'static str],
+        inner: &'static LocalKey<RefCell<ThreadLocalHistogramVec>>,
+    ) -> Self {
+        Self {
+            name,
+            label_names,
+            inner,
+        }
+    }
+}
+
+impl TimerHelper for SinkLocalHistogramVec {
+    type TimerType<'a> = SinkHistogramTimer<ThreadLocalHistogramTimer<'a>>;
+
+    fn timer_with<'a>(&'static self, labels: &'a [&str]) -> Self::TimerType<'a> {
+        SinkHistogramTimer::new(
+            self.inner.timer_with(labels),
+            self.name,
+            self.label_names,
+            labels,
+        )
+    }
+
+    fn observe_with(&'static self, labels: &[&str], val: f64) {
+        self.inner.observe_with(labels, val);
+        forward_observation(self.name, self.label_names, labels, val);
+    }
+}
+
+fn forward_observation(
+    name: &'static str,
+    label_names: &'static [&'static str],
+    label_values: &[&str],
+    val: f64,
+) {
+    for_each_sink(label_names, label_values, |sink, labels| {
+        sink.observe_histogram(name, labels, val)
+    });
+}
+
+/// Wraps the timer of the Prometheus histogram, which observes it when dropped, to forward the
+/// observation too.
+pub struct SinkHistogramTimer<T> {
+    _timer: T,
+    name: &'static str,
+    label_names: &'static [&'static str],
+    /// The label values to forward the observation with, if there were sinks at the start.
+    labels: Option<Vec<String>>,
+    start: Instant,
+}
+
+impl<T> SinkHistogramTimer<T> {
+    fn new(
+        timer: T,
+        name: &'static str,
+        label_names: &'static [&'static str],
+        labels: &[&str],
+    ) -> Self {
+        Self {
+            _timer: timer,
+            name,
+            label_names,
+            labels: capture_labels(labels),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<T> Drop for SinkHistogramTimer<T> {
+    fn drop(&mut self) {
+        if let Some(labels) = &self.labels {
+            let labels = labels.iter().map(String::as_str).collect::<Vec<_>>();
+            forward_observation(
+                self.name,
+                self.label_names,
+                &labels,
+                self.start.elapsed().as_secs_f64(),
+            );
+        }
+    }
+}
+
+/// A thread local counter, i.e. one flushed to Prometheus periodically, which is still forwarded
+/// to the sinks on every increment.
+pub struct SinkLocalIntCounterVec {
+    name: &'static str,
+    label_names: &'static [&'static str],
+    inner: &'static LocalKey<RefCell<ThreadLocalIntCounterVec>>,
+}
+
+impl SinkLocalIntCounterVec {
+    pub(crate) const fn new(
+        name: &'static str,
+        label_names: &'static [&'static str],
+        inner: &'static LocalKey<RefCell<ThreadLocalIntCounterVec>>,
+    ) -> Self {
+        Self {
+            name,
+            label_names,
+            inner,
+        }
+    }
+}
+
+impl IntCounterVecHelper for SinkLocalIntCounterVec {
+    type IntType = u64;
+
+    fn inc_with(&'static self, labels: &[&str]) {
+        self.inc_with_by(labels, 1);
+    }
+
+    fn inc_with_by(&'static self, labels: &[&str], v: Self::IntType) {
+        self.inner.inc_with_by(labels, v);
+        for_each_sink(self.label_names, labels, |sink, labels| {
+            sink.inc_counter(self.name, labels, v)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{COMMITTED_TXNS, OTHER_TIMERS_SECONDS};
+    use aptos_infallible::Mutex;
+
+    /// The name, the labels as "name=value" and the value of each update.
+    type Updates = Vec<(&'static str, Vec<String>, f64)>;
+
+    struct CollectingSink(Mutex<Updates>);
+
+    impl CollectingSink {
+        fn push(&self, name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+            let labels = labels
+                .iter()
+                .map(|(label_name, label_value)| format!("{}={}", label_name, label_value))
+                .collect();
+            self.0.lock().push((name, labels, value));
+        }
+
+        fn contains(&self, name: &str, labels: &[&str], value: f64) -> bool {
+            self.0
+                .lock()
+                .iter()
+                .any(|(n, l, v)| *n == name && l == labels && *v == value)
+        }
+    }
+
+    impl MetricsSink for CollectingSink {
+        fn inc_counter(&self, name: &'static str, labels: &[(&'static str, &str)], value: u64) {
+            self.push(name, labels, value as f64);
+        }
+
+        fn set_gauge(&self, name: &'static str, labels: &[(&'static str, &str)], value: i64) {
+            self.push(name, labels, value as f64);
+        }
+
+        fn observe_histogram(
+            &self,
+            name: &'static str,
+            labels: &[(&'static str, &str)],
+            value: f64,
+        ) {
+            self.push(name, labels, value);
+        }
+    }
+
+    #[test]
+    fn test_metrics_sink() {
+        let sink = Arc::new(CollectingSink(Mutex::new(Vec::new())));
+        let handle = register_metrics_sink(sink.clone());
+
+        COMMITTED_TXNS.inc_by(0);
+        OTHER_TIMERS_SECONDS.observe_with(&["test_metrics_sink"], 1.5);
+        {
+            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["test_metrics_sink_timer"]);
+        }
+        assert!(sink.contains("aptos_storage_committed_txns", &[], 0.0));
+        assert!(sink.contains(
+            "aptos_storage_other_timers_seconds",
+            &["name=test_metrics_sink"],
+            1.5
+        ));
+        assert!(sink.0.lock().iter().any(|(name, labels, _)| {
+            *name == "aptos_storage_other_timers_seconds"
+                && labels == &["name=test_metrics_sink_timer"]
+        }));
+
+        // Nothing reaches the sink once it's unregistered.
+        drop(handle);
+        OTHER_TIMERS_SECONDS.observe_with(&["test_metrics_sink_unregistered"], 1.5);
+        assert!(!sink.contains(
+            "aptos_storage_other_timers_seconds",
+            &["name=test_metrics_sink_unregistered"],
+            1.5
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! An optional log of what the pruners deleted, for auditing the data lifecycle.
+//!
+//! Each record is written to the metadata of the DB the pruner deletes from, in the same batch as
+//! the deletes, so it survives restarts and is never out of sync with the data. It's also emitted
+//! as a structured log line. Records older than the configured retention are deleted by the
+//! pruners as they write new ones, in the same batch.
+
+use crate::schema::db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue};
+use aptos_logger::info;
+use aptos_schemadb::{batch::SchemaBatch, DB};
+use aptos_storage_interface::Result;
+use aptos_types::transaction::Version;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    cmp::max,
+    collections::BTreeMap,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+type ShardId = usize;
+
+/// The (sub) pruners, as named in the deletion log.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[cfg_attr(any(test, feature = "fuzzing"), derive(proptest_derive::Arbitrary))]
+pub enum PrunerName {
+    LedgerMetadata,
+    Event,
+    PersistedAuxiliaryInfo,
+    TransactionAccumulator,
+    TransactionAuxiliaryData,
+    Transaction,
+    TransactionInfo,
+    WriteSet,
+    StateKvMetadata,
+    StateKvShard(ShardId),
+    StateMerkleMetadata,
+    StateMerkleShard(ShardId),
+    EpochSnapshotMetadata,
+    EpochSnapshotShard(ShardId),
+}
+
+/// The timestamp of a deletion record in microseconds since the Unix epoch, serialized big endian
+/// so that the keys of the records sort by it.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(any(test, feature = "fuzzing"), derive(proptest_derive::Arbitrary))]
+pub struct RecordTimestamp(pub u64);
+
+impl Serialize for RecordTimestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.0.to_be_bytes().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RecordTimestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        <[u8; 8]>::deserialize(deserializer).map(|bytes| Self(u64::from_be_bytes(bytes)))
+    }
+}
+
+/// What a pruner deleted in one batch.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(any(test, feature = "fuzzing"), derive(proptest_derive::Arbitrary))]
+pub struct DeletionRecord {
+    pub pruner: PrunerName,
+    /// The versions the batch pruned.
+    pub version_range: Range<Version>,
+    /// Number of records deleted from each column family, which includes those deleted from the
+    /// internal indexer DB along with the batch.
+    pub num_deletes_by_cf: BTreeMap<String, usize>,
+    /// When the batch was written, in microseconds since the Unix epoch.
+    pub timestamp_usecs: u64,
+}
+
+/// Adds to `batch` the record of what it and `other_batches` delete, for `pruner` to write to `db`
+/// along with the deletes. The records in `db` older than `retention` are deleted in `batch` too.
+pub(in crate::pruner) fn record_deletions<'a>(
+    db: &DB,
+    retention: Duration,
+    pruner: PrunerName,
+    version_range: Range<Version>,
+    batch: &mut SchemaBatch,
+    other_batches: impl IntoIterator<Item = &'a SchemaBatch>,
+) -> Result<()> {
+    let mut num_deletes_by_cf = BTreeMap::new();
+    for b in std::iter::once(&*batch).chain(other_batches) {
+        for (cf_name, num_deletes) in b.num_deletes_by_cf() {
+            let count: &mut usize = num_deletes_by_cf.entry(cf_name.to_string()).or_default();
+            *count = count.saturating_add(num_deletes);
+        }
+    }
+    let record = DeletionRecord {
+        pruner,
+        version_range,
+        num_deletes_by_cf,
+        timestamp_usecs: unique_timestamp_usecs(),
+    };
+
+    info!(
+        pruner = ?record.pruner,
+        start_version = record.version_range.start,
+        end_version = record.version_range.end,
+        num_deletes_by_cf = ?record.num_deletes_by_cf,
+        "Pruner deleting records."
+    );
+
+    // Other pruners writing to the same DB might delete the same expired records, which is
+    // harmless.
+    let expire_before_usecs = record
+        .timestamp_usecs
+        .saturating_sub(retention.as_micros() as u64);
+    for (key, _) in deletion_records(db, 0..expire_before_usecs)? {
+        batch.delete::<DbMetadataSchema>(&key)?;
+    }
+
+    batch.put::<DbMetadataSchema>(
+        &DbMetadataKey::PrunerDeletionRecord(RecordTimestamp(record.timestamp_usecs), pruner),
+        &DbMetadataValue::DeletionRecord(record),
+    )
+}
+
+/// Returns the deletion records in `db` written in `time_range_usecs`, oldest first.
+pub(crate) fn get_deletion_records(
+    db: &DB,
+    time_range_usecs: Range<u64>,
+) -> Result<Vec<DeletionRecord>> {
+    Ok(deletion_records(db, time_range_usecs)?
+        .into_iter()
+        .map(|(_, record)| record)
+        .collect())
+}
+
+/// Returns the deletion records in `db` written in `time_range_usecs` with their keys, oldest
+/// first.
+fn deletion_records(
+    db: &DB,
+    time_range_usecs: Range<u64>,
+) -> Result<Vec<(DbMetadataKey, DeletionRecord)>> {
+    let mut records = Vec::new();
+    if time_range_usecs.is_empty() {
+        return Ok(records);
+    }
+
+    let mut iter = db.iter::<DbMetadataSchema>()?;
+    // `LedgerMetadata` is the smallest pruner name, so this is the first record at the start time.
+    iter.seek(&DbMetadataKey::PrunerDeletionRecord(
+        RecordTimestamp(time_range_usecs.start),
+        PrunerName::LedgerMetadata,
+    ))?;
+    for item in iter {
+        let (key, value) = item?;
+        match key {
+            DbMetadataKey::PrunerDeletionRecord(RecordTimestamp(timestamp_usecs), _)
+                if timestamp_usecs < time_range_usecs.end =>
+            {
+                records.push((key, value.expect_deletion_record()));
+            },
+            _ => break,
+        }
+    }
+    Ok(records)
+}
+
+/// The current time, bumped past the previous call's if the clock didn't move (or went back), so
+/// the records of a pruner don't overwrite each other.
+fn unique_timestamp_usecs() -> u64 {
+    static LAST_TIMESTAMP_USECS: AtomicU64 = AtomicU64::new(0);
+
+    let now = aptos_infallible::duration_since_epoch().as_micros() as u64;
+    let last = LAST_TIMESTAMP_USECS
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+            Some(max(now, last.saturating_add(1)))
+        })
+        .expect("The update always succeeds.");
+    max(now, last.saturating_add(1))
+}
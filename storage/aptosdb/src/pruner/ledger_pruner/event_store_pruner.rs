@@ -3,7 +3,10 @@
 
 use crate::{
     ledger_db::LedgerDb,
-    pruner::{db_sub_pruner::DBSubPruner, pruner_utils::get_or_initialize_subpruner_progress},
+    pruner::{
+        db_sub_pruner::DBSubPruner, deletion_log::record_deletions,
+        pruner_utils::get_or_initialize_subpruner_progress, PrunerName,
+    },
     schema::db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
 };
 use aptos_db_indexer::db_indexer::InternalIndexerDB;
@@ -15,12 +18,13 @@ use aptos_logger::info;
 use aptos_schemadb::batch::SchemaBatch;
 use aptos_storage_interface::Result;
 use aptos_types::transaction::Version;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub struct EventStorePruner {
     ledger_db: Arc<LedgerDb>,
     internal_indexer_db: Option<InternalIndexerDB>,
+    deletion_log_retention: Option<Duration>,
 }
 
 impl EventStorePruner {
@@ -52,13 +56,13 @@ impl DBSubPruner for EventStorePruner {
         } else {
             Some(&mut batch)
         };
-        let num_events_per_version = self.ledger_db.event_db().prune_event_indices(
+        let event_keys = self.ledger_db.event_db().prune_event_indices(
             current_progress,
             target_version,
             indices_batch,
         )?;
         self.ledger_db.event_db().prune_events(
-            num_events_per_version,
+            event_keys,
             current_progress,
             target_version,
             &mut batch,
@@ -67,6 +71,16 @@ impl DBSubPruner for EventStorePruner {
             &DbMetadataKey::EventPrunerProgress,
             &DbMetadataValue::Version(target_version),
         )?;
+        if let Some(retention) = self.deletion_log_retention {
+            record_deletions(
+                self.ledger_db.event_db_raw(),
+                retention,
+                PrunerName::Event,
+                current_progress..target_version,
+                &mut batch,
+                indexer_batch.as_ref(),
+            )?;
+        }
 
         if let Some(mut indexer_batch) = indexer_batch {
             indexer_batch.put::<InternalIndexerMetadataSchema>(
@@ -86,6 +100,7 @@ impl EventStorePruner {
         ledger_db: Arc<LedgerDb>,
         metadata_progress: Version,
         internal_indexer_db: Option<InternalIndexerDB>,
+        deletion_log_retention: Option<Duration>,
     ) -> Result<Self> {
         let progress = get_or_initialize_subpruner_progress(
             ledger_db.event_db_raw(),
@@ -96,6 +111,7 @@ impl EventStorePruner {
         let myself = EventStorePruner {
             ledger_db,
             internal_indexer_db,
+            deletion_log_retention,
         };
 
         info!(
@@ -0,0 +1,130 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Copies the ledger data the ledger pruner is about to delete into an archive `AptosDB`, so that
+//! the history stays queryable there while the main DB stays lean.
+//!
+//! The data is read with the backup primitives and written with the restore ones, the same way a
+//! restore from epoch ending and transaction backups does it. The transactions and their infos,
+//! events, write sets, auxiliary info and accumulator are archived, along with the epoch ending
+//! ledger infos of the archived versions so that the archive can be verified on its own. The
+//! state isn't archived, the write sets being the history of it.
+
+use crate::{
+    backup::{backup_handler::BackupHandler, restore_handler::RestoreHandler},
+    get_restore_handler::GetRestoreHandler,
+    ledger_db::LedgerDb,
+    metrics::OTHER_TIMERS_SECONDS,
+    AptosDB,
+};
+use aptos_logger::info;
+use aptos_metrics_core::TimerHelper;
+use aptos_storage_interface::{db_ensure as ensure, Result};
+use aptos_types::transaction::Version;
+use itertools::Itertools;
+use std::sync::Arc;
+
+pub(crate) struct LedgerArchiver {
+    backup_handler: BackupHandler,
+    /// The main DB's ledger DB, which is being pruned.
+    ledger_db: Arc<LedgerDb>,
+    restore_handler: RestoreHandler,
+}
+
+impl LedgerArchiver {
+    pub(crate) fn new(
+        backup_handler: BackupHandler,
+        ledger_db: Arc<LedgerDb>,
+        archive_db: Arc<AptosDB>,
+    ) -> Self {
+        Self {
+            backup_handler,
+            ledger_db,
+            restore_handler: archive_db.get_restore_handler(),
+        }
+    }
+
+    /// Copies the versions in `[begin, end)` into the archive DB, skipping those already there,
+    /// e.g. when the pruner is retrying after a crash.
+    ///
+    /// An empty archive starts at `begin`, otherwise the archive must already have everything
+    /// before `begin`.
+    pub(crate) fn archive(&self, begin: Version, end: Version) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["ledger_archiver__archive"]);
+
+        let next_archived_version = self
+            .restore_handler
+            .get_next_expected_transaction_version()?;
+        if end <= next_archived_version {
+            return Ok(());
+        }
+        let first_version = if next_archived_version == 0 {
+            // Like a restore starting in the middle of the history, the accumulator is seeded with
+            // the frozen subtrees on the left of the first version.
+            let frozen_subtrees = self
+                .ledger_db
+                .transaction_accumulator_db()
+                .get_frozen_subtree_hashes(begin)?;
+            self.restore_handler
+                .confirm_or_save_frozen_subtrees(begin, &frozen_subtrees)?;
+            begin
+        } else {
+            ensure!(
+                next_archived_version >= begin,
+                "Ledger archive would have a gap, it ends at version {}, but archiving from version {}.",
+                next_archived_version,
+                begin,
+            );
+            next_archived_version
+        };
+
+        info!(
+            first_version = first_version,
+            end_version = end,
+            "Archiving ledger data."
+        );
+
+        // Saved first, so that they are in the archive once the transactions are, which is what
+        // a retry goes by. The ledger info of an epoch ending in the range is in an earlier epoch
+        // than that of version `end`.
+        let epoch_ending_ledger_infos = self
+            .backup_handler
+            .get_epoch_ending_ledger_info_iter(
+                self.ledger_db.metadata_db().get_epoch(first_version)?,
+                self.ledger_db.metadata_db().get_epoch(end)?,
+            )?
+            .collect::<Result<Vec<_>>>()?;
+        if !epoch_ending_ledger_infos.is_empty() {
+            self.restore_handler
+                .save_ledger_infos(&epoch_ending_ledger_infos)?;
+        }
+
+        let (txns, persisted_aux_info, txn_infos, events, write_sets): (
+            Vec<_>,
+            Vec<_>,
+            Vec<_>,
+            Vec<_>,
+            Vec<_>,
+        ) = self
+            .backup_handler
+            .get_transaction_iter(first_version, (end - first_version) as usize)?
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .multiunzip();
+        ensure!(
+            txns.len() as Version == end - first_version,
+            "Expecting {} transactions to archive starting from version {}, got {}.",
+            end - first_version,
+            first_version,
+            txns.len(),
+        );
+        self.restore_handler.save_transactions(
+            first_version,
+            &txns,
+            &persisted_aux_info,
+            &txn_infos,
+            &events,
+            write_sets,
+        )
+    }
+}
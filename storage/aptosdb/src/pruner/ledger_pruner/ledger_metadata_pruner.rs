@@ -1,22 +1,29 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
-use crate::schema::{
-    db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
-    version_data::VersionDataSchema,
+use crate::{
+    pruner::{deletion_log::record_deletions, PrunerName},
+    schema::{
+        db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+        version_data::VersionDataSchema,
+    },
 };
 use aptos_schemadb::{batch::SchemaBatch, DB};
 use aptos_storage_interface::{AptosDbError, Result};
 use aptos_types::transaction::Version;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub struct LedgerMetadataPruner {
     ledger_metadata_db: Arc<DB>,
+    deletion_log_retention: Option<Duration>,
 }
 
 impl LedgerMetadataPruner {
-    pub(in crate::pruner) fn new(ledger_metadata_db: Arc<DB>) -> Result<Self> {
+    pub(in crate::pruner) fn new(
+        ledger_metadata_db: Arc<DB>,
+        deletion_log_retention: Option<Duration>,
+    ) -> Result<Self> {
         if let Some(v) =
             ledger_metadata_db.get::<DbMetadataSchema>(&DbMetadataKey::LedgerPrunerProgress)?
         {
@@ -36,7 +43,10 @@ impl LedgerMetadataPruner {
             )?;
         }
 
-        Ok(LedgerMetadataPruner { ledger_metadata_db })
+        Ok(LedgerMetadataPruner {
+            ledger_metadata_db,
+            deletion_log_retention,
+        })
     }
 
     pub(in crate::pruner) fn prune(
@@ -52,6 +62,16 @@ impl LedgerMetadataPruner {
             &DbMetadataKey::LedgerPrunerProgress,
             &DbMetadataValue::Version(target_version),
         )?;
+        if let Some(retention) = self.deletion_log_retention {
+            record_deletions(
+                &self.ledger_metadata_db,
+                retention,
+                PrunerName::LedgerMetadata,
+                current_progress..target_version,
+                &mut batch,
+                [],
+            )?;
+        }
         self.ledger_metadata_db.write_schemas(batch)
     }
 
@@ -5,7 +5,10 @@ use crate::{
     ledger_db::LedgerDb,
     metrics::{PRUNER_BATCH_SIZE, PRUNER_VERSIONS, PRUNER_WINDOW},
     pruner::{
-        ledger_pruner::LedgerPruner, pruner_manager::PrunerManager, pruner_utils,
+        ledger_pruner::{ledger_archiver::LedgerArchiver, LedgerPruner},
+        pinned_versions::PinnedVersions,
+        pruner_manager::PrunerManager,
+        pruner_utils,
         pruner_worker::PrunerWorker,
     },
 };
@@ -14,7 +17,10 @@ use aptos_db_indexer::db_indexer::InternalIndexerDB;
 use aptos_infallible::Mutex;
 use aptos_storage_interface::Result;
 use aptos_types::transaction::{AtomicVersion, Version};
-use std::sync::{atomic::Ordering, Arc};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 
 /// The `PrunerManager` for `LedgerPruner`.
 pub(crate) struct LedgerPrunerManager {
@@ -32,6 +38,8 @@ pub(crate) struct LedgerPrunerManager {
     user_pruning_window_offset: u64,
     /// The minimal readable version for the ledger data.
     min_readable_version: AtomicVersion,
+    /// Versions the min readable version can't advance past.
+    pinned_versions: PinnedVersions,
 }
 
 impl PrunerManager for LedgerPrunerManager {
@@ -88,6 +96,15 @@ impl PrunerManager for LedgerPrunerManager {
         self.ledger_db.write_pruner_progress(min_readable_version)
     }
 
+    fn pin_version(&self, version: Version) -> Result<()> {
+        self.pinned_versions
+            .pin(version, &self.min_readable_version)
+    }
+
+    fn unpin_version(&self, version: Version) -> Result<()> {
+        self.pinned_versions.unpin(version)
+    }
+
     fn is_pruning_pending(&self) -> bool {
         self.pruner_worker
             .as_ref()
@@ -109,12 +126,16 @@ impl LedgerPrunerManager {
         ledger_db: Arc<LedgerDb>,
         ledger_pruner_config: LedgerPrunerConfig,
         internal_indexer_db: Option<InternalIndexerDB>,
+        ledger_archiver: Option<LedgerArchiver>,
+        deletion_log_retention: Option<Duration>,
     ) -> Self {
         let pruner_worker = if ledger_pruner_config.enable {
             Some(Self::init_pruner(
                 Arc::clone(&ledger_db),
                 ledger_pruner_config,
                 internal_indexer_db,
+                ledger_archiver,
+                deletion_log_retention,
             ))
         } else {
             None
@@ -135,6 +156,7 @@ impl LedgerPrunerManager {
             latest_version: Arc::new(Mutex::new(min_readable_version)),
             user_pruning_window_offset: ledger_pruner_config.user_pruning_window_offset,
             min_readable_version: AtomicVersion::new(min_readable_version),
+            pinned_versions: PinnedVersions::default(),
         }
     }
 
@@ -142,10 +164,17 @@ impl LedgerPrunerManager {
         ledger_db: Arc<LedgerDb>,
         ledger_pruner_config: LedgerPrunerConfig,
         internal_indexer_db: Option<InternalIndexerDB>,
+        ledger_archiver: Option<LedgerArchiver>,
+        deletion_log_retention: Option<Duration>,
     ) -> PrunerWorker {
         let pruner = Arc::new(
-            LedgerPruner::new(ledger_db, internal_indexer_db)
-                .expect("Failed to create ledger pruner."),
+            LedgerPruner::new(
+                ledger_db,
+                internal_indexer_db,
+                ledger_archiver,
+                deletion_log_retention,
+            )
+            .expect("Failed to create ledger pruner."),
         );
 
         PRUNER_WINDOW
@@ -161,9 +190,10 @@ impl LedgerPrunerManager {
 
     fn set_pruner_target_db_version(&self, latest_version: Version) {
         assert!(self.pruner_worker.is_some());
-        let min_readable_version = latest_version.saturating_sub(self.prune_window);
-        self.min_readable_version
-            .store(min_readable_version, Ordering::SeqCst);
+        let min_readable_version = self.pinned_versions.advance_min_readable_version(
+            &self.min_readable_version,
+            latest_version.saturating_sub(self.prune_window),
+        );
 
         PRUNER_VERSIONS
             .with_label_values(&["ledger_pruner", "min_readable"])
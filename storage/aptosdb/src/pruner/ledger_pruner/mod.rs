@@ -2,6 +2,7 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 mod event_store_pruner;
+pub(crate) mod ledger_archiver;
 mod ledger_metadata_pruner;
 pub(crate) mod ledger_pruner_manager;
 mod persisted_auxiliary_info_pruner;
@@ -18,7 +19,8 @@ use crate::{
         db_pruner::DBPruner,
         db_sub_pruner::DBSubPruner,
         ledger_pruner::{
-            event_store_pruner::EventStorePruner, ledger_metadata_pruner::LedgerMetadataPruner,
+            event_store_pruner::EventStorePruner, ledger_archiver::LedgerArchiver,
+            ledger_metadata_pruner::LedgerMetadataPruner,
             persisted_auxiliary_info_pruner::PersistedAuxiliaryInfoPruner,
             transaction_accumulator_pruner::TransactionAccumulatorPruner,
             transaction_auxiliary_data_pruner::TransactionAuxiliaryDataPruner,
@@ -38,6 +40,7 @@ use rayon::prelude::*;
 use std::{
     cmp::min,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 pub const LEDGER_PRUNER_NAME: &str = "ledger_pruner";
@@ -52,6 +55,9 @@ pub(crate) struct LedgerPruner {
     ledger_metadata_pruner: Box<LedgerMetadataPruner>,
 
     sub_pruners: Vec<Box<dyn DBSubPruner + Send + Sync>>,
+
+    /// If set, the data is copied into an archive DB before being pruned.
+    ledger_archiver: Option<LedgerArchiver>,
 }
 
 impl DBPruner for LedgerPruner {
@@ -72,6 +78,9 @@ impl DBPruner for LedgerPruner {
                 target_version = current_batch_target_version,
                 "Pruning ledger data."
             );
+            if let Some(ledger_archiver) = &self.ledger_archiver {
+                ledger_archiver.archive(progress, current_batch_target_version)?;
+            }
             self.ledger_metadata_pruner
                 .prune(progress, current_batch_target_version)?;
 
@@ -118,11 +127,13 @@ impl LedgerPruner {
     pub fn new(
         ledger_db: Arc<LedgerDb>,
         internal_indexer_db: Option<InternalIndexerDB>,
+        ledger_archiver: Option<LedgerArchiver>,
+        deletion_log_retention: Option<Duration>,
     ) -> Result<Self> {
         info!(name = LEDGER_PRUNER_NAME, "Initializing...");
 
         let ledger_metadata_pruner = Box::new(
-            LedgerMetadataPruner::new(ledger_db.metadata_db_arc())
+            LedgerMetadataPruner::new(ledger_db.metadata_db_arc(), deletion_log_retention)
                 .expect("Failed to initialize ledger_metadata_pruner."),
         );
 
@@ -139,34 +150,41 @@ impl LedgerPruner {
             Arc::clone(&ledger_db),
             metadata_progress,
             internal_indexer_db.clone(),
+            deletion_log_retention,
         )?);
         let persisted_auxiliary_info_pruner = Box::new(PersistedAuxiliaryInfoPruner::new(
             Arc::clone(&ledger_db),
             metadata_progress,
+            deletion_log_retention,
         )?);
         let transaction_accumulator_pruner = Box::new(TransactionAccumulatorPruner::new(
             Arc::clone(&ledger_db),
             metadata_progress,
+            deletion_log_retention,
         )?);
 
         let transaction_auxiliary_data_pruner = Box::new(TransactionAuxiliaryDataPruner::new(
             Arc::clone(&ledger_db),
             metadata_progress,
+            deletion_log_retention,
         )?);
 
         let transaction_info_pruner = Box::new(TransactionInfoPruner::new(
             Arc::clone(&ledger_db),
             metadata_progress,
+            deletion_log_retention,
         )?);
         let transaction_pruner = Box::new(TransactionPruner::new(
             Arc::clone(&transaction_store),
             Arc::clone(&ledger_db),
             metadata_progress,
             internal_indexer_db,
+            deletion_log_retention,
         )?);
         let write_set_pruner = Box::new(WriteSetPruner::new(
             Arc::clone(&ledger_db),
             metadata_progress,
+            deletion_log_retention,
         )?);
 
         let pruner = LedgerPruner {
@@ -182,6 +200,7 @@ impl LedgerPruner {
                 transaction_pruner,
                 write_set_pruner,
             ],
+            ledger_archiver,
         };
 
         info!(
@@ -3,18 +3,22 @@
 
 use crate::{
     ledger_db::{transaction_auxiliary_data_db::TransactionAuxiliaryDataDb, LedgerDb},
-    pruner::{db_sub_pruner::DBSubPruner, pruner_utils::get_or_initialize_subpruner_progress},
+    pruner::{
+        db_sub_pruner::DBSubPruner, deletion_log::record_deletions,
+        pruner_utils::get_or_initialize_subpruner_progress, PrunerName,
+    },
     schema::db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
 };
 use aptos_logger::info;
 use aptos_schemadb::batch::SchemaBatch;
 use aptos_storage_interface::Result;
 use aptos_types::transaction::Version;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub struct TransactionAuxiliaryDataPruner {
     ledger_db: Arc<LedgerDb>,
+    deletion_log_retention: Option<Duration>,
 }
 
 impl DBSubPruner for TransactionAuxiliaryDataPruner {
@@ -29,6 +33,16 @@ impl DBSubPruner for TransactionAuxiliaryDataPruner {
             &DbMetadataKey::TransactionAuxiliaryDataPrunerProgress,
             &DbMetadataValue::Version(target_version),
         )?;
+        if let Some(retention) = self.deletion_log_retention {
+            record_deletions(
+                self.ledger_db.transaction_auxiliary_data_db_raw(),
+                retention,
+                PrunerName::TransactionAuxiliaryData,
+                current_progress..target_version,
+                &mut batch,
+                [],
+            )?;
+        }
         self.ledger_db
             .transaction_auxiliary_data_db()
             .write_schemas(batch)
@@ -39,6 +53,7 @@ impl TransactionAuxiliaryDataPruner {
     pub(in crate::pruner) fn new(
         ledger_db: Arc<LedgerDb>,
         metadata_progress: Version,
+        deletion_log_retention: Option<Duration>,
     ) -> Result<Self> {
         let progress = get_or_initialize_subpruner_progress(
             ledger_db.transaction_auxiliary_data_db_raw(),
@@ -46,7 +61,10 @@ impl TransactionAuxiliaryDataPruner {
             metadata_progress,
         )?;
 
-        let myself = TransactionAuxiliaryDataPruner { ledger_db };
+        let myself = TransactionAuxiliaryDataPruner {
+            ledger_db,
+            deletion_log_retention,
+        };
 
         info!(
             progress = progress,
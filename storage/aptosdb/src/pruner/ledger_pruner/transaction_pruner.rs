@@ -3,7 +3,10 @@
 
 use crate::{
     ledger_db::LedgerDb,
-    pruner::{db_sub_pruner::DBSubPruner, pruner_utils::get_or_initialize_subpruner_progress},
+    pruner::{
+        db_sub_pruner::DBSubPruner, deletion_log::record_deletions,
+        pruner_utils::get_or_initialize_subpruner_progress, PrunerName,
+    },
     schema::{
         db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
         transaction::TransactionSchema,
@@ -19,13 +22,14 @@ use aptos_logger::info;
 use aptos_schemadb::batch::SchemaBatch;
 use aptos_storage_interface::{db_ensure as ensure, AptosDbError, Result};
 use aptos_types::transaction::{Transaction, Version};
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub struct TransactionPruner {
     transaction_store: Arc<TransactionStore>,
     ledger_db: Arc<LedgerDb>,
     internal_indexer_db: Option<InternalIndexerDB>,
+    deletion_log_retention: Option<Duration>,
 }
 
 impl DBSubPruner for TransactionPruner {
@@ -56,6 +60,7 @@ impl DBSubPruner for TransactionPruner {
             &DbMetadataKey::TransactionPrunerProgress,
             &DbMetadataValue::Version(target_version),
         )?;
+        let mut indexer_write = None;
         if let Some(indexer_db) = self.internal_indexer_db.as_ref() {
             if indexer_db.transaction_enabled() {
                 let mut index_batch = SchemaBatch::new();
@@ -65,12 +70,25 @@ impl DBSubPruner for TransactionPruner {
                     &IndexerMetadataKey::TransactionPrunerProgress,
                     &IndexerMetadataValue::Version(target_version),
                 )?;
-                indexer_db.get_inner_db_ref().write_schemas(index_batch)?;
+                indexer_write = Some((indexer_db, index_batch));
             } else {
                 self.transaction_store
                     .prune_transaction_by_account(&candidate_transactions, &mut batch)?;
             }
         }
+        if let Some(retention) = self.deletion_log_retention {
+            record_deletions(
+                self.ledger_db.transaction_db_raw(),
+                retention,
+                PrunerName::Transaction,
+                current_progress..target_version,
+                &mut batch,
+                indexer_write.as_ref().map(|(_, index_batch)| index_batch),
+            )?;
+        }
+        if let Some((indexer_db, index_batch)) = indexer_write {
+            indexer_db.get_inner_db_ref().write_schemas(index_batch)?;
+        }
         self.ledger_db.transaction_db().write_schemas(batch)
     }
 }
@@ -81,6 +99,7 @@ impl TransactionPruner {
         ledger_db: Arc<LedgerDb>,
         metadata_progress: Version,
         internal_indexer_db: Option<InternalIndexerDB>,
+        deletion_log_retention: Option<Duration>,
     ) -> Result<Self> {
         let progress = get_or_initialize_subpruner_progress(
             ledger_db.transaction_db_raw(),
@@ -92,6 +111,7 @@ impl TransactionPruner {
             transaction_store,
             ledger_db,
             internal_indexer_db,
+            deletion_log_retention,
         };
 
         info!(
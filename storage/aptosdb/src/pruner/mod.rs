@@ -3,14 +3,19 @@
 
 mod db_pruner;
 mod db_sub_pruner;
+pub(crate) mod deletion_log;
 mod ledger_pruner;
+mod pinned_versions;
 mod pruner_manager;
 mod pruner_utils;
 mod pruner_worker;
 mod state_kv_pruner;
 mod state_merkle_pruner;
 
-pub(crate) use ledger_pruner::ledger_pruner_manager::LedgerPrunerManager;
+pub use deletion_log::{DeletionRecord, PrunerName, RecordTimestamp};
+pub(crate) use ledger_pruner::{
+    ledger_archiver::LedgerArchiver, ledger_pruner_manager::LedgerPrunerManager,
+};
 pub(crate) use pruner_manager::PrunerManager;
 pub(crate) use state_kv_pruner::state_kv_pruner_manager::StateKvPrunerManager;
 pub(crate) use state_merkle_pruner::state_merkle_pruner_manager::StateMerklePrunerManager;
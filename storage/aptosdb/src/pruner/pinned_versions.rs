@@ -0,0 +1,59 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+use aptos_infallible::Mutex;
+use aptos_storage_interface::{db_ensure as ensure, db_other_bail as bail, Result};
+use aptos_types::transaction::{AtomicVersion, Version};
+use std::{collections::BTreeMap, sync::atomic::Ordering};
+
+/// Versions which are pinned so that a pruner doesn't prune them, with the number of times each
+/// is pinned.
+#[derive(Default)]
+pub(crate) struct PinnedVersions {
+    pin_counts: Mutex<BTreeMap<Version, usize>>,
+}
+
+impl PinnedVersions {
+    /// Fails if `version` is already pruned, judging by `min_readable_version`.
+    pub(crate) fn pin(&self, version: Version, min_readable_version: &AtomicVersion) -> Result<()> {
+        let mut pin_counts = self.pin_counts.lock();
+        let current_min_readable_version = min_readable_version.load(Ordering::SeqCst);
+        ensure!(
+            version >= current_min_readable_version,
+            "Version {version} is already pruned, min readable version: {current_min_readable_version}.",
+        );
+        *pin_counts.entry(version).or_insert(0) += 1;
+        Ok(())
+    }
+
+    pub(crate) fn unpin(&self, version: Version) -> Result<()> {
+        let mut pin_counts = self.pin_counts.lock();
+        match pin_counts.get_mut(&version) {
+            Some(1) => {
+                pin_counts.remove(&version);
+            },
+            Some(count) => *count -= 1,
+            None => bail!("Version {version} is not pinned."),
+        }
+        Ok(())
+    }
+
+    /// Advances `min_readable_version` towards `target`, but not past the lowest pinned version,
+    /// and returns the new min readable version. Done under the lock so that a version can't be
+    /// pinned after having been checked against a min readable version that's about to change.
+    pub(crate) fn advance_min_readable_version(
+        &self,
+        min_readable_version: &AtomicVersion,
+        target: Version,
+    ) -> Version {
+        let pin_counts = self.pin_counts.lock();
+        let mut new_min_readable_version = target;
+        if let Some(lowest_pinned_version) = pin_counts.keys().next() {
+            new_min_readable_version = new_min_readable_version.min(*lowest_pinned_version);
+        }
+        new_min_readable_version =
+            new_min_readable_version.max(min_readable_version.load(Ordering::SeqCst));
+        min_readable_version.store(new_min_readable_version, Ordering::SeqCst);
+        new_min_readable_version
+    }
+}
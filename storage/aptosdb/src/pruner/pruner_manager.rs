@@ -34,6 +34,13 @@ pub trait PrunerManager: Sync {
     // in memory progress.
     fn save_min_readable_version(&self, min_readable_version: Version) -> Result<()>;
 
+    /// Prevents the min readable version from advancing past `version` until it's unpinned, so
+    /// that the pruner prunes up to `version - 1` at most. A version can be pinned multiple times,
+    /// in which case it needs to be unpinned as many times. Fails if `version` is already pruned.
+    fn pin_version(&self, version: Version) -> Result<()>;
+
+    fn unpin_version(&self, version: Version) -> Result<()>;
+
     #[allow(unused)]
     fn is_pruning_pending(&self) -> bool;
 
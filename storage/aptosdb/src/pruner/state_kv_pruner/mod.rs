@@ -26,6 +26,7 @@ use rayon::prelude::*;
 use std::{
     cmp::min,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 pub const STATE_KV_PRUNER_NAME: &str = "state_kv_pruner";
@@ -109,10 +110,14 @@ impl DBPruner for StateKvPruner {
 }
 
 impl StateKvPruner {
-    pub fn new(state_kv_db: Arc<StateKvDb>) -> Result<Self> {
+    pub fn new(
+        state_kv_db: Arc<StateKvDb>,
+        deletion_log_retention: Option<Duration>,
+    ) -> Result<Self> {
         info!(name = STATE_KV_PRUNER_NAME, "Initializing...");
 
-        let metadata_pruner = StateKvMetadataPruner::new(Arc::clone(&state_kv_db));
+        let metadata_pruner =
+            StateKvMetadataPruner::new(Arc::clone(&state_kv_db), deletion_log_retention);
 
         let metadata_progress = metadata_pruner.progress()?;
 
@@ -129,6 +134,7 @@ impl StateKvPruner {
                     shard_id,
                     state_kv_db.db_shard_arc(shard_id),
                     metadata_progress,
+                    deletion_log_retention,
                 )?);
             }
             shard_pruners
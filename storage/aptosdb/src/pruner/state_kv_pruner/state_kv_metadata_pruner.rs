@@ -2,6 +2,7 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
+    pruner::{deletion_log::record_deletions, PrunerName},
     schema::{
         db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
         stale_state_value_index::StaleStateValueIndexSchema,
@@ -14,15 +15,22 @@ use crate::{
 use aptos_schemadb::batch::SchemaBatch;
 use aptos_storage_interface::Result;
 use aptos_types::transaction::Version;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 pub(in crate::pruner) struct StateKvMetadataPruner {
     state_kv_db: Arc<StateKvDb>,
+    deletion_log_retention: Option<Duration>,
 }
 
 impl StateKvMetadataPruner {
-    pub(in crate::pruner) fn new(state_kv_db: Arc<StateKvDb>) -> Self {
-        Self { state_kv_db }
+    pub(in crate::pruner) fn new(
+        state_kv_db: Arc<StateKvDb>,
+        deletion_log_retention: Option<Duration>,
+    ) -> Self {
+        Self {
+            state_kv_db,
+            deletion_log_retention,
+        }
     }
 
     pub(in crate::pruner) fn prune(
@@ -69,6 +77,17 @@ impl StateKvMetadataPruner {
             &DbMetadataValue::Version(target_version),
         )?;
 
+        if let Some(retention) = self.deletion_log_retention {
+            record_deletions(
+                self.state_kv_db.metadata_db(),
+                retention,
+                PrunerName::StateKvMetadata,
+                current_progress..target_version,
+                &mut batch,
+                [],
+            )?;
+        }
+
         self.state_kv_db.metadata_db().write_schemas(batch)
     }
 
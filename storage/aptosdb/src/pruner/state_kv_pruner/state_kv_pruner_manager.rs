@@ -4,15 +4,18 @@
 use crate::{
     metrics::{PRUNER_BATCH_SIZE, PRUNER_VERSIONS, PRUNER_WINDOW},
     pruner::{
-        pruner_manager::PrunerManager, pruner_utils, pruner_worker::PrunerWorker,
-        state_kv_pruner::StateKvPruner,
+        pinned_versions::PinnedVersions, pruner_manager::PrunerManager, pruner_utils,
+        pruner_worker::PrunerWorker, state_kv_pruner::StateKvPruner,
     },
     state_kv_db::StateKvDb,
 };
 use aptos_config::config::LedgerPrunerConfig;
 use aptos_storage_interface::Result;
 use aptos_types::transaction::{AtomicVersion, Version};
-use std::sync::{atomic::Ordering, Arc};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 
 /// The `PrunerManager` for `StateKvPruner`.
 pub(crate) struct StateKvPrunerManager {
@@ -25,6 +28,8 @@ pub(crate) struct StateKvPrunerManager {
     pruning_batch_size: usize,
     /// The minimal readable version for the ledger data.
     min_readable_version: AtomicVersion,
+    /// Versions the min readable version can't advance past.
+    pinned_versions: PinnedVersions,
 }
 
 impl PrunerManager for StateKvPrunerManager {
@@ -65,6 +70,15 @@ impl PrunerManager for StateKvPrunerManager {
         self.state_kv_db.write_pruner_progress(min_readable_version)
     }
 
+    fn pin_version(&self, version: Version) -> Result<()> {
+        self.pinned_versions
+            .pin(version, &self.min_readable_version)
+    }
+
+    fn unpin_version(&self, version: Version) -> Result<()> {
+        self.pinned_versions.unpin(version)
+    }
+
     fn is_pruning_pending(&self) -> bool {
         self.pruner_worker
             .as_ref()
@@ -81,11 +95,16 @@ impl PrunerManager for StateKvPrunerManager {
 }
 
 impl StateKvPrunerManager {
-    pub fn new(state_kv_db: Arc<StateKvDb>, state_kv_pruner_config: LedgerPrunerConfig) -> Self {
+    pub fn new(
+        state_kv_db: Arc<StateKvDb>,
+        state_kv_pruner_config: LedgerPrunerConfig,
+        deletion_log_retention: Option<Duration>,
+    ) -> Self {
         let pruner_worker = if state_kv_pruner_config.enable {
             Some(Self::init_pruner(
                 Arc::clone(&state_kv_db),
                 state_kv_pruner_config,
+                deletion_log_retention,
             ))
         } else {
             None
@@ -104,15 +123,19 @@ impl StateKvPrunerManager {
             pruner_worker,
             pruning_batch_size: state_kv_pruner_config.batch_size,
             min_readable_version: AtomicVersion::new(min_readable_version),
+            pinned_versions: PinnedVersions::default(),
         }
     }
 
     fn init_pruner(
         state_kv_db: Arc<StateKvDb>,
         state_kv_pruner_config: LedgerPrunerConfig,
+        deletion_log_retention: Option<Duration>,
     ) -> PrunerWorker {
-        let pruner =
-            Arc::new(StateKvPruner::new(state_kv_db).expect("Failed to create state kv pruner."));
+        let pruner = Arc::new(
+            StateKvPruner::new(state_kv_db, deletion_log_retention)
+                .expect("Failed to create state kv pruner."),
+        );
 
         PRUNER_WINDOW
             .with_label_values(&["state_kv_pruner"])
@@ -127,9 +150,10 @@ impl StateKvPrunerManager {
 
     fn set_pruner_target_db_version(&self, latest_version: Version) {
         assert!(self.pruner_worker.is_some());
-        let min_readable_version = latest_version.saturating_sub(self.prune_window);
-        self.min_readable_version
-            .store(min_readable_version, Ordering::SeqCst);
+        let min_readable_version = self.pinned_versions.advance_min_readable_version(
+            &self.min_readable_version,
+            latest_version.saturating_sub(self.prune_window),
+        );
 
         PRUNER_VERSIONS
             .with_label_values(&["state_kv_pruner", "min_readable"])
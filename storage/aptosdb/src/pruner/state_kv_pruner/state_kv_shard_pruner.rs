@@ -2,7 +2,10 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
-    pruner::pruner_utils::get_or_initialize_subpruner_progress,
+    pruner::{
+        deletion_log::record_deletions, pruner_utils::get_or_initialize_subpruner_progress,
+        PrunerName,
+    },
     schema::{
         db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
         stale_state_value_index_by_key_hash::StaleStateValueIndexByKeyHashSchema,
@@ -13,12 +16,13 @@ use aptos_logger::info;
 use aptos_schemadb::{batch::SchemaBatch, DB};
 use aptos_storage_interface::Result;
 use aptos_types::transaction::Version;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 // This pruner is only used when enable_sharding flag is true
 pub(in crate::pruner) struct StateKvShardPruner {
     shard_id: usize,
     db_shard: Arc<DB>,
+    deletion_log_retention: Option<Duration>,
 }
 
 impl StateKvShardPruner {
@@ -26,13 +30,18 @@ impl StateKvShardPruner {
         shard_id: usize,
         db_shard: Arc<DB>,
         metadata_progress: Version,
+        deletion_log_retention: Option<Duration>,
     ) -> Result<Self> {
         let progress = get_or_initialize_subpruner_progress(
             &db_shard,
             &DbMetadataKey::StateKvShardPrunerProgress(shard_id),
             metadata_progress,
         )?;
-        let myself = Self { shard_id, db_shard };
+        let myself = Self {
+            shard_id,
+            db_shard,
+            deletion_log_retention,
+        };
 
         info!(
             progress = progress,
@@ -68,6 +77,17 @@ impl StateKvShardPruner {
             &DbMetadataValue::Version(target_version),
         )?;
 
+        if let Some(retention) = self.deletion_log_retention {
+            record_deletions(
+                &self.db_shard,
+                retention,
+                PrunerName::StateKvShard(self.shard_id),
+                current_progress..target_version,
+                &mut batch,
+                [],
+            )?;
+        }
+
         self.db_shard.write_schemas(batch)
     }
 
@@ -1,9 +1,12 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
-use crate::schema::{
-    db_metadata::DbMetadataKey, stale_node_index::StaleNodeIndexSchema,
-    stale_node_index_cross_epoch::StaleNodeIndexCrossEpochSchema,
+use crate::{
+    pruner::PrunerName,
+    schema::{
+        db_metadata::DbMetadataKey, stale_node_index::StaleNodeIndexSchema,
+        stale_node_index_cross_epoch::StaleNodeIndexCrossEpochSchema,
+    },
 };
 use aptos_jellyfish_merkle::StaleNodeIndex;
 use aptos_schemadb::schema::{KeyCodec, Schema};
@@ -13,6 +16,7 @@ where
     StaleNodeIndex: KeyCodec<Self>,
 {
     fn progress_metadata_key(shard_id: Option<usize>) -> DbMetadataKey;
+    fn pruner_name(shard_id: Option<usize>) -> PrunerName;
     fn name() -> &'static str;
 }
 
@@ -25,6 +29,14 @@ impl StaleNodeIndexSchemaTrait for StaleNodeIndexSchema {
         }
     }
 
+    fn pruner_name(shard_id: Option<usize>) -> PrunerName {
+        if let Some(shard_id) = shard_id {
+            PrunerName::StateMerkleShard(shard_id)
+        } else {
+            PrunerName::StateMerkleMetadata
+        }
+    }
+
     fn name() -> &'static str {
         "state_merkle_pruner"
     }
@@ -39,6 +51,14 @@ impl StaleNodeIndexSchemaTrait for StaleNodeIndexCrossEpochSchema {
         }
     }
 
+    fn pruner_name(shard_id: Option<usize>) -> PrunerName {
+        if let Some(shard_id) = shard_id {
+            PrunerName::EpochSnapshotShard(shard_id)
+        } else {
+            PrunerName::EpochSnapshotMetadata
+        }
+    }
+
     fn name() -> &'static str {
         "epoch_snapshot_pruner"
     }
@@ -32,6 +32,7 @@ use rayon::prelude::*;
 use std::{
     marker::PhantomData,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 /// Responsible for pruning the state tree.
@@ -121,10 +122,16 @@ impl<S: StaleNodeIndexSchemaTrait> StateMerklePruner<S>
 where
     StaleNodeIndex: KeyCodec<S>,
 {
-    pub fn new(state_merkle_db: Arc<StateMerkleDb>) -> Result<Self> {
+    pub fn new(
+        state_merkle_db: Arc<StateMerkleDb>,
+        deletion_log_retention: Option<Duration>,
+    ) -> Result<Self> {
         info!(name = S::name(), "Initializing...");
 
-        let metadata_pruner = StateMerkleMetadataPruner::new(state_merkle_db.metadata_db_arc());
+        let metadata_pruner = StateMerkleMetadataPruner::new(
+            state_merkle_db.metadata_db_arc(),
+            deletion_log_retention,
+        );
         let metadata_progress = metadata_pruner.progress()?;
 
         info!(
@@ -141,6 +148,7 @@ where
                     shard_id,
                     state_merkle_db.db_shard_arc(shard_id),
                     metadata_progress,
+                    deletion_log_retention,
                 )?);
             }
             shard_pruners
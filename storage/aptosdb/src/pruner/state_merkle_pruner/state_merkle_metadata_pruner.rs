@@ -2,7 +2,10 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
-    pruner::state_merkle_pruner::{generics::StaleNodeIndexSchemaTrait, StateMerklePruner},
+    pruner::{
+        deletion_log::record_deletions,
+        state_merkle_pruner::{generics::StaleNodeIndexSchemaTrait, StateMerklePruner},
+    },
     schema::{
         db_metadata::{DbMetadataSchema, DbMetadataValue},
         jellyfish_merkle_node::JellyfishMerkleNodeSchema,
@@ -17,11 +20,13 @@ use std::{
     cmp::max,
     marker::PhantomData,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 pub(in crate::pruner) struct StateMerkleMetadataPruner<S> {
     metadata_db: Arc<DB>,
     next_version: AtomicVersion,
+    deletion_log_retention: Option<Duration>,
     _phantom: PhantomData<S>,
 }
 
@@ -29,10 +34,14 @@ impl<S: StaleNodeIndexSchemaTrait> StateMerkleMetadataPruner<S>
 where
     StaleNodeIndex: KeyCodec<S>,
 {
-    pub(in crate::pruner) fn new(metadata_db: Arc<DB>) -> Self {
+    pub(in crate::pruner) fn new(
+        metadata_db: Arc<DB>,
+        deletion_log_retention: Option<Duration>,
+    ) -> Self {
         Self {
             metadata_db,
             next_version: AtomicVersion::new(0),
+            deletion_log_retention,
             _phantom: PhantomData,
         }
     }
@@ -68,6 +77,17 @@ where
             &DbMetadataValue::Version(target_version_for_this_round),
         )?;
 
+        if let Some(retention) = self.deletion_log_retention {
+            record_deletions(
+                &self.metadata_db,
+                retention,
+                S::pruner_name(None),
+                current_progress..target_version_for_this_round,
+                &mut batch,
+                [],
+            )?;
+        }
+
         self.metadata_db.write_schemas(batch)?;
 
         self.next_version
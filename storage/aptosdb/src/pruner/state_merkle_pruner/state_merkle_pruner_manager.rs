@@ -7,6 +7,7 @@
 use crate::{
     metrics::{PRUNER_BATCH_SIZE, PRUNER_VERSIONS, PRUNER_WINDOW},
     pruner::{
+        pinned_versions::PinnedVersions,
         pruner_manager::PrunerManager,
         pruner_utils,
         pruner_worker::PrunerWorker,
@@ -22,6 +23,7 @@ use aptos_types::transaction::{AtomicVersion, Version};
 use std::{
     marker::PhantomData,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 /// The `Pruner` is meant to be part of a `AptosDB` instance and runs in the background to prune old
@@ -41,6 +43,8 @@ where
     pruner_worker: Option<PrunerWorker>,
     /// The minimal readable version for the state merkle data.
     min_readable_version: AtomicVersion,
+    /// Versions the min readable version can't advance past.
+    pinned_versions: PinnedVersions,
 
     _phantom: PhantomData<S>,
 }
@@ -83,6 +87,15 @@ where
             .write_pruner_progress(&S::progress_metadata_key(None), min_readable_version)
     }
 
+    fn pin_version(&self, version: Version) -> Result<()> {
+        self.pinned_versions
+            .pin(version, &self.min_readable_version)
+    }
+
+    fn unpin_version(&self, version: Version) -> Result<()> {
+        self.pinned_versions.unpin(version)
+    }
+
     fn is_pruning_pending(&self) -> bool {
         self.pruner_worker
             .as_ref()
@@ -106,11 +119,13 @@ where
     pub fn new(
         state_merkle_db: Arc<StateMerkleDb>,
         state_merkle_pruner_config: StateMerklePrunerConfig,
+        deletion_log_retention: Option<Duration>,
     ) -> Self {
         let pruner_worker = if state_merkle_pruner_config.enable {
             Some(Self::init_pruner(
                 Arc::clone(&state_merkle_db),
                 state_merkle_pruner_config,
+                deletion_log_retention,
             ))
         } else {
             None
@@ -128,6 +143,7 @@ where
             prune_window: state_merkle_pruner_config.prune_window,
             pruner_worker,
             min_readable_version: AtomicVersion::new(min_readable_version),
+            pinned_versions: PinnedVersions::default(),
             _phantom: PhantomData,
         }
     }
@@ -135,9 +151,10 @@ where
     fn init_pruner(
         state_merkle_db: Arc<StateMerkleDb>,
         state_merkle_pruner_config: StateMerklePrunerConfig,
+        deletion_log_retention: Option<Duration>,
     ) -> PrunerWorker {
         let pruner = Arc::new(
-            StateMerklePruner::<S>::new(Arc::clone(&state_merkle_db))
+            StateMerklePruner::<S>::new(Arc::clone(&state_merkle_db), deletion_log_retention)
                 .expect("Failed to create state merkle pruner."),
         );
 
@@ -159,9 +176,10 @@ where
     fn set_pruner_target_db_version(&self, latest_version: Version) {
         assert!(self.pruner_worker.is_some());
 
-        let min_readable_version = latest_version.saturating_sub(self.prune_window);
-        self.min_readable_version
-            .store(min_readable_version, Ordering::SeqCst);
+        let min_readable_version = self.pinned_versions.advance_min_readable_version(
+            &self.min_readable_version,
+            latest_version.saturating_sub(self.prune_window),
+        );
 
         PRUNER_VERSIONS
             .with_label_values(&[S::name(), "min_readable"])
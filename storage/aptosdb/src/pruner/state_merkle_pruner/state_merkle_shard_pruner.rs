@@ -3,6 +3,7 @@
 
 use crate::{
     pruner::{
+        deletion_log::record_deletions,
         pruner_utils::get_or_initialize_subpruner_progress,
         state_merkle_pruner::{generics::StaleNodeIndexSchemaTrait, StateMerklePruner},
     },
@@ -16,11 +17,12 @@ use aptos_jellyfish_merkle::StaleNodeIndex;
 use aptos_logger::info;
 use aptos_schemadb::{batch::SchemaBatch, schema::KeyCodec, DB};
 use aptos_types::transaction::Version;
-use std::{marker::PhantomData, sync::Arc};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
 
 pub(in crate::pruner) struct StateMerkleShardPruner<S> {
     shard_id: usize,
     db_shard: Arc<DB>,
+    deletion_log_retention: Option<Duration>,
     _phantom: PhantomData<S>,
 }
 
@@ -32,6 +34,7 @@ where
         shard_id: usize,
         db_shard: Arc<DB>,
         metadata_progress: Version,
+        deletion_log_retention: Option<Duration>,
     ) -> Result<Self> {
         let progress = get_or_initialize_subpruner_progress(
             &db_shard,
@@ -41,6 +44,7 @@ where
         let myself = Self {
             shard_id,
             db_shard,
+            deletion_log_retention,
             _phantom: PhantomData,
         };
 
@@ -89,6 +93,17 @@ where
                 )?;
             }
 
+            if let Some(retention) = self.deletion_log_retention {
+                record_deletions(
+                    &self.db_shard,
+                    retention,
+                    S::pruner_name(Some(self.shard_id)),
+                    current_progress..target_version,
+                    &mut batch,
+                    [],
+                )?;
+            }
+
             self.db_shard.write_schemas(batch)?;
 
             if done {
@@ -6,13 +6,14 @@ use crate::{
         test_helper::{arb_state_kv_sets_with_genesis, update_store},
         AptosDB,
     },
-    pruner::{PrunerManager, StateKvPrunerManager, StateMerklePrunerManager},
+    pruner::{PrunerManager, PrunerName, StateKvPrunerManager, StateMerklePrunerManager},
     schema::{
-        stale_node_index::StaleNodeIndexSchema,
+        jellyfish_merkle_node::JellyfishMerkleNodeSchema, stale_node_index::StaleNodeIndexSchema,
         stale_state_value_index::StaleStateValueIndexSchema,
         stale_state_value_index_by_key_hash::StaleStateValueIndexByKeyHashSchema,
+        JELLYFISH_MERKLE_NODE_CF_NAME,
     },
-    state_merkle_db::StateMerkleDb,
+    state_merkle_db::{Node, StateMerkleDb},
     state_store::StateStore,
 };
 use aptos_config::config::{LedgerPrunerConfig, StateMerklePrunerConfig};
@@ -28,7 +29,11 @@ use aptos_types::{
     transaction::Version,
 };
 use proptest::{prelude::*, proptest};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 fn put_value_set(
     state_store: &StateStore,
@@ -55,11 +60,15 @@ fn create_state_merkle_pruner_manager(
     state_merkle_db: &Arc<StateMerkleDb>,
     prune_batch_size: usize,
 ) -> StateMerklePrunerManager<StaleNodeIndexSchema> {
-    StateMerklePrunerManager::new(Arc::clone(state_merkle_db), StateMerklePrunerConfig {
-        enable: true,
-        prune_window: 0,
-        batch_size: prune_batch_size,
-    })
+    StateMerklePrunerManager::new(
+        Arc::clone(state_merkle_db),
+        StateMerklePrunerConfig {
+            enable: true,
+            prune_window: 0,
+            batch_size: prune_batch_size,
+        },
+        None, /* deletion_log_retention */
+    )
 }
 
 #[test]
@@ -301,6 +310,188 @@ fn test_state_store_pruner_disabled() {
     }
 }
 
+#[test]
+fn test_state_merkle_pruner_pin_version() {
+    let key = StateKey::raw(b"test_key1");
+    let num_versions = 20;
+    let tmp_dir = TempPath::new();
+    let aptos_db = AptosDB::new_for_test_no_cache(&tmp_dir);
+    let state_store = &aptos_db.state_store;
+    for i in 0..num_versions {
+        put_value_set(
+            state_store,
+            vec![(key.clone(), StateValue::from(vec![i as u8]))],
+            i, /* version */
+        );
+    }
+
+    let pruner = create_state_merkle_pruner_manager(&aptos_db.state_merkle_db(), 100);
+    pruner.pin_version(5).unwrap();
+    pruner
+        .wake_and_wait_pruner(10 /* latest_version */)
+        .unwrap();
+    assert_eq!(pruner.get_min_readable_version(), 5);
+    verify_state_in_store(
+        state_store,
+        key.clone(),
+        Some(&StateValue::from(vec![5])),
+        5,
+    );
+    assert!(state_store
+        .get_state_value_with_proof_by_version(&key, 4)
+        .is_err());
+    assert!(pruner.pin_version(4).is_err());
+
+    pruner.unpin_version(5).unwrap();
+    assert!(pruner.unpin_version(5).is_err());
+    pruner
+        .wake_and_wait_pruner(10 /* latest_version */)
+        .unwrap();
+    assert_eq!(pruner.get_min_readable_version(), 10);
+}
+
+#[test]
+fn test_state_merkle_pruner_deletion_log() {
+    let key = StateKey::raw(b"test_key1");
+    let tmp_dir = TempPath::new();
+    let aptos_db = AptosDB::new_for_test_no_cache(&tmp_dir);
+    let state_store = &aptos_db.state_store;
+    for i in 0..10 {
+        put_value_set(
+            state_store,
+            vec![(key.clone(), StateValue::from(vec![i as u8]))],
+            i, /* version */
+        );
+    }
+
+    let since = SystemTime::now();
+    let pruner = StateMerklePrunerManager::<StaleNodeIndexSchema>::new(
+        aptos_db.state_merkle_db(),
+        StateMerklePrunerConfig {
+            enable: true,
+            prune_window: 0,
+            batch_size: 100,
+        },
+        Some(Duration::from_secs(3600)), /* deletion_log_retention */
+    );
+    pruner.wake_and_wait_pruner(5 /* latest_version */).unwrap();
+    drop(pruner);
+    drop(aptos_db);
+
+    // The records are persisted along with the deletes.
+    let aptos_db = AptosDB::new_for_test_no_cache(&tmp_dir);
+    let records = aptos_db
+        .pruner_deletion_log(since..SystemTime::now())
+        .unwrap();
+    assert!(!records.is_empty());
+    for record in &records {
+        assert!(matches!(
+            record.pruner,
+            PrunerName::StateMerkleMetadata | PrunerName::StateMerkleShard(_)
+        ));
+        assert!(record.version_range.end <= 5);
+    }
+    let num_node_deletes: usize = records
+        .iter()
+        .filter_map(|record| record.num_deletes_by_cf.get(JELLYFISH_MERKLE_NODE_CF_NAME))
+        .sum();
+    assert!(num_node_deletes > 0);
+    assert!(aptos_db
+        .pruner_deletion_log(since..since)
+        .unwrap()
+        .is_empty());
+    let now = SystemTime::now();
+    assert!(aptos_db
+        .pruner_deletion_log(now..now + Duration::from_secs(3600))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_state_merkle_pruner_deletion_log_retention() {
+    let key = StateKey::raw(b"test_key1");
+    let tmp_dir = TempPath::new();
+    let aptos_db = AptosDB::new_for_test_no_cache(&tmp_dir);
+    let state_store = &aptos_db.state_store;
+    for i in 0..10 {
+        put_value_set(
+            state_store,
+            vec![(key.clone(), StateValue::from(vec![i as u8]))],
+            i, /* version */
+        );
+    }
+
+    let since = SystemTime::now();
+    let pruner = StateMerklePrunerManager::<StaleNodeIndexSchema>::new(
+        aptos_db.state_merkle_db(),
+        StateMerklePrunerConfig {
+            enable: true,
+            prune_window: 0,
+            batch_size: 100,
+        },
+        Some(Duration::ZERO), /* deletion_log_retention */
+    );
+    pruner.wake_and_wait_pruner(5 /* latest_version */).unwrap();
+    pruner.wake_and_wait_pruner(8 /* latest_version */).unwrap();
+
+    // Each pruner writes a record per batch, deleting the expired ones of its DB.
+    let records = aptos_db
+        .pruner_deletion_log(since..SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    assert!(!records.is_empty());
+    for record in &records {
+        assert!(record.version_range.start >= 5);
+    }
+}
+
+#[test]
+fn test_verify_no_dangling_after_prune() {
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let tmp_dir = TempPath::new();
+    let aptos_db = AptosDB::new_for_test_no_cache(&tmp_dir);
+    let state_store = &aptos_db.state_store;
+    let state_merkle_db = aptos_db.state_merkle_db();
+
+    // key2 is written once, so its leaf is referenced by the trees at all versions.
+    put_value_set(
+        state_store,
+        vec![
+            (key1.clone(), StateValue::from(vec![0])),
+            (key2, StateValue::from(vec![0])),
+        ],
+        0, /* version */
+    );
+    for i in 1..10 {
+        put_value_set(
+            state_store,
+            vec![(key1.clone(), StateValue::from(vec![i as u8]))],
+            i, /* version */
+        );
+    }
+
+    let pruner = create_state_merkle_pruner_manager(&state_merkle_db, 100);
+    pruner.wake_and_wait_pruner(5 /* latest_version */).unwrap();
+    state_merkle_db.verify_no_dangling_after_prune(5).unwrap();
+
+    // Drop the leaf of key2 as a buggy pruner would.
+    let mut iter = state_merkle_db
+        .metadata_db()
+        .iter::<JellyfishMerkleNodeSchema>()
+        .unwrap();
+    iter.seek_to_first();
+    let leaf_key = iter
+        .map(|item| item.unwrap())
+        .find(|(node_key, node)| node_key.version() == 0 && matches!(node, Node::Leaf(_)))
+        .map(|(node_key, _node)| node_key)
+        .unwrap();
+    state_merkle_db
+        .metadata_db()
+        .delete::<JellyfishMerkleNodeSchema>(&leaf_key)
+        .unwrap();
+    assert!(state_merkle_db.verify_no_dangling_after_prune(5).is_err());
+}
+
 proptest! {
     #![proptest_config(ProptestConfig::with_cases(10))]
 
@@ -319,12 +510,16 @@ fn verify_state_value_pruner(inputs: Vec<Vec<(StateKey, Option<StateValue>)>>) {
 
     let mut version = 0;
     let mut current_state_values = HashMap::new();
-    let pruner = StateKvPrunerManager::new(Arc::clone(&db.state_kv_db), LedgerPrunerConfig {
-        enable: true,
-        prune_window: 0,
-        batch_size: 1,
-        user_pruning_window_offset: 0,
-    });
+    let pruner = StateKvPrunerManager::new(
+        Arc::clone(&db.state_kv_db),
+        LedgerPrunerConfig {
+            enable: true,
+            prune_window: 0,
+            batch_size: 1,
+            user_pruning_window_offset: 0,
+        },
+        None, /* deletion_log_retention */
+    );
     for batch in inputs {
         update_store(store, batch.clone().into_iter(), version);
         for (k, v) in batch.iter() {
@@ -10,7 +10,9 @@ use crate::{
         write_set_db_column_families,
     },
     ledger_db::LedgerDb,
-    metrics::{OTHER_TIMERS_SECONDS, ROCKSDB_PROPERTIES, ROCKSDB_SHARD_PROPERTIES},
+    metrics::{
+        OTHER_TIMERS_SECONDS, ROCKSDB_DB_PROPERTIES, ROCKSDB_PROPERTIES, ROCKSDB_SHARD_PROPERTIES,
+    },
     state_kv_db::StateKvDb,
     state_merkle_db::StateMerkleDb,
 };
@@ -19,10 +21,11 @@ use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
 use aptos_metrics_core::TimerHelper;
 use aptos_schemadb::{ColumnFamilyName, DB};
+use aptos_storage_interface::Result as DbResult;
 use aptos_types::state_store::NUM_STATE_SHARDS;
 use once_cell::sync::Lazy;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     sync::{mpsc, Arc},
     thread,
     thread::JoinHandle,
@@ -99,69 +102,216 @@ fn set_shard_property(cf_name: ColumnFamilyName, db: &DB, shard: usize) -> Resul
     Ok(())
 }
 
-fn update_rocksdb_properties(
-    ledger_db: &LedgerDb,
-    state_merkle_db: &StateMerkleDb,
-    state_kv_db: &StateKvDb,
-) -> Result<()> {
-    let _timer = OTHER_TIMERS_SECONDS.timer_with(&["update_rocksdb_properties"]);
+const ACTUAL_DELAYED_WRITE_RATE: &str = "rocksdb.actual-delayed-write-rate";
+const IS_WRITE_STOPPED: &str = "rocksdb.is-write-stopped";
 
-    let enable_storage_sharding = state_kv_db.enabled_sharding();
+/// Write stall state of a single RocksDB instance.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DbWriteStallStatus {
+    /// The rate (bytes per second) writes are currently throttled to, 0 if not throttled.
+    pub actual_delayed_write_rate: u64,
+    /// Whether writes are stopped entirely, e.g. due to too many L0 files or pending compaction.
+    pub is_write_stopped: bool,
+}
 
-    if enable_storage_sharding {
-        for cf in ledger_metadata_db_column_families() {
-            set_property(cf, &ledger_db.metadata_db_arc())?;
-        }
+impl DbWriteStallStatus {
+    pub fn is_stalled(&self) -> bool {
+        self.actual_delayed_write_rate > 0 || self.is_write_stopped
+    }
+}
 
-        for cf in write_set_db_column_families() {
-            set_property(cf, ledger_db.write_set_db_raw())?;
-        }
+/// Write stall state of all the RocksDB instances of an `AptosDB`, keyed by the DB name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WriteStallStatus {
+    pub dbs: BTreeMap<String, DbWriteStallStatus>,
+}
 
-        for cf in transaction_info_db_column_families() {
-            set_property(cf, ledger_db.transaction_info_db_raw())?;
-        }
+impl WriteStallStatus {
+    /// Whether any of the DBs is delaying or stopping writes.
+    pub fn is_stalled(&self) -> bool {
+        self.dbs.values().any(DbWriteStallStatus::is_stalled)
+    }
+}
 
-        for cf in transaction_db_column_families() {
-            set_property(cf, ledger_db.transaction_db_raw())?;
+/// Reads the write stall state of the given DBs, updating the corresponding gauges on the way.
+pub(crate) fn get_write_stall_status(dbs: &[&DB]) -> DbResult<WriteStallStatus> {
+    let mut status = WriteStallStatus::default();
+    for db in dbs {
+        let actual_delayed_write_rate = db.get_db_property(ACTUAL_DELAYED_WRITE_RATE)?;
+        let is_write_stopped = db.get_db_property(IS_WRITE_STOPPED)?;
+        for (property_name, value) in [
+            (ACTUAL_DELAYED_WRITE_RATE, actual_delayed_write_rate),
+            (IS_WRITE_STOPPED, is_write_stopped),
+        ] {
+            ROCKSDB_DB_PROPERTIES
+                .with_label_values(&[db.name(), &ROCKSDB_PROPERTY_MAP[property_name]])
+                .set(value as i64);
         }
+        status
+            .dbs
+            .insert(db.name().to_string(), DbWriteStallStatus {
+                actual_delayed_write_rate,
+                is_write_stopped: is_write_stopped != 0,
+            });
+    }
+    Ok(status)
+}
 
-        for cf in event_db_column_families() {
-            set_property(cf, ledger_db.event_db_raw())?;
-        }
+/// Returns the column families whose properties are reported, with the DB each of them is in and
+/// the shard of that DB, if it's a shard. The hot state DBs are left out.
+fn reported_column_families<'a>(
+    ledger_db: &'a LedgerDb,
+    state_merkle_db: &'a StateMerkleDb,
+    state_kv_db: &'a StateKvDb,
+) -> Vec<(ColumnFamilyName, &'a DB, Option<usize>)> {
+    let mut cfs = Vec::new();
+    let enable_storage_sharding = state_kv_db.enabled_sharding();
 
-        for cf in transaction_accumulator_db_column_families() {
-            set_property(cf, ledger_db.transaction_accumulator_db_raw())?;
+    if enable_storage_sharding {
+        for (db_cfs, db) in [
+            (
+                ledger_metadata_db_column_families(),
+                ledger_db.metadata_db().db(),
+            ),
+            (write_set_db_column_families(), ledger_db.write_set_db_raw()),
+            (
+                transaction_info_db_column_families(),
+                ledger_db.transaction_info_db_raw(),
+            ),
+            (
+                transaction_db_column_families(),
+                ledger_db.transaction_db_raw(),
+            ),
+            (event_db_column_families(), ledger_db.event_db_raw()),
+            (
+                transaction_accumulator_db_column_families(),
+                ledger_db.transaction_accumulator_db_raw(),
+            ),
+        ] {
+            cfs.extend(db_cfs.into_iter().map(|cf| (cf, db, None)));
         }
 
         if !state_kv_db.enabled_sharding() {
             for cf in state_kv_db_column_families() {
-                set_property(cf, state_kv_db.metadata_db())?;
+                cfs.push((cf, state_kv_db.metadata_db(), None));
             }
         } else {
             for cf in state_kv_db_new_key_column_families() {
-                set_property(cf, state_kv_db.metadata_db())?;
+                cfs.push((cf, state_kv_db.metadata_db(), None));
                 for shard in 0..NUM_STATE_SHARDS {
-                    set_shard_property(cf, state_kv_db.db_shard(shard), shard)?;
+                    cfs.push((cf, state_kv_db.db_shard(shard), Some(shard)));
                 }
             }
         }
     } else {
         for cf in ledger_db_column_families() {
-            set_property(cf, &ledger_db.metadata_db_arc())?;
+            cfs.push((cf, ledger_db.metadata_db().db(), None));
         }
     }
 
     for cf_name in state_merkle_db_column_families() {
-        set_property(cf_name, state_merkle_db.metadata_db())?;
+        cfs.push((cf_name, state_merkle_db.metadata_db(), None));
         if state_merkle_db.sharding_enabled() {
             for shard in 0..NUM_STATE_SHARDS {
-                set_shard_property(cf_name, state_merkle_db.db_shard(shard), shard)?;
+                cfs.push((cf_name, state_merkle_db.db_shard(shard), Some(shard)));
             }
         }
     }
+    cfs
+}
+
+fn update_rocksdb_properties(
+    ledger_db: &LedgerDb,
+    state_merkle_db: &StateMerkleDb,
+    state_kv_db: &StateKvDb,
+) -> Result<()> {
+    let _timer = OTHER_TIMERS_SECONDS.timer_with(&["update_rocksdb_properties"]);
+
+    for (cf, db, shard) in reported_column_families(ledger_db, state_merkle_db, state_kv_db) {
+        match shard {
+            Some(shard) => set_shard_property(cf, db, shard)?,
+            None => set_property(cf, db)?,
+        }
+    }
+
+    let dbs = ledger_db
+        .all_dbs()
+        .into_iter()
+        .chain(state_kv_db.all_dbs())
+        .chain(state_merkle_db.all_dbs())
+        .collect::<Vec<_>>();
+    get_write_stall_status(&dbs)?;
     Ok(())
 }
 
+const ESTIMATE_PENDING_COMPACTION_BYTES: &str = "rocksdb.estimate-pending-compaction-bytes";
+const COMPACTION_PENDING: &str = "rocksdb.compaction-pending";
+const NUM_RUNNING_COMPACTIONS: &str = "rocksdb.num-running-compactions";
+
+/// Compaction state of a column family in one RocksDB instance.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CfCompactionStats {
+    pub db_name: String,
+    pub cf_name: String,
+    /// Bytes RocksDB estimates compactions need to rewrite to get all the levels under their
+    /// target sizes.
+    pub estimate_pending_compaction_bytes: u64,
+    /// Whether at least one compaction is pending.
+    pub compaction_pending: bool,
+    /// Compactions running in the DB. RocksDB only tracks this per DB, so it's the same for all
+    /// the column families of a DB.
+    pub num_running_compactions: u64,
+    /// Total size in bytes of the live SST files at each level, from L0 up to the last non-empty
+    /// level.
+    pub level_sizes: Vec<u64>,
+}
+
+/// Reads the compaction state of all the column families whose properties are reported.
+pub(crate) fn get_compaction_stats(
+    ledger_db: &LedgerDb,
+    state_merkle_db: &StateMerkleDb,
+    state_kv_db: &StateKvDb,
+) -> DbResult<Vec<CfCompactionStats>> {
+    let _timer = OTHER_TIMERS_SECONDS.timer_with(&["get_compaction_stats"]);
+
+    // Level sizes by DB name and then CF name, from a single listing of the files of each DB.
+    let mut level_sizes_by_db: HashMap<String, HashMap<String, Vec<u64>>> = HashMap::new();
+    let mut stats = Vec::new();
+    for (cf, db, _shard) in reported_column_families(ledger_db, state_merkle_db, state_kv_db) {
+        if skip_reporting_cf(cf) {
+            continue;
+        }
+        if !level_sizes_by_db.contains_key(db.name()) {
+            let mut level_sizes_by_cf: HashMap<String, Vec<u64>> = HashMap::new();
+            for file in db.live_files()? {
+                let level_sizes = level_sizes_by_cf
+                    .entry(file.column_family_name)
+                    .or_default();
+                let level = file.level.max(0) as usize;
+                if level_sizes.len() <= level {
+                    level_sizes.resize(level + 1, 0);
+                }
+                level_sizes[level] += file.size as u64;
+            }
+            level_sizes_by_db.insert(db.name().to_string(), level_sizes_by_cf);
+        }
+
+        stats.push(CfCompactionStats {
+            db_name: db.name().to_string(),
+            cf_name: cf.to_string(),
+            estimate_pending_compaction_bytes: db
+                .get_property(cf, ESTIMATE_PENDING_COMPACTION_BYTES)?,
+            compaction_pending: db.get_property(cf, COMPACTION_PENDING)? != 0,
+            num_running_compactions: db.get_property(cf, NUM_RUNNING_COMPACTIONS)?,
+            level_sizes: level_sizes_by_db[db.name()]
+                .get(cf)
+                .cloned()
+                .unwrap_or_default(),
+        });
+    }
+    Ok(stats)
+}
+
 #[derive(Debug)]
 pub(crate) struct RocksdbPropertyReporter {
     sender: Mutex<mpsc::Sender<()>>,
@@ -9,14 +9,17 @@
 //! ```
 //!
 
-use crate::schema::DB_METADATA_CF_NAME;
+use crate::{
+    pruner::{DeletionRecord, PrunerName, RecordTimestamp},
+    schema::DB_METADATA_CF_NAME,
+};
 use anyhow::Result;
 use aptos_db_indexer_schemas::metadata::StateSnapshotProgress;
 use aptos_schemadb::{
     define_schema,
     schema::{KeyCodec, ValueCodec},
 };
-use aptos_types::transaction::Version;
+use aptos_types::{state_store::NUM_STATE_SHARDS, transaction::Version};
 use serde::{Deserialize, Serialize};
 
 type ShardId = usize;
@@ -26,6 +29,10 @@ type ShardId = usize;
 pub(crate) enum DbMetadataValue {
     Version(Version),
     StateSnapshotProgress(StateSnapshotProgress),
+    ShardConfig(ShardConfig),
+    ValueEncryption(bool),
+    DeletionRecord(DeletionRecord),
+    StateKvRemapProgress(StateKvRemapProgress),
 }
 
 impl DbMetadataValue {
@@ -42,6 +49,71 @@ impl DbMetadataValue {
             _ => unreachable!("expected KeyHashAndUsage, got {:?}", self),
         }
     }
+
+    pub fn expect_shard_config(self) -> ShardConfig {
+        match self {
+            Self::ShardConfig(shard_config) => shard_config,
+            _ => unreachable!("expected ShardConfig, got {:?}", self),
+        }
+    }
+
+    pub fn expect_value_encryption(self) -> bool {
+        match self {
+            Self::ValueEncryption(encrypted) => encrypted,
+            _ => unreachable!("expected ValueEncryption, got {:?}", self),
+        }
+    }
+
+    pub fn expect_deletion_record(self) -> DeletionRecord {
+        match self {
+            Self::DeletionRecord(record) => record,
+            _ => unreachable!("expected DeletionRecord, got {:?}", self),
+        }
+    }
+
+    pub fn expect_state_kv_remap_progress(self) -> StateKvRemapProgress {
+        match self {
+            Self::StateKvRemapProgress(progress) => progress,
+            _ => unreachable!("expected StateKvRemapProgress, got {:?}", self),
+        }
+    }
+}
+
+/// How state keys are routed to the shards, which can't change over the life of a DB without
+/// misrouting the data written before.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(any(test, feature = "fuzzing"), derive(proptest_derive::Arbitrary))]
+pub(crate) struct ShardConfig {
+    pub enable_sharding: bool,
+    pub num_shards: usize,
+    /// Identifies the function computing the shard of a state key.
+    pub hash_function_id: u8,
+}
+
+impl ShardConfig {
+    /// The shard of a state key is the first nibble of its hash, see `StateKey::get_shard_id`.
+    pub const KEY_HASH_FIRST_NIBBLE: u8 = 0;
+
+    pub fn new(enable_sharding: bool) -> Self {
+        Self {
+            enable_sharding,
+            num_shards: NUM_STATE_SHARDS,
+            hash_function_id: Self::KEY_HASH_FIRST_NIBBLE,
+        }
+    }
+}
+
+/// Progress of `StateKvDb::remap_keys` on a shard, recorded once the SST files of the remapped
+/// entries of all the shards are written.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[cfg_attr(any(test, feature = "fuzzing"), derive(proptest_derive::Arbitrary))]
+pub(crate) struct StateKvRemapProgress {
+    /// Number of state values in the shard before the remapping.
+    pub num_values: usize,
+    /// Number of stale state value indices in the shard before the remapping.
+    pub num_stale_indices: usize,
+    /// Whether the SST files of the remapped entries going to the shard have been ingested.
+    pub ingested: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -69,6 +141,12 @@ pub enum DbMetadataKey {
     StateMerkleShardRestoreProgress(ShardId, Version),
     TransactionAuxiliaryDataPrunerProgress,
     PersistedAuxiliaryInfoPrunerProgress,
+    ShardConfig,
+    EventRetentionProgress,
+    ValueEncryption,
+    /// Keyed by time first, so that the records can be read by time range.
+    PrunerDeletionRecord(RecordTimestamp, PrunerName),
+    StateKvRemapProgress(ShardId),
 }
 
 define_schema!(
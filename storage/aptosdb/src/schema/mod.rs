@@ -33,7 +33,8 @@ pub(crate) mod version_data;
 pub(crate) mod write_set;
 
 use anyhow::{ensure, Result};
-use aptos_schemadb::ColumnFamilyName;
+use aptos_schemadb::{schema::Schema, ColumnFamilyName};
+use std::any::type_name;
 
 pub const BLOCK_BY_VERSION_CF_NAME: ColumnFamilyName = "block_by_version";
 pub const BLOCK_INFO_CF_NAME: ColumnFamilyName = "block_info";
@@ -68,6 +69,67 @@ pub const TRANSACTION_INFO_CF_NAME: ColumnFamilyName = "transaction_info";
 pub const VERSION_DATA_CF_NAME: ColumnFamilyName = "version_data";
 pub const WRITE_SET_CF_NAME: ColumnFamilyName = "write_set";
 
+/// Describes a schema, see [`schema_registry`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SchemaInfo {
+    /// Name of the schema type, e.g. `TransactionSchema`.
+    pub name: &'static str,
+    pub cf_name: ColumnFamilyName,
+    /// Type names of the key and value as given by `std::any::type_name`, which is meant for
+    /// diagnostics, so the exact format may change with the compiler version.
+    pub key_type: &'static str,
+    pub value_type: &'static str,
+}
+
+impl SchemaInfo {
+    fn new<S: Schema>(name: &'static str) -> Self {
+        Self {
+            name,
+            cf_name: S::COLUMN_FAMILY_NAME,
+            key_type: type_name::<S::Key>(),
+            value_type: type_name::<S::Value>(),
+        }
+    }
+}
+
+macro_rules! schema_infos {
+    ($($module:ident::$schema:ident),* $(,)?) => {
+        vec![$(SchemaInfo::new::<$module::$schema>(stringify!($schema))),*]
+    };
+}
+
+/// Returns the info of every schema defined in this module, e.g. for external decoders to check
+/// that they support all of them before scanning a DB.
+pub fn schema_registry() -> Vec<SchemaInfo> {
+    schema_infos![
+        block_by_version::BlockByVersionSchema,
+        block_info::BlockInfoSchema,
+        db_metadata::DbMetadataSchema,
+        epoch_by_version::EpochByVersionSchema,
+        event::EventSchema,
+        event_accumulator::EventAccumulatorSchema,
+        hot_state_value_by_key_hash::HotStateValueByKeyHashSchema,
+        jellyfish_merkle_node::JellyfishMerkleNodeSchema,
+        ledger_info::LedgerInfoSchema,
+        persisted_auxiliary_info::PersistedAuxiliaryInfoSchema,
+        stale_node_index::StaleNodeIndexSchema,
+        stale_node_index_cross_epoch::StaleNodeIndexCrossEpochSchema,
+        stale_state_value_index::StaleStateValueIndexSchema,
+        stale_state_value_index_by_key_hash::StaleStateValueIndexByKeyHashSchema,
+        state_value::StateValueSchema,
+        state_value_by_key_hash::StateValueByKeyHashSchema,
+        transaction::TransactionSchema,
+        transaction_accumulator::TransactionAccumulatorSchema,
+        transaction_accumulator_root_hash::TransactionAccumulatorRootHashSchema,
+        transaction_auxiliary_data::TransactionAuxiliaryDataSchema,
+        transaction_by_hash::TransactionByHashSchema,
+        transaction_info::TransactionInfoSchema,
+        transaction_summaries_by_account::TransactionSummariesByAccountSchema,
+        version_data::VersionDataSchema,
+        write_set::WriteSetSchema,
+    ]
+}
+
 fn ensure_slice_len_eq(data: &[u8], len: usize) -> Result<()> {
     ensure!(
         data.len() == len,
@@ -52,7 +52,7 @@ impl KeyCodec<StateValueSchema> for Key {
 
         ensure_slice_len_gt(data, VERSION_SIZE)?;
         let state_key_len = data.len() - VERSION_SIZE;
-        let state_key: StateKey = StateKey::decode(&data[..state_key_len])?;
+        let state_key = StateKey::decode_stored(data)?;
         let version = !(&data[state_key_len..]).read_u64::<BigEndian>()?;
         Ok((state_key, version))
     }
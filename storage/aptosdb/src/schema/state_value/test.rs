@@ -14,6 +14,16 @@ proptest! {
     ) {
         assert_encode_decode::<StateValueSchema>(&(state_key, version), &v);
     }
+
+    #[test]
+    fn test_decode_stored(
+        state_key in any::<StateKey>(),
+        version in any::<Version>(),
+    ) {
+        let encoded = <Key as KeyCodec<StateValueSchema>>::encode_key(&(state_key.clone(), version))
+            .unwrap();
+        prop_assert_eq!(StateKey::decode_stored(&encoded).unwrap(), state_key);
+    }
 }
 
 test_no_panic_decoding!(StateValueSchema);
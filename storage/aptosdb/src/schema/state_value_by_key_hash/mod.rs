@@ -15,7 +15,7 @@
 //! ```
 
 use crate::schema::{ensure_slice_len_eq, STATE_VALUE_BY_KEY_HASH_CF_NAME};
-use anyhow::Result;
+use anyhow::{anyhow, bail, ensure, Result};
 use aptos_crypto::HashValue;
 use aptos_schemadb::{
     define_pub_schema,
@@ -62,5 +62,47 @@ impl ValueCodec<StateValueByKeyHashSchema> for Option<StateValue> {
     }
 }
 
+/// Copies the bytes of the state value in an encoded `Option<StateValue>` into `buf`, without
+/// decoding the rest of it, e.g. the metadata. Returns `false`, leaving `buf` empty, if it's `None`.
+///
+/// This relies on the BCS layout of the value: the option tag, then the variant of the persisted
+/// state value, both variants starting with the bytes, i.e. their ULEB128 length then the bytes.
+pub(crate) fn decode_value_bytes_into(data: &[u8], buf: &mut Vec<u8>) -> Result<bool> {
+    buf.clear();
+    let (&option_tag, rest) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("Empty encoded state value."))?;
+    match option_tag {
+        0 => return Ok(false),
+        1 => (),
+        tag => bail!("Bad option tag {} of encoded state value.", tag),
+    }
+    let (&variant, rest) = rest
+        .split_first()
+        .ok_or_else(|| anyhow!("Encoded state value truncated before its variant."))?;
+    ensure!(variant <= 1, "Unknown state value variant {}.", variant);
+    let (len, rest) = read_uleb128_len(rest)?;
+    ensure!(
+        rest.len() >= len,
+        "Encoded state value truncated, expecting {} bytes, got {}.",
+        len,
+        rest.len(),
+    );
+    buf.extend_from_slice(&rest[..len]);
+    Ok(true)
+}
+
+/// Reads a ULEB128 encoded length, which BCS limits to `u32`, i.e. up to 5 bytes.
+fn read_uleb128_len(data: &[u8]) -> Result<(usize, &[u8])> {
+    let mut len = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(5) {
+        len |= u64::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((len as usize, &data[i + 1..]));
+        }
+    }
+    bail!("Bad ULEB128 length in encoded state value.")
+}
+
 #[cfg(test)]
 mod test;
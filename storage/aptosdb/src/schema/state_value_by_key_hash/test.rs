@@ -15,6 +15,17 @@ proptest! {
     ) {
         assert_encode_decode::<StateValueByKeyHashSchema>(&(state_key, version), &v);
     }
+
+    #[test]
+    fn test_decode_value_bytes_into(v in any::<Option<StateValue>>()) {
+        let encoded =
+            <Option<StateValue> as ValueCodec<StateValueByKeyHashSchema>>::encode_value(&v)
+                .unwrap();
+        // A reused buffer is overwritten.
+        let mut buf = vec![0xFF; 10];
+        prop_assert_eq!(decode_value_bytes_into(&encoded, &mut buf).unwrap(), v.is_some());
+        prop_assert_eq!(&buf[..], v.as_ref().map_or(&[][..], |v| &v.bytes()[..]));
+    }
 }
 
 test_no_panic_decoding!(StateValueByKeyHashSchema);
@@ -4,43 +4,84 @@
 #![forbid(unsafe_code)]
 
 use crate::{
-    db_options::{gen_hot_state_kv_shard_cfds, gen_state_kv_shard_cfds},
+    db_options::{
+        gen_hot_state_kv_shard_cfds, gen_state_kv_cf_opts, gen_state_kv_shard_cfds,
+        state_kv_db_new_key_column_families,
+    },
     metrics::OTHER_TIMERS_SECONDS,
     schema::{
-        db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+        db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue, StateKvRemapProgress},
+        stale_state_value_index::StaleStateValueIndexSchema,
+        stale_state_value_index_by_key_hash::StaleStateValueIndexByKeyHashSchema,
         state_value::StateValueSchema,
-        state_value_by_key_hash::StateValueByKeyHashSchema,
+        state_value_by_key_hash::{decode_value_bytes_into, StateValueByKeyHashSchema},
+        HOT_STATE_VALUE_BY_KEY_HASH_CF_NAME, STATE_VALUE_BY_KEY_HASH_CF_NAME,
     },
     utils::{
+        dir_paths_with_wal, set_wal_dir,
         truncation_helper::{get_state_kv_commit_progress, truncate_state_kv_db_shards},
         ShardedStateKvSchemaBatch,
     },
 };
 use aptos_config::config::{RocksdbConfig, RocksdbConfigs, StorageDirPaths};
-use aptos_crypto::hash::CryptoHash;
+use aptos_crypto::{hash::CryptoHash, HashValue};
 use aptos_experimental_runtimes::thread_manager::THREAD_MANAGER;
 use aptos_logger::prelude::info;
 use aptos_metrics_core::TimerHelper;
 use aptos_rocksdb_options::gen_rocksdb_options;
 use aptos_schemadb::{
     batch::{SchemaBatch, WriteBatch},
-    Cache, Env, ReadOptions, DB,
+    schema::Schema,
+    value_cipher::ValueCipher,
+    Cache, ColumnFamilyName, Env, Options, ReadOptions, WriteBufferManager, DB,
 };
-use aptos_storage_interface::Result;
+use aptos_storage_interface::{db_ensure as ensure, AptosDbError, Result};
 use aptos_types::{
-    state_store::{state_key::StateKey, state_value::StateValue, NUM_STATE_SHARDS},
+    state_store::{
+        state_key::StateKey,
+        state_value::{StaleStateValueByKeyHashIndex, StaleStateValueIndex, StateValue},
+        NUM_STATE_SHARDS,
+    },
     transaction::Version,
 };
 use arr_macro::arr;
-use rayon::prelude::*;
+use rayon::{prelude::*, ThreadPool};
 use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
     sync::Arc,
 };
+use tokio::sync::oneshot;
 
 pub const STATE_KV_DB_FOLDER_NAME: &str = "state_kv_db";
 pub const STATE_KV_METADATA_DB_NAME: &str = "state_kv_metadata_db";
 
+/// Share of deletions in an SST file above which `StateKvDb::compact_tombstone_ranges` compacts
+/// its key range.
+const TOMBSTONE_RATIO_TO_COMPACT: f64 = 0.5;
+
+/// Max number of entries in each SST file, and of key hash pairs in each sorted run, written by
+/// `StateKvDb::remap_keys`, which bounds the memory it takes.
+const REMAP_SST_FILE_MAX_ENTRIES: usize = 1 << 20;
+
+/// Size of a pair of key hashes in the files written by `StateKvDb::remap_keys`.
+const KEY_HASH_PAIR_SIZE: usize = 2 * HashValue::LENGTH;
+
+/// Statistics on byte-identical state values, see [`StateKvDb::value_dedup_stats`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DedupStats {
+    /// Number of keys with a value at the version.
+    pub total_values: u64,
+    /// Number of distinct contents among these values.
+    pub distinct_values: u64,
+    /// Bytes taken by the values which are copies of another one, i.e. what storing each distinct
+    /// value only once would save.
+    pub duplicate_bytes: u64,
+}
+
 pub struct StateKvDb {
     state_kv_metadata_db: Arc<DB>,
     state_kv_db_shards: [Arc<DB>; NUM_STATE_SHARDS],
@@ -48,6 +89,10 @@ pub struct StateKvDb {
     #[allow(dead_code)] // TODO(HotState): can remove later.
     hot_state_kv_db_shards: Option<[Arc<DB>; NUM_STATE_SHARDS]>,
     enabled_sharding: bool,
+    // See `RocksdbConfig::flush_on_commit`.
+    flush_on_commit: bool,
+    // The config the DBs are opened with, see `Self::sst_cf_opts`.
+    rocksdb_config: RocksdbConfig,
 }
 
 impl StateKvDb {
@@ -56,6 +101,7 @@ impl StateKvDb {
         rocksdb_configs: RocksdbConfigs,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
         ledger_db: Arc<DB>,
     ) -> Result<Self> {
@@ -67,6 +113,8 @@ impl StateKvDb {
                 state_kv_db_shards: arr![Arc::clone(&ledger_db); 16],
                 hot_state_kv_db_shards: None,
                 enabled_sharding: false,
+                flush_on_commit: false,
+                rocksdb_config: rocksdb_configs.ledger_db_config,
             });
         }
 
@@ -75,6 +123,7 @@ impl StateKvDb {
             rocksdb_configs.state_kv_db_config,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
         )
     }
@@ -84,6 +133,7 @@ impl StateKvDb {
         state_kv_db_config: RocksdbConfig,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
     ) -> Result<Self> {
         let state_kv_metadata_db_path =
@@ -92,9 +142,11 @@ impl StateKvDb {
         let state_kv_metadata_db = Arc::new(Self::open_db(
             state_kv_metadata_db_path.clone(),
             STATE_KV_METADATA_DB_NAME,
+            db_paths.wal_root_path(),
             &state_kv_db_config,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
             /* is_hot = */ false,
         )?);
@@ -111,9 +163,11 @@ impl StateKvDb {
                 let db = Self::open_shard(
                     shard_root_path,
                     shard_id,
+                    db_paths.wal_root_path(),
                     &state_kv_db_config,
                     env,
                     block_cache,
+                    write_buffer_manager,
                     readonly,
                     /* is_hot = */ false,
                 )
@@ -137,9 +191,11 @@ impl StateKvDb {
                         let db = Self::open_shard(
                             shard_root_path,
                             shard_id,
+                            db_paths.wal_root_path(),
                             &state_kv_db_config,
                             env,
                             block_cache,
+                            write_buffer_manager,
                             readonly,
                             /* is_hot = */ true,
                         )
@@ -159,6 +215,8 @@ impl StateKvDb {
             state_kv_db_shards,
             hot_state_kv_db_shards,
             enabled_sharding: true,
+            flush_on_commit: state_kv_db_config.flush_on_commit,
+            rocksdb_config: state_kv_db_config,
         };
 
         if !readonly {
@@ -179,11 +237,28 @@ impl StateKvDb {
         version: Version,
         state_kv_metadata_batch: Option<SchemaBatch>,
         sharded_state_kv_batches: ShardedStateKvSchemaBatch,
+    ) -> Result<()> {
+        self.commit_in_pool(
+            version,
+            state_kv_metadata_batch,
+            sharded_state_kv_batches,
+            THREAD_MANAGER.get_io_pool(),
+        )
+    }
+
+    /// Same as [`Self::commit`], but the shards are committed in `pool`, so that its number of
+    /// threads bounds how many shards are written concurrently.
+    pub(crate) fn commit_in_pool(
+        &self,
+        version: Version,
+        state_kv_metadata_batch: Option<SchemaBatch>,
+        sharded_state_kv_batches: ShardedStateKvSchemaBatch,
+        pool: &ThreadPool,
     ) -> Result<()> {
         let _timer = OTHER_TIMERS_SECONDS.timer_with(&["state_kv_db__commit"]);
         {
             let _timer = OTHER_TIMERS_SECONDS.timer_with(&["state_kv_db__commit_shards"]);
-            THREAD_MANAGER.get_io_pool().scope(|s| {
+            pool.scope(|s| {
                 let mut batches = sharded_state_kv_batches.into_iter();
                 for shard_id in 0..NUM_STATE_SHARDS {
                     let state_kv_batch = batches
@@ -204,7 +279,17 @@ impl StateKvDb {
             self.state_kv_metadata_db.write_schemas(batch)?;
         }
 
-        self.write_progress(version)
+        self.write_progress(version)?;
+        if self.flush_on_commit {
+            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["state_kv_db__flush_on_commit"]);
+            let cf_names = state_kv_db_new_key_column_families();
+            pool.install(|| {
+                self.all_dbs()
+                    .into_par_iter()
+                    .try_for_each(|db| db.flush_cfs(&cf_names))
+            })?;
+        }
+        Ok(())
     }
 
     pub(crate) fn write_progress(&self, version: Version) -> Result<()> {
@@ -223,14 +308,16 @@ impl StateKvDb {
 
     pub(crate) fn create_checkpoint(
         db_root_path: impl AsRef<Path>,
+        wal_root_path: Option<&Path>,
         cp_root_path: impl AsRef<Path>,
     ) -> Result<()> {
         // TODO(grao): Support path override here.
         let state_kv_db = Self::open_sharded(
-            &StorageDirPaths::from_path(db_root_path),
+            &dir_paths_with_wal(db_root_path, wal_root_path),
             RocksdbConfig::default(),
             None,
             None,
+            None,
             false,
         )?;
         let cp_state_kv_db_path = cp_root_path.as_ref().join(STATE_KV_DB_FOLDER_NAME);
@@ -274,6 +361,45 @@ impl StateKvDb {
         Arc::clone(&self.state_kv_db_shards[shard_id])
     }
 
+    /// Returns all the underlying RocksDB instances, each of them exactly once. Empty if sharding
+    /// is not enabled, in which case the state K/V data lives in the ledger DB.
+    pub(crate) fn all_dbs(&self) -> Vec<&DB> {
+        if !self.enabled_sharding {
+            return vec![];
+        }
+        std::iter::once(self.metadata_db())
+            .chain(self.state_kv_db_shards.iter().map(|db| db.as_ref()))
+            .collect()
+    }
+
+    /// Like `all_dbs()`, with the hot state shards.
+    pub(crate) fn all_dbs_with_hot_state(&self) -> Vec<&DB> {
+        let mut dbs = self.all_dbs();
+        dbs.extend(
+            self.hot_state_kv_db_shards
+                .iter()
+                .flatten()
+                .map(|db| db.as_ref()),
+        );
+        dbs
+    }
+
+    /// Encrypts the state values in the shards, hot state ones included, with `value_cipher` from
+    /// now on. Without sharding, the state values are in the ledger DB instead.
+    pub(crate) fn set_value_cipher(&self, value_cipher: Arc<dyn ValueCipher>) -> Result<()> {
+        ensure!(
+            self.enabled_sharding,
+            "The state values are in the ledger DB without sharding."
+        );
+        for db in &self.state_kv_db_shards {
+            db.set_value_cipher(value_cipher.clone(), &[STATE_VALUE_BY_KEY_HASH_CF_NAME])?;
+        }
+        for db in self.hot_state_kv_db_shards.iter().flatten() {
+            db.set_value_cipher(value_cipher.clone(), &[HOT_STATE_VALUE_BY_KEY_HASH_CF_NAME])?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn enabled_sharding(&self) -> bool {
         self.enabled_sharding
     }
@@ -290,6 +416,12 @@ impl StateKvDb {
         }
     }
 
+    /// Returns the options the column family `cf_name` is opened with, for the SST files written
+    /// to be ingested into it.
+    fn sst_cf_opts(&self, cf_name: ColumnFamilyName) -> Options {
+        gen_state_kv_cf_opts(&self.rocksdb_config, cf_name)
+    }
+
     pub(crate) fn commit_single_shard(
         &self,
         version: Version,
@@ -303,12 +435,43 @@ impl StateKvDb {
         self.state_kv_db_shards[shard_id].write_schemas(batch)
     }
 
+    /// Writes the given state values of a single shard into an SST file at `sst_path` and ingests
+    /// it into the shard, bypassing the memtable and WAL, then bumps the shard commit progress.
+    /// Only meant for bulk loading during restore, and only supported with sharding enabled.
+    pub(crate) fn ingest_single_shard(
+        &self,
+        version: Version,
+        shard_id: usize,
+        values: &[((HashValue, Version), Option<StateValue>)],
+        sst_path: &Path,
+    ) -> Result<()> {
+        ensure!(
+            self.enabled_sharding,
+            "Ingesting SST files requires storage sharding."
+        );
+        let db = self.db_shard(shard_id);
+        if !values.is_empty() {
+            db.write_sst_file::<StateValueByKeyHashSchema>(
+                sst_path,
+                &self.sst_cf_opts(STATE_VALUE_BY_KEY_HASH_CF_NAME),
+                values.iter().map(|(key, value)| (key, value)),
+            )?;
+            db.ingest_sst_files::<StateValueByKeyHashSchema>(vec![sst_path.to_path_buf()])?;
+        }
+        db.put::<DbMetadataSchema>(
+            &DbMetadataKey::StateKvShardCommitProgress(shard_id),
+            &DbMetadataValue::Version(version),
+        )
+    }
+
     fn open_shard<P: AsRef<Path>>(
         db_root_path: P,
         shard_id: usize,
+        wal_root_path: Option<&Path>,
         state_kv_db_config: &RocksdbConfig,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
         is_hot: bool,
     ) -> Result<DB> {
@@ -320,9 +483,11 @@ impl StateKvDb {
         Self::open_db(
             Self::db_shard_path(db_root_path, shard_id, is_hot),
             &db_name,
+            wal_root_path,
             state_kv_db_config,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
             is_hot,
         )
@@ -331,9 +496,11 @@ impl StateKvDb {
     fn open_db(
         path: PathBuf,
         name: &str,
+        wal_root_path: Option<&Path>,
         state_kv_db_config: &RocksdbConfig,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
         is_hot: bool,
     ) -> Result<DB> {
@@ -342,7 +509,11 @@ impl StateKvDb {
         } else {
             DB::open_cf
         };
-        let rocksdb_opts = gen_rocksdb_options(state_kv_db_config, env, readonly);
+        let mut rocksdb_opts = gen_rocksdb_options(state_kv_db_config, env, readonly);
+        if let Some(write_buffer_manager) = write_buffer_manager {
+            rocksdb_opts.set_write_buffer_manager(write_buffer_manager);
+        }
+        set_wal_dir(&mut rocksdb_opts, wal_root_path, name, readonly)?;
         let cfds = if is_hot {
             gen_hot_state_kv_shard_cfds
         } else {
@@ -400,4 +571,1094 @@ impl StateKvDb {
                 .and_then(|((_, version), value_opt)| value_opt.map(|value| (version, value))))
         }
     }
+
+    /// Returns the writes to `state_key` at versions in `[start_version, end_version)`, oldest
+    /// first, a deletion being `None`.
+    pub(crate) fn get_state_value_history(
+        &self,
+        state_key: &StateKey,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<Vec<(Version, Option<StateValue>)>> {
+        let Some(last_version) = end_version.checked_sub(1) else {
+            return Ok(Vec::new());
+        };
+        let mut read_opts = ReadOptions::default();
+        // We want the iteration to stop once the state_key changes.
+        read_opts.set_prefix_same_as_start(true);
+
+        // Versions of a key sort from the latest to the oldest.
+        let mut history = Vec::new();
+        if !self.enabled_sharding() {
+            let mut iter = self
+                .db_shard(state_key.get_shard_id())
+                .iter_with_opts::<StateValueSchema>(read_opts)?;
+            iter.seek(&(state_key.clone(), last_version))?;
+            for res in iter {
+                let ((_, version), value_opt) = res?;
+                if version < start_version {
+                    break;
+                }
+                history.push((version, value_opt));
+            }
+        } else {
+            let mut iter = self
+                .db_shard(state_key.get_shard_id())
+                .iter_with_opts::<StateValueByKeyHashSchema>(read_opts)?;
+            iter.seek(&(state_key.hash(), last_version))?;
+            for res in iter {
+                let ((_, version), value_opt) = res?;
+                if version < start_version {
+                    break;
+                }
+                history.push((version, value_opt));
+            }
+        }
+        history.reverse();
+        Ok(history)
+    }
+
+    /// Returns, for each version in `[start_version, end_version)`, the number of keys written at
+    /// it which had a value right before, according to the stale state value index: each such
+    /// write makes the previous value stale since its version. The tombstones, which the index
+    /// also holds for the deletions, are not counted.
+    pub(crate) fn num_values_overwritten_by_version(
+        &self,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<Vec<u64>> {
+        let num_versions = end_version.saturating_sub(start_version) as usize;
+        let mut counts = vec![0; num_versions];
+        let mut count = |stale_since_version: Version, version: Version| {
+            if version < stale_since_version {
+                counts[(stale_since_version - start_version) as usize] += 1;
+            }
+        };
+
+        // The indices are ordered by the version they are stale since.
+        if !self.enabled_sharding() {
+            let mut iter = self.db_shard(0).iter::<StaleStateValueIndexSchema>()?;
+            iter.seek(&start_version)?;
+            for res in iter {
+                let (index, _) = res?;
+                if index.stale_since_version >= end_version {
+                    break;
+                }
+                count(index.stale_since_version, index.version);
+            }
+        } else {
+            for shard_id in 0..self.num_shards() {
+                let mut iter = self
+                    .db_shard(shard_id)
+                    .iter::<StaleStateValueIndexByKeyHashSchema>()?;
+                iter.seek(&start_version)?;
+                for res in iter {
+                    let (index, _) = res?;
+                    if index.stale_since_version >= end_version {
+                        break;
+                    }
+                    count(index.stale_since_version, index.version);
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Scans all the state values as of `version` and tallies the byte-identical ones, hashing
+    /// their contents.
+    ///
+    /// This reads every version of every key in the DB and keeps a hash per distinct value in
+    /// memory, so it's meant for offline analysis rather than a live node.
+    pub fn value_dedup_stats(&self, version: Version) -> Result<DedupStats> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["value_dedup_stats"]);
+
+        let mut stats = DedupStats::default();
+        let mut seen_value_hashes = HashSet::new();
+        let mut tally = |value: StateValue| {
+            stats.total_values += 1;
+            if seen_value_hashes.insert(HashValue::sha3_256_of(value.bytes())) {
+                stats.distinct_values += 1;
+            } else {
+                stats.duplicate_bytes += value.size() as u64;
+            }
+        };
+
+        // The versions of a key are ordered from the latest, so the first one at or before
+        // `version` is the value of the key at `version`.
+        if !self.enabled_sharding() {
+            // All the shards are the same DB.
+            let mut iter = self.metadata_db().iter::<StateValueSchema>()?;
+            iter.seek_to_first();
+            let mut last_state_key = None;
+            for res in iter {
+                let ((state_key, value_version), value_opt) = res?;
+                if value_version > version || last_state_key.as_ref() == Some(&state_key) {
+                    continue;
+                }
+                if let Some(value) = value_opt {
+                    tally(value);
+                }
+                last_state_key = Some(state_key);
+            }
+        } else {
+            for shard_id in 0..self.num_shards() {
+                let mut iter = self
+                    .db_shard(shard_id)
+                    .iter::<StateValueByKeyHashSchema>()?;
+                iter.seek_to_first();
+                let mut last_key_hash = None;
+                for res in iter {
+                    let ((key_hash, value_version), value_opt) = res?;
+                    if value_version > version || last_key_hash == Some(key_hash) {
+                        continue;
+                    }
+                    if let Some(value) = value_opt {
+                        tally(value);
+                    }
+                    last_key_hash = Some(key_hash);
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Returns the `n` largest state values as of `version` by their sizes in bytes, largest
+    /// first, e.g. to find what's bloating the state. Only the keys and sizes are kept, in a heap
+    /// bounded by `n`, but every version of every key in the DB is read.
+    ///
+    /// The sharded layout stores the values by key hash, so each shard keeps its own `n` largest
+    /// by key hash, and only the keys of the `n` largest of all are looked up with
+    /// `resolve_state_key`, from a key hash and a version the key was written at.
+    pub fn largest_values(
+        &self,
+        version: Version,
+        n: usize,
+        resolve_state_key: impl Fn(HashValue, Version) -> Result<StateKey>,
+    ) -> Result<Vec<(StateKey, usize)>> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["largest_values"]);
+
+        if !self.enabled_sharding() {
+            // A min-heap of the largest ones so far, the smallest of them on top to be evicted.
+            let mut heap = BinaryHeap::with_capacity(n.saturating_add(1));
+            let mut iter = self.metadata_db().iter::<StateValueSchema>()?;
+            iter.seek_to_first();
+            let mut last_state_key = None;
+            for res in iter {
+                let ((state_key, value_version), value_opt) = res?;
+                // The versions of a key are ordered from the latest, so the first one at or
+                // before `version` is the value of the key at `version`.
+                if value_version > version || last_state_key.as_ref() == Some(&state_key) {
+                    continue;
+                }
+                last_state_key = Some(state_key.clone());
+                if let Some(value) = value_opt {
+                    push_bounded(&mut heap, (value.size(), state_key), n);
+                }
+            }
+            return Ok(heap
+                .into_sorted_vec()
+                .into_iter()
+                .map(|Reverse((size, state_key))| (state_key, size))
+                .collect());
+        }
+
+        let heaps = THREAD_MANAGER.get_io_pool().install(|| {
+            (0..self.num_shards())
+                .into_par_iter()
+                .map(|shard_id| self.largest_values_in_shard(shard_id, version, n))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        let mut heap = BinaryHeap::with_capacity(n.saturating_add(1));
+        for Reverse(entry) in heaps.into_iter().flatten() {
+            push_bounded(&mut heap, entry, n);
+        }
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((size, key_hash, value_version))| {
+                Ok((resolve_state_key(key_hash, value_version)?, size))
+            })
+            .collect()
+    }
+
+    /// The `n` largest state values of a shard in the sharded layout as of `version`, as a
+    /// min-heap of their sizes, key hashes and the versions they were written at.
+    fn largest_values_in_shard(
+        &self,
+        shard_id: usize,
+        version: Version,
+        n: usize,
+    ) -> Result<BinaryHeap<Reverse<(usize, HashValue, Version)>>> {
+        let mut heap = BinaryHeap::with_capacity(n.saturating_add(1));
+        let mut iter = self
+            .db_shard(shard_id)
+            .iter::<StateValueByKeyHashSchema>()?;
+        iter.seek_to_first();
+        let mut last_key_hash = None;
+        for res in iter {
+            let ((key_hash, value_version), value_opt) = res?;
+            if value_version > version || last_key_hash == Some(key_hash) {
+                continue;
+            }
+            last_key_hash = Some(key_hash);
+            if let Some(value) = value_opt {
+                push_bounded(&mut heap, (value.size(), key_hash, value_version), n);
+            }
+        }
+        Ok(heap)
+    }
+
+    /// Rewrites all the state values, and their stale indices, under the keys `remap` maps their
+    /// keys to, e.g. after a change of the key encoding, then checks that the numbers of entries
+    /// haven't changed.
+    ///
+    /// This goes in two steps, both meant for an offline migration of a backed up DB:
+    /// 1. The remapped entries of each shard are written into SST files of bounded size under
+    ///    `sst_dir`, the deletions of the old entries with the shard and the new entries with the
+    ///    shards they go to, without touching the DB. Once the files of all the shards are
+    ///    written, the progress of each shard is recorded. Until then, a failure, e.g. the
+    ///    remapping not being injective, leaves the DB untouched, and a re-run starts over.
+    /// 2. The files going to each shard are ingested, the deletions first, then the shard is
+    ///    recorded as done. Ingesting the same files again writes the same entries, so a re-run
+    ///    after a failure ingests the files of the shards not recorded as done, whichever
+    ///    `remap` it's given. Once all the shards are done, a re-run only checks the numbers of
+    ///    entries.
+    ///
+    /// The sharded layout stores the values by key hash without the keys themselves, so
+    /// `resolve_key` gives the key of a key hash from a version it was written at, see
+    /// `StateStore::remap_keys`. The key hashes, and with them the shards, change with the keys, so
+    /// the state merkle DB, and the hot state, have to be rebuilt afterwards.
+    pub fn remap_keys(
+        &self,
+        remap: impl Fn(&StateKey) -> StateKey,
+        resolve_key: impl Fn(HashValue, Version) -> Result<StateKey>,
+        sst_dir: &Path,
+    ) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["remap_keys"]);
+        let num_shards = self.hack_num_real_shards();
+        let progress = (0..num_shards)
+            .map(|shard_id| self.get_remap_progress(shard_id))
+            .collect::<Result<Vec<_>>>()?;
+        let mut progress = match progress.into_iter().collect::<Option<Vec<_>>>() {
+            Some(progress) => {
+                info!("Resuming the remapping of state keys.");
+                progress
+            },
+            None => {
+                // Files left behind by an earlier run which didn't get to write all of them.
+                let dirs = (0..num_shards)
+                    .map(|shard_id| remap_shard_dir(sst_dir, shard_id))
+                    .chain([remap_key_hashes_dir(sst_dir)]);
+                for dir in dirs {
+                    if dir.exists() {
+                        std::fs::remove_dir_all(&dir)?;
+                    }
+                    std::fs::create_dir_all(&dir)?;
+                }
+                let progress = if self.enabled_sharding() {
+                    self.write_remapped_sharded_keys(remap, resolve_key, sst_dir)?
+                } else {
+                    self.write_remapped_unsharded_keys(remap, sst_dir)?
+                };
+                std::fs::remove_dir_all(remap_key_hashes_dir(sst_dir))?;
+                let mut batch = SchemaBatch::new();
+                for (shard_id, shard_progress) in progress.iter().enumerate() {
+                    batch.put::<DbMetadataSchema>(
+                        &DbMetadataKey::StateKvRemapProgress(shard_id),
+                        &DbMetadataValue::StateKvRemapProgress(*shard_progress),
+                    )?;
+                }
+                self.metadata_db().write_schemas(batch)?;
+                progress
+            },
+        };
+
+        for (shard_id, shard_progress) in progress.iter_mut().enumerate() {
+            if shard_progress.ingested {
+                continue;
+            }
+            let dir = remap_shard_dir(sst_dir, shard_id);
+            ensure!(
+                dir.exists(),
+                "{:?} not found, the remapping of state keys has to be resumed with the same SST \
+                directory.",
+                dir,
+            );
+            let db = self.db_shard(shard_id);
+            if self.enabled_sharding() {
+                ingest_remapped_sst_files::<StateValueByKeyHashSchema>(
+                    db, sst_dir, shard_id, num_shards,
+                )?;
+                ingest_remapped_sst_files::<StaleStateValueIndexByKeyHashSchema>(
+                    db, sst_dir, shard_id, num_shards,
+                )?;
+            } else {
+                ingest_remapped_sst_files::<StateValueSchema>(db, sst_dir, shard_id, num_shards)?;
+                ingest_remapped_sst_files::<StaleStateValueIndexSchema>(
+                    db, sst_dir, shard_id, num_shards,
+                )?;
+            }
+            shard_progress.ingested = true;
+            self.metadata_db().put::<DbMetadataSchema>(
+                &DbMetadataKey::StateKvRemapProgress(shard_id),
+                &DbMetadataValue::StateKvRemapProgress(*shard_progress),
+            )?;
+            std::fs::remove_dir_all(dir)?;
+        }
+
+        let counts_before = progress.iter().fold((0, 0), |counts, shard_progress| {
+            (
+                counts.0 + shard_progress.num_values,
+                counts.1 + shard_progress.num_stale_indices,
+            )
+        });
+        let counts_after = self.count_values_and_stale_indices()?;
+        ensure!(
+            counts_after == counts_before,
+            "Remapping state keys changed the numbers of state values and stale indices from {:?} \
+            to {:?}.",
+            counts_before,
+            counts_after,
+        );
+        info!(
+            num_values = counts_after.0,
+            num_stale_indices = counts_after.1,
+            "Remapped state keys."
+        );
+        Ok(())
+    }
+
+    fn get_remap_progress(&self, shard_id: usize) -> Result<Option<StateKvRemapProgress>> {
+        Ok(self
+            .metadata_db()
+            .get::<DbMetadataSchema>(&DbMetadataKey::StateKvRemapProgress(shard_id))?
+            .map(DbMetadataValue::expect_state_kv_remap_progress))
+    }
+
+    /// Writes the SST files of the remapped entries, returning the progress of the only shard.
+    fn write_remapped_unsharded_keys(
+        &self,
+        remap: impl Fn(&StateKey) -> StateKey,
+        sst_dir: &Path,
+    ) -> Result<Vec<StateKvRemapProgress>> {
+        let db = self.metadata_db();
+        let mut injectivity_checker = InjectivityChecker::new(remap_key_hashes_dir(sst_dir));
+
+        let mut writer = RemappedSstWriter::<StateValueSchema>::new(
+            db,
+            self.sst_cf_opts(StateValueSchema::COLUMN_FAMILY_NAME),
+            sst_dir,
+            0,
+            1,
+        );
+        let mut num_values = 0;
+        // The versions of a key are next to each other, so it's remapped once for all of them.
+        let mut last_remapped: Option<(StateKey, StateKey)> = None;
+        let mut iter = db.iter::<StateValueSchema>()?;
+        iter.seek_to_first();
+        for res in iter {
+            let ((state_key, version), value) = res?;
+            num_values += 1;
+            if last_remapped
+                .as_ref()
+                .is_none_or(|(old_key, _new_key)| *old_key != state_key)
+            {
+                let new_key = remap(&state_key);
+                injectivity_checker.add(new_key.hash(), state_key.hash())?;
+                last_remapped = Some((state_key.clone(), new_key));
+            }
+            let (_old_key, new_key) = last_remapped.as_ref().expect("Remapped above.");
+            if *new_key != state_key {
+                writer.remap(
+                    (state_key, version),
+                    (new_key.clone(), version),
+                    /* new_shard_id = */ 0,
+                    value,
+                )?;
+            }
+        }
+        writer.finish()?;
+        injectivity_checker.finish()?;
+
+        let mut writer = RemappedSstWriter::<StaleStateValueIndexSchema>::new(
+            db,
+            self.sst_cf_opts(StaleStateValueIndexSchema::COLUMN_FAMILY_NAME),
+            sst_dir,
+            0,
+            1,
+        );
+        let mut num_stale_indices = 0;
+        let mut iter = db.iter::<StaleStateValueIndexSchema>()?;
+        iter.seek_to_first();
+        for res in iter {
+            let (index, ()) = res?;
+            num_stale_indices += 1;
+            let new_key = remap(&index.state_key);
+            if new_key != index.state_key {
+                let new_index = StaleStateValueIndex {
+                    state_key: new_key,
+                    ..index
+                };
+                writer.remap(index, new_index, /* new_shard_id = */ 0, ())?;
+            }
+        }
+        writer.finish()?;
+
+        Ok(vec![StateKvRemapProgress {
+            num_values,
+            num_stale_indices,
+            ingested: false,
+        }])
+    }
+
+    /// Writes the SST files of the remapped entries, returning the progress of each shard.
+    fn write_remapped_sharded_keys(
+        &self,
+        remap: impl Fn(&StateKey) -> StateKey,
+        resolve_key: impl Fn(HashValue, Version) -> Result<StateKey>,
+        sst_dir: &Path,
+    ) -> Result<Vec<StateKvRemapProgress>> {
+        let key_hashes_dir = remap_key_hashes_dir(sst_dir);
+        let mut injectivity_checker = InjectivityChecker::new(key_hashes_dir.clone());
+        let mut progress = Vec::with_capacity(NUM_STATE_SHARDS);
+        let remap_key_hash = |key_hash: HashValue, version| -> Result<HashValue> {
+            let state_key = resolve_key(key_hash, version)?;
+            ensure!(
+                CryptoHash::hash(&state_key) == key_hash,
+                "Resolved state key {:?} doesn't have hash {}.",
+                state_key,
+                key_hash,
+            );
+            Ok(CryptoHash::hash(&remap(&state_key)))
+        };
+
+        for shard_id in 0..NUM_STATE_SHARDS {
+            let db = self.db_shard(shard_id);
+            // Of the keys of the shard, for the stale indices, which are in the shard of their key.
+            let mut key_hash_mapping = KeyHashMappingWriter::create(
+                key_hashes_dir.join(format!("mapping_shard_{shard_id}")),
+            )?;
+
+            let mut writer = RemappedSstWriter::<StateValueByKeyHashSchema>::new(
+                db,
+                self.sst_cf_opts(StateValueByKeyHashSchema::COLUMN_FAMILY_NAME),
+                sst_dir,
+                shard_id,
+                NUM_STATE_SHARDS,
+            );
+            let mut num_values = 0;
+            let mut last_remapped: Option<(HashValue, HashValue)> = None;
+            let mut iter = db.iter::<StateValueByKeyHashSchema>()?;
+            iter.seek_to_first();
+            for res in iter {
+                // The versions of a key are ordered from the latest, so the key is resolved at
+                // the version it was last written at, once for all of them.
+                let ((key_hash, version), value) = res?;
+                num_values += 1;
+                let new_key_hash = match last_remapped {
+                    Some((old_key_hash, new_key_hash)) if old_key_hash == key_hash => new_key_hash,
+                    _ => {
+                        let new_key_hash = remap_key_hash(key_hash, version)?;
+                        injectivity_checker.add(new_key_hash, key_hash)?;
+                        key_hash_mapping.add(key_hash, new_key_hash)?;
+                        last_remapped = Some((key_hash, new_key_hash));
+                        new_key_hash
+                    },
+                };
+                if new_key_hash != key_hash {
+                    writer.remap(
+                        (key_hash, version),
+                        (new_key_hash, version),
+                        usize::from(new_key_hash.nibble(0)),
+                        value,
+                    )?;
+                }
+            }
+            writer.finish()?;
+            let mut key_hash_mapping = key_hash_mapping.finish()?;
+
+            let mut writer = RemappedSstWriter::<StaleStateValueIndexByKeyHashSchema>::new(
+                db,
+                self.sst_cf_opts(StaleStateValueIndexByKeyHashSchema::COLUMN_FAMILY_NAME),
+                sst_dir,
+                shard_id,
+                NUM_STATE_SHARDS,
+            );
+            let mut num_stale_indices = 0;
+            let mut iter = db.iter::<StaleStateValueIndexByKeyHashSchema>()?;
+            iter.seek_to_first();
+            for res in iter {
+                let (index, ()) = res?;
+                num_stale_indices += 1;
+                // The value of a stale index is pruned along with it, so the key is normally
+                // found among those of the values.
+                let new_key_hash = match key_hash_mapping.get(index.state_key_hash)? {
+                    Some(new_key_hash) => new_key_hash,
+                    None => remap_key_hash(index.state_key_hash, index.version)?,
+                };
+                if new_key_hash != index.state_key_hash {
+                    let new_index = StaleStateValueByKeyHashIndex {
+                        state_key_hash: new_key_hash,
+                        ..index
+                    };
+                    writer.remap(index, new_index, usize::from(new_key_hash.nibble(0)), ())?;
+                }
+            }
+            writer.finish()?;
+
+            progress.push(StateKvRemapProgress {
+                num_values,
+                num_stale_indices,
+                ingested: false,
+            });
+        }
+        injectivity_checker.finish()?;
+        Ok(progress)
+    }
+
+    /// Returns the numbers of state values and of stale indices of all the shards.
+    fn count_values_and_stale_indices(&self) -> Result<(usize, usize)> {
+        if !self.enabled_sharding() {
+            let db = self.metadata_db();
+            let mut iter = db.iter::<StateValueSchema>()?;
+            iter.seek_to_first();
+            let num_values = iter.count();
+            let mut iter = db.iter::<StaleStateValueIndexSchema>()?;
+            iter.seek_to_first();
+            return Ok((num_values, iter.count()));
+        }
+
+        let mut counts = (0, 0);
+        for shard_id in 0..self.num_shards() {
+            let db = self.db_shard(shard_id);
+            let mut iter = db.iter::<StateValueByKeyHashSchema>()?;
+            iter.seek_to_first();
+            counts.0 += iter.count();
+            let mut iter = db.iter::<StaleStateValueIndexByKeyHashSchema>()?;
+            iter.seek_to_first();
+            counts.1 += iter.count();
+        }
+        Ok(counts)
+    }
+
+    /// Compacts the key ranges of the SST files of the shard dense with tombstones, i.e. with at
+    /// least `TOMBSTONE_RATIO_TO_COMPACT` of their entries being deletions, like those left behind
+    /// by a big prune. This reclaims most of the space a full compaction would, without the IO of
+    /// rewriting the whole keyspace. Overlapping ranges are compacted together, and the number of
+    /// ranges compacted is returned.
+    ///
+    /// It works on a single shard so that the work can be staggered across the shards.
+    pub fn compact_tombstone_ranges(&self, shard_id: usize) -> Result<usize> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["compact_tombstone_ranges"]);
+        ensure!(
+            shard_id < self.num_shards(),
+            "Shard id {} out of range, there are {} shards.",
+            shard_id,
+            self.num_shards(),
+        );
+
+        let db = self.db_shard(shard_id);
+        let mut ranges_by_cf: HashMap<String, Vec<(Vec<u8>, Vec<u8>)>> = HashMap::new();
+        for file in db.live_files()? {
+            // `num_entries` counts the deletions too.
+            if file.num_entries == 0
+                || (file.num_deletions as f64)
+                    < file.num_entries as f64 * TOMBSTONE_RATIO_TO_COMPACT
+            {
+                continue;
+            }
+            if let (Some(start_key), Some(end_key)) = (file.start_key, file.end_key) {
+                ranges_by_cf
+                    .entry(file.column_family_name)
+                    .or_default()
+                    .push((start_key, end_key));
+            }
+        }
+
+        let mut num_ranges = 0;
+        for (cf_name, mut ranges) in ranges_by_cf {
+            ranges.sort_unstable();
+            let mut merged: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(ranges.len());
+            for (start_key, end_key) in ranges {
+                match merged.last_mut() {
+                    Some((_, last_end_key)) if start_key <= *last_end_key => {
+                        if end_key > *last_end_key {
+                            *last_end_key = end_key;
+                        }
+                    },
+                    _ => merged.push((start_key, end_key)),
+                }
+            }
+            for (start_key, end_key) in &merged {
+                db.compact_range_cf(&cf_name, start_key, end_key)?;
+            }
+            num_ranges += merged.len();
+        }
+
+        info!(
+            shard_id = shard_id,
+            num_ranges = num_ranges,
+            "Compacted the tombstone heavy ranges of a state kv db shard."
+        );
+        Ok(num_ranges)
+    }
+
+    /// Like `compact_tombstone_ranges`, but the compactions run on a background thread, and the
+    /// returned future resolves once RocksDB reports all of them done, so that the next
+    /// maintenance step can be chained on it without blocking or polling the compaction
+    /// properties. The compactions go on even if the future is dropped.
+    pub fn compact_tombstone_ranges_async(
+        self: &Arc<Self>,
+        shard_id: usize,
+    ) -> impl Future<Output = Result<usize>> + Send + use<> {
+        let (sender, receiver) = oneshot::channel();
+        let db = Arc::clone(self);
+        std::thread::Builder::new()
+            .name("compact_tombstone_ranges".to_string())
+            .spawn(move || {
+                // Nobody is waiting for the result if the receiver is gone.
+                let _ = sender.send(db.compact_tombstone_ranges(shard_id));
+            })
+            .expect("Failed to spawn compaction thread.");
+        async move {
+            receiver.await.map_err(|_| {
+                AptosDbError::Other("Compaction thread exited without a result.".to_string())
+            })?
+        }
+    }
+
+    /// Gets the latest value at or before `version` by the hash of the state key, for when only
+    /// the key hash is known, e.g. from a proof. The shard is derived from the key hash itself.
+    /// Only supported with sharding enabled, since the unsharded layout is keyed by `StateKey`.
+    pub fn get_value_by_key_hash(
+        &self,
+        key_hash: HashValue,
+        version: Version,
+    ) -> Result<Option<StateValue>> {
+        ensure!(
+            self.enabled_sharding(),
+            "Looking up state values by key hash requires storage sharding."
+        );
+        let mut read_opts = ReadOptions::default();
+
+        // We want `None` if the key hash changes in iteration.
+        read_opts.set_prefix_same_as_start(true);
+        let mut iter = self
+            .db_shard(usize::from(key_hash.nibble(0)))
+            .iter_with_opts::<StateValueByKeyHashSchema>(read_opts)?;
+        iter.seek(&(key_hash, version))?;
+        Ok(iter
+            .next()
+            .transpose()?
+            .and_then(|(_, value_opt)| value_opt))
+    }
+
+    /// Gets the values of the key with hash `key_hash` at each of `versions`, in the same order,
+    /// like [`Self::get_value_by_key_hash`] for each of them but in a single scan of the key's
+    /// history from the largest requested version down to the smallest one. This is cheaper than
+    /// independent lookups when the versions are close to each other, but every version of the key
+    /// in between is read.
+    pub fn get_value_at_versions(
+        &self,
+        key_hash: HashValue,
+        versions: &[Version],
+    ) -> Result<Vec<Option<StateValue>>> {
+        ensure!(
+            self.enabled_sharding(),
+            "Looking up state values by key hash requires storage sharding."
+        );
+        let mut values = vec![None; versions.len()];
+        let mut order = (0..versions.len()).collect::<Vec<_>>();
+        order.sort_unstable_by_key(|i| Reverse(versions[*i]));
+        let Some(&latest) = order.first() else {
+            return Ok(values);
+        };
+
+        let mut read_opts = ReadOptions::default();
+        // We want `None` if the key hash changes in iteration.
+        read_opts.set_prefix_same_as_start(true);
+        let mut iter = self
+            .db_shard(usize::from(key_hash.nibble(0)))
+            .iter_with_opts::<StateValueByKeyHashSchema>(read_opts)?;
+        // Versions of a key sort from the latest to the oldest.
+        iter.seek(&(key_hash, versions[latest]))?;
+        let mut entry = iter.next().transpose()?;
+        for i in order {
+            let version = versions[i];
+            while entry
+                .as_ref()
+                .is_some_and(|((_, entry_version), _)| *entry_version > version)
+            {
+                entry = iter.next().transpose()?;
+            }
+            values[i] = entry.as_ref().and_then(|(_, value_opt)| value_opt.clone());
+        }
+        Ok(values)
+    }
+
+    /// Like [`Self::get_value_by_key_hash`], but copies the bytes of the value into `buf` instead
+    /// of returning a `StateValue`, so that a tight read loop can reuse the same buffer rather than
+    /// allocate a value per read. The metadata of the value is not decoded. Returns whether the key
+    /// has a value, `buf` being left empty otherwise.
+    pub fn get_value_bytes_into(
+        &self,
+        key_hash: HashValue,
+        version: Version,
+        buf: &mut Vec<u8>,
+    ) -> Result<bool> {
+        ensure!(
+            self.enabled_sharding(),
+            "Looking up state values by key hash requires storage sharding."
+        );
+        let mut read_opts = ReadOptions::default();
+
+        // We want `None` if the key hash changes in iteration.
+        read_opts.set_prefix_same_as_start(true);
+        let mut iter = self
+            .db_shard(usize::from(key_hash.nibble(0)))
+            .iter_with_opts::<StateValueByKeyHashSchema>(read_opts)?;
+        iter.seek(&(key_hash, version))?;
+        match iter.next_with_raw_value()? {
+            Some((_, raw_value)) => Ok(decode_value_bytes_into(raw_value, buf)?),
+            None => {
+                buf.clear();
+                Ok(false)
+            },
+        }
+    }
+}
+
+/// Pushes `item` into the min-heap `heap`, evicting the smallest if it then has more than `n`.
+fn push_bounded<T: Ord>(heap: &mut BinaryHeap<Reverse<T>>, item: T, n: usize) {
+    heap.push(Reverse(item));
+    if heap.len() > n {
+        heap.pop();
+    }
+}
+
+/// Checks that a remapping of state keys is injective without holding all the keys in memory. The
+/// (new, old) key hash pairs, one per old key, are sorted in runs of at most
+/// `REMAP_SST_FILE_MAX_ENTRIES` pairs written to files, which are then merged, i.e. an external
+/// sort of the pairs by the new key hashes.
+struct InjectivityChecker {
+    dir: PathBuf,
+    pairs: Vec<(HashValue, HashValue)>,
+    run_lens: Vec<usize>,
+}
+
+impl InjectivityChecker {
+    fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            pairs: Vec::new(),
+            run_lens: Vec::new(),
+        }
+    }
+
+    /// Records that the key of hash `old_key_hash` is remapped to the key of hash `new_key_hash`.
+    /// Each old key is to be recorded once.
+    fn add(&mut self, new_key_hash: HashValue, old_key_hash: HashValue) -> Result<()> {
+        self.pairs.push((new_key_hash, old_key_hash));
+        if self.pairs.len() >= REMAP_SST_FILE_MAX_ENTRIES {
+            self.write_run()?;
+        }
+        Ok(())
+    }
+
+    fn write_run(&mut self) -> Result<()> {
+        if self.pairs.is_empty() {
+            return Ok(());
+        }
+        self.pairs.sort_unstable();
+        let mut writer = BufWriter::new(File::create(self.run_path(self.run_lens.len()))?);
+        for pair in &self.pairs {
+            write_key_hash_pair(&mut writer, *pair)?;
+        }
+        writer.flush()?;
+        self.run_lens.push(self.pairs.len());
+        self.pairs.clear();
+        Ok(())
+    }
+
+    fn run_path(&self, run_id: usize) -> PathBuf {
+        self.dir.join(format!("run_{run_id:06}"))
+    }
+
+    /// Merges the runs, failing if two old keys are remapped to the same new key.
+    fn finish(mut self) -> Result<()> {
+        self.write_run()?;
+        let mut runs = self
+            .run_lens
+            .iter()
+            .enumerate()
+            .map(|(run_id, len)| Ok((BufReader::new(File::open(self.run_path(run_id))?), *len)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut next_pair = |run_id: usize| -> Result<Option<(HashValue, HashValue)>> {
+            let (reader, remaining) = &mut runs[run_id];
+            if *remaining == 0 {
+                return Ok(None);
+            }
+            *remaining -= 1;
+            read_key_hash_pair(reader).map(Some)
+        };
+
+        let mut heap = BinaryHeap::new();
+        for run_id in 0..self.run_lens.len() {
+            if let Some(pair) = next_pair(run_id)? {
+                heap.push(Reverse((pair, run_id)));
+            }
+        }
+        let mut last_pair: Option<(HashValue, HashValue)> = None;
+        while let Some(Reverse(((new_key_hash, old_key_hash), run_id))) = heap.pop() {
+            if let Some((last_new_key_hash, last_old_key_hash)) = last_pair {
+                ensure!(
+                    last_new_key_hash != new_key_hash,
+                    "The keys of hashes {} and {} are remapped to the same key, of hash {}, the \
+                    remapping must be injective.",
+                    last_old_key_hash,
+                    old_key_hash,
+                    new_key_hash,
+                );
+            }
+            last_pair = Some((new_key_hash, old_key_hash));
+            if let Some(pair) = next_pair(run_id)? {
+                heap.push(Reverse((pair, run_id)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the (old, new) key hash pairs of the keys of a shard to a file, in the order of the old
+/// ones, for `KeyHashMapping` to look the new key hashes up without holding them in memory.
+struct KeyHashMappingWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    len: u64,
+}
+
+impl KeyHashMappingWriter {
+    fn create(path: PathBuf) -> Result<Self> {
+        let writer = BufWriter::new(File::create(&path)?);
+        Ok(Self {
+            path,
+            writer,
+            len: 0,
+        })
+    }
+
+    /// Records that the key of hash `old_key_hash` is remapped to the key of hash `new_key_hash`,
+    /// `old_key_hash` being larger than the ones recorded before.
+    fn add(&mut self, old_key_hash: HashValue, new_key_hash: HashValue) -> Result<()> {
+        write_key_hash_pair(&mut self.writer, (old_key_hash, new_key_hash))?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<KeyHashMapping> {
+        self.writer.flush()?;
+        Ok(KeyHashMapping {
+            file: File::open(&self.path)?,
+            len: self.len,
+        })
+    }
+}
+
+/// The file written by `KeyHashMappingWriter`, looked up by binary search.
+struct KeyHashMapping {
+    file: File,
+    len: u64,
+}
+
+impl KeyHashMapping {
+    /// Returns the hash of the key the key of hash `old_key_hash` is remapped to, `None` if it
+    /// wasn't recorded.
+    fn get(&mut self, old_key_hash: HashValue) -> Result<Option<HashValue>> {
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            self.file
+                .seek(SeekFrom::Start(mid * KEY_HASH_PAIR_SIZE as u64))?;
+            let (key_hash, new_key_hash) = read_key_hash_pair(&mut self.file)?;
+            match key_hash.cmp(&old_key_hash) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(Some(new_key_hash)),
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn write_key_hash_pair(writer: &mut impl Write, pair: (HashValue, HashValue)) -> Result<()> {
+    writer.write_all(&*pair.0)?;
+    writer.write_all(&*pair.1)?;
+    Ok(())
+}
+
+fn read_key_hash_pair(reader: &mut impl Read) -> Result<(HashValue, HashValue)> {
+    let mut first = [0; HashValue::LENGTH];
+    let mut second = [0; HashValue::LENGTH];
+    reader.read_exact(&mut first)?;
+    reader.read_exact(&mut second)?;
+    Ok((HashValue::new(first), HashValue::new(second)))
+}
+
+/// The directory of the files of key hashes written by `StateKvDb::remap_keys`.
+fn remap_key_hashes_dir(sst_dir: &Path) -> PathBuf {
+    sst_dir.join("key_hashes")
+}
+
+/// The directory of the SST files going to shard `shard_id` written by `StateKvDb::remap_keys`.
+fn remap_shard_dir(sst_dir: &Path, shard_id: usize) -> PathBuf {
+    sst_dir.join(format!("shard_{shard_id}"))
+}
+
+/// The directory of the SST files of schema `S` going from shard `from_shard_id` to shard
+/// `to_shard_id`.
+fn remap_sst_dir<S: Schema>(sst_dir: &Path, to_shard_id: usize, from_shard_id: usize) -> PathBuf {
+    remap_shard_dir(sst_dir, to_shard_id)
+        .join(S::COLUMN_FAMILY_NAME)
+        .join(format!("from_shard_{from_shard_id}"))
+}
+
+/// Writes the remapped entries of schema `S` of a shard into SST files of at most
+/// `REMAP_SST_FILE_MAX_ENTRIES` entries, the deletions of the old entries going to the shard
+/// itself and the new entries to the shards of their keys.
+struct RemappedSstWriter<'a, S: Schema> {
+    db: &'a DB,
+    cf_opts: Options,
+    sst_dir: &'a Path,
+    shard_id: usize,
+    deletes: Vec<S::Key>,
+    num_deletes_files: usize,
+    puts_by_shard: Vec<Vec<(S::Key, S::Value)>>,
+    num_puts_files_by_shard: Vec<usize>,
+}
+
+impl<'a, S: Schema> RemappedSstWriter<'a, S> {
+    fn new(
+        db: &'a DB,
+        cf_opts: Options,
+        sst_dir: &'a Path,
+        shard_id: usize,
+        num_shards: usize,
+    ) -> Self {
+        Self {
+            db,
+            cf_opts,
+            sst_dir,
+            shard_id,
+            deletes: Vec::new(),
+            num_deletes_files: 0,
+            puts_by_shard: (0..num_shards).map(|_| Vec::new()).collect(),
+            num_puts_files_by_shard: vec![0; num_shards],
+        }
+    }
+
+    /// Moves `value` from `old_key` to `new_key`, which belongs to shard `new_shard_id`.
+    fn remap(
+        &mut self,
+        old_key: S::Key,
+        new_key: S::Key,
+        new_shard_id: usize,
+        value: S::Value,
+    ) -> Result<()> {
+        self.deletes.push(old_key);
+        if self.deletes.len() >= REMAP_SST_FILE_MAX_ENTRIES {
+            self.write_deletes()?;
+        }
+        self.puts_by_shard[new_shard_id].push((new_key, value));
+        if self.puts_by_shard[new_shard_id].len() >= REMAP_SST_FILE_MAX_ENTRIES {
+            self.write_puts(new_shard_id)?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the entries left.
+    fn finish(mut self) -> Result<()> {
+        self.write_deletes()?;
+        for new_shard_id in 0..self.puts_by_shard.len() {
+            self.write_puts(new_shard_id)?;
+        }
+        Ok(())
+    }
+
+    fn write_deletes(&mut self) -> Result<()> {
+        if self.deletes.is_empty() {
+            return Ok(());
+        }
+        let dir = remap_sst_dir::<S>(self.sst_dir, self.shard_id, self.shard_id);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("deletes_{:06}.sst", self.num_deletes_files));
+        self.db
+            .write_deletion_sst_file::<S>(&path, &self.cf_opts, &self.deletes)?;
+        self.deletes.clear();
+        self.num_deletes_files += 1;
+        Ok(())
+    }
+
+    fn write_puts(&mut self, new_shard_id: usize) -> Result<()> {
+        if self.puts_by_shard[new_shard_id].is_empty() {
+            return Ok(());
+        }
+        let dir = remap_sst_dir::<S>(self.sst_dir, new_shard_id, self.shard_id);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "puts_{:06}.sst",
+            self.num_puts_files_by_shard[new_shard_id]
+        ));
+        let puts = &mut self.puts_by_shard[new_shard_id];
+        self.db.write_sst_file::<S>(
+            &path,
+            &self.cf_opts,
+            puts.iter().map(|(key, value)| (key, value)),
+        )?;
+        puts.clear();
+        self.num_puts_files_by_shard[new_shard_id] += 1;
+        Ok(())
+    }
+}
+
+/// Ingests the SST files of schema `S` going to shard `shard_id` written by
+/// `StateKvDb::remap_keys`, the deletions of the old entries first, for the new entries to take
+/// precedence where a key is remapped to another remapped one. The files are ingested at once, and
+/// copied, so that they can be ingested again if the remapping is resumed.
+fn ingest_remapped_sst_files<S: Schema>(
+    db: &DB,
+    sst_dir: &Path,
+    shard_id: usize,
+    num_shards: usize,
+) -> Result<()> {
+    let mut paths = sst_file_paths(&remap_sst_dir::<S>(sst_dir, shard_id, shard_id), "deletes")?;
+    for from_shard_id in 0..num_shards {
+        paths.extend(sst_file_paths(
+            &remap_sst_dir::<S>(sst_dir, shard_id, from_shard_id),
+            "puts",
+        )?);
+    }
+    if paths.is_empty() {
+        return Ok(());
+    }
+    db.ingest_sst_file_copies::<S>(paths)
+}
+
+/// Returns the paths of the files in `dir` with names starting with `prefix`, sorted by name, or
+/// none if `dir` doesn't exist.
+fn sst_file_paths(dir: &Path, prefix: &str) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(prefix))
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
 }
@@ -2,7 +2,7 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
-    db_options::gen_state_merkle_cfds,
+    db_options::{gen_state_merkle_cfds, state_merkle_db_column_families},
     lru_node_cache::LruNodeCache,
     metrics::{NODE_CACHE_SECONDS, OTHER_TIMERS_SECONDS},
     schema::{
@@ -11,38 +11,47 @@ use crate::{
         stale_node_index::StaleNodeIndexSchema,
         stale_node_index_cross_epoch::StaleNodeIndexCrossEpochSchema,
     },
-    utils::truncation_helper::{get_state_merkle_commit_progress, truncate_state_merkle_db_shards},
+    utils::{
+        dir_paths_with_wal, set_wal_dir,
+        truncation_helper::{get_state_merkle_commit_progress, truncate_state_merkle_db_shards},
+    },
     versioned_node_cache::VersionedNodeCache,
 };
 use aptos_config::config::{RocksdbConfig, RocksdbConfigs, StorageDirPaths};
 use aptos_crypto::HashValue;
 use aptos_experimental_runtimes::thread_manager::THREAD_MANAGER;
 use aptos_jellyfish_merkle::{
-    node_type::NodeKey, JellyfishMerkleTree, TreeReader, TreeUpdateBatch, TreeWriter,
+    node_type::{Children, InternalNode, NodeKey},
+    JellyfishMerkleTree, StaleNodeIndex, TreeReader, TreeUpdateBatch, TreeWriter,
 };
 use aptos_logger::prelude::*;
 use aptos_metrics_core::TimerHelper;
 use aptos_rocksdb_options::gen_rocksdb_options;
 use aptos_schemadb::{
     batch::{IntoRawBatch, RawBatch, SchemaBatch, WriteBatch},
-    Cache, Env, DB,
+    schema::{Schema, SeekKeyCodec},
+    Cache, Env, WriteBufferManager, DB,
 };
 #[cfg(test)]
 use aptos_scratchpad::get_state_shard_id;
-use aptos_storage_interface::{db_ensure as ensure, AptosDbError, Result};
+use aptos_storage_interface::{db_ensure as ensure, db_other_bail as bail, AptosDbError, Result};
 use aptos_types::{
-    nibble::{nibble_path::NibblePath, ROOT_NIBBLE_HEIGHT},
+    nibble::{nibble_path::NibblePath, Nibble, ROOT_NIBBLE_HEIGHT},
     proof::{SparseMerkleProofExt, SparseMerkleRangeProof},
     state_store::{state_key::StateKey, NUM_STATE_SHARDS},
     transaction::Version,
 };
 use arr_macro::arr;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use rayon::prelude::*;
 use std::{
     collections::HashMap,
     num::NonZeroUsize,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Instant,
 };
 
@@ -56,6 +65,25 @@ fn db_folder_name(is_hot: bool) -> &'static str {
     }
 }
 
+fn write_length_prefixed(writer: &mut impl std::io::Write, bytes: &[u8]) -> Result<()> {
+    let len = u32::try_from(bytes.len()).expect("Encoded nodes are small.");
+    writer.write_u32::<BigEndian>(len)?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+/// Returns `None` at the end of the input.
+fn read_length_prefixed(reader: &mut impl std::io::Read) -> Result<Option<Vec<u8>>> {
+    let len = match reader.read_u32::<BigEndian>() {
+        Ok(len) => len,
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
 fn metadata_db_name(is_hot: bool) -> &'static str {
     if is_hot {
         "hot_state_merkle_metadata_db"
@@ -79,6 +107,10 @@ pub struct StateMerkleDb {
     version_caches: HashMap<Option<usize>, VersionedNodeCache>,
     // `None` means the cache is not enabled.
     lru_cache: Option<LruNodeCache>,
+    // Whether the nodes written skip the LRU cache once evicted from the version caches.
+    cache_on_read_only: AtomicBool,
+    // See `RocksdbConfig::flush_on_commit`.
+    flush_on_commit: bool,
 }
 
 impl StateMerkleDb {
@@ -87,6 +119,7 @@ impl StateMerkleDb {
         rocksdb_configs: RocksdbConfigs,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
         // TODO(grao): Currently when this value is set to 0 we disable both caches. This is
         // hacky, need to revisit.
@@ -102,23 +135,42 @@ impl StateMerkleDb {
         let sharding = rocksdb_configs.enable_storage_sharding;
         let state_merkle_db_config = rocksdb_configs.state_merkle_db_config;
 
+        let max_versions_to_cache = rocksdb_configs
+            .versioned_node_cache_max_versions
+            .unwrap_or(VersionedNodeCache::DEFAULT_NUM_VERSIONS_TO_CACHE);
+        ensure!(
+            max_versions_to_cache >= VersionedNodeCache::MIN_NUM_VERSIONS_TO_CACHE,
+            "versioned_node_cache_max_versions must be at least {}, got {}.",
+            VersionedNodeCache::MIN_NUM_VERSIONS_TO_CACHE,
+            max_versions_to_cache,
+        );
         let mut version_caches = HashMap::with_capacity(NUM_STATE_SHARDS + 1);
-        version_caches.insert(None, VersionedNodeCache::new());
+        version_caches.insert(None, VersionedNodeCache::new(max_versions_to_cache));
         for i in 0..NUM_STATE_SHARDS {
-            version_caches.insert(Some(i), VersionedNodeCache::new());
+            version_caches.insert(Some(i), VersionedNodeCache::new(max_versions_to_cache));
         }
-        let lru_cache = NonZeroUsize::new(max_nodes_per_lru_cache_shard).map(LruNodeCache::new);
+        let lru_cache =
+            NonZeroUsize::new(max_nodes_per_lru_cache_shard).map(|max_nodes_per_shard| {
+                match rocksdb_configs.lru_node_cache_max_bytes {
+                    Some(max_bytes) => LruNodeCache::new_with_max_bytes(max_bytes),
+                    None => LruNodeCache::new(max_nodes_per_shard),
+                }
+            });
 
         if !sharding {
             assert!(!is_hot, "Hot state not supported for unsharded db.");
             info!("Sharded state merkle DB is not enabled!");
-            let state_merkle_db_path = db_paths.default_root_path().join(STATE_MERKLE_DB_NAME);
+            let state_merkle_db_path = db_paths
+                .state_merkle_db_metadata_root_path()
+                .join(STATE_MERKLE_DB_NAME);
             let db = Arc::new(Self::open_db(
                 state_merkle_db_path,
                 STATE_MERKLE_DB_NAME,
+                db_paths.wal_root_path(),
                 &state_merkle_db_config,
                 env,
                 block_cache,
+                write_buffer_manager,
                 readonly,
                 delete_on_restart,
             )?);
@@ -128,6 +180,8 @@ impl StateMerkleDb {
                 enable_sharding: false,
                 version_caches,
                 lru_cache,
+                cache_on_read_only: AtomicBool::new(rocksdb_configs.cache_jmt_nodes_on_read_only),
+                flush_on_commit: state_merkle_db_config.flush_on_commit,
             });
         }
 
@@ -136,9 +190,11 @@ impl StateMerkleDb {
             state_merkle_db_config,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
             version_caches,
             lru_cache,
+            rocksdb_configs.cache_jmt_nodes_on_read_only,
             is_hot,
             delete_on_restart,
         )
@@ -167,7 +223,17 @@ impl StateMerkleDb {
                 })
         });
 
-        self.commit_top_levels(version, top_levels_batch)
+        self.commit_top_levels(version, top_levels_batch)?;
+        if self.flush_on_commit {
+            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["state_merkle_db__flush_on_commit"]);
+            let cf_names = state_merkle_db_column_families();
+            THREAD_MANAGER.get_io_pool().install(|| {
+                self.all_dbs()
+                    .into_par_iter()
+                    .try_for_each(|db| db.flush_cfs(&cf_names))
+            })?;
+        }
+        Ok(())
     }
 
     /// Only used by fast sync / restore.
@@ -191,6 +257,7 @@ impl StateMerkleDb {
 
     pub(crate) fn create_checkpoint(
         db_root_path: impl AsRef<Path>,
+        wal_root_path: Option<&Path>,
         cp_root_path: impl AsRef<Path>,
         sharding: bool,
         is_hot: bool,
@@ -201,10 +268,11 @@ impl StateMerkleDb {
         };
         // TODO(grao): Support path override here.
         let state_merkle_db = Self::new(
-            &StorageDirPaths::from_path(db_root_path),
+            &dir_paths_with_wal(db_root_path, wal_root_path),
             rocksdb_configs,
             /*env=*/ None,
             /*block_cache=*/ None,
+            /*write_buffer_manager=*/ None,
             /*readonly=*/ false,
             /*max_nodes_per_lru_cache_shard=*/ 0,
             is_hot,
@@ -266,6 +334,16 @@ impl StateMerkleDb {
         }
     }
 
+    /// Returns all the underlying RocksDB instances, each of them exactly once.
+    pub(crate) fn all_dbs(&self) -> Vec<&DB> {
+        if !self.enable_sharding {
+            return vec![self.metadata_db()];
+        }
+        std::iter::once(self.metadata_db())
+            .chain(self.state_merkle_db_shards.iter().map(|db| db.as_ref()))
+            .collect()
+    }
+
     pub(crate) fn commit_top_levels(
         &self,
         version: Version,
@@ -287,6 +365,58 @@ impl StateMerkleDb {
         JellyfishMerkleTree::new(self).get_with_proof_ext(key, version, root_depth)
     }
 
+    /// Returns the number of internal nodes, i.e. nibbles, the proof of `key` at `version` walks
+    /// through before ending at a leaf or an empty subtree, without reading the siblings. Internal
+    /// nodes with a single leaf, which only exist for sharding and contribute no siblings, don't
+    /// count.
+    pub fn get_proof_depth(&self, key: &HashValue, version: Version) -> Result<usize> {
+        let tag = "get_proof_depth";
+        let nibble_path = NibblePath::new_even(key.to_vec());
+        let mut nibbles = nibble_path.nibbles();
+        let mut node_key = NodeKey::new_empty_path(version);
+        let mut depth = 0;
+        // Bounded like the proof walk, against cycles in a corrupted tree.
+        for _ in 0..=ROOT_NIBBLE_HEIGHT {
+            match self.get_node_with_tag(&node_key, tag)? {
+                Node::Internal(internal_node) => {
+                    let (nibble, child) = if internal_node.leaf_count() == 1 {
+                        let (nibble, child) = internal_node
+                            .children_sorted()
+                            .next()
+                            .expect("Internal node has no children.");
+                        (*nibble, child)
+                    } else {
+                        let nibble = nibbles
+                            .next()
+                            .ok_or_else(|| AptosDbError::Other("ran out of nibbles".to_string()))?;
+                        depth += 1;
+                        match internal_node.child(nibble) {
+                            Some(child) => (nibble, child),
+                            None => return Ok(depth),
+                        }
+                    };
+                    node_key = node_key.gen_child_node_key(child.version, nibble);
+                },
+                Node::Leaf(_) | Node::Null => return Ok(depth),
+            }
+        }
+        bail!("Jellyfish Merkle tree has cyclic graph inside.");
+    }
+
+    /// Returns whether every node the proof of `key` at `version` reads is present, i.e. the
+    /// nodes on the path from the root to the leaf or empty subtree the key ends at, and the leaf
+    /// siblings whose keys the proof carries. Meant as a check before serving a proof, so that a
+    /// partially pruned tree fails the request cleanly instead of producing a broken proof.
+    ///
+    /// Builds the proof to find out, reading through the node caches as serving it does.
+    pub fn is_proof_servable(&self, key: &HashValue, version: Version) -> Result<bool> {
+        match self.get_with_proof_ext(key, version, 0) {
+            Ok(_) => Ok(true),
+            Err(AptosDbError::NotFound(_) | AptosDbError::MissingRootError(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
     pub fn get_range_proof(
         &self,
         rightmost_key: HashValue,
@@ -303,6 +433,400 @@ impl StateMerkleDb {
         JellyfishMerkleTree::new(self).get_leaf_count(version)
     }
 
+    /// Returns the ids of the shards having any update at `version`, which needs to be a version
+    /// the tree is persisted at.
+    ///
+    /// The root of every non-empty shard is rewritten at each persisted version, so a node at
+    /// `version` alone doesn't mean the shard changed. Instead the shard root is compared with the
+    /// previous one, found through the stale node index, so the previous version of the tree must
+    /// not have been pruned yet.
+    pub fn changed_shards(&self, version: Version) -> Result<Vec<usize>> {
+        // Fails if the tree is not persisted at `version`.
+        self.get_root_hash(version)?;
+
+        let mut changed_shards = Vec::new();
+        for shard_id in 0..NUM_STATE_SHARDS {
+            let db = self.db_shard(shard_id);
+            let shard_root_nibble_path = NibblePath::new_odd(vec![(shard_id as u8) << 4]);
+            let root = db.get::<JellyfishMerkleNodeSchema>(&NodeKey::new(
+                version,
+                shard_root_nibble_path.clone(),
+            ))?;
+            let previous_root_key = match Self::find_stale_node_key::<StaleNodeIndexSchema>(
+                db,
+                version,
+                &shard_root_nibble_path,
+            )? {
+                Some(node_key) => Some(node_key),
+                None => Self::find_stale_node_key::<StaleNodeIndexCrossEpochSchema>(
+                    db,
+                    version,
+                    &shard_root_nibble_path,
+                )?,
+            };
+            let previous_root = previous_root_key
+                .map(|node_key| {
+                    db.get::<JellyfishMerkleNodeSchema>(&node_key)?
+                        .ok_or_else(|| AptosDbError::NotFound(format!("Node {node_key:?}.")))
+                })
+                .transpose()?;
+
+            if root.map(|node| node.hash()) != previous_root.map(|node| node.hash()) {
+                changed_shards.push(shard_id);
+            }
+        }
+        Ok(changed_shards)
+    }
+
+    /// Finds the key of the node at `nibble_path` that became stale at `version`.
+    fn find_stale_node_key<S>(
+        db: &DB,
+        version: Version,
+        nibble_path: &NibblePath,
+    ) -> Result<Option<NodeKey>>
+    where
+        S: Schema<Key = StaleNodeIndex>,
+        Version: SeekKeyCodec<S>,
+    {
+        let mut iter = db.iter::<S>()?;
+        iter.seek(&version)?;
+        for item in iter {
+            let (index, _) = item?;
+            if index.stale_since_version != version {
+                break;
+            }
+            if index.node_key.nibble_path() == nibble_path {
+                return Ok(Some(index.node_key));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Writes the subtree of the given shard at `version` in Graphviz DOT format, with internal
+    /// nodes and leaves labeled by their nibble paths. Meant for visualizing the tree shape, so it
+    /// walks the whole shard and should only be used on small trees.
+    pub fn export_dot(
+        &self,
+        version: Version,
+        shard_id: usize,
+        writer: &mut impl std::io::Write,
+    ) -> Result<()> {
+        ensure!(
+            shard_id < NUM_STATE_SHARDS,
+            "Invalid shard id: {}",
+            shard_id
+        );
+        let tag = "export_dot";
+        let dot_id = |node_key: &NodeKey| {
+            if node_key.nibble_path().is_empty() {
+                "root".to_string()
+            } else {
+                format!("{:?}", node_key.nibble_path())
+            }
+        };
+
+        let root_key = NodeKey::new_empty_path(version);
+        let subtree_root_key = match self.get_node_with_tag(&root_key, tag)? {
+            Node::Internal(internal_node) => internal_node
+                .children_sorted()
+                .find(|(nibble, _child)| usize::from(**nibble) == shard_id)
+                .map(|(nibble, child)| root_key.gen_child_node_key(child.version, *nibble)),
+            // A tree with a single leaf has no shards, export the root leaf itself.
+            Node::Leaf(_) => Some(root_key),
+            Node::Null => None,
+        };
+
+        writeln!(writer, "digraph \"shard_{shard_id}_version_{version}\" {{")?;
+        let mut pending = subtree_root_key.into_iter().collect::<Vec<_>>();
+        while let Some(node_key) = pending.pop() {
+            let id = dot_id(&node_key);
+            match self.get_node_with_tag(&node_key, tag)? {
+                Node::Internal(internal_node) => {
+                    writeln!(writer, "  \"{id}\" [shape=circle, label=\"{id}\"];")?;
+                    for (nibble, child) in internal_node.children_sorted() {
+                        let child_key = node_key.gen_child_node_key(child.version, *nibble);
+                        writeln!(
+                            writer,
+                            "  \"{id}\" -> \"{}\" [label=\"{:x}\"];",
+                            dot_id(&child_key),
+                            nibble
+                        )?;
+                        pending.push(child_key);
+                    }
+                },
+                Node::Leaf(leaf_node) => writeln!(
+                    writer,
+                    "  \"{id}\" [shape=box, label=\"{id}\\n{}..\"];",
+                    &leaf_node.account_key().to_hex()[..8]
+                )?,
+                Node::Null => (),
+            }
+        }
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    /// Writes the subtree of the given shard in the tree at `version`, for `import_shard()` to
+    /// restore it into the same shard of another DB having the tree at `version`, e.g. to repair
+    /// a shard from a healthy copy without copying the whole DB. Returns the number of nodes
+    /// written.
+    ///
+    /// The output is the version, then the encoded key and node of each node of the subtree, each
+    /// prefixed by its length.
+    pub fn export_shard(
+        &self,
+        shard_id: usize,
+        version: Version,
+        writer: &mut impl std::io::Write,
+    ) -> Result<usize> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["export_shard"]);
+        ensure!(
+            shard_id < NUM_STATE_SHARDS,
+            "Invalid shard id: {}",
+            shard_id
+        );
+
+        writer.write_u64::<BigEndian>(version)?;
+        let mut num_nodes = 0;
+        let mut to_visit = self
+            .shard_root_key(version, shard_id)?
+            .into_iter()
+            .collect::<Vec<_>>();
+        while let Some(node_key) = to_visit.pop() {
+            let node = self
+                .db_shard(shard_id)
+                .get::<JellyfishMerkleNodeSchema>(&node_key)?
+                .ok_or_else(|| AptosDbError::NotFound(format!("Node {node_key:?}.")))?;
+            write_length_prefixed(writer, &node_key.encode()?)?;
+            write_length_prefixed(writer, &node.encode()?)?;
+            if let Node::Internal(internal_node) = node {
+                for (nibble, child) in internal_node.children_sorted() {
+                    to_visit.push(node_key.gen_child_node_key(child.version, *nibble));
+                }
+            }
+            num_nodes += 1;
+        }
+        Ok(num_nodes)
+    }
+
+    /// Writes the nodes exported by `export_shard()` into the given shard. They're checked as they
+    /// come against the top levels of the tree at the exported version, which must already be in
+    /// the DB: the root recomputed with the hash of the shard root must be the root of that
+    /// version, each node must come in the order `export_shard()` writes them and hash to what its
+    /// parent has for it, and none may be missing or left over. Returns the number of nodes
+    /// imported.
+    ///
+    /// The nodes are written in batches of about `IMPORT_SHARD_BATCH_SIZE` as they're checked, so a
+    /// failure can leave part of them written, all of them in the tree, which a retry finishes.
+    /// The nodes that were in the shard at the positions of the subtree but aren't in it are
+    /// indexed as stale since the exported version, for the epoch snapshot pruner, the later of
+    /// the two, to delete them.
+    pub fn import_shard(&self, shard_id: usize, reader: &mut impl std::io::Read) -> Result<usize> {
+        const IMPORT_SHARD_BATCH_SIZE: usize = 10_000;
+
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["import_shard"]);
+        ensure!(
+            shard_id < NUM_STATE_SHARDS,
+            "Invalid shard id: {}",
+            shard_id
+        );
+
+        let version = reader.read_u64::<BigEndian>()?;
+        let db = self.db_shard(shard_id);
+        let mut batch = SchemaBatch::new();
+        let mut batch_size = 0;
+        let mut num_nodes = 0;
+        // The nodes expected next, in the order they're exported, with the hash their parent has
+        // for them (none for the shard root) and the key of the node at their position in the
+        // shard before the import, if any.
+        let mut to_import = self
+            .shard_root_key(version, shard_id)?
+            .map(|node_key| (node_key.clone(), None, Some(node_key)))
+            .into_iter()
+            .collect::<Vec<_>>();
+        while let Some(encoded_key) = read_length_prefixed(reader)? {
+            let node_key = NodeKey::decode(&encoded_key)?;
+            let Some(encoded_node) = read_length_prefixed(reader)? else {
+                bail!("Node {:?} is truncated.", node_key);
+            };
+            let Some((expected_key, expected_hash, old_key)) = to_import.pop() else {
+                bail!(
+                    "Node {:?} is not in the subtree of shard {} at version {}.",
+                    node_key,
+                    shard_id,
+                    version,
+                );
+            };
+            ensure!(
+                node_key == expected_key,
+                "Got node {:?}, expected {:?}.",
+                node_key,
+                expected_key,
+            );
+            let node = Node::decode(&encoded_node)?;
+            let hash = node.hash();
+            match expected_hash {
+                Some(expected_hash) => ensure!(
+                    hash == expected_hash,
+                    "Node {:?} has hash {}, its parent expects {}.",
+                    node_key,
+                    hash,
+                    expected_hash,
+                ),
+                None => self.verify_shard_root_hash(version, shard_id, hash)?,
+            }
+
+            let old_node = match &old_key {
+                Some(old_key) => db.get::<JellyfishMerkleNodeSchema>(old_key)?,
+                None => None,
+            };
+            if let Some(old_key) = old_key
+                && old_node.is_some()
+                && old_key != node_key
+            {
+                batch.put::<StaleNodeIndexCrossEpochSchema>(
+                    &StaleNodeIndex {
+                        stale_since_version: version,
+                        node_key: old_key,
+                    },
+                    &(),
+                )?;
+                batch_size += 1;
+            }
+            let old_internal_node = match old_node {
+                Some(Node::Internal(internal_node)) => Some(internal_node),
+                Some(Node::Leaf(_)) | Some(Node::Null) | None => None,
+            };
+            let internal_node = match &node {
+                Node::Internal(internal_node) => Some(internal_node),
+                Node::Leaf(_) | Node::Null => None,
+            };
+            if let Some(internal_node) = internal_node {
+                for (nibble, child) in internal_node.children_sorted() {
+                    let old_child_key = old_internal_node
+                        .as_ref()
+                        .and_then(|old| old.child(*nibble))
+                        .map(|old_child| node_key.gen_child_node_key(old_child.version, *nibble));
+                    to_import.push((
+                        node_key.gen_child_node_key(child.version, *nibble),
+                        Some(child.hash),
+                        old_child_key,
+                    ));
+                }
+            }
+            if let Some(old_internal_node) = &old_internal_node {
+                // Nothing of the subtree is under the children it doesn't have.
+                for (nibble, old_child) in old_internal_node.children_sorted() {
+                    if internal_node
+                        .is_none_or(|internal_node| internal_node.child(*nibble).is_none())
+                    {
+                        batch_size += self.put_stale_subtree(
+                            shard_id,
+                            node_key.gen_child_node_key(old_child.version, *nibble),
+                            version,
+                            &mut batch,
+                        )?;
+                    }
+                }
+            }
+
+            batch.put::<JellyfishMerkleNodeSchema>(&node_key, &node)?;
+            batch_size += 1;
+            num_nodes += 1;
+            if batch_size >= IMPORT_SHARD_BATCH_SIZE {
+                db.write_schemas(std::mem::take(&mut batch))?;
+                batch_size = 0;
+            }
+        }
+        if let Some((node_key, _expected_hash, _old_key)) = to_import.pop() {
+            bail!("Node {:?} is not imported.", node_key);
+        }
+        db.write_schemas(batch)?;
+        Ok(num_nodes)
+    }
+
+    /// Checks that the root recomputed with `shard_root_hash` for the given shard is the root of
+    /// the tree at `version`.
+    fn verify_shard_root_hash(
+        &self,
+        version: Version,
+        shard_id: usize,
+        shard_root_hash: HashValue,
+    ) -> Result<()> {
+        let root_key = NodeKey::new_empty_path(version);
+        let Node::Internal(root) = self.get_node_with_tag(&root_key, "import_shard")? else {
+            bail!("The tree at version {} has no shards.", version);
+        };
+        let shard_nibble = Nibble::from(shard_id as u8);
+        let recomputed_root_hash = InternalNode::new(Children::from_sorted(
+            root.children_sorted().map(|(nibble, child)| {
+                let mut child = child.clone();
+                if *nibble == shard_nibble {
+                    child.hash = shard_root_hash;
+                }
+                (*nibble, child)
+            }),
+        ))
+        .hash();
+        let root_hash = self.get_root_hash(version)?;
+        ensure!(
+            recomputed_root_hash == root_hash,
+            "Root recomputed with the imported shard {} is {}, expected {}.",
+            shard_id,
+            recomputed_root_hash,
+            root_hash,
+        );
+        Ok(())
+    }
+
+    /// Indexes the nodes of the subtree at `root_key` in the given shard as stale since
+    /// `stale_since_version`, returning how many there are.
+    fn put_stale_subtree(
+        &self,
+        shard_id: usize,
+        root_key: NodeKey,
+        stale_since_version: Version,
+        batch: &mut SchemaBatch,
+    ) -> Result<usize> {
+        let mut num_nodes = 0;
+        let mut to_visit = vec![root_key];
+        while let Some(node_key) = to_visit.pop() {
+            let Some(node) = self
+                .db_shard(shard_id)
+                .get::<JellyfishMerkleNodeSchema>(&node_key)?
+            else {
+                continue;
+            };
+            if let Node::Internal(internal_node) = node {
+                for (nibble, child) in internal_node.children_sorted() {
+                    to_visit.push(node_key.gen_child_node_key(child.version, *nibble));
+                }
+            }
+            batch.put::<StaleNodeIndexCrossEpochSchema>(
+                &StaleNodeIndex {
+                    stale_since_version,
+                    node_key,
+                },
+                &(),
+            )?;
+            num_nodes += 1;
+        }
+        Ok(num_nodes)
+    }
+
+    /// Returns the key of the root of the subtree of the given shard in the tree at `version`, if
+    /// any.
+    fn shard_root_key(&self, version: Version, shard_id: usize) -> Result<Option<NodeKey>> {
+        let root_key = NodeKey::new_empty_path(version);
+        let shard_nibble = Nibble::from(shard_id as u8);
+        Ok(match self.get_node_with_tag(&root_key, "shard_root_key")? {
+            Node::Internal(internal_node) => internal_node
+                .child(shard_nibble)
+                .map(|child| root_key.gen_child_node_key(child.version, shard_nibble)),
+            Node::Leaf(_) | Node::Null => None,
+        })
+    }
+
     pub fn batch_put_value_set_for_shard(
         &self,
         shard_id: usize,
@@ -347,6 +871,71 @@ impl StateMerkleDb {
         Ok(None)
     }
 
+    /// Returns the first version in `[search_range.0, search_range.1)` the tree is persisted at
+    /// with `root` as its root hash, e.g. to tell when a root hash reported elsewhere was
+    /// produced. Only the versions the tree is persisted at are looked at, and the pruned ones are
+    /// not found.
+    pub fn find_version_by_root(
+        &self,
+        root: HashValue,
+        search_range: (Version, Version),
+    ) -> Result<Option<Version>> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["find_version_by_root"]);
+        let (begin, end) = search_range;
+
+        for res in self.roots_from(begin)? {
+            let (version, node) = res?;
+            if version >= end {
+                break;
+            }
+            if node.hash() == root {
+                return Ok(Some(version));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the versions in `[begin, end)` the tree is persisted at, in ascending order. The
+    /// ones pruned, or whose commit hasn't finished, i.e. whose root isn't written yet, are left
+    /// out.
+    pub fn get_snapshot_versions(&self, begin: Version, end: Version) -> Result<Vec<Version>> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["get_snapshot_versions"]);
+
+        let mut versions = Vec::new();
+        for res in self.roots_from(begin)? {
+            let (version, _root) = res?;
+            if version >= end {
+                break;
+            }
+            versions.push(version);
+        }
+        Ok(versions)
+    }
+
+    /// Iterates the roots of the trees persisted at `begin` and later, with their versions in
+    /// ascending order. The versions pruned, or whose commit hasn't finished, i.e. whose root isn't
+    /// written yet, are skipped, seeking past their nodes.
+    fn roots_from(&self, begin: Version) -> Result<impl Iterator<Item = Result<(Version, Node)>>> {
+        let mut iter = self.metadata_db().iter::<JellyfishMerkleNodeSchema>()?;
+        let mut next_version = Some(begin);
+        let mut next_root = move || -> Result<Option<(Version, Node)>> {
+            while let Some(version) = next_version {
+                // The root sorts first among the nodes of a version.
+                iter.seek(&NodeKey::new_empty_path(version))?;
+                let Some((node_key, node)) = iter.next().transpose()? else {
+                    break;
+                };
+                next_version = node_key.version().checked_add(1);
+                if node_key.nibble_path().num_nibbles() == 0 {
+                    return Ok(Some((node_key.version(), node)));
+                }
+            }
+            next_version = None;
+            Ok(None)
+        };
+        Ok(std::iter::from_fn(move || next_root().transpose()))
+    }
+
     pub(crate) fn create_jmt_commit_batch_for_shard(
         &self,
         version: Version,
@@ -451,6 +1040,57 @@ impl StateMerkleDb {
         Ok((top_levels_batch, sharded_batches, root_hash))
     }
 
+    /// Merklizes `value_set` on top of the tree at `base_version`, returning the root hash and
+    /// all the nodes created for `version`. Unlike `merklize_value_set`, nothing is written to
+    /// the DB or the node caches, and the result only depends on the input and the persisted
+    /// tree, which makes it suitable for fuzzing against a reference implementation.
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub fn merklize_for_test(
+        &self,
+        value_set: Vec<(HashValue, Option<&(HashValue, StateKey)>)>,
+        version: Version,
+        base_version: Option<Version>,
+    ) -> Result<(HashValue, Vec<Node>)> {
+        let mut sharded_value_set: Vec<Vec<(HashValue, Option<&(HashValue, StateKey)>)>> =
+            Vec::new();
+        sharded_value_set.resize(NUM_STATE_SHARDS, Default::default());
+        value_set.into_iter().for_each(|(k, v)| {
+            sharded_value_set[get_state_shard_id(&k) as usize].push((k, v));
+        });
+
+        let mut nodes = Vec::new();
+        let mut shard_root_nodes = Vec::with_capacity(NUM_STATE_SHARDS);
+        for (shard_id, value_set) in sharded_value_set.into_iter().enumerate() {
+            let (shard_root_node, tree_update_batch) = self.batch_put_value_set_for_shard(
+                shard_id,
+                value_set,
+                /*node_hashes=*/ None,
+                base_version,
+                version,
+            )?;
+            shard_root_nodes.push(shard_root_node);
+            nodes.extend(
+                tree_update_batch
+                    .node_batch
+                    .into_iter()
+                    .flatten()
+                    .map(|(_node_key, node)| node),
+            );
+        }
+
+        let (root_hash, _leaf_count, tree_update_batch) = JellyfishMerkleTree::new(self)
+            .put_top_levels_nodes(shard_root_nodes, base_version, version)?;
+        nodes.extend(
+            tree_update_batch
+                .node_batch
+                .into_iter()
+                .flatten()
+                .map(|(_node_key, node)| node),
+        );
+
+        Ok((root_hash, nodes))
+    }
+
     /// Calculates db updates for nodes in shard `shard_id`.
     ///
     /// Assumes 16 shards in total for now.
@@ -553,6 +1193,192 @@ impl StateMerkleDb {
         self.enable_sharding
     }
 
+    /// Returns the number of leaves at each depth, in nibbles, of the tree at `version`, or of the
+    /// subtree of a single shard if `shard_id` is given. The deeper a leaf, the larger its proof.
+    ///
+    /// Leaves are counted from their parents, so only the internal nodes are read, bypassing the
+    /// node caches, but it's still a walk of the whole (sub)tree.
+    pub fn depth_histogram(&self, version: Version, shard_id: Option<usize>) -> Result<Vec<u64>> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["depth_histogram"]);
+        if let Some(shard_id) = shard_id {
+            ensure!(
+                shard_id < NUM_STATE_SHARDS,
+                "Invalid shard_id: {}",
+                shard_id
+            );
+        }
+        let in_shard = |first_nibble: u8| shard_id.is_none_or(|id| first_nibble as usize == id);
+
+        let mut histogram = Vec::new();
+        let mut count_leaf = |depth: usize| {
+            if histogram.len() <= depth {
+                histogram.resize(depth + 1, 0);
+            }
+            histogram[depth] += 1;
+        };
+        let mut to_visit = vec![NodeKey::new_empty_path(version)];
+        while let Some(node_key) = to_visit.pop() {
+            let depth = node_key.nibble_path().num_nibbles();
+            let node = self
+                .db_by_key(&node_key)
+                .get::<JellyfishMerkleNodeSchema>(&node_key)?
+                .ok_or_else(|| AptosDbError::NotFound(format!("Node {node_key:?}.")))?;
+            match node {
+                Node::Internal(internal_node) => {
+                    for (nibble, child) in internal_node.children_sorted() {
+                        if depth == 0 && !in_shard(u8::from(*nibble)) {
+                            continue;
+                        }
+                        if child.is_leaf() {
+                            count_leaf(depth + 1);
+                        } else {
+                            to_visit.push(node_key.gen_child_node_key(child.version, *nibble));
+                        }
+                    }
+                },
+                // Only the root can be a leaf here.
+                Node::Leaf(leaf_node) => {
+                    if in_shard(leaf_node.account_key().nibble(0)) {
+                        count_leaf(depth);
+                    }
+                },
+                Node::Null => (),
+            }
+        }
+
+        Ok(histogram)
+    }
+
+    /// Returns the number of nodes and their total encoded size in bytes of the subtree of each
+    /// shard in the tree at `version`, i.e. of the subtree under each child of the root. The root
+    /// itself is left out unless it's a leaf, which is then counted in the shard of its key.
+    ///
+    /// Like `depth_histogram()`, it's a walk of the whole tree, bypassing the node caches.
+    pub fn shard_subtree_sizes(&self, version: Version) -> Result<[(u64, u64); NUM_STATE_SHARDS]> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["shard_subtree_sizes"]);
+
+        let mut sizes = [(0, 0); NUM_STATE_SHARDS];
+        let mut to_visit = vec![NodeKey::new_empty_path(version)];
+        while let Some(node_key) = to_visit.pop() {
+            let node = self
+                .db_by_key(&node_key)
+                .get::<JellyfishMerkleNodeSchema>(&node_key)?
+                .ok_or_else(|| AptosDbError::NotFound(format!("Node {node_key:?}.")))?;
+            let shard_id = match node_key.get_shard_id() {
+                Some(shard_id) => Some(shard_id),
+                None => match &node {
+                    Node::Leaf(leaf_node) => Some(leaf_node.account_key().nibble(0) as usize),
+                    Node::Internal(_) | Node::Null => None,
+                },
+            };
+            if let Some(shard_id) = shard_id {
+                let (num_nodes, num_bytes) = &mut sizes[shard_id];
+                *num_nodes += 1;
+                *num_bytes += node.encoded_len()? as u64;
+            }
+            if let Node::Internal(internal_node) = node {
+                for (nibble, child) in internal_node.children_sorted() {
+                    to_visit.push(node_key.gen_child_node_key(child.version, *nibble));
+                }
+            }
+        }
+
+        Ok(sizes)
+    }
+
+    /// Returns the total encoded size in bytes of the internal nodes and of the leaves created
+    /// after `from_version` up to `to_version` inclusively, i.e. what the commits of these versions
+    /// added to the tree on disk, as opposed to the process wide encoded bytes metrics. Only the
+    /// nodes still in the DB are counted, so the range is expected to be neither pruned nor
+    /// beyond the last persisted snapshot.
+    pub fn encoded_bytes_delta(
+        &self,
+        from_version: Version,
+        to_version: Version,
+    ) -> Result<(u64, u64)> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["encoded_bytes_delta"]);
+        ensure!(
+            from_version <= to_version,
+            "from_version {} is after to_version {}.",
+            from_version,
+            to_version,
+        );
+        let Some(start_version) = from_version.checked_add(1) else {
+            return Ok((0, 0));
+        };
+
+        let mut internal_bytes = 0;
+        let mut leaf_bytes = 0;
+        for db in self.all_dbs() {
+            let mut iter = db.iter::<JellyfishMerkleNodeSchema>()?;
+            // The nodes are ordered by version first.
+            iter.seek(&NodeKey::new_empty_path(start_version))?;
+            for item in iter {
+                let (node_key, node) = item?;
+                if node_key.version() > to_version {
+                    break;
+                }
+                match node {
+                    Node::Internal(_) => internal_bytes += node.encoded_len()? as u64,
+                    Node::Leaf(_) => leaf_bytes += node.encoded_len()? as u64,
+                    Node::Null => (),
+                }
+            }
+        }
+
+        Ok((internal_bytes, leaf_bytes))
+    }
+
+    /// Checks that the pruner left the trees at all versions from `min_readable_version` on whole,
+    /// i.e. that every node reachable from the root of each of them, which are the versions the
+    /// tree is persisted at, is still in the DB. Errors on the first missing node.
+    ///
+    /// Only the first of the trees is walked in full. A node of a later tree that's no newer than
+    /// the tree before it is part of that one as well, so only the nodes written since are walked
+    /// for the rest. It's still a walk of a whole tree, meant for checks after pruning rather than
+    /// the serving path.
+    pub fn verify_no_dangling_after_prune(&self, min_readable_version: Version) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["verify_no_dangling_after_prune"]);
+
+        let mut prev_root_version = None;
+        for res in self.roots_from(min_readable_version)? {
+            let (version, _root) = res?;
+            let min_node_version = prev_root_version.map_or(0, |v: Version| v + 1);
+            self.verify_reachable_nodes_exist(NodeKey::new_empty_path(version), min_node_version)?;
+            prev_root_version = Some(version);
+        }
+        Ok(())
+    }
+
+    fn verify_reachable_nodes_exist(
+        &self,
+        root_key: NodeKey,
+        min_node_version: Version,
+    ) -> Result<()> {
+        let root_version = root_key.version();
+        let mut to_visit = vec![root_key];
+        while let Some(node_key) = to_visit.pop() {
+            let Some(node) = self
+                .db_by_key(&node_key)
+                .get::<JellyfishMerkleNodeSchema>(&node_key)?
+            else {
+                bail!(
+                    "Node {:?} reachable from the root at version {} is missing.",
+                    node_key,
+                    root_version,
+                );
+            };
+            if let Node::Internal(internal_node) = node {
+                for (nibble, child) in internal_node.children_sorted() {
+                    if child.version >= min_node_version {
+                        to_visit.push(node_key.gen_child_node_key(child.version, *nibble));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn cache_enabled(&self) -> bool {
         self.lru_cache.is_some()
     }
@@ -565,6 +1391,16 @@ impl StateMerkleDb {
         self.lru_cache.as_ref()
     }
 
+    /// Stops, or resumes, putting the nodes written into the LRU cache, see
+    /// `RocksdbConfigs::cache_jmt_nodes_on_read_only`.
+    pub(crate) fn set_cache_on_read_only(&self, enabled: bool) {
+        self.cache_on_read_only.store(enabled, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cache_on_read_only(&self) -> bool {
+        self.cache_on_read_only.load(Ordering::Relaxed)
+    }
+
     pub(crate) fn write_pruner_progress(
         &self,
         progress_key: &DbMetadataKey,
@@ -599,9 +1435,11 @@ impl StateMerkleDb {
         state_merkle_db_config: RocksdbConfig,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
         version_caches: HashMap<Option<usize>, VersionedNodeCache>,
         lru_cache: Option<LruNodeCache>,
+        cache_on_read_only: bool,
         is_hot: bool,
         delete_on_restart: bool,
     ) -> Result<Self> {
@@ -618,9 +1456,11 @@ impl StateMerkleDb {
         let state_merkle_metadata_db = Arc::new(Self::open_db(
             state_merkle_metadata_db_path.clone(),
             metadata_db_name(is_hot),
+            db_paths.wal_root_path(),
             &state_merkle_db_config,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
             delete_on_restart,
         )?);
@@ -641,9 +1481,11 @@ impl StateMerkleDb {
                 let db = Self::open_shard(
                     shard_root_path,
                     shard_id,
+                    db_paths.wal_root_path(),
                     &state_merkle_db_config,
                     env,
                     block_cache,
+                    write_buffer_manager,
                     readonly,
                     is_hot,
                     delete_on_restart,
@@ -663,6 +1505,8 @@ impl StateMerkleDb {
             enable_sharding: true,
             version_caches,
             lru_cache,
+            cache_on_read_only: AtomicBool::new(cache_on_read_only),
+            flush_on_commit: state_merkle_db_config.flush_on_commit,
         };
 
         if !readonly {
@@ -682,9 +1526,11 @@ impl StateMerkleDb {
     fn open_shard<P: AsRef<Path>>(
         db_root_path: P,
         shard_id: usize,
+        wal_root_path: Option<&Path>,
         state_merkle_db_config: &RocksdbConfig,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
         is_hot: bool,
         delete_on_restart: bool,
@@ -697,9 +1543,11 @@ impl StateMerkleDb {
         Self::open_db(
             Self::db_shard_path(db_root_path, shard_id, is_hot),
             &db_name,
+            wal_root_path,
             state_merkle_db_config,
             env,
             block_cache,
+            write_buffer_manager,
             readonly,
             delete_on_restart,
         )
@@ -708,9 +1556,11 @@ impl StateMerkleDb {
     fn open_db(
         path: PathBuf,
         name: &str,
+        wal_root_path: Option<&Path>,
         state_merkle_db_config: &RocksdbConfig,
         env: Option<&Env>,
         block_cache: Option<&Cache>,
+        write_buffer_manager: Option<&WriteBufferManager>,
         readonly: bool,
         delete_on_restart: bool,
     ) -> Result<DB> {
@@ -718,18 +1568,26 @@ impl StateMerkleDb {
             ensure!(!readonly, "Should not reset DB in read-only mode.");
             info!("delete_on_restart is true. Removing {path:?} entirely.");
             std::fs::remove_dir_all(&path).unwrap_or(());
+            if let Some(wal_root_path) = wal_root_path {
+                std::fs::remove_dir_all(wal_root_path.join(name)).unwrap_or(());
+            }
         }
 
+        let mut rocksdb_opts = gen_rocksdb_options(state_merkle_db_config, env, readonly);
+        if let Some(write_buffer_manager) = write_buffer_manager {
+            rocksdb_opts.set_write_buffer_manager(write_buffer_manager);
+        }
+        set_wal_dir(&mut rocksdb_opts, wal_root_path, name, readonly)?;
         Ok(if readonly {
             DB::open_cf_readonly(
-                &gen_rocksdb_options(state_merkle_db_config, env, true),
+                &rocksdb_opts,
                 path,
                 name,
                 gen_state_merkle_cfds(state_merkle_db_config, block_cache),
             )?
         } else {
             DB::open_cf(
-                &gen_rocksdb_options(state_merkle_db_config, env, false),
+                &rocksdb_opts,
                 path,
                 name,
                 gen_state_merkle_cfds(state_merkle_db_config, block_cache),
@@ -56,6 +56,7 @@ impl BufferedState {
         state_db: &Arc<StateDb>,
         last_snapshot: StateWithSummary,
         target_items: usize,
+        commit_concurrency: Option<usize>,
         out_current_state: Arc<Mutex<LedgerStateWithSummary>>,
         out_persisted_state: PersistedState,
     ) -> Self {
@@ -77,6 +78,7 @@ impl BufferedState {
                     state_commit_receiver,
                     last_snapshot_clone,
                     persisted_state_clone,
+                    commit_concurrency,
                 );
                 committer.run();
             })
@@ -6,7 +6,7 @@ use anyhow::{ensure, Result};
 use aptos_config::config::HotStateConfig;
 use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
-use aptos_metrics_core::{IntCounterVecHelper, IntGaugeVecHelper, TimerHelper};
+use aptos_metrics_core::{IntCounterVecHelper, TimerHelper};
 use aptos_storage_interface::state_store::{
     state::State, state_view::hot_state_view::HotStateView,
 };
@@ -6,9 +6,10 @@
 use crate::{
     ledger_db::LedgerDb,
     metrics::{OTHER_TIMERS_SECONDS, STATE_ITEMS, TOTAL_STATE_BYTES},
-    pruner::{StateKvPrunerManager, StateMerklePrunerManager},
+    pruner::{PrunerManager, StateKvPrunerManager, StateMerklePrunerManager},
     schema::{
         db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+        jellyfish_merkle_node::JellyfishMerkleNodeSchema,
         stale_node_index::StaleNodeIndexSchema,
         stale_node_index_cross_epoch::StaleNodeIndexCrossEpochSchema,
         stale_state_value_index::StaleStateValueIndexSchema,
@@ -20,12 +21,18 @@ use crate::{
     state_kv_db::StateKvDb,
     state_merkle_db::StateMerkleDb,
     state_restore::{StateSnapshotRestore, StateSnapshotRestoreMode, StateValueWriter},
-    state_store::{buffered_state::BufferedState, persisted_state::PersistedState},
+    state_store::{
+        buffered_state::BufferedState,
+        persisted_state::PersistedState,
+        transition_proof::{StateTransitionEntry, StateTransitionProof},
+    },
     utils::{
+        get_progress,
         iterators::PrefixedStateValueIterator,
         truncation_helper::{
-            find_tree_root_at_or_before, get_max_version_in_state_merkle_db, truncate_ledger_db,
-            truncate_state_kv_db, truncate_state_merkle_db,
+            find_tree_root_at_or_before, get_max_version_in_state_merkle_db,
+            get_state_kv_commit_progress, truncate_ledger_db, truncate_state_kv_db,
+            truncate_state_merkle_db,
         },
         ShardedStateKvSchemaBatch,
     },
@@ -38,18 +45,22 @@ use aptos_crypto::{
 use aptos_db_indexer::db_indexer::InternalIndexerDB;
 use aptos_db_indexer_schemas::{
     metadata::{MetadataKey, MetadataValue, StateSnapshotProgress},
-    schema::indexer_metadata::InternalIndexerMetadataSchema,
+    schema::{indexer_metadata::InternalIndexerMetadataSchema, state_keys::StateKeysSchema},
 };
+use aptos_experimental_runtimes::thread_manager::THREAD_MANAGER;
 use aptos_infallible::Mutex;
 use aptos_jellyfish_merkle::{
     iterator::JellyfishMerkleIterator,
-    node_type::{Node, NodeKey},
+    node_type::{LeafNode, Node, NodeKey},
     TreeUpdateBatch,
 };
 use aptos_logger::info;
 use aptos_metrics_core::TimerHelper;
-use aptos_schemadb::batch::{NativeBatch, SchemaBatch, WriteBatch};
-use aptos_scratchpad::SparseMerkleTree;
+use aptos_schemadb::{
+    batch::{NativeBatch, SchemaBatch, WriteBatch},
+    ReadOptions,
+};
+use aptos_scratchpad::{ProofRead, SparseMerkleTree};
 use aptos_storage_interface::{
     db_ensure as ensure, db_other_bail as bail,
     state_store::{
@@ -67,9 +78,10 @@ use aptos_storage_interface::{
     AptosDbError, DbReader, Result, StateSnapshotReceiver,
 };
 use aptos_types::{
+    account_address::AccountAddress,
     proof::{definition::LeafCount, SparseMerkleProofExt, SparseMerkleRangeProof},
     state_store::{
-        state_key::{prefix::StateKeyPrefix, StateKey},
+        state_key::{inner::StateKeyInner, prefix::StateKeyPrefix, StateKey},
         state_slot::StateSlot,
         state_storage_usage::StateStorageUsage,
         state_value::{
@@ -78,17 +90,25 @@ use aptos_types::{
         },
         NUM_STATE_SHARDS,
     },
-    transaction::Version,
+    transaction::{Transaction, Version},
 };
+use bytes::Bytes;
 use claims::{assert_ge, assert_le};
-use itertools::Itertools;
-use rayon::prelude::*;
+use itertools::{EitherOrBoth, Itertools};
+use move_core_types::language_storage::ModuleId;
+use rayon::{prelude::*, ThreadPool};
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
     ops::Deref,
+    path::Path,
     sync::{Arc, MutexGuard},
 };
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
 
 pub(crate) mod buffered_state;
+pub(crate) mod sst_ingest;
 mod state_merkle_batch_committer;
 mod state_snapshot_committer;
 
@@ -96,6 +116,7 @@ pub mod hot_state;
 mod persisted_state;
 #[cfg(test)]
 mod tests;
+pub mod transition_proof;
 
 type StateValueBatch = crate::state_restore::StateValueBatch<StateKey, Option<StateValue>>;
 
@@ -106,6 +127,82 @@ const MAX_WRITE_SETS_AFTER_SNAPSHOT: LeafCount = buffered_state::TARGET_SNAPSHOT
 
 pub const MAX_COMMIT_PROGRESS_DIFFERENCE: u64 = 1_000_000;
 
+/// How many items the background scan of `StateStore::state_value_stream` can get ahead of the
+/// consumer of the stream.
+const STATE_VALUE_STREAM_BUFFER_SIZE: usize = 1000;
+
+/// A key whose value differs between the two states compared by `StateStore::compare_states`,
+/// with its value in each of them (`a` being the store compared from), `None` if it doesn't exist
+/// there.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StateDiffEntry {
+    pub state_key: StateKey,
+    pub value_a: Option<StateValue>,
+    pub value_b: Option<StateValue>,
+}
+
+/// The latest versions of the sub-DBs, either as found on open before
+/// `StateStore::sync_commit_progress` truncates them to the overall commit progress, see
+/// `AptosDB::subdb_version_skew`, or as of now, see `AptosDB::sub_db_versions`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SubDbVersions {
+    /// The synced version, written once all the sub-DBs have the data of a commit.
+    pub overall_commit_progress: Option<Version>,
+    pub ledger_commit_progress: Option<Version>,
+    pub state_kv_commit_progress: Option<Version>,
+    /// The latest version the state merkle DB has a tree at, normally behind the others since the
+    /// latest state is kept in memory.
+    pub state_merkle_max_version: Option<Version>,
+}
+
+impl SubDbVersions {
+    pub(crate) fn read(
+        ledger_db: &LedgerDb,
+        state_kv_db: &StateKvDb,
+        state_merkle_db: &StateMerkleDb,
+    ) -> Result<Self> {
+        Ok(Self {
+            overall_commit_progress: ledger_db.metadata_db().get_synced_version()?,
+            ledger_commit_progress: get_progress(
+                &ledger_db.metadata_db_arc(),
+                &DbMetadataKey::LedgerCommitProgress,
+            )?,
+            state_kv_commit_progress: get_state_kv_commit_progress(state_kv_db)?,
+            state_merkle_max_version: get_max_version_in_state_merkle_db(state_merkle_db)?,
+        })
+    }
+
+    /// Returns whether `version` is committed to the ledger DB and the state KV DB, as well as to
+    /// the overall commit progress. The state merkle DB isn't checked: it only persists a snapshot
+    /// every so often, and the state after it is replayed from the ledger on open.
+    pub fn is_version_committed(&self, version: Version) -> bool {
+        [
+            self.overall_commit_progress,
+            self.ledger_commit_progress,
+            self.state_kv_commit_progress,
+        ]
+        .into_iter()
+        .all(|progress| progress.is_some_and(|progress| progress >= version))
+    }
+
+    /// Returns whether the versions are what interrupted commits can leave behind, i.e. what
+    /// `StateStore::sync_commit_progress` truncates: the ledger and the state K/V DBs not behind
+    /// the overall commit progress, nor more than `MAX_COMMIT_PROGRESS_DIFFERENCE` versions ahead
+    /// of it, and neither is the state merkle DB, which is normally behind. Otherwise part of a
+    /// commit was lost, or a sub-DB comes from another DB.
+    pub fn is_consistent(&self) -> bool {
+        // The number of versions committed, so that `None` compares as nothing committed.
+        let num_versions = |version: Option<Version>| version.map_or(0, |v| v.saturating_add(1));
+        let overall = num_versions(self.overall_commit_progress);
+        let max_allowed = overall.saturating_add(MAX_COMMIT_PROGRESS_DIFFERENCE);
+        let in_window = |version| (overall..=max_allowed).contains(&num_versions(version));
+
+        in_window(self.ledger_commit_progress)
+            && in_window(self.state_kv_commit_progress)
+            && num_versions(self.state_merkle_max_version) <= max_allowed
+    }
+}
+
 pub(crate) struct StatePruner {
     pub hot_state_merkle_pruner: Option<StateMerklePrunerManager<StaleNodeIndexSchema>>,
     pub hot_epoch_snapshot_pruner: Option<StateMerklePrunerManager<StaleNodeIndexCrossEpochSchema>>,
@@ -121,21 +218,36 @@ impl StatePruner {
         state_kv_db: Arc<StateKvDb>,
         config: PrunerConfig,
     ) -> Self {
+        let deletion_log_retention = config.deletion_log_retention();
         let hot_state_merkle_pruner = hot_state_merkle_db.as_ref().map(|db| {
-            StateMerklePrunerManager::new(Arc::clone(db), config.state_merkle_pruner_config)
+            StateMerklePrunerManager::new(
+                Arc::clone(db),
+                config.state_merkle_pruner_config,
+                deletion_log_retention,
+            )
         });
         let hot_epoch_snapshot_pruner = hot_state_merkle_db.map(|db| {
-            StateMerklePrunerManager::new(db, config.epoch_snapshot_pruner_config.into())
+            StateMerklePrunerManager::new(
+                db,
+                config.epoch_snapshot_pruner_config.into(),
+                deletion_log_retention,
+            )
         });
         let state_merkle_pruner = StateMerklePrunerManager::new(
             Arc::clone(&state_merkle_db),
             config.state_merkle_pruner_config,
+            deletion_log_retention,
         );
         let epoch_snapshot_pruner = StateMerklePrunerManager::new(
             state_merkle_db,
             config.epoch_snapshot_pruner_config.into(),
+            deletion_log_retention,
+        );
+        let state_kv_pruner = StateKvPrunerManager::new(
+            state_kv_db,
+            config.ledger_pruner_config,
+            deletion_log_retention,
         );
-        let state_kv_pruner = StateKvPrunerManager::new(state_kv_db, config.ledger_pruner_config);
 
         Self {
             hot_state_merkle_pruner,
@@ -145,6 +257,28 @@ impl StatePruner {
             state_kv_pruner,
         }
     }
+
+    /// Pins `version` in the state KV, the state merkle and the epoch snapshot pruners, or in none
+    /// of them if it fails for any. The hot state isn't versioned for reads, so it's left alone.
+    pub fn pin_version(&self, version: Version) -> Result<()> {
+        self.state_kv_pruner.pin_version(version)?;
+        if let Err(err) = self.state_merkle_pruner.pin_version(version) {
+            self.state_kv_pruner.unpin_version(version)?;
+            return Err(err);
+        }
+        if let Err(err) = self.epoch_snapshot_pruner.pin_version(version) {
+            self.state_kv_pruner.unpin_version(version)?;
+            self.state_merkle_pruner.unpin_version(version)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    pub fn unpin_version(&self, version: Version) -> Result<()> {
+        self.state_kv_pruner.unpin_version(version)?;
+        self.state_merkle_pruner.unpin_version(version)?;
+        self.epoch_snapshot_pruner.unpin_version(version)
+    }
 }
 
 pub(crate) struct StateDb {
@@ -168,6 +302,7 @@ pub(crate) struct StateStore {
     /// Tracks a persisted smt, any state older than that is guaranteed to be found in RocksDB
     persisted_state: PersistedState,
     buffered_state_target_items: usize,
+    state_snapshot_commit_concurrency: Option<usize>,
     internal_indexer_db: Option<InternalIndexerDB>,
     hot_state_config: HotStateConfig,
 }
@@ -381,6 +516,7 @@ impl StateStore {
         state_kv_db: Arc<StateKvDb>,
         state_pruner: StatePruner,
         buffered_state_target_items: usize,
+        state_snapshot_commit_concurrency: Option<usize>,
         hack_for_tests: bool,
         empty_buffered_state_for_restore: bool,
         skip_usage: bool,
@@ -413,6 +549,7 @@ impl StateStore {
                 &state_db,
                 StateWithSummary::new_empty(hot_state_config),
                 buffered_state_target_items,
+                state_snapshot_commit_concurrency,
                 current_state.clone(),
                 persisted_state.clone(),
             )
@@ -420,6 +557,7 @@ impl StateStore {
             Self::create_buffered_state_from_latest_snapshot(
                 &state_db,
                 buffered_state_target_items,
+                state_snapshot_commit_concurrency,
                 hack_for_tests,
                 /*check_max_versions_after_snapshot=*/ true,
                 current_state.clone(),
@@ -433,6 +571,7 @@ impl StateStore {
             state_db,
             buffered_state: Mutex::new(buffered_state),
             buffered_state_target_items,
+            state_snapshot_commit_concurrency,
             current_state,
             persisted_state,
             internal_indexer_db,
@@ -564,6 +703,7 @@ impl StateStore {
         let _ = Self::create_buffered_state_from_latest_snapshot(
             &state_db,
             0,
+            /*state_snapshot_commit_concurrency=*/ None,
             /*hack_for_tests=*/ false,
             /*check_max_versions_after_snapshot=*/ false,
             current_state.clone(),
@@ -577,6 +717,7 @@ impl StateStore {
     fn create_buffered_state_from_latest_snapshot(
         state_db: &Arc<StateDb>,
         buffered_state_target_items: usize,
+        state_snapshot_commit_concurrency: Option<usize>,
         hack_for_tests: bool,
         check_max_versions_after_snapshot: bool,
         out_current_state: Arc<Mutex<LedgerStateWithSummary>>,
@@ -620,6 +761,7 @@ impl StateStore {
             state_db,
             state.clone(),
             buffered_state_target_items,
+            state_snapshot_commit_concurrency,
             out_current_state.clone(),
             out_persisted_state.clone(),
         );
@@ -734,6 +876,7 @@ impl StateStore {
         *self.buffered_state.lock() = Self::create_buffered_state_from_latest_snapshot(
             &self.state_db,
             self.buffered_state_target_items,
+            self.state_snapshot_commit_concurrency,
             false,
             true,
             self.current_state.clone(),
@@ -751,6 +894,17 @@ impl StateStore {
         self.current_state.lock()
     }
 
+    /// Returns the number of state keys updated in each shard since the state last persisted in
+    /// the state merkle DB, i.e. what the next flushes of the buffered state will have to
+    /// merklize. A key updated many times counts once. Walks all the pending updates.
+    pub fn pending_buffered_by_shard(&self) -> [usize; NUM_STATE_SHARDS] {
+        // The persisted state first, so that it's an ancestor of the current one.
+        let (_hot_state, persisted_state) = self.persisted_state.get_state();
+        let current_state = self.current_state_locked().state().clone();
+        let delta = current_state.make_delta(&persisted_state);
+        std::array::from_fn(|shard_id| delta.shards[shard_id].iter().count())
+    }
+
     /// Returns the key, value pairs for a particular state key prefix at desired version. This
     /// API can be used to get all resources of an account by passing the account address as the
     /// key prefix.
@@ -770,6 +924,132 @@ impl StateStore {
         )
     }
 
+    /// Returns all the resources and modules under `address` at `version`, by scanning the keys
+    /// prefixed by the address. With the non-sharded DB the state values are keyed by the raw
+    /// state key, so they're scanned directly. The sharded DB is keyed by the hash of the state
+    /// key, so the keys are scanned in the state keys index of the internal indexer instead, which
+    /// then has to be enabled and to cover `version`, as for the other prefix APIs.
+    pub fn get_account_state(
+        &self,
+        address: AccountAddress,
+        version: Version,
+    ) -> Result<BTreeMap<StateKey, StateValue>> {
+        let key_prefix = StateKeyPrefix::from(address);
+        if !self.state_kv_db.enabled_sharding() {
+            return self
+                .get_prefixed_state_value_iterator(&key_prefix, None, version)?
+                .collect();
+        }
+
+        let indexer_db = self
+            .internal_indexer_db
+            .as_ref()
+            .filter(|indexer_db| indexer_db.statekeys_enabled())
+            .ok_or_else(|| {
+                AptosDbError::Other(
+                    "Prefix scan of the sharded DB requires the internal indexer with state keys."
+                        .to_string(),
+                )
+            })?;
+        ensure!(
+            indexer_db
+                .get_state_version()?
+                .is_some_and(|indexed_version| indexed_version >= version),
+            "The internal indexer hasn't indexed the state keys up to version {version} yet."
+        );
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_total_order_seek(true);
+        let mut iter = indexer_db
+            .get_inner_db_ref()
+            .iter_with_opts::<StateKeysSchema>(read_opts)?;
+        iter.seek(&&key_prefix)?;
+
+        let mut account_state = BTreeMap::new();
+        for res in iter {
+            let (state_key, ()) = res?;
+            if !key_prefix.is_prefix(&state_key)? {
+                break;
+            }
+            // Keys are indexed once created, they may not exist at `version`.
+            if let Some((_version, value)) = self
+                .state_kv_db
+                .get_state_value_with_version_by_version(&state_key, version)?
+            {
+                account_state.insert(state_key, value);
+            }
+        }
+        Ok(account_state)
+    }
+
+    /// Returns the proof that `state_key` doesn't exist at `version`: either the path to the key
+    /// ends at an empty subtree, or at a leaf of another key. Errors out if the key exists. The
+    /// proof is verified against the root hash at `version` before being returned.
+    pub fn get_state_value_nonexistence_proof(
+        &self,
+        state_key: &StateKey,
+        version: Version,
+    ) -> Result<SparseMerkleProofExt> {
+        let key_hash = state_key.hash();
+        let (leaf_data, proof) = self
+            .state_merkle_db
+            .get_with_proof_ext(&key_hash, version, /* root_depth = */ 0)?;
+        ensure!(
+            leaf_data.is_none(),
+            "State key {state_key:?} exists at version {version}."
+        );
+        proof.verify_by_hash(
+            self.state_merkle_db.get_root_hash(version)?,
+            key_hash,
+            /* element_hash = */ None,
+        )?;
+        Ok(proof)
+    }
+
+    /// Estimates the size in bytes of the BCS serialized proof of `state_key` at `version`, to
+    /// reject or queue requests for large proofs before building them. Only the path to the key
+    /// is read, and every internal node on it is assumed to contribute a full nibble of siblings,
+    /// so it's an upper bound.
+    pub fn estimate_proof_size(&self, state_key: &StateKey, version: Version) -> Result<usize> {
+        // The optional leaf, i.e. a tag and two hashes, and the length prefix of the siblings,
+        // which takes at most 2 bytes for up to 256 of them.
+        const OVERHEAD: usize = 1 + 2 * HashValue::LENGTH + 2;
+
+        let depth = self
+            .state_merkle_db
+            .get_proof_depth(&state_key.hash(), version)?;
+        Ok(depth * 4 * HashValue::LENGTH + OVERHEAD)
+    }
+
+    /// Returns the versions and root hashes of the latest `n` state snapshots persisted in the
+    /// state merkle DB, latest first. Fewer are returned if there aren't as many left unpruned.
+    pub fn recent_state_roots(&self, n: usize) -> Result<Vec<(Version, HashValue)>> {
+        let mut roots = Vec::with_capacity(n);
+        let mut next_version = Version::MAX;
+        while roots.len() < n {
+            let Some(version) = self
+                .state_merkle_db
+                .get_state_snapshot_version_before(next_version)?
+            else {
+                break;
+            };
+            roots.push((version, self.state_merkle_db.get_root_hash(version)?));
+            next_version = version;
+        }
+        Ok(roots)
+    }
+
+    /// Returns the versions in `[start_version, end_version)` with a state checkpoint persisted in
+    /// the state merkle DB, in ascending order. Proofs can only be served at these versions, the
+    /// state at the others is only in the buffered state or in the state kv DB.
+    pub fn checkpoint_versions(
+        &self,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<Vec<Version>> {
+        self.state_merkle_db
+            .get_snapshot_versions(start_version, end_version)
+    }
+
     /// Gets the proof that proves a range of accounts.
     pub fn get_value_range_proof(
         &self,
@@ -1052,6 +1332,34 @@ impl StateStore {
         Ok(())
     }
 
+    /// Same as `StateValueWriter::write_kv_batch`, but the shards are committed in `pool`, see
+    /// [`StateKvDb::commit_in_pool`].
+    pub(crate) fn write_kv_batch_in_pool(
+        &self,
+        version: Version,
+        node_batch: &StateValueBatch,
+        progress: StateSnapshotProgress,
+        pool: &ThreadPool,
+    ) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["state_value_writer_write_chunk"]);
+        let mut batch = SchemaBatch::new();
+        let mut sharded_schema_batch = self.state_kv_db.new_sharded_native_batches();
+
+        batch.put::<DbMetadataSchema>(
+            &DbMetadataKey::StateSnapshotKvRestoreProgress(version),
+            &DbMetadataValue::StateSnapshotProgress(progress),
+        )?;
+
+        self.write_restored_keys_to_indexer_db(version, node_batch, progress)?;
+        self.shard_state_value_batch(
+            &mut sharded_schema_batch,
+            node_batch,
+            self.state_kv_db.enabled_sharding(),
+        )?;
+        self.state_kv_db
+            .commit_in_pool(version, Some(batch), sharded_schema_batch, pool)
+    }
+
     pub(crate) fn shard_state_value_batch(
         &self,
         sharded_batch: &mut ShardedStateKvSchemaBatch,
@@ -1078,6 +1386,28 @@ impl StateStore {
         Ok(())
     }
 
+    fn write_restored_keys_to_indexer_db(
+        &self,
+        version: Version,
+        node_batch: &StateValueBatch,
+        progress: StateSnapshotProgress,
+    ) -> Result<()> {
+        if self.internal_indexer_db.is_some()
+            && self
+                .internal_indexer_db
+                .as_ref()
+                .unwrap()
+                .statekeys_enabled()
+        {
+            let keys = node_batch.keys().map(|key| key.0.clone()).collect();
+            self.internal_indexer_db
+                .as_ref()
+                .unwrap()
+                .write_keys_to_indexer_db(&keys, version, progress)?;
+        }
+        Ok(())
+    }
+
     pub fn get_root_hash(&self, version: Version) -> Result<HashValue> {
         self.state_merkle_db.get_root_hash(version)
     }
@@ -1086,6 +1416,41 @@ impl StateStore {
         self.state_merkle_db.get_leaf_count(version)
     }
 
+    /// Applies `updates` (state key hash to value hash, `None` meaning deletion) to `current` and
+    /// returns the new tree, without writing anything to the DB. This allows maintaining a state
+    /// root in memory incrementally as updates stream in.
+    ///
+    /// `base` is the tree `current` was derived from, created by `SparseMerkleTree::new()` with
+    /// the root hash at `base_version` (or empty if `None`). Subtrees not held in memory are read
+    /// from the state merkle tree persisted at `base_version`, so the caller must keep `base`
+    /// alive, which keeps alive all the in-memory nodes of the trees derived from it, and that
+    /// version must not be pruned in the meantime.
+    pub fn apply_update_to_smt(
+        &self,
+        base: &SparseMerkleTree,
+        base_version: Option<Version>,
+        current: &SparseMerkleTree,
+        updates: &[(HashValue, Option<HashValue>)],
+    ) -> Result<SparseMerkleTree> {
+        let proof_reader = PersistedStateProofReader {
+            state_merkle_db: &self.state_merkle_db,
+            version: base_version,
+            error: Mutex::new(None),
+        };
+        Ok(current
+            .freeze(base)
+            .batch_update(updates.iter(), &proof_reader)
+            .map_err(|err| {
+                // The update fails on the missing proof, report why it's missing.
+                proof_reader
+                    .error
+                    .lock()
+                    .take()
+                    .unwrap_or_else(|| anyhow::Error::from(err).into())
+            })?
+            .unfreeze())
+    }
+
     pub fn get_state_key_and_value_iter(
         self: &Arc<Self>,
         version: Version,
@@ -1105,6 +1470,429 @@ impl StateStore {
         }))
     }
 
+    /// Streams the state values at `version`, or only those of a single shard if `shard_id` is
+    /// given, ordered by state key hash. The values are read by a blocking scan on a background
+    /// thread, which gets at most `STATE_VALUE_STREAM_BUFFER_SIZE` items ahead of the consumer, so
+    /// a slow consumer applies backpressure. The scan stops once the stream is dropped.
+    pub fn state_value_stream(
+        self: &Arc<Self>,
+        version: Version,
+        shard_id: Option<usize>,
+    ) -> impl Stream<Item = Result<(StateKey, StateValue)>> + Send + Sync + use<> {
+        let (sender, receiver) = mpsc::channel(STATE_VALUE_STREAM_BUFFER_SIZE);
+        let store = Arc::clone(self);
+        std::thread::Builder::new()
+            .name("state_value_stream".to_string())
+            .spawn(move || {
+                let iter = match store.get_shard_state_key_and_value_iter(version, shard_id) {
+                    Ok(iter) => iter,
+                    Err(err) => {
+                        let _ = sender.blocking_send(Err(err));
+                        return;
+                    },
+                };
+                for item in iter {
+                    if sender.blocking_send(item).is_err() {
+                        // The stream is dropped.
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn state value stream thread.");
+        ReceiverStream::new(receiver)
+    }
+
+    fn get_shard_state_key_and_value_iter(
+        self: &Arc<Self>,
+        version: Version,
+        shard_id: Option<usize>,
+    ) -> Result<impl Iterator<Item = Result<(StateKey, StateValue)>> + use<>> {
+        let mut starting_key = [0u8; HashValue::LENGTH];
+        if let Some(shard_id) = shard_id {
+            ensure!(
+                shard_id < NUM_STATE_SHARDS,
+                "Invalid shard id {shard_id}, there are {NUM_STATE_SHARDS} shards."
+            );
+            // The shard of a key is the first nibble of its hash.
+            starting_key[0] = (shard_id as u8) << 4;
+        }
+        let store = Arc::clone(self);
+        Ok(JellyfishMerkleIterator::new(
+            Arc::clone(&self.state_merkle_db),
+            version,
+            HashValue::new(starting_key),
+        )?
+        .take_while(move |res| match (res, shard_id) {
+            (Ok((hashed_key, _)), Some(shard_id)) => usize::from(hashed_key.nibble(0)) == shard_id,
+            _ => true,
+        })
+        .map(move |res| {
+            let (_hashed_key, (key, version)) = res?;
+            let value = store.expect_value_by_version(&key, version)?;
+            Ok((key, value))
+        }))
+    }
+
+    /// Compares the state at `version_a` in this store with the state at `version_b` in `other`,
+    /// e.g. the tips of two forks at the same height before accepting a reorg, yielding the keys
+    /// whose values differ, ordered by state key hash. `other` can be this store.
+    ///
+    /// The leaves of the two state trees are iterated side by side, and only the values of the keys
+    /// whose leaves hold different value hashes are read.
+    pub fn compare_states(
+        self: &Arc<Self>,
+        version_a: Version,
+        other: &Arc<StateStore>,
+        version_b: Version,
+    ) -> Result<impl Iterator<Item = Result<StateDiffEntry>> + Send + Sync + use<>> {
+        let leaves_a =
+            JellyfishMerkleIterator::new_by_index(Arc::clone(&self.state_merkle_db), version_a, 0)?
+                .into_leaves();
+        let leaves_b = JellyfishMerkleIterator::new_by_index(
+            Arc::clone(&other.state_merkle_db),
+            version_b,
+            0,
+        )?
+        .into_leaves();
+        type Leaf = Result<LeafNode<StateKey>>;
+        let store_a = Arc::clone(self);
+        let store_b = Arc::clone(other);
+        let diff = move |pair: EitherOrBoth<Leaf, Leaf>| -> Result<Option<StateDiffEntry>> {
+            let (leaf_a, leaf_b) = match pair {
+                EitherOrBoth::Both(a, b) => {
+                    let (a, b) = (a?, b?);
+                    if a.value_hash() == b.value_hash() {
+                        return Ok(None);
+                    }
+                    (Some(a), Some(b))
+                },
+                EitherOrBoth::Left(a) => (Some(a?), None),
+                EitherOrBoth::Right(b) => (None, Some(b?)),
+            };
+            let read_value = |store: &StateStore, leaf: LeafNode<StateKey>| {
+                let (state_key, version) = leaf.value_index();
+                store.expect_value_by_version(state_key, *version)
+            };
+            let state_key = leaf_a
+                .as_ref()
+                .or(leaf_b.as_ref())
+                .expect("At least one of the leaves is there.")
+                .value_index()
+                .0
+                .clone();
+            Ok(Some(StateDiffEntry {
+                state_key,
+                value_a: leaf_a.map(|leaf| read_value(&store_a, leaf)).transpose()?,
+                value_b: leaf_b.map(|leaf| read_value(&store_b, leaf)).transpose()?,
+            }))
+        };
+
+        Ok(leaves_a
+            .merge_join_by(leaves_b, |a, b| match (a, b) {
+                (Ok(leaf_a), Ok(leaf_b)) => leaf_a.account_key().cmp(leaf_b.account_key()),
+                // Surface the error right away.
+                _ => Ordering::Equal,
+            })
+            .filter_map(move |pair| diff(pair).transpose()))
+    }
+
+    /// Returns all the Move modules published as of `version`, ordered by state key hash. Only
+    /// the values of the module state keys are read.
+    pub fn iter_modules(
+        self: &Arc<Self>,
+        version: Version,
+    ) -> Result<impl Iterator<Item = Result<(ModuleId, Bytes)>> + Send + Sync + use<>> {
+        let store = Arc::clone(self);
+        Ok(
+            JellyfishMerkleIterator::new_by_index(Arc::clone(&self.state_merkle_db), version, 0)?
+                .filter_map(move |res| {
+                    let (key, version) = match res {
+                        Ok((_hashed_key, key_and_version)) => key_and_version,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    let module_id = match key.inner() {
+                        StateKeyInner::AccessPath(access_path) => access_path.try_get_module_id(),
+                        StateKeyInner::TableItem { .. } | StateKeyInner::Raw(_) => None,
+                    }?;
+                    Some(
+                        store
+                            .expect_value_by_version(&key, version)
+                            .map(|value| (module_id, value.into_bytes())),
+                    )
+                }),
+        )
+    }
+
+    /// Returns the state values as of `version` for which `pred` holds, ordered by state key hash.
+    /// The predicate is applied as the state is walked, so only the matches are kept in memory,
+    /// but every value still has to be read to be tested.
+    pub fn filter_state_values<F>(
+        self: &Arc<Self>,
+        version: Version,
+        pred: F,
+    ) -> Result<impl Iterator<Item = Result<(StateKey, StateValue)>> + Send + Sync + use<F>>
+    where
+        F: Fn(&StateKey, &StateValue) -> bool + Send + Sync,
+    {
+        Ok(self
+            .get_state_key_and_value_iter(version, 0)?
+            .filter(move |res| match res {
+                Ok((key, value)) => pred(key, value),
+                // Surface the error.
+                Err(_) => true,
+            }))
+    }
+
+    /// Returns the versions in `[start_version, end_version)` at which the bytecode of the module
+    /// changed, oldest first, along with the bytecode as of each. Republishing the same bytecode
+    /// is not a change, neither against an earlier write in the range nor against the bytecode
+    /// already published before `start_version`.
+    pub fn module_upgrade_history(
+        &self,
+        module_id: &ModuleId,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<Vec<(Version, Bytes)>> {
+        let state_key = StateKey::module_id(module_id);
+        let mut last_bytes = match start_version.checked_sub(1) {
+            Some(version) => self
+                .get_state_value_by_version(&state_key, version)?
+                .map(StateValue::into_bytes),
+            None => None,
+        };
+
+        let mut history = Vec::new();
+        for (version, value_opt) in
+            self.state_kv_db
+                .get_state_value_history(&state_key, start_version, end_version)?
+        {
+            // Modules can't be deleted, but don't report a deletion as an upgrade.
+            let Some(value) = value_opt else {
+                last_bytes = None;
+                continue;
+            };
+            let bytes = value.into_bytes();
+            if last_bytes.as_ref() != Some(&bytes) {
+                history.push((version, bytes.clone()));
+                last_bytes = Some(bytes);
+            }
+        }
+        Ok(history)
+    }
+
+    /// Returns the transactions in `[start_version, end_version)` that wrote to `state_key`,
+    /// including deleting it, oldest first, along with their versions. Only the writes still in
+    /// the state K/V DB are found, i.e. not the ones the state K/V pruner already deleted.
+    pub fn writers_of_key(
+        &self,
+        state_key: &StateKey,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<Vec<(Version, Transaction)>> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["writers_of_key"]);
+
+        self.state_kv_db
+            .get_state_value_history(state_key, start_version, end_version)?
+            .into_iter()
+            .map(|(version, _value_opt)| {
+                let txn = self.ledger_db.transaction_db().get_transaction(version)?;
+                Ok((version, txn))
+            })
+            .collect()
+    }
+
+    /// Returns the net number of state keys created at each version in `[start_version,
+    /// end_version)`, i.e. the keys created minus the keys deleted. The keys written with a value
+    /// come from the write sets, and those which had one before from the stale state value index,
+    /// so the range must not be pruned yet.
+    pub fn key_count_deltas(
+        &self,
+        start_version: Version,
+        end_version: Version,
+    ) -> Result<Vec<(Version, i64)>> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["key_count_deltas"]);
+        ensure!(
+            start_version <= end_version,
+            "Invalid version range [{}, {}).",
+            start_version,
+            end_version,
+        );
+        let min_readable_version = self.state_pruner.state_kv_pruner.get_min_readable_version();
+        ensure!(
+            start_version >= min_readable_version,
+            "Version {} is pruned, min readable version: {}.",
+            start_version,
+            min_readable_version,
+        );
+
+        let num_overwritten = self
+            .state_kv_db
+            .num_values_overwritten_by_version(start_version, end_version)?;
+        let write_sets = self
+            .ledger_db
+            .write_set_db()
+            .get_write_set_iter(start_version, num_overwritten.len())?;
+        (start_version..)
+            .zip(write_sets)
+            .zip(num_overwritten)
+            .map(|((version, write_set), num_overwritten)| {
+                let num_written = write_set?
+                    .state_update_refs()
+                    .filter(|(_key, value_opt)| value_opt.is_some())
+                    .count();
+                Ok((version, num_written as i64 - num_overwritten as i64))
+            })
+            .collect()
+    }
+
+    /// Returns the keys written in `(from_version, to_version]`, with their values at both versions
+    /// and the proofs of these against the state roots at both, for a verifier to check the
+    /// transition between the two roots, see `StateTransitionProof::verify`. The state merkle
+    /// trees at both versions must be persisted and not pruned.
+    pub fn get_transition_proof(
+        &self,
+        from_version: Version,
+        to_version: Version,
+    ) -> Result<StateTransitionProof> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["get_transition_proof"]);
+        ensure!(
+            from_version <= to_version,
+            "Invalid version range ({}, {}].",
+            from_version,
+            to_version,
+        );
+
+        let mut state_keys = BTreeMap::new();
+        for write_set in self
+            .ledger_db
+            .write_set_db()
+            .get_write_set_iter(from_version + 1, (to_version - from_version) as usize)?
+        {
+            for (state_key, _value_opt) in write_set?.state_update_refs() {
+                state_keys
+                    .entry(CryptoHash::hash(state_key))
+                    .or_insert_with(|| state_key.clone());
+            }
+        }
+
+        let entries = state_keys
+            .into_iter()
+            .map(|(key_hash, state_key)| {
+                let value_with_proof = |version| {
+                    self.get_state_value_with_proof_by_version_ext(
+                        &key_hash, version, /* root_depth = */ 0,
+                        /* use_hot_state = */ false,
+                    )
+                };
+                let (old_value, old_proof) = value_with_proof(from_version)?;
+                let (new_value, new_proof) = value_with_proof(to_version)?;
+                Ok(StateTransitionEntry {
+                    state_key,
+                    old_value,
+                    new_value,
+                    old_proof,
+                    new_proof,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(StateTransitionProof {
+            from_version,
+            to_version,
+            entries,
+        })
+    }
+
+    /// Returns the values of the given keys with their proofs, each at its own version, in the
+    /// order of `requests`. The proofs are read in parallel on the non-execution CPU pool, which
+    /// helps when serving many proofs at once, e.g. to state sync peers.
+    pub fn get_proofs_parallel(
+        &self,
+        requests: Vec<(StateKey, Version)>,
+    ) -> Result<Vec<(Option<StateValue>, SparseMerkleProofExt)>> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["get_proofs_parallel"]);
+
+        THREAD_MANAGER.get_non_exe_cpu_pool().install(|| {
+            requests
+                .par_iter()
+                .map(|(state_key, version)| {
+                    self.get_state_value_with_proof_by_version_ext(
+                        &CryptoHash::hash(state_key),
+                        *version,
+                        /* root_depth = */ 0,
+                        /* use_hot_state = */ false,
+                    )
+                })
+                .collect()
+        })
+    }
+
+    /// Remaps the state keys of the state KV DB, see `StateKvDb::remap_keys`. In the sharded
+    /// layout, the key of a key hash is found in the write set of a version it was written at.
+    ///
+    /// The leaves of the state merkle tree hold the old keys, and its structure follows their
+    /// hashes, so the tree can't be patched in place. This fails unless the state merkle DB is
+    /// empty, i.e. it has to be cleared before, and rebuilt from the remapped state values
+    /// afterwards.
+    pub fn remap_keys(&self, remap: impl Fn(&StateKey) -> StateKey, sst_dir: &Path) -> Result<()> {
+        for db in self.state_merkle_db.all_dbs() {
+            let mut iter = db.iter::<JellyfishMerkleNodeSchema>()?;
+            iter.seek_to_first();
+            ensure!(
+                iter.next().transpose()?.is_none(),
+                "The state merkle DB must be cleared before remapping the state keys, its nodes \
+                refer to the keys before the remapping."
+            );
+        }
+        self.state_kv_db.remap_keys(
+            remap,
+            |key_hash, version| self.resolve_state_key(key_hash, version),
+            sst_dir,
+        )
+    }
+
+    /// Returns the `n` largest state values as of `version`, see `StateKvDb::largest_values`. In
+    /// the sharded layout, the key of a key hash is found in the write set of the version it was
+    /// written at.
+    pub fn largest_values(&self, version: Version, n: usize) -> Result<Vec<(StateKey, usize)>> {
+        self.state_kv_db
+            .largest_values(version, n, |key_hash, version| {
+                self.resolve_state_key(key_hash, version)
+            })
+    }
+
+    fn resolve_state_key(&self, key_hash: HashValue, version: Version) -> Result<StateKey> {
+        self.ledger_db
+            .write_set_db()
+            .get_write_set(version)?
+            .state_update_refs()
+            .find(|(state_key, _value_opt)| CryptoHash::hash(*state_key) == key_hash)
+            .map(|(state_key, _value_opt)| state_key.clone())
+            .ok_or_else(|| {
+                AptosDbError::NotFound(format!(
+                    "State key of hash {key_hash} in the write set of version {version}."
+                ))
+            })
+    }
+
+    /// Counts the distinct addresses with at least one resource or module at `version`. Table
+    /// items don't belong to an address so they aren't counted.
+    ///
+    /// This walks all the keys in the state tree, so it's as costly as a state snapshot backup.
+    pub fn count_accounts(self: &Arc<Self>, version: Version) -> Result<u64> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["count_accounts"]);
+
+        let mut addresses = HashSet::new();
+        for res in
+            JellyfishMerkleIterator::new_by_index(Arc::clone(&self.state_merkle_db), version, 0)?
+        {
+            let (_hashed_key, (key, _version)) = res?;
+            if let StateKeyInner::AccessPath(access_path) = key.inner() {
+                addresses.insert(access_path.address);
+            }
+        }
+        Ok(addresses.len() as u64)
+    }
+
     pub fn get_value_chunk_with_proof(
         self: &Arc<Self>,
         version: Version,
@@ -1264,6 +2052,30 @@ impl StateStore {
     }
 }
 
+/// Reads proofs from the state merkle tree persisted at a fixed version.
+struct PersistedStateProofReader<'a> {
+    state_merkle_db: &'a StateMerkleDb,
+    version: Option<Version>,
+    /// The first error reading a proof, e.g. at a pruned version, which `ProofRead` can't return.
+    error: Mutex<Option<AptosDbError>>,
+}
+
+impl ProofRead for PersistedStateProofReader<'_> {
+    fn get_proof(&self, key: &HashValue, root_depth: usize) -> Option<SparseMerkleProofExt> {
+        let version = self.version?;
+        match self
+            .state_merkle_db
+            .get_with_proof_ext(key, version, root_depth)
+        {
+            Ok((_leaf, proof)) => Some(proof),
+            Err(err) => {
+                self.error.lock().get_or_insert(err);
+                None
+            },
+        }
+    }
+}
+
 impl StateValueWriter<StateKey, StateValue> for StateStore {
     // This already turns on sharded KV
     fn write_kv_batch(
@@ -1272,35 +2084,7 @@ impl StateValueWriter<StateKey, StateValue> for StateStore {
         node_batch: &StateValueBatch,
         progress: StateSnapshotProgress,
     ) -> Result<()> {
-        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["state_value_writer_write_chunk"]);
-        let mut batch = SchemaBatch::new();
-        let mut sharded_schema_batch = self.state_kv_db.new_sharded_native_batches();
-
-        batch.put::<DbMetadataSchema>(
-            &DbMetadataKey::StateSnapshotKvRestoreProgress(version),
-            &DbMetadataValue::StateSnapshotProgress(progress),
-        )?;
-
-        if self.internal_indexer_db.is_some()
-            && self
-                .internal_indexer_db
-                .as_ref()
-                .unwrap()
-                .statekeys_enabled()
-        {
-            let keys = node_batch.keys().map(|key| key.0.clone()).collect();
-            self.internal_indexer_db
-                .as_ref()
-                .unwrap()
-                .write_keys_to_indexer_db(&keys, version, progress)?;
-        }
-        self.shard_state_value_batch(
-            &mut sharded_schema_batch,
-            node_batch,
-            self.state_kv_db.enabled_sharding(),
-        )?;
-        self.state_kv_db
-            .commit(version, Some(batch), sharded_schema_batch)
+        self.write_kv_batch_in_pool(version, node_batch, progress, THREAD_MANAGER.get_io_pool())
     }
 
     fn kv_finish(&self, version: Version, usage: StateStorageUsage) -> Result<()> {
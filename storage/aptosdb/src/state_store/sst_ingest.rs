@@ -0,0 +1,118 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! A state value writer for restore that builds SST files out of the restored chunks and ingests
+//! them into the state KV shards, instead of writing through the memtable and the WAL.
+//!
+//! Keys need to arrive sorted per shard, which the state snapshot backup format already
+//! guarantees: chunks are ordered by key hash, and the key hash prefixes the encoded key of
+//! `StateValueByKeyHashSchema`. So the SST files ingested into a shard never overlap and RocksDB
+//! can place them directly in the bottommost level.
+
+use crate::{
+    metrics::OTHER_TIMERS_SECONDS,
+    schema::db_metadata::{DbMetadataKey, DbMetadataSchema, DbMetadataValue},
+    state_restore::{StateValueBatch, StateValueWriter},
+    state_store::StateStore,
+};
+use aptos_crypto::hash::CryptoHash;
+use aptos_db_indexer_schemas::metadata::StateSnapshotProgress;
+use aptos_experimental_runtimes::thread_manager::THREAD_MANAGER;
+use aptos_metrics_core::TimerHelper;
+use aptos_schemadb::batch::SchemaBatch;
+use aptos_storage_interface::{db_ensure as ensure, Result};
+use aptos_types::{
+    state_store::{
+        state_key::StateKey, state_storage_usage::StateStorageUsage, state_value::StateValue,
+        NUM_STATE_SHARDS,
+    },
+    transaction::Version,
+};
+use rayon::{prelude::*, ThreadPool};
+use std::{path::PathBuf, sync::Arc};
+
+pub(crate) struct SstIngestStateValueWriter {
+    state_store: Arc<StateStore>,
+    /// Where the SST files are staged before being moved into the shards by ingestion.
+    sst_dir: PathBuf,
+    /// Pool to ingest the shards in, the IO pool if `None`.
+    shard_pool: Option<Arc<ThreadPool>>,
+}
+
+impl SstIngestStateValueWriter {
+    pub(crate) fn new(
+        state_store: Arc<StateStore>,
+        sst_dir: PathBuf,
+        shard_pool: Option<Arc<ThreadPool>>,
+    ) -> Result<Self> {
+        ensure!(
+            state_store.state_kv_db.enabled_sharding(),
+            "Restoring state KV by SST ingestion requires storage sharding."
+        );
+        std::fs::create_dir_all(&sst_dir)?;
+        Ok(Self {
+            state_store,
+            sst_dir,
+            shard_pool,
+        })
+    }
+}
+
+impl StateValueWriter<StateKey, StateValue> for SstIngestStateValueWriter {
+    fn write_kv_batch(
+        &self,
+        version: Version,
+        node_batch: &StateValueBatch<StateKey, Option<StateValue>>,
+        progress: StateSnapshotProgress,
+    ) -> Result<()> {
+        let _timer = OTHER_TIMERS_SECONDS.timer_with(&["state_value_writer_ingest_chunk"]);
+
+        let mut sharded_values = vec![Vec::new(); NUM_STATE_SHARDS];
+        for ((key, version), value) in node_batch {
+            sharded_values[key.get_shard_id()].push(((key.hash(), *version), value.clone()));
+        }
+        let pool = self
+            .shard_pool
+            .as_deref()
+            .unwrap_or_else(|| THREAD_MANAGER.get_io_pool());
+        pool.install(|| {
+            sharded_values
+                .into_par_iter()
+                .enumerate()
+                .try_for_each(|(shard_id, values)| {
+                    // The last key hash is unique per chunk, so a retried chunk overwrites the
+                    // leftover file of a previous attempt instead of clashing with another chunk.
+                    let sst_path = self.sst_dir.join(format!(
+                        "state_kv_{version}_{}_shard_{shard_id}.sst",
+                        progress.key_hash
+                    ));
+                    self.state_store
+                        .state_kv_db
+                        .ingest_single_shard(version, shard_id, &values, &sst_path)
+                })
+        })?;
+
+        self.state_store
+            .write_restored_keys_to_indexer_db(version, node_batch, progress)?;
+
+        // Progress is only recorded once all shards have the data, same as the normal write path.
+        let mut batch = SchemaBatch::new();
+        batch.put::<DbMetadataSchema>(
+            &DbMetadataKey::StateSnapshotKvRestoreProgress(version),
+            &DbMetadataValue::StateSnapshotProgress(progress),
+        )?;
+        self.state_store
+            .state_kv_db
+            .metadata_db()
+            .write_schemas(batch)?;
+        self.state_store.state_kv_db.write_progress(version)
+    }
+
+    fn kv_finish(&self, version: Version, usage: StateStorageUsage) -> Result<()> {
+        self.state_store.kv_finish(version, usage)
+    }
+
+    fn get_progress(&self, version: Version) -> Result<Option<StateSnapshotProgress>> {
+        self.state_store.get_progress(version)
+    }
+}
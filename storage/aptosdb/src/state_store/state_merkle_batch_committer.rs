@@ -132,6 +132,7 @@ impl StateMerkleBatchCommitter {
         } = state_merkle_batch;
         db.commit(current_version, top_levels_batch, batches_for_shards)?;
         if let Some(lru_cache) = db.lru_cache() {
+            let lru_cache = (!db.cache_on_read_only()).then_some(lru_cache);
             db.version_caches()
                 .iter()
                 .for_each(|(_, cache)| cache.maybe_evict_version(lru_cache));
@@ -28,7 +28,7 @@ use aptos_types::{
     state_store::{hot_state::HotStateValueRef, state_key::StateKey, NUM_STATE_SHARDS},
     transaction::Version,
 };
-use rayon::prelude::*;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 use static_assertions::const_assert;
 use std::{
     sync::{
@@ -44,6 +44,9 @@ pub(crate) struct StateSnapshotCommitter {
     last_snapshot: StateWithSummary,
     state_snapshot_commit_receiver: Receiver<CommitMessage<StateWithSummary>>,
     state_merkle_batch_commit_sender: SyncSender<CommitMessage<StateMerkleCommit>>,
+    /// Dedicated pool to merklize in, if configured. Otherwise the shared non-execution CPU pool
+    /// is used.
+    merklize_pool: Option<ThreadPool>,
     join_handle: Option<JoinHandle<()>>,
 }
 
@@ -55,10 +58,11 @@ impl StateSnapshotCommitter {
         state_snapshot_commit_receiver: Receiver<CommitMessage<StateWithSummary>>,
         last_snapshot: StateWithSummary,
         persisted_state: PersistedState,
+        concurrency: Option<usize>,
     ) -> Self {
         // Note: This is to ensure we cache nodes in memory from previous batches before they get committed to DB.
         const_assert!(
-            StateSnapshotCommitter::CHANNEL_SIZE < VersionedNodeCache::NUM_VERSIONS_TO_CACHE
+            StateSnapshotCommitter::CHANNEL_SIZE < VersionedNodeCache::MIN_NUM_VERSIONS_TO_CACHE
         );
         // Rendezvous channel
         let (state_merkle_batch_commit_sender, state_merkle_batch_commit_receiver) =
@@ -75,11 +79,19 @@ impl StateSnapshotCommitter {
                 committer.run();
             })
             .expect("Failed to spawn state merkle batch committer thread.");
+        let merklize_pool = concurrency.map(|num_threads| {
+            ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .thread_name(|index| format!("state-merklize-{}", index))
+                .build()
+                .expect("Failed to create state merklize thread pool.")
+        });
         Self {
             state_db,
             last_snapshot,
             state_snapshot_commit_receiver,
             state_merkle_batch_commit_sender,
+            merklize_pool,
             join_handle: Some(join_handle),
         }
     }
@@ -138,6 +150,7 @@ impl StateSnapshotCommitter {
                     {
                         self.state_db.hot_state_merkle_db.as_ref().map(|db| {
                             Self::merklize(
+                                self.merklize_pool(),
                                 db,
                                 base_version,
                                 version,
@@ -154,6 +167,7 @@ impl StateSnapshotCommitter {
                         None
                     };
                     let (state_merkle_batch, leaf_count) = Self::merklize(
+                        self.merklize_pool(),
                         &self.state_db.state_merkle_db,
                         base_version,
                         version,
@@ -200,7 +214,14 @@ impl StateSnapshotCommitter {
         info!("State snapshot committing thread exit.");
     }
 
+    fn merklize_pool(&self) -> &ThreadPool {
+        self.merklize_pool
+            .as_ref()
+            .unwrap_or_else(|| THREAD_MANAGER.get_non_exe_cpu_pool())
+    }
+
     fn merklize(
+        pool: &ThreadPool,
         db: &StateMerkleDb,
         base_version: Option<Version>,
         version: Version,
@@ -211,29 +232,28 @@ impl StateSnapshotCommitter {
     ) -> Result<(StateMerkleBatch, usize)> {
         let shard_persisted_versions = db.get_shard_persisted_versions(base_version)?;
 
-        let (shard_root_nodes, batches_for_shards) =
-            THREAD_MANAGER.get_non_exe_cpu_pool().install(|| {
-                let _timer = OTHER_TIMERS_SECONDS.timer_with(&["calculate_batches_for_shards"]);
-                all_updates
-                    .par_iter()
-                    .enumerate()
-                    .map(|(shard_id, updates)| {
-                        let node_hashes = smt.new_node_hashes_since(last_smt, shard_id as u8);
-                        db.merklize_value_set_for_shard(
-                            shard_id,
-                            jmt_update_refs(updates),
-                            Some(&node_hashes),
-                            version,
-                            base_version,
-                            shard_persisted_versions[shard_id],
-                            previous_epoch_ending_version,
-                        )
-                    })
-                    .collect::<Result<Vec<_>>>()
-                    .expect("Error calculating StateMerkleBatch for shards.")
-                    .into_iter()
-                    .unzip()
-            });
+        let (shard_root_nodes, batches_for_shards) = pool.install(|| {
+            let _timer = OTHER_TIMERS_SECONDS.timer_with(&["calculate_batches_for_shards"]);
+            all_updates
+                .par_iter()
+                .enumerate()
+                .map(|(shard_id, updates)| {
+                    let node_hashes = smt.new_node_hashes_since(last_smt, shard_id as u8);
+                    db.merklize_value_set_for_shard(
+                        shard_id,
+                        jmt_update_refs(updates),
+                        Some(&node_hashes),
+                        version,
+                        base_version,
+                        shard_persisted_versions[shard_id],
+                        previous_epoch_ending_version,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+                .expect("Error calculating StateMerkleBatch for shards.")
+                .into_iter()
+                .unzip()
+        });
 
         let _timer = OTHER_TIMERS_SECONDS.timer_with(&["calculate_top_levels_batch"]);
         let (root_hash, leaf_count, top_levels_batch) = db.calculate_top_levels(
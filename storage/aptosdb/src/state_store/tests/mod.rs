@@ -6,10 +6,20 @@ mod speculative_state_workflow;
 use super::*;
 use crate::{
     db::test_helper::{arb_state_kv_sets_with_genesis, update_store},
-    schema::jellyfish_merkle_node::JellyfishMerkleNodeSchema,
+    schema::{
+        jellyfish_merkle_node::JellyfishMerkleNodeSchema,
+        stale_node_index_cross_epoch::StaleNodeIndexCrossEpochSchema,
+        STATE_VALUE_BY_KEY_HASH_CF_NAME,
+    },
+    state_kv_db::DedupStats,
     state_restore::StateSnapshotRestore,
     AptosDB,
 };
+use aptos_config::config::{
+    internal_indexer_db_config::InternalIndexerDBConfig, RocksdbConfig, RocksdbConfigs,
+    StorageDirPaths, BUFFERED_STATE_TARGET_ITEMS_FOR_TEST, NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use aptos_db_indexer::db_ops::open_internal_indexer_db;
 use aptos_jellyfish_merkle::{
     node_type::{Node, NodeKey},
     TreeReader,
@@ -20,11 +30,14 @@ use aptos_types::{
     account_address::AccountAddress,
     account_config::{AccountResource, ChainIdResource, CoinInfoResource, CoinStoreResource},
     nibble::nibble_path::NibblePath,
+    proof::SparseMerkleProof,
     state_store::state_key::inner::StateKeyTag,
     AptosCoinType,
 };
+use move_core_types::identifier::Identifier;
 use proptest::{collection::hash_map, prelude::*};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio_stream::StreamExt;
 
 fn put_value_set(
     state_store: &StateStore,
@@ -123,6 +136,1296 @@ fn test_state_store_reader_writer() {
     verify_value_and_proof(store, key3, Some(&value3), 1, root);
 }
 
+#[test]
+fn test_get_state_value_nonexistence_proof() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let value1 = StateValue::from(String::from("test_val1").into_bytes());
+
+    let root = put_value_set(store, vec![(key1.clone(), value1)], 0);
+
+    let proof = store.get_state_value_nonexistence_proof(&key2, 0).unwrap();
+    proof.verify::<StateValue>(root, key2.hash(), None).unwrap();
+    assert!(store.get_state_value_nonexistence_proof(&key1, 0).is_err());
+}
+
+#[test]
+fn test_estimate_proof_size() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let keys = (0..20)
+        .map(|i| StateKey::raw(format!("test_key{i}").as_bytes()))
+        .collect::<Vec<_>>();
+    put_value_set(
+        store,
+        keys.iter()
+            .map(|key| (key.clone(), StateValue::from(b"val".to_vec())))
+            .collect(),
+        0,
+    );
+
+    let missing_key = StateKey::raw(b"missing_key");
+    for key in keys.iter().chain([&missing_key]) {
+        let (_, proof) = store
+            .state_merkle_db
+            .get_with_proof_ext(&key.hash(), 0, /* root_depth = */ 0)
+            .unwrap();
+        let proof_size = bcs::to_bytes(&SparseMerkleProof::from(proof))
+            .unwrap()
+            .len();
+        let estimate = store.estimate_proof_size(key, 0).unwrap();
+        // Off by at most the siblings missing from the last nibble.
+        assert!(estimate >= proof_size);
+        assert!(estimate < proof_size + 4 * HashValue::LENGTH);
+    }
+}
+
+#[test]
+fn test_module_upgrade_history() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let module_id = ModuleId::new(AccountAddress::ONE, Identifier::new("m").unwrap());
+    let module_key = StateKey::module_id(&module_id);
+    let other_key = StateKey::raw(b"other_key");
+    let code_v1 = StateValue::from(b"code_v1".to_vec());
+    let code_v2 = StateValue::from(b"code_v2".to_vec());
+    let other_value = StateValue::from(b"other_value".to_vec());
+
+    put_value_set(store, vec![(module_key.clone(), code_v1.clone())], 0);
+    put_value_set(store, vec![(other_key.clone(), other_value.clone())], 1);
+    // Republishing the same code is not an upgrade.
+    put_value_set(store, vec![(module_key.clone(), code_v1.clone())], 2);
+    put_value_set(store, vec![(module_key, code_v2.clone())], 3);
+    put_value_set(store, vec![(other_key, other_value)], 4);
+
+    let history = |start_version, end_version| {
+        store
+            .module_upgrade_history(&module_id, start_version, end_version)
+            .unwrap()
+    };
+    assert_eq!(history(0, 5), vec![
+        (0, code_v1.bytes().clone()),
+        (3, code_v2.bytes().clone())
+    ]);
+    assert_eq!(history(1, 5), vec![(3, code_v2.bytes().clone())]);
+    assert_eq!(history(0, 3), vec![(0, code_v1.bytes().clone())]);
+    assert!(history(4, 5).is_empty());
+    assert!(history(0, 0).is_empty());
+}
+
+#[test]
+fn test_compare_states() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let key3 = StateKey::raw(b"test_key3");
+    let value1 = StateValue::from(b"test_val1".to_vec());
+    let value1_updated = StateValue::from(b"test_val1_updated".to_vec());
+    let value2 = StateValue::from(b"test_val2".to_vec());
+    let value3 = StateValue::from(b"test_val3".to_vec());
+
+    put_value_set(
+        store,
+        vec![
+            (key1.clone(), value1.clone()),
+            (key2.clone(), value2.clone()),
+        ],
+        0,
+    );
+    put_value_set(
+        store,
+        vec![
+            (key1.clone(), value1_updated.clone()),
+            (key3.clone(), value3.clone()),
+        ],
+        1,
+    );
+    // Rewriting the same value is no difference.
+    put_value_set(store, vec![(key2.clone(), value2.clone())], 2);
+
+    let mut expected = vec![
+        StateDiffEntry {
+            state_key: key1.clone(),
+            value_a: Some(value1.clone()),
+            value_b: Some(value1_updated.clone()),
+        },
+        StateDiffEntry {
+            state_key: key3.clone(),
+            value_a: None,
+            value_b: Some(value3.clone()),
+        },
+    ];
+    expected.sort_by_key(|entry| entry.state_key.hash());
+    let compare = |version_a, version_b| {
+        store
+            .compare_states(version_a, store, version_b)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+    };
+    assert_eq!(compare(0, 2), expected);
+    assert!(compare(1, 2).is_empty());
+
+    // The other way around.
+    let reversed = expected
+        .into_iter()
+        .map(|entry| StateDiffEntry {
+            state_key: entry.state_key,
+            value_a: entry.value_b,
+            value_b: entry.value_a,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(compare(2, 0), reversed);
+
+    // A fork sharing version 0, where key1 is written at version 1 in both with different values.
+    let fork_dir = TempPath::new();
+    let fork_db = AptosDB::new_for_test(&fork_dir);
+    let fork_store = &fork_db.state_store;
+    let value1_forked = StateValue::from(b"test_val1_forked".to_vec());
+    put_value_set(fork_store, vec![(key1.clone(), value1), (key2, value2)], 0);
+    put_value_set(fork_store, vec![(key1.clone(), value1_forked.clone())], 1);
+
+    let mut expected = vec![
+        StateDiffEntry {
+            state_key: key1,
+            value_a: Some(value1_updated),
+            value_b: Some(value1_forked),
+        },
+        StateDiffEntry {
+            state_key: key3,
+            value_a: Some(value3),
+            value_b: None,
+        },
+    ];
+    expected.sort_by_key(|entry| entry.state_key.hash());
+    let diff = store
+        .compare_states(1, fork_store, 1)
+        .unwrap()
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(diff, expected);
+}
+
+#[test]
+fn test_recent_state_roots() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    assert!(store.recent_state_roots(2).unwrap().is_empty());
+
+    let key = StateKey::raw(b"test_key");
+    let roots = (0..3)
+        .map(|version| {
+            let value = StateValue::from(vec![version as u8]);
+            put_value_set(store, vec![(key.clone(), value)], version)
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(store.recent_state_roots(2).unwrap(), vec![
+        (2, roots[2]),
+        (1, roots[1])
+    ]);
+    assert_eq!(store.recent_state_roots(5).unwrap().len(), 3);
+}
+
+#[test]
+fn test_checkpoint_versions() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    assert!(store.checkpoint_versions(0, 10).unwrap().is_empty());
+
+    let key = StateKey::raw(b"test_key");
+    for version in 0..4 {
+        let value = StateValue::from(vec![version as u8]);
+        put_value_set(store, vec![(key.clone(), value)], version);
+    }
+    // As if the commit of version 2 didn't finish.
+    store
+        .state_merkle_db
+        .metadata_db()
+        .delete::<JellyfishMerkleNodeSchema>(&NodeKey::new_empty_path(2))
+        .unwrap();
+
+    assert_eq!(store.checkpoint_versions(0, 10).unwrap(), vec![0, 1, 3]);
+    assert_eq!(store.checkpoint_versions(1, 3).unwrap(), vec![1]);
+    assert!(store.checkpoint_versions(4, 10).unwrap().is_empty());
+}
+
+#[test]
+fn test_iter_modules() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+
+    let module_id = ModuleId::new(AccountAddress::random(), Identifier::new("test").unwrap());
+    let module_bytes = vec![1, 2, 3];
+    put_value_set(
+        store,
+        vec![
+            (
+                StateKey::module_id(&module_id),
+                StateValue::from(module_bytes.clone()),
+            ),
+            (
+                StateKey::resource_typed::<AccountResource>(module_id.address()).unwrap(),
+                StateValue::from(vec![4, 5, 6]),
+            ),
+            (StateKey::raw(b"test_key"), StateValue::from(vec![7, 8, 9])),
+        ],
+        0,
+    );
+
+    let modules = store
+        .iter_modules(0)
+        .unwrap()
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(modules, vec![(module_id, Bytes::from(module_bytes))]);
+}
+
+#[test]
+fn test_filter_state_values() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+
+    let address = AccountAddress::random();
+    let resource_key = StateKey::resource_typed::<AccountResource>(&address).unwrap();
+    put_value_set(
+        store,
+        vec![
+            (resource_key.clone(), StateValue::from(vec![1])),
+            (StateKey::raw(b"key_0"), StateValue::from(vec![2])),
+            (StateKey::raw(b"key_1"), StateValue::from(vec![3])),
+        ],
+        0,
+    );
+
+    let resources = store
+        .filter_state_values(0, |key, _value| {
+            matches!(key.inner(), StateKeyInner::AccessPath(_))
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(resources, vec![(resource_key, StateValue::from(vec![1]))]);
+
+    let mut raw_values = store
+        .filter_state_values(0, |_key, value| value.bytes().as_ref() != [1])
+        .unwrap()
+        .map(|res| res.unwrap().1)
+        .collect::<Vec<_>>();
+    raw_values.sort_by(|a, b| a.bytes().cmp(b.bytes()));
+    assert_eq!(raw_values, vec![
+        StateValue::from(vec![2]),
+        StateValue::from(vec![3])
+    ]);
+    assert_eq!(
+        store.filter_state_values(0, |_, _| false).unwrap().count(),
+        0
+    );
+}
+
+#[test]
+fn test_count_accounts() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+
+    let address1 = AccountAddress::random();
+    let address2 = AccountAddress::random();
+    let module_id = ModuleId::new(address1, Identifier::new("test").unwrap());
+    put_value_set(
+        store,
+        vec![
+            (
+                StateKey::resource_typed::<AccountResource>(&address1).unwrap(),
+                StateValue::from(vec![1]),
+            ),
+            (StateKey::module_id(&module_id), StateValue::from(vec![2])),
+            (
+                StateKey::resource_typed::<AccountResource>(&address2).unwrap(),
+                StateValue::from(vec![3]),
+            ),
+            (StateKey::raw(b"test_key"), StateValue::from(vec![4])),
+        ],
+        0,
+    );
+    assert_eq!(store.count_accounts(0).unwrap(), 2);
+
+    // Deleting the only entry of an address removes it from the count.
+    store.commit_block_for_test(1, [vec![(
+        StateKey::resource_typed::<AccountResource>(&address2).unwrap(),
+        None,
+    )]]);
+    assert_eq!(store.count_accounts(1).unwrap(), 1);
+    assert_eq!(store.count_accounts(0).unwrap(), 2);
+}
+
+#[test]
+fn test_state_value_stream() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+
+    let kvs = (0..50u8)
+        .map(|i| (StateKey::raw(&[i]), StateValue::from(vec![i])))
+        .collect::<Vec<_>>();
+    put_value_set(store, kvs.clone(), 0);
+    let mut expected = kvs;
+    expected.sort_by_key(|(key, _value)| key.hash());
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let collect = |shard_id| {
+        rt.block_on(
+            store
+                .state_value_stream(0, shard_id)
+                .collect::<Result<Vec<_>>>(),
+        )
+        .unwrap()
+    };
+    assert_eq!(collect(None), expected);
+    for shard_id in 0..NUM_STATE_SHARDS {
+        let expected_in_shard = expected
+            .iter()
+            .filter(|(key, _value)| key.get_shard_id() == shard_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(collect(Some(shard_id)), expected_in_shard);
+    }
+    assert!(rt
+        .block_on(store.state_value_stream(0, Some(NUM_STATE_SHARDS)).next())
+        .unwrap()
+        .is_err());
+}
+
+#[test]
+fn test_value_dedup_stats() {
+    let tmp_dir = TempPath::new();
+    let unsharded_db = AptosDB::new_for_test(&tmp_dir);
+    let sharded_tmp_dir = TempPath::new();
+    let sharded_db = AptosDB::new_for_test_with_sharding(&sharded_tmp_dir, 1000);
+
+    for db in [unsharded_db, sharded_db] {
+        let store = &db.state_store;
+        let key1 = StateKey::raw(b"test_key1");
+        let key2 = StateKey::raw(b"test_key2");
+        let key3 = StateKey::raw(b"test_key3");
+        let value = StateValue::from(vec![1, 2, 3]);
+        let other_value = StateValue::from(vec![4, 5]);
+
+        put_value_set(
+            store,
+            vec![
+                (key1.clone(), value.clone()),
+                (key2.clone(), value.clone()),
+                (key3.clone(), value.clone()),
+            ],
+            0,
+        );
+        store.commit_block_for_test(1, [vec![
+            (key2.clone(), Some(other_value)),
+            (key3.clone(), None),
+        ]]);
+
+        let state_kv_db = &store.state_kv_db;
+        assert_eq!(state_kv_db.value_dedup_stats(0).unwrap(), DedupStats {
+            total_values: 3,
+            distinct_values: 1,
+            duplicate_bytes: 6,
+        });
+        assert_eq!(state_kv_db.value_dedup_stats(1).unwrap(), DedupStats {
+            total_values: 2,
+            distinct_values: 2,
+            duplicate_bytes: 0,
+        });
+    }
+}
+
+#[test]
+fn test_largest_values() {
+    for enable_sharding in [false, true] {
+        let tmp_dir = TempPath::new();
+        let db = if enable_sharding {
+            AptosDB::new_for_test_with_sharding(&tmp_dir, 1000)
+        } else {
+            AptosDB::new_for_test(&tmp_dir)
+        };
+        let store = &db.state_store;
+        let key1 = StateKey::raw(b"test_key1");
+        let key2 = StateKey::raw(b"test_key2");
+        let key3 = StateKey::raw(b"test_key3");
+
+        put_value_set(
+            store,
+            vec![
+                (key1.clone(), StateValue::from(vec![0; 10])),
+                (key2.clone(), StateValue::from(vec![0; 30])),
+                (key3.clone(), StateValue::from(vec![0; 20])),
+            ],
+            0,
+        );
+        store.commit_block_for_test(1, [vec![
+            (key1.clone(), Some(StateValue::from(vec![0; 40]))),
+            (key2.clone(), None),
+        ]]);
+
+        // Standing in for the write sets, which the test DBs don't have.
+        let keys_by_hash = [&key1, &key2, &key3]
+            .into_iter()
+            .map(|key| (key.hash(), key.clone()))
+            .collect::<HashMap<_, _>>();
+        let largest_values = |version, n| {
+            store
+                .state_kv_db
+                .largest_values(version, n, |key_hash, _version| {
+                    Ok(keys_by_hash[&key_hash].clone())
+                })
+                .unwrap()
+        };
+        assert_eq!(largest_values(0, 2), vec![(key2, 30), (key3.clone(), 20)]);
+        assert_eq!(largest_values(1, 5), vec![(key1, 40), (key3, 20)]);
+        assert!(largest_values(1, 0).is_empty());
+    }
+}
+
+#[test]
+fn test_get_value_bytes_into() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let value1 = StateValue::from(vec![1, 2, 3]);
+    let value2 = StateValue::from(vec![4, 5]);
+
+    put_value_set(
+        store,
+        vec![(key1.clone(), value1.clone()), (key2.clone(), value2)],
+        0,
+    );
+    store.commit_block_for_test(1, [vec![(key2.clone(), None)]]);
+
+    let state_kv_db = &store.state_kv_db;
+    let mut buf = Vec::new();
+    assert!(state_kv_db
+        .get_value_bytes_into(key1.hash(), 1, &mut buf)
+        .unwrap());
+    assert_eq!(&buf[..], &value1.bytes()[..]);
+    // Reusing the buffer.
+    assert!(state_kv_db
+        .get_value_bytes_into(key2.hash(), 0, &mut buf)
+        .unwrap());
+    assert_eq!(buf, vec![4, 5]);
+    assert!(!state_kv_db
+        .get_value_bytes_into(key2.hash(), 1, &mut buf)
+        .unwrap());
+    assert!(buf.is_empty());
+    assert!(!state_kv_db
+        .get_value_bytes_into(StateKey::raw(b"missing").hash(), 1, &mut buf)
+        .unwrap());
+}
+
+#[test]
+fn test_get_value_at_versions() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+    let key = StateKey::raw(b"test_key");
+    let value1 = StateValue::from(vec![1]);
+    let value3 = StateValue::from(vec![3]);
+
+    put_value_set(
+        store,
+        vec![(StateKey::raw(b"other_key"), StateValue::from(vec![0]))],
+        0,
+    );
+    put_value_set(store, vec![(key.clone(), value1.clone())], 1);
+    store.commit_block_for_test(2, [vec![(key.clone(), None)]]);
+    put_value_set(store, vec![(key.clone(), value3.clone())], 3);
+
+    let state_kv_db = &store.state_kv_db;
+    let versions = [2, 0, 5, 1, 3, 1];
+    assert_eq!(
+        state_kv_db
+            .get_value_at_versions(key.hash(), &versions)
+            .unwrap(),
+        versions
+            .iter()
+            .map(|v| state_kv_db.get_value_by_key_hash(key.hash(), *v).unwrap())
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(
+        state_kv_db
+            .get_value_at_versions(key.hash(), &versions)
+            .unwrap(),
+        vec![
+            None,
+            None,
+            Some(value3.clone()),
+            Some(value1.clone()),
+            Some(value3),
+            Some(value1)
+        ],
+    );
+    assert!(state_kv_db
+        .get_value_at_versions(key.hash(), &[])
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        state_kv_db
+            .get_value_at_versions(StateKey::raw(b"missing").hash(), &[0, 3])
+            .unwrap(),
+        vec![None, None],
+    );
+}
+
+#[test]
+fn test_compact_tombstone_ranges() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let state_kv_db = &db.state_store.state_kv_db;
+    let shard = state_kv_db.db_shard(0);
+    let keys = (0..100u8)
+        .map(|i| (HashValue::new([i; HashValue::LENGTH]), 0))
+        .collect::<Vec<_>>();
+
+    for key in &keys {
+        shard
+            .put::<StateValueByKeyHashSchema>(key, &Some(StateValue::from(vec![1])))
+            .unwrap();
+    }
+    shard.flush_cf(STATE_VALUE_BY_KEY_HASH_CF_NAME).unwrap();
+    // No tombstones yet.
+    assert_eq!(state_kv_db.compact_tombstone_ranges(0).unwrap(), 0);
+
+    for key in &keys {
+        shard.delete::<StateValueByKeyHashSchema>(key).unwrap();
+    }
+    shard.flush_cf(STATE_VALUE_BY_KEY_HASH_CF_NAME).unwrap();
+    assert_eq!(state_kv_db.compact_tombstone_ranges(0).unwrap(), 1);
+    // The tombstones are gone with the values they deleted.
+    assert_eq!(state_kv_db.compact_tombstone_ranges(0).unwrap(), 0);
+
+    assert!(state_kv_db
+        .compact_tombstone_ranges(NUM_STATE_SHARDS)
+        .is_err());
+}
+
+#[test]
+fn test_compact_tombstone_ranges_async() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let state_kv_db = &db.state_store.state_kv_db;
+    let shard = state_kv_db.db_shard(0);
+    let keys = (0..100u8)
+        .map(|i| (HashValue::new([i; HashValue::LENGTH]), 0))
+        .collect::<Vec<_>>();
+
+    for key in &keys {
+        shard
+            .put::<StateValueByKeyHashSchema>(key, &Some(StateValue::from(vec![1])))
+            .unwrap();
+        shard.delete::<StateValueByKeyHashSchema>(key).unwrap();
+    }
+    shard.flush_cf(STATE_VALUE_BY_KEY_HASH_CF_NAME).unwrap();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    assert_eq!(
+        rt.block_on(state_kv_db.compact_tombstone_ranges_async(0))
+            .unwrap(),
+        1
+    );
+    // Done by the time the future resolves.
+    assert_eq!(state_kv_db.compact_tombstone_ranges(0).unwrap(), 0);
+    assert!(rt
+        .block_on(state_kv_db.compact_tombstone_ranges_async(NUM_STATE_SHARDS))
+        .is_err());
+}
+
+#[test]
+fn test_remap_keys() {
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let value1 = StateValue::from(vec![1]);
+    let value1_update = StateValue::from(vec![2]);
+    let value2 = StateValue::from(vec![3]);
+    let prefix = |key: &StateKey| match key.inner() {
+        StateKeyInner::Raw(bytes) => {
+            StateKey::raw(&[b"new_".as_slice(), bytes.as_slice()].concat())
+        },
+        _ => key.clone(),
+    };
+    let (new_key1, new_key2) = (prefix(&key1), prefix(&key2));
+    // Standing in for the write sets, which the test DBs don't have.
+    let keys_by_hash = [&key1, &key2]
+        .into_iter()
+        .map(|key| (key.hash(), key.clone()))
+        .collect::<HashMap<_, _>>();
+    let resolve_key = |key_hash: HashValue, _version: Version| -> Result<StateKey> {
+        Ok(keys_by_hash[&key_hash].clone())
+    };
+
+    for enable_sharding in [false, true] {
+        let open_db = |tmp_dir: &TempPath| {
+            let db = if enable_sharding {
+                AptosDB::new_for_test_with_sharding(tmp_dir, 1000)
+            } else {
+                AptosDB::new_for_test(tmp_dir)
+            };
+            put_value_set(
+                &db.state_store,
+                vec![
+                    (key1.clone(), value1.clone()),
+                    (key2.clone(), value2.clone()),
+                ],
+                0,
+            );
+            put_value_set(
+                &db.state_store,
+                vec![(key1.clone(), value1_update.clone())],
+                1,
+            );
+            db
+        };
+
+        let tmp_dir = TempPath::new();
+        let db = open_db(&tmp_dir);
+        let store = &db.state_store;
+        let state_kv_db = &store.state_kv_db;
+        let sst_dir = TempPath::new();
+        // The state merkle DB still refers to the old keys.
+        assert!(store.remap_keys(prefix, sst_dir.path()).is_err());
+        // Not injective.
+        assert!(state_kv_db
+            .remap_keys(|_| StateKey::raw(b"same_key"), resolve_key, sst_dir.path())
+            .is_err());
+
+        state_kv_db
+            .remap_keys(prefix, resolve_key, sst_dir.path())
+            .unwrap();
+        // Already done, so the keys aren't remapped again.
+        state_kv_db
+            .remap_keys(prefix, resolve_key, sst_dir.path())
+            .unwrap();
+        for key in [&key1, &key2] {
+            assert_eq!(
+                state_kv_db
+                    .get_state_value_with_version_by_version(key, 1)
+                    .unwrap(),
+                None
+            );
+        }
+        assert_eq!(
+            state_kv_db
+                .get_state_value_with_version_by_version(&new_key1, 0)
+                .unwrap(),
+            Some((0, value1.clone()))
+        );
+        assert_eq!(
+            state_kv_db
+                .get_state_value_with_version_by_version(&new_key1, 1)
+                .unwrap(),
+            Some((1, value1_update.clone()))
+        );
+        assert_eq!(
+            state_kv_db
+                .get_state_value_with_version_by_version(&new_key2, 1)
+                .unwrap(),
+            Some((0, value2.clone()))
+        );
+
+        // Swapping the keys, each of them being remapped to another remapped one.
+        let tmp_dir = TempPath::new();
+        let db = open_db(&tmp_dir);
+        let state_kv_db = &db.state_store.state_kv_db;
+        let sst_dir = TempPath::new();
+        state_kv_db
+            .remap_keys(
+                |key| match key {
+                    _ if *key == key1 => key2.clone(),
+                    _ if *key == key2 => key1.clone(),
+                    _ => key.clone(),
+                },
+                resolve_key,
+                sst_dir.path(),
+            )
+            .unwrap();
+        assert_eq!(
+            state_kv_db
+                .get_state_value_with_version_by_version(&key2, 1)
+                .unwrap(),
+            Some((1, value1_update.clone()))
+        );
+        assert_eq!(
+            state_kv_db
+                .get_state_value_with_version_by_version(&key1, 1)
+                .unwrap(),
+            Some((0, value2.clone()))
+        );
+    }
+}
+
+#[test]
+fn test_depth_histogram() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+
+    let num_keys = 100;
+    put_value_set(
+        store,
+        (0..num_keys)
+            .map(|i| {
+                (
+                    StateKey::raw(format!("test_key{i}").as_bytes()),
+                    StateValue::from(vec![1]),
+                )
+            })
+            .collect(),
+        0,
+    );
+
+    let state_merkle_db = &store.state_merkle_db;
+    let histogram = state_merkle_db.depth_histogram(0, None).unwrap();
+    assert_eq!(histogram.iter().sum::<u64>(), num_keys);
+    // The root is an internal node.
+    assert_eq!(histogram[0], 0);
+
+    let mut merged_shard_histograms = vec![0; histogram.len()];
+    for shard_id in 0..NUM_STATE_SHARDS {
+        let shard_histogram = state_merkle_db.depth_histogram(0, Some(shard_id)).unwrap();
+        for (depth, num_leaves) in shard_histogram.into_iter().enumerate() {
+            merged_shard_histograms[depth] += num_leaves;
+        }
+    }
+    assert_eq!(merged_shard_histograms, histogram);
+    assert!(state_merkle_db
+        .depth_histogram(0, Some(NUM_STATE_SHARDS))
+        .is_err());
+}
+
+#[test]
+fn test_encoded_bytes_delta() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+
+    for version in 0..3 {
+        put_value_set(
+            store,
+            (0..10)
+                .map(|i| {
+                    (
+                        StateKey::raw(format!("test_key{version}_{i}").as_bytes()),
+                        StateValue::from(vec![1]),
+                    )
+                })
+                .collect(),
+            version,
+        );
+    }
+
+    let state_merkle_db = &store.state_merkle_db;
+    let delta = |from_version, to_version| {
+        state_merkle_db
+            .encoded_bytes_delta(from_version, to_version)
+            .unwrap()
+    };
+    let (internal_bytes_1, leaf_bytes_1) = delta(0, 1);
+    let (internal_bytes_2, leaf_bytes_2) = delta(1, 2);
+    assert!(internal_bytes_1 > 0 && leaf_bytes_1 > 0);
+    assert_eq!(
+        delta(0, 2),
+        (
+            internal_bytes_1 + internal_bytes_2,
+            leaf_bytes_1 + leaf_bytes_2
+        )
+    );
+    assert_eq!(delta(1, 1), (0, 0));
+    assert!(state_merkle_db.encoded_bytes_delta(2, 1).is_err());
+}
+
+#[test]
+fn test_cache_jmt_nodes_on_read_only() {
+    let root_cached = |cache_on_read_only| {
+        let tmp_dir = TempPath::new();
+        let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+        db.set_cache_jmt_nodes_on_read_only(cache_on_read_only);
+        let store = &db.state_store;
+        for version in 0..5 {
+            // Enough items for a snapshot of each version.
+            put_value_set(
+                store,
+                (0..BUFFERED_STATE_TARGET_ITEMS_FOR_TEST)
+                    .map(|i| {
+                        (
+                            StateKey::raw(format!("test_key{version}_{i}").as_bytes()),
+                            StateValue::from(vec![1]),
+                        )
+                    })
+                    .collect(),
+                version,
+            );
+        }
+        store.buffered_state().lock().sync_commit();
+
+        // Later versions don't read the root of version 0, so only the writes can cache it.
+        store
+            .state_merkle_db
+            .lru_cache()
+            .unwrap()
+            .get(&NodeKey::new_empty_path(0))
+            .is_some()
+    };
+    assert!(root_cached(false));
+    assert!(!root_cached(true));
+}
+
+#[test]
+fn test_shard_subtree_sizes() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+
+    let num_keys = 100;
+    put_value_set(
+        store,
+        (0..num_keys)
+            .map(|i| {
+                (
+                    StateKey::raw(format!("test_key{i}").as_bytes()),
+                    StateValue::from(vec![1]),
+                )
+            })
+            .collect(),
+        0,
+    );
+
+    let state_merkle_db = &store.state_merkle_db;
+    let sizes = state_merkle_db.shard_subtree_sizes(0).unwrap();
+    for (shard_id, (num_nodes, num_bytes)) in sizes.into_iter().enumerate() {
+        let num_leaves = state_merkle_db
+            .depth_histogram(0, Some(shard_id))
+            .unwrap()
+            .into_iter()
+            .sum::<u64>();
+        assert!(num_nodes >= num_leaves);
+        assert_eq!(num_nodes == 0, num_bytes == 0);
+    }
+    assert!(sizes.iter().map(|(num_nodes, _)| num_nodes).sum::<u64>() >= num_keys);
+}
+
+#[test]
+fn test_get_value_by_key_hash() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let value1 = StateValue::from(String::from("test_val1").into_bytes());
+    let value1_update = StateValue::from(String::from("test_val1_update").into_bytes());
+
+    put_value_set(store, vec![(key1.clone(), value1.clone())], 0);
+    put_value_set(store, vec![(key1.clone(), value1_update.clone())], 1);
+
+    let state_kv_db = &store.state_kv_db;
+    assert_eq!(
+        state_kv_db.get_value_by_key_hash(key1.hash(), 0).unwrap(),
+        Some(value1)
+    );
+    assert_eq!(
+        state_kv_db.get_value_by_key_hash(key1.hash(), 1).unwrap(),
+        Some(value1_update)
+    );
+    assert_eq!(
+        state_kv_db.get_value_by_key_hash(key2.hash(), 1).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_export_dot() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+
+    let keys = (0..100)
+        .map(|i| StateKey::raw(format!("test_key{i}").as_bytes()))
+        .collect::<Vec<_>>();
+    put_value_set(
+        store,
+        keys.iter()
+            .map(|key| (key.clone(), StateValue::from(vec![1])))
+            .collect(),
+        0,
+    );
+
+    let state_merkle_db = &store.state_merkle_db;
+    for shard_id in 0..NUM_STATE_SHARDS {
+        let mut dot = Vec::new();
+        state_merkle_db.export_dot(0, shard_id, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with(&format!("digraph \"shard_{shard_id}_version_0\" {{")));
+
+        let num_exported_nodes = dot.lines().filter(|line| line.contains("[shape=")).count();
+        let num_leaves = dot
+            .lines()
+            .filter(|line| line.contains("[shape=box"))
+            .count();
+        let num_edges = dot.lines().filter(|line| line.contains(" -> ")).count();
+        assert_eq!(
+            num_leaves,
+            keys.iter()
+                .filter(|key| key.get_shard_id() == shard_id)
+                .count()
+        );
+        // Every node but the root of the subtree has exactly one incoming edge.
+        assert_eq!(num_edges, num_exported_nodes.saturating_sub(1));
+    }
+}
+
+#[test]
+fn test_apply_update_to_smt() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let key3 = StateKey::raw(b"test_key3");
+    let value1 = StateValue::from(String::from("test_val1").into_bytes());
+    let value2 = StateValue::from(String::from("test_val2").into_bytes());
+    let value3 = StateValue::from(String::from("test_val3").into_bytes());
+
+    let root0 = put_value_set(store, vec![(key1.clone(), value1)], 0);
+    let root1 = put_value_set(store, vec![(key2.clone(), value2.clone())], 1);
+    let root2 = put_value_set(store, vec![(key3.clone(), value3.clone())], 2);
+
+    // Catch up from version 0 in memory, one update at a time.
+    let base = SparseMerkleTree::new(root0);
+    let smt1 = store
+        .apply_update_to_smt(&base, Some(0), &base, &[(key2.hash(), Some(value2.hash()))])
+        .unwrap();
+    assert_eq!(smt1.root_hash(), root1);
+    let smt2 = store
+        .apply_update_to_smt(&base, Some(0), &smt1, &[(key3.hash(), Some(value3.hash()))])
+        .unwrap();
+    assert_eq!(smt2.root_hash(), root2);
+
+    // The base version isn't in the DB, so the proofs can't be read.
+    assert!(store
+        .apply_update_to_smt(&base, Some(10), &base, &[(
+            key2.hash(),
+            Some(value2.hash())
+        )])
+        .is_err());
+}
+
+#[test]
+fn test_merklize_for_test() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = StateKey::raw(b"test_key2");
+    let value1 = StateValue::from(String::from("test_val1").into_bytes());
+    let value2 = StateValue::from(String::from("test_val2").into_bytes());
+
+    put_value_set(store, vec![(key1, value1)], 0);
+
+    let update = (value2.hash(), key2.clone());
+    let (root_hash, nodes) = store
+        .state_merkle_db
+        .merklize_for_test(vec![(key2.hash(), Some(&update))], 1, Some(0))
+        .unwrap();
+    assert!(nodes.iter().any(|node| matches!(node, Node::Leaf(_))));
+    // Nothing is persisted.
+    assert!(store.get_root_hash(1).is_err());
+
+    assert_eq!(put_value_set(store, vec![(key2, value2)], 1), root_hash);
+}
+
+#[test]
+fn test_changed_shards() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 1000);
+    let store = &db.state_store;
+    // Two keys falling in different shards.
+    let key1 = StateKey::raw(b"test_key1");
+    let key2 = (0u8..)
+        .map(|i| StateKey::raw(&[i]))
+        .find(|key| key.get_shard_id() != key1.get_shard_id())
+        .unwrap();
+    let value1 = StateValue::from(String::from("test_val1").into_bytes());
+    let value2 = StateValue::from(String::from("test_val2").into_bytes());
+
+    put_value_set(store, vec![(key1.clone(), value1)], 0);
+    put_value_set(store, vec![(key2.clone(), value2)], 1);
+
+    let state_merkle_db = &store.state_merkle_db;
+    assert_eq!(state_merkle_db.changed_shards(0).unwrap(), vec![
+        key1.get_shard_id()
+    ]);
+    assert_eq!(state_merkle_db.changed_shards(1).unwrap(), vec![
+        key2.get_shard_id()
+    ]);
+    assert!(state_merkle_db.changed_shards(2).is_err());
+}
+
+#[test]
+fn test_export_import_shard() {
+    let value_set = |num_keys, value: &[u8]| {
+        (0..num_keys)
+            .map(|i| {
+                (
+                    StateKey::raw(format!("test_key{i}").as_bytes()),
+                    StateValue::from(value.to_vec()),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let tmp_dirs = [
+        TempPath::new(),
+        TempPath::new(),
+        TempPath::new(),
+        TempPath::new(),
+    ];
+    let [db1, db2, db3, db4] = tmp_dirs
+        .each_ref()
+        .map(|dir| AptosDB::new_for_test_with_sharding(dir, 1000));
+    put_value_set(&db1.state_store, value_set(100, &[1]), 0);
+    put_value_set(&db2.state_store, value_set(100, &[1]), 0);
+    put_value_set(&db3.state_store, value_set(100, &[2]), 0);
+    put_value_set(&db4.state_store, value_set(300, &[1]), 0);
+
+    let shard_id = StateKey::raw(b"test_key0").get_shard_id();
+    let mut exported = Vec::new();
+    let num_nodes = db1
+        .state_merkle_db()
+        .export_shard(shard_id, 0, &mut exported)
+        .unwrap();
+    assert!(num_nodes > 0);
+
+    let shard_node_keys = |db: &AptosDB| {
+        db.state_merkle_db()
+            .db_shard(shard_id)
+            .iter::<JellyfishMerkleNodeSchema>()
+            .unwrap()
+            .map(|item| item.unwrap().0)
+            .collect::<HashSet<_>>()
+    };
+    let node_keys = shard_node_keys(&db1);
+    let other_node_keys = shard_node_keys(&db4);
+    assert_eq!(node_keys.len(), num_nodes);
+
+    // Replace the shard with the one of a tree of more keys, then restore it from the export.
+    let state_merkle_db = db2.state_merkle_db();
+    let shard = state_merkle_db.db_shard(shard_id);
+    for node_key in shard_node_keys(&db2) {
+        shard
+            .delete::<JellyfishMerkleNodeSchema>(&node_key)
+            .unwrap();
+    }
+    for item in db4
+        .state_merkle_db()
+        .db_shard(shard_id)
+        .iter::<JellyfishMerkleNodeSchema>()
+        .unwrap()
+    {
+        let (node_key, node) = item.unwrap();
+        shard
+            .put::<JellyfishMerkleNodeSchema>(&node_key, &node)
+            .unwrap();
+    }
+    assert!(state_merkle_db.verify_no_dangling_after_prune(0).is_err());
+    assert_eq!(
+        state_merkle_db
+            .import_shard(shard_id, &mut exported.as_slice())
+            .unwrap(),
+        num_nodes
+    );
+    state_merkle_db.verify_no_dangling_after_prune(0).unwrap();
+    // The nodes of the other tree the import didn't overwrite are left for the pruner.
+    let stale_node_keys = shard
+        .iter::<StaleNodeIndexCrossEpochSchema>()
+        .unwrap()
+        .map(|item| {
+            let (index, ()) = item.unwrap();
+            assert_eq!(index.stale_since_version, 0);
+            index.node_key
+        })
+        .collect::<HashSet<_>>();
+    assert!(!stale_node_keys.is_empty());
+    assert_eq!(
+        stale_node_keys,
+        other_node_keys
+            .difference(&node_keys)
+            .cloned()
+            .collect::<HashSet<_>>()
+    );
+
+    // The shard doesn't belong to the tree of another DB, nor to another shard.
+    let state_merkle_db = db3.state_merkle_db();
+    assert!(state_merkle_db
+        .import_shard(shard_id, &mut exported.as_slice())
+        .is_err());
+    assert!(db2
+        .state_merkle_db()
+        .import_shard((shard_id + 1) % NUM_STATE_SHARDS, &mut exported.as_slice())
+        .is_err());
+    // Truncated.
+    assert!(db2
+        .state_merkle_db()
+        .import_shard(shard_id, &mut &exported[..exported.len() - 1])
+        .is_err());
+}
+
+#[test]
+fn test_is_proof_servable() {
+    let tmp_dir = TempPath::new();
+    // Without the node caches, so that deleting nodes takes effect.
+    let db = AptosDB::new_for_test_with_sharding(&tmp_dir, 0);
+    let store = &db.state_store;
+    let keys = (0..100)
+        .map(|i| StateKey::raw(format!("test_key{i}").as_bytes()))
+        .collect::<Vec<_>>();
+    put_value_set(
+        store,
+        keys.iter()
+            .map(|key| (key.clone(), StateValue::from(vec![1])))
+            .collect(),
+        0,
+    );
+
+    let state_merkle_db = &store.state_merkle_db;
+    let missing_key = StateKey::raw(b"missing_key");
+    for key in keys.iter().chain([&missing_key]) {
+        assert!(state_merkle_db.is_proof_servable(&key.hash(), 0).unwrap());
+    }
+    assert!(!state_merkle_db
+        .is_proof_servable(&keys[0].hash(), 1)
+        .unwrap());
+
+    // Pruning the leaf of a key breaks its proof.
+    let shard = state_merkle_db.db_shard(keys[0].get_shard_id());
+    let leaf_key = shard
+        .iter::<JellyfishMerkleNodeSchema>()
+        .unwrap()
+        .map(|item| item.unwrap())
+        .find_map(|(node_key, node)| match node {
+            Node::Leaf(leaf_node) if *leaf_node.account_key() == keys[0].hash() => Some(node_key),
+            _ => None,
+        })
+        .unwrap();
+    shard
+        .delete::<JellyfishMerkleNodeSchema>(&leaf_key)
+        .unwrap();
+    assert!(!state_merkle_db
+        .is_proof_servable(&keys[0].hash(), 0)
+        .unwrap());
+    assert!(state_merkle_db
+        .get_with_proof_ext(&keys[0].hash(), 0, 0)
+        .is_err());
+    // Nor are the proofs carrying it as a sibling, which the check reports rather than fails on.
+    for key in keys.iter().chain([&missing_key]) {
+        assert_eq!(
+            state_merkle_db.is_proof_servable(&key.hash(), 0).unwrap(),
+            state_merkle_db
+                .get_with_proof_ext(&key.hash(), 0, 0)
+                .is_ok(),
+        );
+    }
+}
+
+#[test]
+fn test_get_proofs_parallel() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let keys = (0..20)
+        .map(|i| StateKey::raw(format!("test_key{i}").as_bytes()))
+        .collect::<Vec<_>>();
+    let mut roots = Vec::new();
+    for version in 0..3 {
+        roots.push(put_value_set(
+            store,
+            keys.iter()
+                .skip(version as usize)
+                .map(|key| (key.clone(), StateValue::from(vec![version as u8])))
+                .collect(),
+            version,
+        ));
+    }
+
+    let missing_key = StateKey::raw(b"missing_key");
+    let requests = keys
+        .iter()
+        .chain([&missing_key])
+        .enumerate()
+        .map(|(i, key)| (key.clone(), (i % 3) as Version))
+        .collect::<Vec<_>>();
+    let results = store.get_proofs_parallel(requests.clone()).unwrap();
+    assert_eq!(results.len(), requests.len());
+    for ((key, version), (value, proof)) in requests.iter().zip(results) {
+        let expected = store
+            .get_state_value_with_proof_by_version_ext(&key.hash(), *version, 0, false)
+            .unwrap();
+        assert_eq!((value.clone(), proof.clone()), expected);
+        proof
+            .verify(roots[*version as usize], key.hash(), value.as_ref())
+            .unwrap();
+    }
+    assert!(store.get_proofs_parallel(vec![]).unwrap().is_empty());
+    assert!(store.get_proofs_parallel(vec![(missing_key, 3)]).is_err());
+}
+
+#[test]
+fn test_find_version_by_root() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let key = StateKey::raw(b"test_key");
+    let value1 = StateValue::from(String::from("test_val1").into_bytes());
+    let value2 = StateValue::from(String::from("test_val2").into_bytes());
+
+    let root1 = put_value_set(store, vec![(key.clone(), value1.clone())], 0);
+    let root2 = put_value_set(store, vec![(key.clone(), value2)], 1);
+    // Back to the root of version 0.
+    assert_eq!(put_value_set(store, vec![(key, value1)], 2), root1);
+
+    let state_merkle_db = &store.state_merkle_db;
+    assert_eq!(
+        state_merkle_db.find_version_by_root(root1, (0, 3)).unwrap(),
+        Some(0)
+    );
+    assert_eq!(
+        state_merkle_db.find_version_by_root(root1, (1, 3)).unwrap(),
+        Some(2)
+    );
+    assert_eq!(
+        state_merkle_db.find_version_by_root(root2, (0, 3)).unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        state_merkle_db.find_version_by_root(root2, (2, 3)).unwrap(),
+        None
+    );
+    assert_eq!(
+        state_merkle_db
+            .find_version_by_root(HashValue::random(), (0, 3))
+            .unwrap(),
+        None
+    );
+}
+
 fn traverse_values(
     store: &StateStore,
     prefix: &StateKeyPrefix,
@@ -221,6 +1524,128 @@ fn test_get_values_by_key_prefix() {
     assert_eq!(*key_value_map.get(&key5).unwrap(), value5_v2);
 }
 
+#[test]
+fn test_get_account_state() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.state_store;
+    let address = AccountAddress::new([12u8; AccountAddress::LENGTH]);
+    let other_address = AccountAddress::new([22u8; AccountAddress::LENGTH]);
+
+    let key1 = StateKey::resource_typed::<AccountResource>(&address).unwrap();
+    let key2 = StateKey::resource_typed::<ChainIdResource>(&address).unwrap();
+    let other_key = StateKey::resource_typed::<AccountResource>(&other_address).unwrap();
+    let value1 = StateValue::from(String::from("value1").into_bytes());
+    let value2 = StateValue::from(String::from("value2").into_bytes());
+    let other_value = StateValue::from(String::from("other_value").into_bytes());
+
+    put_value_set(
+        store,
+        vec![(key1.clone(), value1.clone()), (other_key, other_value)],
+        0,
+    );
+    put_value_set(store, vec![(key2.clone(), value2.clone())], 1);
+
+    assert_eq!(
+        store.get_account_state(address, 0).unwrap(),
+        BTreeMap::from([(key1.clone(), value1.clone())])
+    );
+    assert_eq!(
+        store.get_account_state(address, 1).unwrap(),
+        BTreeMap::from([(key1, value1), (key2, value2)])
+    );
+    assert!(store
+        .get_account_state(AccountAddress::ONE, 1)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_get_account_state_sharded() {
+    let indexer_tmp_dir = TempPath::new();
+    let internal_indexer_db = InternalIndexerDB::new(
+        Arc::new(
+            open_internal_indexer_db(indexer_tmp_dir.path(), &RocksdbConfig::default()).unwrap(),
+        ),
+        InternalIndexerDBConfig::new(
+            false,  /* enable_transaction */
+            false,  /* enable_event */
+            false,  /* enable_event_v2_translation */
+            0,      /* event_v2_translation_ignores_below_version */
+            true,   /* enable_statekeys */
+            10_000, /* batch_size */
+        ),
+    );
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::open(
+        StorageDirPaths::from_path(&tmp_dir),
+        false, /* readonly */
+        NO_OP_STORAGE_PRUNER_CONFIG,
+        RocksdbConfigs {
+            enable_storage_sharding: true,
+            ..Default::default()
+        },
+        false, /* enable_indexer */
+        BUFFERED_STATE_TARGET_ITEMS_FOR_TEST,
+        1000, /* max_num_nodes_per_lru_cache_shard */
+        Some(internal_indexer_db.clone()),
+        HotStateConfig::default(),
+    )
+    .unwrap();
+    let store = &db.state_store;
+    let address = AccountAddress::new([12u8; AccountAddress::LENGTH]);
+    let other_address = AccountAddress::new([22u8; AccountAddress::LENGTH]);
+
+    let key1 = StateKey::resource_typed::<AccountResource>(&address).unwrap();
+    let key2 = StateKey::resource_typed::<ChainIdResource>(&address).unwrap();
+    let other_key = StateKey::resource_typed::<AccountResource>(&other_address).unwrap();
+    let value1 = StateValue::from(String::from("value1").into_bytes());
+    let value2 = StateValue::from(String::from("value2").into_bytes());
+    let other_value = StateValue::from(String::from("other_value").into_bytes());
+
+    put_value_set(
+        store,
+        vec![
+            (key1.clone(), value1.clone()),
+            (other_key.clone(), other_value),
+        ],
+        0,
+    );
+    put_value_set(store, vec![(key2.clone(), value2.clone())], 1);
+
+    // Not indexed yet.
+    assert!(store.get_account_state(address, 1).is_err());
+
+    // Index the keys as the internal indexer would.
+    let mut batch = SchemaBatch::new();
+    for key in [&key1, &key2, &other_key] {
+        batch.put::<StateKeysSchema>(key, &()).unwrap();
+    }
+    batch
+        .put::<InternalIndexerMetadataSchema>(
+            &MetadataKey::StateVersion,
+            &MetadataValue::Version(1),
+        )
+        .unwrap();
+    internal_indexer_db
+        .get_inner_db_ref()
+        .write_schemas(batch)
+        .unwrap();
+
+    assert_eq!(
+        store.get_account_state(address, 0).unwrap(),
+        BTreeMap::from([(key1.clone(), value1.clone())])
+    );
+    assert_eq!(
+        store.get_account_state(address, 1).unwrap(),
+        BTreeMap::from([(key1, value1), (key2, value2)])
+    );
+    assert!(store
+        .get_account_state(AccountAddress::ONE, 1)
+        .unwrap()
+        .is_empty());
+}
+
 #[test]
 pub fn test_get_state_snapshot_before() {
     let tmp_dir = TempPath::new();
@@ -0,0 +1,93 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+use anyhow::{ensure, format_err, Result};
+use aptos_crypto::{hash::CryptoHash, HashValue};
+use aptos_scratchpad::{ProofRead, SparseMerkleTree};
+use aptos_types::{
+    proof::SparseMerkleProofExt,
+    state_store::{state_key::StateKey, state_value::StateValue},
+    transaction::Version,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Proof that the state at `to_version` is the state at `from_version` with the writes of the
+/// versions in between applied, see `StateStore::get_transition_proof`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StateTransitionProof {
+    pub from_version: Version,
+    pub to_version: Version,
+    /// One entry per key written in `(from_version, to_version]`, ordered by state key hash.
+    pub entries: Vec<StateTransitionEntry>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StateTransitionEntry {
+    pub state_key: StateKey,
+    /// Value at `from_version`, `None` if the key didn't exist.
+    pub old_value: Option<StateValue>,
+    /// Value at `to_version`, `None` if the key doesn't exist.
+    pub new_value: Option<StateValue>,
+    /// Proof of `old_value` against the root at `from_version`.
+    pub old_proof: SparseMerkleProofExt,
+    /// Proof of `new_value` against the root at `to_version`.
+    pub new_proof: SparseMerkleProofExt,
+}
+
+impl StateTransitionProof {
+    /// Verifies that the state with root `to_root` is the state with root `from_root` where only
+    /// the keys of the entries changed, to their new values. Checking each proof alone isn't
+    /// enough for that: the new root is recomputed from the old one and the old sibling paths, so
+    /// that a change left out of the proof is caught too.
+    pub fn verify(&self, from_root: HashValue, to_root: HashValue) -> Result<()> {
+        ensure!(
+            self.from_version <= self.to_version,
+            "Invalid version range ({}, {}].",
+            self.from_version,
+            self.to_version,
+        );
+
+        let mut old_proofs = HashMap::new();
+        let mut updates = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let key_hash = entry.state_key.hash();
+            entry
+                .old_proof
+                .verify(from_root, key_hash, entry.old_value.as_ref())?;
+            entry
+                .new_proof
+                .verify(to_root, key_hash, entry.new_value.as_ref())?;
+            ensure!(
+                old_proofs
+                    .insert(key_hash, entry.old_proof.clone())
+                    .is_none(),
+                "Duplicate entry for {:?}.",
+                entry.state_key,
+            );
+            updates.push((key_hash, entry.new_value.as_ref().map(CryptoHash::hash)));
+        }
+
+        let base = SparseMerkleTree::new(from_root);
+        let new_root = base
+            .freeze(&base)
+            .batch_update(updates.iter(), &TransitionProofReader(old_proofs))
+            .map_err(|err| format_err!("Failed to apply the entries: {err}"))?
+            .root_hash();
+        ensure!(
+            new_root == to_root,
+            "Root after applying the entries is {}, expected {}.",
+            new_root,
+            to_root,
+        );
+        Ok(())
+    }
+}
+
+struct TransitionProofReader(HashMap<HashValue, SparseMerkleProofExt>);
+
+impl ProofRead for TransitionProofReader {
+    fn get_proof(&self, key: &HashValue, _root_depth: usize) -> Option<SparseMerkleProofExt> {
+        self.0.get(key).cloned()
+    }
+}
@@ -13,12 +13,13 @@ use aptos_db_indexer_schemas::{
     utils::AccountOrderedTransactionsIter,
 };
 use aptos_schemadb::{batch::SchemaBatch, iterator::ScanDirection};
-use aptos_storage_interface::{AptosDbError, Result};
+use aptos_storage_interface::{db_ensure as ensure, AptosDbError, Result};
 use aptos_types::{
     account_address::AccountAddress,
-    transaction::{ReplayProtector, Transaction, Version},
+    state_store::{state_key::StateKey, state_value::StateValue},
+    transaction::{ReplayProtector, Transaction, TransactionInfo, TransactionOutput, Version},
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 #[cfg(test)]
 mod test;
@@ -32,6 +33,79 @@ impl TransactionStore {
         Self { ledger_db }
     }
 
+    /// Gets the output of the transaction at `version`, assembled from its write set, events,
+    /// auxiliary data and the gas used and status in its transaction info.
+    pub fn get_transaction_output(&self, version: Version) -> Result<TransactionOutput> {
+        let txn_info = self
+            .ledger_db
+            .transaction_info_db()
+            .get_transaction_info(version)?;
+        self.get_transaction_output_with_info(version, &txn_info)
+    }
+
+    /// Same as `get_transaction_output`, but with the transaction info already at hand.
+    pub(crate) fn get_transaction_output_with_info(
+        &self,
+        version: Version,
+        txn_info: &TransactionInfo,
+    ) -> Result<TransactionOutput> {
+        let write_set = self.ledger_db.write_set_db().get_write_set(version)?;
+        let events = self.ledger_db.event_db().get_events_by_version(version)?;
+        let auxiliary_data = self
+            .ledger_db
+            .transaction_auxiliary_data_db()
+            .get_transaction_auxiliary_data(version)?
+            .unwrap_or_default();
+        Ok(TransactionOutput::new(
+            write_set,
+            events,
+            txn_info.gas_used(),
+            txn_info.status().clone().into(),
+            auxiliary_data,
+        ))
+    }
+
+    /// Returns the serialized sizes in bytes of the write sets of up to `limit` transactions
+    /// starting from `start_version`, fewer if the DB ends before that.
+    pub fn write_set_sizes(
+        &self,
+        start_version: Version,
+        limit: usize,
+    ) -> Result<Vec<(Version, u64)>> {
+        self.ledger_db
+            .write_set_db()
+            .get_write_set_sizes(start_version, limit)
+    }
+
+    /// Returns the net effect of the write sets of the block spanning `[block_first_version,
+    /// block_last_version]`: each key written in it with its value after the block, `None` if it
+    /// ended up deleted.
+    pub fn block_write_effects(
+        &self,
+        block_first_version: Version,
+        block_last_version: Version,
+    ) -> Result<HashMap<StateKey, Option<StateValue>>> {
+        ensure!(
+            block_first_version <= block_last_version,
+            "Invalid block version range [{}, {}].",
+            block_first_version,
+            block_last_version,
+        );
+        let num_txns = (block_last_version - block_first_version + 1) as usize;
+
+        let mut effects = HashMap::new();
+        for write_set in self
+            .ledger_db
+            .write_set_db()
+            .get_write_set_iter(block_first_version, num_txns)?
+        {
+            for (state_key, value_opt) in write_set?.state_update_refs() {
+                effects.insert(state_key.clone(), value_opt.cloned());
+            }
+        }
+        Ok(effects)
+    }
+
     /// Gets the version of a transaction by the sender `address` and `sequence_number`.
     pub fn get_account_ordered_transaction_version(
         &self,
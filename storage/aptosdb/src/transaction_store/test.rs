@@ -7,7 +7,11 @@ use super::*;
 use crate::{ledger_db::transaction_db_test::init_db, AptosDB};
 use aptos_proptest_helpers::Index;
 use aptos_temppath::TempPath;
-use aptos_types::proptest_types::{AccountInfoUniverse, SignatureCheckedTransactionGen};
+use aptos_types::{
+    proptest_types::{AccountInfoUniverse, SignatureCheckedTransactionGen},
+    transaction::{ExecutionStatus, TransactionAuxiliaryData},
+    write_set::WriteSet,
+};
 use proptest::{collection::vec, prelude::*};
 use std::collections::BTreeMap;
 
@@ -133,3 +137,54 @@ proptest! {
         prop_assert_eq!(&actual_scan, &expected_scan);
     }
 }
+
+#[test]
+fn test_block_write_effects() {
+    let tmp_dir = TempPath::new();
+    let db = AptosDB::new_for_test(&tmp_dir);
+    let store = &db.transaction_store;
+    let value = |bytes: &[u8]| Some(StateValue::from(bytes.to_vec()));
+    let key = |bytes: &[u8]| StateKey::raw(bytes);
+    let txn_outs = [
+        vec![
+            (key(b"key_0"), value(b"val_0")),
+            (key(b"key_1"), value(b"val_1")),
+        ],
+        vec![
+            (key(b"key_0"), value(b"val_2")),
+            (key(b"key_2"), value(b"val_3")),
+        ],
+        vec![(key(b"key_1"), None)],
+    ]
+    .into_iter()
+    .map(|write_set| {
+        TransactionOutput::new(
+            WriteSet::new_for_test(write_set),
+            vec![],
+            0,
+            ExecutionStatus::Success.into(),
+            TransactionAuxiliaryData::default(),
+        )
+    })
+    .collect::<Vec<_>>();
+    db.ledger_db
+        .write_set_db()
+        .commit_write_sets(0, &txn_outs)
+        .unwrap();
+
+    assert_eq!(
+        store.block_write_effects(0, 2).unwrap(),
+        HashMap::from([
+            (key(b"key_0"), value(b"val_2")),
+            (key(b"key_1"), None),
+            (key(b"key_2"), value(b"val_3")),
+        ])
+    );
+    assert_eq!(
+        store.block_write_effects(2, 2).unwrap(),
+        HashMap::from([(key(b"key_1"), None)])
+    );
+    // Fails if the block isn't all in the DB.
+    assert!(store.block_write_effects(1, 3).is_err());
+    assert!(store.block_write_effects(2, 1).is_err());
+}
@@ -2,15 +2,49 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 pub mod iterators;
+pub mod options_diff;
 pub(crate) mod truncation_helper;
 
 use crate::schema::db_metadata::{DbMetadataKey, DbMetadataSchema};
-use aptos_schemadb::{batch::NativeBatch, DB};
+use aptos_config::config::StorageDirPaths;
+use aptos_schemadb::{batch::NativeBatch, Options, DB};
 use aptos_storage_interface::Result;
 use aptos_types::{state_store::NUM_STATE_SHARDS, transaction::Version};
+use std::path::Path;
 
 pub(crate) type ShardedStateKvSchemaBatch<'db> = [NativeBatch<'db>; NUM_STATE_SHARDS];
 
+/// Puts the WAL of the DB named `name` in its own directory under `wal_root_path`, if set. The
+/// directory is created unless `readonly`, in which case it's expected to exist already.
+pub(crate) fn set_wal_dir(
+    rocksdb_opts: &mut Options,
+    wal_root_path: Option<&Path>,
+    name: &str,
+    readonly: bool,
+) -> Result<()> {
+    if let Some(wal_root_path) = wal_root_path {
+        let wal_dir = wal_root_path.join(name);
+        if !readonly {
+            std::fs::create_dir_all(&wal_dir)?;
+        }
+        rocksdb_opts.set_wal_dir(wal_dir);
+    }
+    Ok(())
+}
+
+/// The paths of a DB with everything under `db_root_path`, but the WALs under `wal_root_path`, if
+/// set.
+pub(crate) fn dir_paths_with_wal(
+    db_root_path: impl AsRef<Path>,
+    wal_root_path: Option<&Path>,
+) -> StorageDirPaths {
+    let db_paths = StorageDirPaths::from_path(db_root_path);
+    match wal_root_path {
+        Some(wal_root_path) => db_paths.with_wal_path(wal_root_path),
+        None => db_paths,
+    }
+}
+
 pub(crate) fn get_progress(db: &DB, progress_key: &DbMetadataKey) -> Result<Option<Version>> {
     Ok(db
         .get::<DbMetadataSchema>(progress_key)?
@@ -0,0 +1,163 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Comparison of the RocksDB options of the DB instances under two AptosDB roots.
+//!
+//! RocksDB writes the options a DB is opened with, and any changed at runtime, to an `OPTIONS-*`
+//! file in the DB directory, so the latest of these files reflects the options the DB last ran
+//! with. They're INI-like: a `[DBOptions]` section, then a `[CFOptions "<cf>"]` and a
+//! `[TableOptions/BlockBasedTable "<cf>"]` section per column family.
+
+use aptos_storage_interface::Result;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+const OPTIONS_FILE_PREFIX: &str = "OPTIONS-";
+
+/// An option set differently, or only set, in one of the two DBs compared.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OptionDiff {
+    /// Directory of the DB instance, relative to the AptosDB root, e.g. `state_kv_db/shard_0`.
+    pub db_dir: PathBuf,
+    /// Section of the OPTIONS file, e.g. `DBOptions` or `CFOptions "default"`.
+    pub section: String,
+    pub option: String,
+    /// Value in the first DB, `None` if it doesn't have it, e.g. if the DB instance is missing.
+    pub value: Option<String>,
+    /// Value in the other DB, `None` if it doesn't have it.
+    pub other_value: Option<String>,
+}
+
+type OptionsBySection = BTreeMap<(String, String), String>;
+
+/// Compares the latest OPTIONS files of all the DB instances under `db_root_path` and
+/// `other_root_path`, matched by their directories relative to the roots. The differences are
+/// ordered by DB directory, then section and option.
+pub fn diff_options(
+    db_root_path: impl AsRef<Path>,
+    other_root_path: impl AsRef<Path>,
+) -> Result<Vec<OptionDiff>> {
+    let options_files = find_options_files(db_root_path.as_ref())?;
+    let other_options_files = find_options_files(other_root_path.as_ref())?;
+    let db_dirs = options_files
+        .keys()
+        .chain(other_options_files.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    let mut diffs = Vec::new();
+    for db_dir in db_dirs {
+        let options = parse_options_file(options_files.get(&db_dir))?;
+        let other_options = parse_options_file(other_options_files.get(&db_dir))?;
+        let names = options
+            .keys()
+            .chain(other_options.keys())
+            .collect::<BTreeSet<_>>();
+        for name @ (section, option) in names {
+            let value = options.get(name);
+            let other_value = other_options.get(name);
+            if value != other_value {
+                diffs.push(OptionDiff {
+                    db_dir: db_dir.clone(),
+                    section: section.clone(),
+                    option: option.clone(),
+                    value: value.cloned(),
+                    other_value: other_value.cloned(),
+                });
+            }
+        }
+    }
+    Ok(diffs)
+}
+
+/// Returns the latest OPTIONS file of each DB directory under `root_path`, by the directory
+/// relative to `root_path`.
+fn find_options_files(root_path: &Path) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    let mut options_files = BTreeMap::new();
+    let mut dirs = vec![root_path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut latest: Option<(u64, PathBuf)> = None;
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            // Skips the temporary files, e.g. `OPTIONS-000007.dbtmp`.
+            let Some(number) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(OPTIONS_FILE_PREFIX))
+                .and_then(|number| number.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            if latest.as_ref().is_none_or(|(latest, _)| number > *latest) {
+                latest = Some((number, path));
+            }
+        }
+        if let Some((_number, path)) = latest {
+            let db_dir = dir
+                .strip_prefix(root_path)
+                .expect("Walked from the root.")
+                .to_path_buf();
+            options_files.insert(db_dir, path);
+        }
+    }
+    Ok(options_files)
+}
+
+fn parse_options_file(path: Option<&PathBuf>) -> Result<OptionsBySection> {
+    let Some(path) = path else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(parse_options(&std::fs::read_to_string(path)?))
+}
+
+fn parse_options(content: &str) -> OptionsBySection {
+    let mut options = BTreeMap::new();
+    let mut section = String::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.to_string();
+        } else if let Some((option, value)) = line.split_once('=') {
+            options.insert(
+                (section.clone(), option.trim().to_string()),
+                value.trim().to_string(),
+            );
+        }
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse_options(
+            "# This is a RocksDB option file.\n\
+             [Version]\n  rocksdb_version=9.3.1\n\n\
+             [DBOptions]\n  max_open_files=5000\n\n\
+             [CFOptions \"default\"]\n  write_buffer_size=67108864\n  comparator=leveldb.BytewiseComparator\n",
+        );
+        let get = |section: &str, option: &str| {
+            options
+                .get(&(section.to_string(), option.to_string()))
+                .map(String::as_str)
+        };
+        assert_eq!(options.len(), 4);
+        assert_eq!(get("DBOptions", "max_open_files"), Some("5000"));
+        assert_eq!(
+            get("CFOptions \"default\"", "write_buffer_size"),
+            Some("67108864")
+        );
+        assert_eq!(get("Version", "rocksdb_version"), Some("9.3.1"));
+    }
+}
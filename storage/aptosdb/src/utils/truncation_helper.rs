@@ -531,7 +531,7 @@ fn delete_event_data(
                 latest_version = latest_version,
                 "Truncate event data."
             );
-            let num_events_per_version = ledger_db.event_db().prune_event_indices(
+            let event_keys = ledger_db.event_db().prune_event_indices(
                 start_version,
                 latest_version + 1,
                 // Assuming same data will be overwritten into indices, we don't bother to deal
@@ -540,7 +540,7 @@ fn delete_event_data(
                 None,
             )?;
             ledger_db.event_db().prune_events(
-                num_events_per_version,
+                event_keys,
                 start_version,
                 latest_version + 1,
                 batch,
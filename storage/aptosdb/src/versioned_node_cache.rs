@@ -18,6 +18,7 @@ type NodeCache = HashMap<NodeKey, Node>;
 
 pub(crate) struct VersionedNodeCache {
     inner: RwLock<VecDeque<(Version, Arc<NodeCache>)>>,
+    max_versions: usize,
 }
 
 impl fmt::Debug for VersionedNodeCache {
@@ -32,11 +33,16 @@ impl fmt::Debug for VersionedNodeCache {
 }
 
 impl VersionedNodeCache {
-    pub(crate) const NUM_VERSIONS_TO_CACHE: usize = 2;
+    /// Default of `RocksdbConfigs::versioned_node_cache_max_versions`.
+    pub(crate) const DEFAULT_NUM_VERSIONS_TO_CACHE: usize = 2;
+    /// The nodes of a version must stay cached until they're committed, see the assertion in
+    /// `StateSnapshotCommitter::new`.
+    pub(crate) const MIN_NUM_VERSIONS_TO_CACHE: usize = 1;
 
-    pub fn new() -> Self {
+    pub fn new(max_versions: usize) -> Self {
         Self {
             inner: RwLock::new(Default::default()),
+            max_versions,
         }
     }
 
@@ -56,31 +62,38 @@ impl VersionedNodeCache {
         locked.push_back((version, Arc::new(nodes)));
     }
 
-    pub fn maybe_evict_version(&self, lru_cache: &LruNodeCache) {
+    /// Drops the oldest versions while more than `max_versions` are cached, moving their nodes
+    /// into `lru_cache` if given.
+    pub fn maybe_evict_version(&self, lru_cache: Option<&LruNodeCache>) {
         let _timer = OTHER_TIMERS_SECONDS.timer_with(&["version_cache_evict"]);
 
-        let to_evict = {
-            let locked = self.inner.read();
-            if locked.len() > Self::NUM_VERSIONS_TO_CACHE {
-                locked
-                    .front()
-                    .map(|(version, cache)| (*version, cache.clone()))
-            } else {
-                None
+        loop {
+            let to_evict = {
+                let locked = self.inner.read();
+                if locked.len() > self.max_versions {
+                    locked
+                        .front()
+                        .map(|(version, cache)| (*version, cache.clone()))
+                } else {
+                    None
+                }
+            };
+            let Some((version, cache)) = to_evict else {
+                break;
+            };
+
+            if let Some(lru_cache) = lru_cache {
+                THREAD_MANAGER.get_non_exe_cpu_pool().install(|| {
+                    cache
+                        .iter()
+                        .collect::<Vec<_>>()
+                        .into_par_iter()
+                        .with_min_len(100)
+                        .for_each(|(node_key, node)| {
+                            lru_cache.put(node_key.clone(), node.clone());
+                        });
+                });
             }
-        };
-
-        if let Some((version, cache)) = to_evict {
-            THREAD_MANAGER.get_non_exe_cpu_pool().install(|| {
-                cache
-                    .iter()
-                    .collect::<Vec<_>>()
-                    .into_par_iter()
-                    .with_min_len(100)
-                    .for_each(|(node_key, node)| {
-                        lru_cache.put(node_key.clone(), node.clone());
-                    });
-            });
 
             let evicted = self.inner.write().pop_front();
             assert_eq!(evicted, Some((version, cache)));
@@ -96,3 +109,38 @@ impl VersionedNodeCache {
             .map(|(_ver, nodes)| nodes.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroUsize;
+
+    fn cached_versions(cache: &VersionedNodeCache) -> Vec<Version> {
+        cache
+            .inner
+            .read()
+            .iter()
+            .map(|(version, _)| *version)
+            .collect()
+    }
+
+    #[test]
+    fn test_max_versions() {
+        for max_versions in [1, 3] {
+            let cache = VersionedNodeCache::new(max_versions);
+            let lru_cache = LruNodeCache::new(NonZeroUsize::new(10).unwrap());
+            for version in 0..5 {
+                let node_key = NodeKey::new_empty_path(version);
+                cache.add_version(version, HashMap::from([(node_key, Node::Null)]));
+            }
+
+            cache.maybe_evict_version(Some(&lru_cache));
+            let kept = (5 - max_versions as Version..5).collect::<Vec<_>>();
+            assert_eq!(cached_versions(&cache), kept);
+            // The evicted nodes went to the LRU cache, where the root of the last evicted version
+            // replaced the ones before.
+            let last_evicted = NodeKey::new_empty_path(4 - max_versions as Version);
+            assert!(lru_cache.get(&last_evicted).is_some());
+        }
+    }
+}
@@ -127,7 +127,7 @@ impl EpochEndingBackupController {
         format!("epoch_ending_{}-", self.start_epoch)
     }
 
-    fn manifest_name() -> &'static ShellSafeName {
+    pub(crate) fn manifest_name() -> &'static ShellSafeName {
         static NAME: Lazy<ShellSafeName> =
             Lazy::new(|| ShellSafeName::from_str("epoch_ending.manifest").unwrap());
         &NAME
@@ -0,0 +1,171 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Tells what a backup in a local directory covers by reading only its manifest, without loading
+//! or verifying any of the chunks, e.g. to pick a backup to restore from.
+
+use crate::{
+    backup_types::{
+        epoch_ending::{backup::EpochEndingBackupController, manifest::EpochEndingBackup},
+        state_snapshot::{backup::StateSnapshotBackupController, manifest::StateSnapshotBackup},
+        transaction::{backup::TransactionBackupController, manifest::TransactionBackup},
+    },
+    utils::error_notes::ErrorNotes,
+};
+use anyhow::{bail, Result};
+use aptos_types::transaction::Version;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+use tokio::fs::{metadata, read, read_dir};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BackupInfo {
+    /// First transaction in a transaction backup.
+    pub first_version: Option<Version>,
+    /// Last transaction in a transaction backup.
+    pub last_version: Option<Version>,
+    /// Version of a state snapshot backup.
+    pub state_snapshot_version: Option<Version>,
+    pub chunk_count: usize,
+    /// Size of all the files in the backup directory, including the manifest.
+    pub total_bytes: u64,
+}
+
+/// Inspects the backup in `backup_dir`, i.e. the directory of a single transaction, state snapshot
+/// or epoch ending backup, telling which one it is by the manifest in it.
+pub async fn inspect(backup_dir: &Path) -> Result<BackupInfo> {
+    let total_bytes = dir_size(backup_dir).await?;
+    Ok(load_backup_manifest(backup_dir).await?.info(total_bytes))
+}
+
+pub(super) enum BackupManifest {
+    Transaction(TransactionBackup),
+    StateSnapshot(StateSnapshotBackup),
+    EpochEnding(EpochEndingBackup),
+}
+
+impl BackupManifest {
+    pub(super) fn info(&self, total_bytes: u64) -> BackupInfo {
+        match self {
+            Self::Transaction(manifest) => BackupInfo {
+                first_version: Some(manifest.first_version),
+                last_version: Some(manifest.last_version),
+                state_snapshot_version: None,
+                chunk_count: manifest.chunks.len(),
+                total_bytes,
+            },
+            Self::StateSnapshot(manifest) => BackupInfo {
+                first_version: None,
+                last_version: None,
+                state_snapshot_version: Some(manifest.version),
+                chunk_count: manifest.chunks.len(),
+                total_bytes,
+            },
+            Self::EpochEnding(manifest) => BackupInfo {
+                first_version: None,
+                last_version: None,
+                state_snapshot_version: None,
+                chunk_count: manifest.chunks.len(),
+                total_bytes,
+            },
+        }
+    }
+}
+
+/// Loads the manifest in `backup_dir`, whichever kind of backup it is.
+pub(super) async fn load_backup_manifest(backup_dir: &Path) -> Result<BackupManifest> {
+    let transaction_manifest =
+        backup_dir.join(TransactionBackupController::manifest_name().as_ref());
+    let state_snapshot_manifest =
+        backup_dir.join(StateSnapshotBackupController::manifest_name().as_ref());
+    let epoch_ending_manifest =
+        backup_dir.join(EpochEndingBackupController::manifest_name().as_ref());
+    if metadata(&transaction_manifest).await.is_ok() {
+        Ok(BackupManifest::Transaction(
+            load_manifest(&transaction_manifest).await?,
+        ))
+    } else if metadata(&state_snapshot_manifest).await.is_ok() {
+        Ok(BackupManifest::StateSnapshot(
+            load_manifest(&state_snapshot_manifest).await?,
+        ))
+    } else if metadata(&epoch_ending_manifest).await.is_ok() {
+        Ok(BackupManifest::EpochEnding(
+            load_manifest(&epoch_ending_manifest).await?,
+        ))
+    } else {
+        bail!("No backup manifest found in {:?}.", backup_dir)
+    }
+}
+
+async fn load_manifest<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    Ok(serde_json::from_slice(&read(path).await.err_notes(path)?)?)
+}
+
+pub(super) async fn dir_size(dir: &Path) -> Result<u64> {
+    let mut total_bytes = 0;
+    let mut entries = read_dir(dir).await.err_notes(dir)?;
+    while let Some(entry) = entries.next_entry().await? {
+        let entry_metadata = entry.metadata().await?;
+        if entry_metadata.is_file() {
+            total_bytes += entry_metadata.len();
+        }
+    }
+    Ok(total_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup_types::transaction::manifest::{TransactionChunk, TransactionChunkFormat};
+    use aptos_temppath::TempPath;
+    use std::fs::write;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_inspect_transaction_backup() {
+        let backup_dir = TempPath::new();
+        backup_dir.create_as_dir().unwrap();
+        let chunk = |first_version, last_version| TransactionChunk {
+            first_version,
+            last_version,
+            transactions: format!("backup/{first_version}-.chunk"),
+            proof: format!("backup/{first_version}-.proof"),
+            format: TransactionChunkFormat::V1,
+        };
+        let manifest = TransactionBackup {
+            first_version: 10,
+            last_version: 29,
+            chunks: vec![chunk(10, 19), chunk(20, 29)],
+        };
+        let manifest_bytes = serde_json::to_vec(&manifest).unwrap();
+        write(
+            backup_dir
+                .path()
+                .join(TransactionBackupController::manifest_name().as_ref()),
+            &manifest_bytes,
+        )
+        .unwrap();
+        write(backup_dir.path().join("10-.chunk"), [0u8; 100]).unwrap();
+
+        let rt = Runtime::new().unwrap();
+        assert_eq!(
+            rt.block_on(inspect(backup_dir.path())).unwrap(),
+            BackupInfo {
+                first_version: Some(10),
+                last_version: Some(29),
+                state_snapshot_version: None,
+                chunk_count: 2,
+                total_bytes: manifest_bytes.len() as u64 + 100,
+            }
+        );
+    }
+
+    #[test]
+    fn test_inspect_no_manifest() {
+        let backup_dir = TempPath::new();
+        backup_dir.create_as_dir().unwrap();
+
+        let rt = Runtime::new().unwrap();
+        assert!(rt.block_on(inspect(backup_dir.path())).is_err());
+    }
+}
@@ -2,8 +2,10 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 pub mod epoch_ending;
+pub mod inspect;
 pub mod state_snapshot;
 pub mod transaction;
+pub mod validate;
 
 #[cfg(test)]
 pub mod tests;
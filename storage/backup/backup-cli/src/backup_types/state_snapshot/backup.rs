@@ -364,7 +364,7 @@ impl StateSnapshotBackupController {
         format!("state_epoch_{}_ver_{}", self.epoch, self.version())
     }
 
-    fn manifest_name() -> &'static ShellSafeName {
+    pub(crate) fn manifest_name() -> &'static ShellSafeName {
         static NAME: Lazy<ShellSafeName> =
             Lazy::new(|| ShellSafeName::from_str("state.manifest").unwrap());
         &NAME
@@ -0,0 +1,329 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Validates a backup in a local directory end to end without restoring it, e.g. as a cheap
+//! integrity check before promoting a backup to be restored from.
+
+use crate::{
+    backup_types::{
+        epoch_ending::{
+            backup::EpochEndingBackupController,
+            restore::{EpochEndingRestoreController, EpochEndingRestoreOpt},
+        },
+        inspect::{dir_size, load_backup_manifest, BackupInfo, BackupManifest},
+        state_snapshot::{
+            backup::StateSnapshotBackupController,
+            restore::{StateSnapshotRestoreController, StateSnapshotRestoreOpt},
+        },
+        transaction::{
+            backup::TransactionBackupController, restore::TransactionRestoreBatchController,
+        },
+    },
+    storage::{local_fs::LocalFs, BackupStorage},
+    utils::{GlobalRestoreOptions, PathToString, RestoreRunMode},
+};
+use anyhow::{anyhow, Result};
+use aptos_crypto::HashValue;
+use aptos_db::state_restore::StateSnapshotRestoreMode;
+use aptos_executor_types::VerifyExecutionMode;
+use aptos_types::transaction::Version;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidationReport {
+    /// What the backup covers, as told by its manifest.
+    pub info: BackupInfo,
+    /// Root hash of the state tree rebuilt from a state snapshot backup, which matched the one in
+    /// the manifest.
+    pub state_root_hash: Option<HashValue>,
+}
+
+/// Validates the backup in `backup_dir`, i.e. the directory of a single transaction, state snapshot
+/// or epoch ending backup, the way the verify coordinator does but without the metadata: every
+/// chunk is loaded and decoded, and nothing is written to disk. Transactions are checked against
+/// the accumulator range proofs of their chunks, a state snapshot is rebuilt in memory chunk by
+/// chunk against its root hash and epoch ending ledger infos against the waypoints in the manifest.
+///
+/// Without the epoch history, the signatures on the ledger infos the proofs lead to are not
+/// verified, except those on the epoch ending ones signed by the previous epoch in the backup. The
+/// chunks are found by the paths in the manifest, relative to the parent of `backup_dir`, so the
+/// backup must sit in the directory it was taken to.
+pub async fn validate(backup_dir: &Path) -> Result<ValidationReport> {
+    let manifest = load_backup_manifest(backup_dir).await?;
+    let info = manifest.info(dir_size(backup_dir).await?);
+
+    let storage_dir = backup_dir
+        .parent()
+        .ok_or_else(|| anyhow!("Backup directory {:?} has no parent.", backup_dir))?;
+    let backup_name = backup_dir
+        .file_name()
+        .ok_or_else(|| anyhow!("Backup directory {:?} has no name.", backup_dir))?;
+    let manifest_name = match &manifest {
+        BackupManifest::Transaction(_) => TransactionBackupController::manifest_name(),
+        BackupManifest::StateSnapshot(_) => StateSnapshotBackupController::manifest_name(),
+        BackupManifest::EpochEnding(_) => EpochEndingBackupController::manifest_name(),
+    };
+    let manifest_handle = Path::new(backup_name)
+        .join(manifest_name.as_ref())
+        .path_to_string()?;
+    let storage: Arc<dyn BackupStorage> = Arc::new(LocalFs::new(storage_dir.to_path_buf()));
+    let global_opt = GlobalRestoreOptions {
+        target_version: Version::MAX,
+        trusted_waypoints: Arc::new(HashMap::new()),
+        run_mode: Arc::new(RestoreRunMode::Verify),
+        concurrent_downloads: num_cpus::get(),
+        replay_concurrency_level: 0, // won't replay, doesn't matter
+    };
+
+    let state_root_hash = match manifest {
+        BackupManifest::Transaction(_) => {
+            TransactionRestoreBatchController::new(
+                global_opt,
+                storage,
+                vec![manifest_handle],
+                None, /* first_version */
+                None, /* replay_from_version */
+                None, /* epoch_history */
+                VerifyExecutionMode::NoVerify,
+                None, /* output_transaction_analysis */
+            )
+            .run()
+            .await?;
+            None
+        },
+        BackupManifest::StateSnapshot(manifest) => {
+            StateSnapshotRestoreController::new(
+                StateSnapshotRestoreOpt {
+                    manifest_handle,
+                    version: manifest.version,
+                    validate_modules: false,
+                    restore_mode: StateSnapshotRestoreMode::Default,
+                },
+                global_opt,
+                storage,
+                None, /* epoch_history */
+            )
+            .run()
+            .await?;
+            Some(manifest.root_hash)
+        },
+        BackupManifest::EpochEnding(_) => {
+            EpochEndingRestoreController::new(
+                EpochEndingRestoreOpt { manifest_handle },
+                global_opt,
+                storage,
+            )
+            .run(None)
+            .await?;
+            None
+        },
+    };
+
+    Ok(ValidationReport {
+        info,
+        state_root_hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backup_types::{
+            epoch_ending::backup::EpochEndingBackupOpt,
+            state_snapshot::backup::StateSnapshotBackupOpt,
+            transaction::backup::TransactionBackupOpt,
+        },
+        utils::{
+            backup_service_client::BackupServiceClient,
+            test_utils::{start_local_backup_service, tmp_db_with_random_content},
+            GlobalBackupOpt,
+        },
+    };
+    use aptos_db::AptosDB;
+    use aptos_storage_interface::DbReader;
+    use aptos_temppath::TempPath;
+    use tokio::{runtime::Runtime, time::Duration};
+
+    struct TestEnv {
+        src_db: Arc<AptosDB>,
+        _src_db_dir: TempPath,
+        backup_dir: TempPath,
+        store: Arc<dyn BackupStorage>,
+        client: Arc<BackupServiceClient>,
+        rt: Runtime,
+    }
+
+    impl TestEnv {
+        fn new() -> Self {
+            let (src_db_dir, src_db, _blocks) = tmp_db_with_random_content();
+            let backup_dir = TempPath::new();
+            backup_dir.create_as_dir().unwrap();
+            let store: Arc<dyn BackupStorage> =
+                Arc::new(LocalFs::new(backup_dir.path().to_path_buf()));
+            let (rt, port) = start_local_backup_service(Arc::clone(&src_db));
+            let client = Arc::new(BackupServiceClient::new(format!(
+                "http://localhost:{}",
+                port
+            )));
+            Self {
+                src_db,
+                _src_db_dir: src_db_dir,
+                backup_dir,
+                store,
+                client,
+                rt,
+            }
+        }
+
+        fn global_backup_opt() -> GlobalBackupOpt {
+            GlobalBackupOpt {
+                max_chunk_size: 1024,
+                concurrent_data_requests: 2,
+            }
+        }
+
+        /// The directory of the backup with the manifest at `manifest_handle`.
+        fn backup_dir(&self, manifest_handle: &str) -> std::path::PathBuf {
+            self.backup_dir
+                .path()
+                .join(Path::new(manifest_handle).parent().unwrap())
+        }
+
+        fn validate(&self, manifest_handle: &str) -> Result<ValidationReport> {
+            self.rt
+                .block_on(validate(&self.backup_dir(manifest_handle)))
+        }
+
+        fn shutdown(self) {
+            self.rt.shutdown_timeout(Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_validate_state_snapshot_backup() {
+        let env = TestEnv::new();
+        let epoch = env
+            .src_db
+            .get_latest_ledger_info()
+            .unwrap()
+            .ledger_info()
+            .next_block_epoch()
+            - 1;
+        let version = env
+            .src_db
+            .get_epoch_ending_ledger_infos(epoch, epoch + 1)
+            .unwrap()
+            .ledger_info_with_sigs
+            .pop()
+            .unwrap()
+            .ledger_info()
+            .version();
+        let state_root_hash = env
+            .src_db
+            .get_transactions(version, 1, version, false)
+            .unwrap()
+            .consume_transaction_list_with_proof()
+            .proof
+            .transaction_infos
+            .pop()
+            .unwrap()
+            .state_checkpoint_hash()
+            .unwrap();
+
+        let manifest_handle = env
+            .rt
+            .block_on(
+                StateSnapshotBackupController::new(
+                    StateSnapshotBackupOpt { epoch },
+                    TestEnv::global_backup_opt(),
+                    Arc::clone(&env.client),
+                    Arc::clone(&env.store),
+                )
+                .run(),
+            )
+            .unwrap();
+
+        let report = env.validate(&manifest_handle).unwrap();
+        assert_eq!(report.info.state_snapshot_version, Some(version));
+        assert_eq!(report.state_root_hash, Some(state_root_hash));
+        env.shutdown();
+    }
+
+    #[test]
+    fn test_validate_transaction_backup() {
+        let env = TestEnv::new();
+        let latest_version = env.src_db.expect_synced_version();
+        let manifest_handle = env
+            .rt
+            .block_on(
+                TransactionBackupController::new(
+                    TransactionBackupOpt {
+                        start_version: 0,
+                        num_transactions: latest_version as usize + 1,
+                    },
+                    GlobalBackupOpt {
+                        // Fits the biggest transaction.
+                        max_chunk_size: 1 << 20,
+                        concurrent_data_requests: 2,
+                    },
+                    Arc::clone(&env.client),
+                    Arc::clone(&env.store),
+                )
+                .run(),
+            )
+            .unwrap();
+
+        let report = env.validate(&manifest_handle).unwrap();
+        assert_eq!(report.info.first_version, Some(0));
+        assert_eq!(report.info.last_version, Some(latest_version));
+        assert_eq!(report.state_root_hash, None);
+
+        // A chunk cut short fails the validation.
+        let manifest = env
+            .rt
+            .block_on(load_backup_manifest(&env.backup_dir(&manifest_handle)))
+            .unwrap();
+        let BackupManifest::Transaction(manifest) = manifest else {
+            panic!("Expected a transaction backup manifest.");
+        };
+        let chunk_path = env.backup_dir.path().join(&manifest.chunks[0].transactions);
+        let chunk_bytes = std::fs::read(&chunk_path).unwrap();
+        std::fs::write(&chunk_path, &chunk_bytes[..chunk_bytes.len() / 2]).unwrap();
+        assert!(env.validate(&manifest_handle).is_err());
+        env.shutdown();
+    }
+
+    #[test]
+    fn test_validate_epoch_ending_backup() {
+        let env = TestEnv::new();
+        let latest_epoch = env
+            .src_db
+            .get_latest_ledger_info()
+            .unwrap()
+            .ledger_info()
+            .next_block_epoch();
+        let manifest_handle = env
+            .rt
+            .block_on(
+                EpochEndingBackupController::new(
+                    EpochEndingBackupOpt {
+                        start_epoch: 0,
+                        end_epoch: latest_epoch,
+                    },
+                    TestEnv::global_backup_opt(),
+                    Arc::clone(&env.client),
+                    Arc::clone(&env.store),
+                )
+                .run(),
+            )
+            .unwrap();
+
+        let report = env.validate(&manifest_handle).unwrap();
+        assert_eq!(report.info.first_version, None);
+        assert_eq!(report.info.state_snapshot_version, None);
+        assert_eq!(report.state_root_hash, None);
+        assert!(report.info.chunk_count > 0);
+        env.shutdown();
+    }
+}
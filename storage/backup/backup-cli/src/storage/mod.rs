@@ -3,6 +3,7 @@
 
 pub mod command_adapter;
 pub mod local_fs;
+pub mod stream;
 
 #[cfg(test)]
 mod test_util;
@@ -12,6 +13,7 @@ mod tests;
 use crate::storage::{
     command_adapter::{CommandAdapter, CommandAdapterOpt},
     local_fs::{LocalFs, LocalFsOpt},
+    stream::{StreamReader, StreamWriter},
 };
 use anyhow::{ensure, Result};
 use async_trait::async_trait;
@@ -22,7 +24,13 @@ use proptest::prelude::*;
 use regex::Regex;
 #[cfg(test)]
 use std::convert::TryInto;
-use std::{convert::TryFrom, ops::Deref, str::FromStr, sync::Arc};
+use std::{
+    convert::TryFrom,
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 use tokio::io::{AsyncRead, AsyncWrite};
 
 /// String returned by a specific storage implementation to identify a backup, probably a folder name
@@ -213,7 +221,7 @@ impl StorageOpt {
 #[clap(group(
     ArgGroup::new("storage")
     .required(true)
-    .args(&["local_fs_dir", "command_adapter_config"]),
+    .args(&["local_fs_dir", "command_adapter_config", "stream_to", "stream_from"]),
 ))]
 pub struct DBToolStorageOpt {
     #[clap(
@@ -229,12 +237,40 @@ pub struct DBToolStorageOpt {
     https://github.com/aptos-labs/aptos-networks/tree/main/testnet/backups "
     )]
     command_adapter_config: Option<CommandAdapterOpt>,
+    #[clap(
+        long,
+        value_parser,
+        help = "Write a one-off backup as a single framed stream into this file, e.g. a named pipe \
+    into an upload tool, instead of a backup storage. Pass \"-\" to write to stdout, in which case \
+    the logs go to stderr."
+    )]
+    stream_to: Option<PathBuf>,
+    #[clap(
+        long,
+        value_parser,
+        help = "Restore from a backup stream written with --stream-to, read from this file. Pass \"-\" \
+    to read from stdin. A stream that isn't a regular file, e.g. a pipe, is read forward only, and \
+    a restore, which reads the manifests at the end of the stream first, holds the files before \
+    them in memory, so it only works for small backups."
+    )]
+    stream_from: Option<PathBuf>,
 }
 
 impl DBToolStorageOpt {
+    /// Whether a backup stream is to be written to stdout, with "-" as the stream path.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.stream_to.as_deref() == Some(Path::new("-"))
+    }
+
     pub async fn init_storage(self) -> Result<Arc<dyn BackupStorage>> {
-        Ok(if self.local_fs_dir.is_some() {
-            Arc::new(LocalFs::new_with_opt(self.local_fs_dir.unwrap()))
+        Ok(if let Some(opt) = self.local_fs_dir {
+            Arc::new(LocalFs::new_with_opt(opt))
+        } else if self.writes_to_stdout() {
+            Arc::new(StreamWriter::new(tokio::io::stdout()))
+        } else if let Some(path) = self.stream_to {
+            Arc::new(StreamWriter::new(tokio::fs::File::create(path).await?))
+        } else if let Some(path) = self.stream_from {
+            Arc::new(StreamReader::open(path).await?)
         } else {
             Arc::new(CommandAdapter::new_with_opt(self.command_adapter_config.unwrap()).await?)
         })
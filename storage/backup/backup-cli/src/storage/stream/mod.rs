@@ -0,0 +1,585 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Backup storages carrying a whole backup in a single stream, e.g. a pipe into an upload tool,
+//! so that no local staging disk is needed.
+//!
+//! The stream starts with `MAGIC` and is followed by frames each carrying a chunk of a file:
+//!
+//! ```text
+//! | handle len (u32 LE) | handle (utf-8) | chunk len (u32 LE) | chunk |
+//! ```
+//!
+//! The content of a file is the concatenation of its chunks, and ends with an empty chunk. Files
+//! being written concurrently have their frames interleaved.
+//!
+//! File handles are the same as those of `LocalFs`, i.e. `<backup>/<file>` and
+//! `metadata/<file>`, so the manifests in the stream refer to the files by their frame handles.
+
+#[cfg(test)]
+mod tests;
+
+use super::{BackupHandle, BackupHandleRef, FileHandle, FileHandleRef};
+use crate::{
+    storage::{BackupStorage, ShellSafeName, TextLine},
+    utils::PathToString,
+};
+use anyhow::{bail, ensure, format_err, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream, FutureExt, TryStreamExt};
+use std::{
+    cmp::min,
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter},
+    sync::Mutex,
+};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+const MAGIC: &[u8; 8] = b"APTBKS01";
+const METADATA_DIR: &str = "metadata";
+/// A file is written into the stream in chunks of up to this size, which bounds the memory used
+/// per file being written.
+const CHUNK_SIZE: usize = 1 << 20;
+
+fn metadata_file_handle(name: &ShellSafeName) -> Result<FileHandle> {
+    Path::new(METADATA_DIR).join(name.as_ref()).path_to_string()
+}
+
+type SharedWriter = Arc<Mutex<StreamWriterInner>>;
+
+struct StreamWriterInner {
+    writer: BufWriter<Box<dyn AsyncWrite + Send + Unpin>>,
+    magic_written: bool,
+}
+
+impl StreamWriterInner {
+    async fn write_frame(&mut self, file_handle: &FileHandleRef, chunk: &[u8]) -> io::Result<()> {
+        if !self.magic_written {
+            self.writer.write_all(MAGIC).await?;
+            self.magic_written = true;
+        }
+        let handle_len = u32::try_from(file_handle.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "File handle too long."))?;
+        let chunk_len = u32::try_from(chunk.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Chunk too long."))?;
+        self.writer.write_all(&handle_len.to_le_bytes()).await?;
+        self.writer.write_all(file_handle.as_bytes()).await?;
+        self.writer.write_all(&chunk_len.to_le_bytes()).await?;
+        self.writer.write_all(chunk).await?;
+        // Hand the chunk over to the consumer right away.
+        self.writer.flush().await
+    }
+
+    /// Writes all of `content` as a file, ending with the empty chunk.
+    async fn write_file(&mut self, file_handle: &FileHandleRef, content: &[u8]) -> io::Result<()> {
+        for chunk in content.chunks(CHUNK_SIZE) {
+            self.write_frame(file_handle, chunk).await?;
+        }
+        self.write_frame(file_handle, &[]).await
+    }
+}
+
+/// A write only storage that writes a backup as a framed stream into `writer`, see the module
+/// doc.
+///
+/// Nothing can be read back, so only the one-off backups, which don't read the metadata, can be
+/// taken to it.
+pub struct StreamWriter {
+    inner: SharedWriter,
+}
+
+impl StreamWriter {
+    pub fn new(writer: impl AsyncWrite + Send + Unpin + 'static) -> Self {
+        let writer: Box<dyn AsyncWrite + Send + Unpin> = Box::new(writer);
+        Self {
+            inner: Arc::new(Mutex::new(StreamWriterInner {
+                writer: BufWriter::new(writer),
+                magic_written: false,
+            })),
+        }
+    }
+}
+
+/// Writes a file into the stream, a frame each time a chunk is filled, and the rest followed by
+/// the empty chunk on shutdown. Only one frame of a file is in flight at a time, so its chunks
+/// are in order in the stream.
+struct FrameWriter {
+    file_handle: FileHandle,
+    buf: Vec<u8>,
+    stream: SharedWriter,
+    pending_frame: Option<BoxFuture<'static, io::Result<()>>>,
+    done: bool,
+}
+
+impl FrameWriter {
+    fn start_frame(&mut self) {
+        let chunk = std::mem::take(&mut self.buf);
+        let file_handle = self.file_handle.clone();
+        let stream = Arc::clone(&self.stream);
+        self.pending_frame = Some(
+            async move { stream.lock().await.write_frame(&file_handle, &chunk).await }.boxed(),
+        );
+    }
+
+    fn poll_pending_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(frame) = self.pending_frame.as_mut() {
+            let res = ready!(frame.poll_unpin(cx));
+            self.pending_frame = None;
+            res?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for FrameWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(Err(io::Error::other(format!(
+                "File {} already shut down.",
+                this.file_handle
+            ))));
+        }
+        ready!(this.poll_pending_frame(cx))?;
+
+        let num_bytes = min(buf.len(), CHUNK_SIZE - this.buf.len());
+        this.buf.extend_from_slice(&buf[..num_bytes]);
+        if this.buf.len() == CHUNK_SIZE {
+            this.start_frame();
+        }
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_pending_frame(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_pending_frame(cx))?;
+            if this.done {
+                return Poll::Ready(Ok(()));
+            }
+            // Once the rest of the content is written, the empty chunk ends the file.
+            if this.buf.is_empty() {
+                this.done = true;
+            }
+            this.start_frame();
+        }
+    }
+}
+
+#[async_trait]
+impl BackupStorage for StreamWriter {
+    async fn create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
+        Ok(name.to_string())
+    }
+
+    async fn create_for_write(
+        &self,
+        backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        let file_handle = Path::new(backup_handle)
+            .join(name.as_ref())
+            .path_to_string()?;
+        let file = FrameWriter {
+            file_handle: file_handle.clone(),
+            buf: Vec::new(),
+            stream: Arc::clone(&self.inner),
+            pending_frame: None,
+            done: false,
+        };
+        Ok((file_handle, Box::new(file)))
+    }
+
+    async fn open_for_read(
+        &self,
+        file_handle: &FileHandleRef,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        bail!(
+            "Can't read {} from a backup stream being written.",
+            file_handle
+        )
+    }
+
+    async fn list_metadata_files(&self) -> Result<Vec<FileHandle>> {
+        bail!("Can't list the metadata files of a backup stream being written.")
+    }
+
+    async fn backup_metadata_file(&self, file_handle: &FileHandleRef) -> Result<()> {
+        bail!(
+            "Can't move {} in a backup stream being written.",
+            file_handle
+        )
+    }
+
+    async fn save_metadata_lines(
+        &self,
+        name: &ShellSafeName,
+        lines: &[TextLine],
+    ) -> Result<FileHandle> {
+        let file_handle = metadata_file_handle(name)?;
+        let content = lines.iter().map(|e| e.as_ref()).collect::<String>();
+        self.inner
+            .lock()
+            .await
+            .write_file(&file_handle, content.as_bytes())
+            .await?;
+        Ok(file_handle)
+    }
+}
+
+/// A read only storage serving the files in a backup stream written by `StreamWriter`.
+///
+/// A stream in a regular file is indexed on construction, i.e. only the positions of the chunks
+/// are recorded, and the files are read from it on demand, in any order.
+///
+/// Any other stream, e.g. a pipe or stdin, is read forward only, as the files are asked for. The
+/// frames passed over on the way to a file are held in memory until their files are read, up to
+/// `MAX_BUFFERED_BYTES`, so the files are best read in stream order. A restore reads the
+/// manifests, which are written last, first, so it needs a regular file unless the backup is
+/// small.
+pub struct StreamReader {
+    source: StreamSource,
+}
+
+enum StreamSource {
+    Indexed {
+        path: PathBuf,
+        /// The offsets and lengths of the chunks of each file.
+        files: HashMap<FileHandle, Vec<(u64, u32)>>,
+    },
+    Forward(Arc<Mutex<ForwardReader>>),
+}
+
+/// The most bytes of chunks a forward-only stream holds in memory for the files not read yet.
+const MAX_BUFFERED_BYTES: usize = 1 << 30;
+
+impl StreamReader {
+    /// Opens the backup stream at `path`, with "-" meaning stdin. A regular file is indexed, and
+    /// anything else is read forward only.
+    pub async fn open(path: PathBuf) -> Result<Self> {
+        if path.as_path() == Path::new("-") {
+            return Self::from_reader(tokio::io::stdin()).await;
+        }
+        let file = File::open(&path).await?;
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            return Self::from_reader(file).await;
+        }
+        let stream_len = metadata.len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic).await?;
+        ensure!(&magic == MAGIC, "Not a backup stream.");
+        let mut offset = MAGIC.len() as u64;
+
+        let mut files: HashMap<FileHandle, Vec<(u64, u32)>> = HashMap::new();
+        let mut complete_files = HashSet::new();
+        while let Some(handle_len) = read_u32_or_eof(&mut reader).await? {
+            let mut file_handle = vec![0u8; handle_len as usize];
+            reader.read_exact(&mut file_handle).await?;
+            let file_handle = String::from_utf8(file_handle)?;
+            let chunk_len = reader.read_u32_le().await?;
+            offset = offset
+                .checked_add(8 + u64::from(handle_len))
+                .ok_or_else(|| format_err!("Backup stream offset overflow."))?;
+
+            ensure!(
+                !complete_files.contains(&file_handle),
+                "File {} appears more than once in the backup stream.",
+                file_handle,
+            );
+            if chunk_len == 0 {
+                files.entry(file_handle.clone()).or_default();
+                complete_files.insert(file_handle);
+            } else {
+                files
+                    .entry(file_handle)
+                    .or_default()
+                    .push((offset, chunk_len));
+                reader.seek(SeekFrom::Current(i64::from(chunk_len))).await?;
+                offset = offset
+                    .checked_add(u64::from(chunk_len))
+                    .ok_or_else(|| format_err!("Backup stream offset overflow."))?;
+            }
+        }
+        ensure!(
+            offset == stream_len,
+            "Backup stream truncated, {} bytes expected but {} found.",
+            offset,
+            stream_len,
+        );
+        if let Some(file_handle) = files.keys().find(|h| !complete_files.contains(*h)) {
+            bail!("Backup stream truncated in {}.", file_handle);
+        }
+
+        Ok(Self {
+            source: StreamSource::Indexed { path, files },
+        })
+    }
+
+    /// Reads the backup stream from `reader` forward only, see the type doc.
+    pub async fn from_reader(reader: impl AsyncRead + Send + Unpin + 'static) -> Result<Self> {
+        Self::from_reader_impl(reader, MAX_BUFFERED_BYTES).await
+    }
+
+    async fn from_reader_impl(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        max_buffered_bytes: usize,
+    ) -> Result<Self> {
+        let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(reader);
+        let mut reader = BufReader::new(reader);
+
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic).await?;
+        ensure!(&magic == MAGIC, "Not a backup stream.");
+
+        Ok(Self {
+            source: StreamSource::Forward(Arc::new(Mutex::new(ForwardReader {
+                reader,
+                eof: false,
+                files: HashMap::new(),
+                opened: HashSet::new(),
+                metadata_files: Vec::new(),
+                buffered_bytes: 0,
+                max_buffered_bytes,
+            }))),
+        })
+    }
+}
+
+/// The chunks of a file read from a forward-only stream and not yet handed out.
+#[derive(Default)]
+struct ForwardFile {
+    chunks: VecDeque<Bytes>,
+    /// Whether any frame of the file was read, as opposed to it only being asked for.
+    seen: bool,
+    complete: bool,
+}
+
+struct ForwardReader {
+    reader: BufReader<Box<dyn AsyncRead + Send + Unpin>>,
+    eof: bool,
+    /// The files passed over or being read. A file is dropped once it's read to the end.
+    files: HashMap<FileHandle, ForwardFile>,
+    /// The files asked for, each of which can only be read once.
+    opened: HashSet<FileHandle>,
+    metadata_files: Vec<FileHandle>,
+    buffered_bytes: usize,
+    max_buffered_bytes: usize,
+}
+
+impl ForwardReader {
+    /// Reads the next frame into the chunks of its file. Returns false at the end of the stream.
+    async fn read_frame(&mut self) -> Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        let Some(handle_len) = read_u32_or_eof(&mut self.reader).await? else {
+            self.eof = true;
+            if let Some((file_handle, _file)) =
+                self.files.iter().find(|(_h, f)| f.seen && !f.complete)
+            {
+                bail!("Backup stream truncated in {}.", file_handle);
+            }
+            return Ok(false);
+        };
+        let mut file_handle = vec![0u8; handle_len as usize];
+        self.reader.read_exact(&mut file_handle).await?;
+        let file_handle = String::from_utf8(file_handle)?;
+        let chunk_len = self.reader.read_u32_le().await?;
+
+        // A file read to the end is no longer tracked, but stays opened.
+        ensure!(
+            self.files.contains_key(&file_handle) || !self.opened.contains(&file_handle),
+            "File {} appears more than once in the backup stream.",
+            file_handle,
+        );
+        let file = self.files.entry(file_handle.clone()).or_default();
+        ensure!(
+            !file.complete,
+            "File {} appears more than once in the backup stream.",
+            file_handle,
+        );
+        if !file.seen {
+            file.seen = true;
+            if Path::new(&file_handle).parent() == Some(Path::new(METADATA_DIR)) {
+                self.metadata_files.push(file_handle.clone());
+            }
+        }
+        if chunk_len == 0 {
+            file.complete = true;
+        } else {
+            ensure!(
+                self.buffered_bytes + chunk_len as usize <= self.max_buffered_bytes,
+                "More than {} bytes of the backup stream would be held for the files not read \
+                yet. Read the files in stream order, or from a stream in a regular file.",
+                self.max_buffered_bytes,
+            );
+            let mut chunk = vec![0u8; chunk_len as usize];
+            self.reader.read_exact(&mut chunk).await?;
+            self.buffered_bytes += chunk.len();
+            file.chunks.push_back(Bytes::from(chunk));
+        }
+        Ok(true)
+    }
+
+    /// Returns the next chunk of an opened file, reading the stream forward as far as needed, or
+    /// `None` once the file is read to the end.
+    async fn next_chunk(&mut self, file_handle: &FileHandleRef) -> Result<Option<Bytes>> {
+        loop {
+            let file = self
+                .files
+                .get_mut(file_handle)
+                .ok_or_else(|| format_err!("File {} already read.", file_handle))?;
+            if let Some(chunk) = file.chunks.pop_front() {
+                self.buffered_bytes -= chunk.len();
+                return Ok(Some(chunk));
+            }
+            if file.complete {
+                self.files.remove(file_handle);
+                return Ok(None);
+            }
+            if !self.read_frame().await? {
+                bail!("File {} not found in the backup stream.", file_handle);
+            }
+        }
+    }
+}
+
+/// Reads the length prefix of the next frame, or `None` if the stream ends cleanly before it.
+async fn read_u32_or_eof(reader: &mut (impl AsyncRead + Unpin)) -> Result<Option<u32>> {
+    let mut buf = [0u8; 4];
+    let mut num_read = 0;
+    while num_read < buf.len() {
+        match reader.read(&mut buf[num_read..]).await? {
+            0 if num_read == 0 => return Ok(None),
+            0 => bail!("Backup stream truncated in a frame header."),
+            n => num_read += n,
+        }
+    }
+    Ok(Some(u32::from_le_bytes(buf)))
+}
+
+#[async_trait]
+impl BackupStorage for StreamReader {
+    async fn create_backup(&self, name: &ShellSafeName) -> Result<BackupHandle> {
+        bail!(
+            "Can't create backup {} in a backup stream being read.",
+            name.as_ref()
+        )
+    }
+
+    async fn create_for_write(
+        &self,
+        _backup_handle: &BackupHandleRef,
+        name: &ShellSafeName,
+    ) -> Result<(FileHandle, Box<dyn AsyncWrite + Send + Unpin>)> {
+        bail!(
+            "Can't create file {} in a backup stream being read.",
+            name.as_ref()
+        )
+    }
+
+    async fn open_for_read(
+        &self,
+        file_handle: &FileHandleRef,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let (path, files) = match &self.source {
+            StreamSource::Indexed { path, files } => (path, files),
+            StreamSource::Forward(reader) => {
+                let mut reader_guard = reader.lock().await;
+                ensure!(
+                    reader_guard.opened.insert(file_handle.to_string()),
+                    "File {} already read from the forward-only backup stream.",
+                    file_handle,
+                );
+                reader_guard
+                    .files
+                    .entry(file_handle.to_string())
+                    .or_default();
+                let chunks = stream::try_unfold(
+                    (Arc::clone(reader), file_handle.to_string()),
+                    |(reader, file_handle)| async move {
+                        let chunk = reader
+                            .lock()
+                            .await
+                            .next_chunk(&file_handle)
+                            .await
+                            .map_err(io::Error::other)?;
+                        Ok::<_, io::Error>(chunk.map(|chunk| (chunk, (reader, file_handle))))
+                    },
+                );
+                return Ok(Box::new(Box::pin(chunks).into_async_read().compat()));
+            },
+        };
+        let chunks = match files.get(file_handle) {
+            Some(chunks) => chunks.clone(),
+            None => bail!("File {} not found in the backup stream.", file_handle),
+        };
+        let file = File::open(path).await?;
+        let chunks = stream::try_unfold(
+            (file, chunks.into_iter()),
+            |(mut file, mut chunks)| async move {
+                let Some((offset, len)) = chunks.next() else {
+                    return Ok(None);
+                };
+                file.seek(SeekFrom::Start(offset)).await?;
+                let mut chunk = vec![0u8; len as usize];
+                file.read_exact(&mut chunk).await?;
+                Ok::<_, io::Error>(Some((Bytes::from(chunk), (file, chunks))))
+            },
+        );
+        Ok(Box::new(Box::pin(chunks).into_async_read().compat()))
+    }
+
+    async fn list_metadata_files(&self) -> Result<Vec<FileHandle>> {
+        let files = match &self.source {
+            StreamSource::Indexed { files, .. } => files,
+            StreamSource::Forward(reader) => {
+                // The metadata files are at the end of the stream.
+                let mut reader = reader.lock().await;
+                while reader.read_frame().await? {}
+                return Ok(reader.metadata_files.clone());
+            },
+        };
+        let metadata_dir = Path::new(METADATA_DIR);
+        Ok(files
+            .keys()
+            .filter(|file_handle| Path::new(file_handle).parent() == Some(metadata_dir))
+            .cloned()
+            .collect())
+    }
+
+    async fn backup_metadata_file(&self, file_handle: &FileHandleRef) -> Result<()> {
+        bail!("Can't move {} in a backup stream being read.", file_handle)
+    }
+
+    async fn save_metadata_lines(
+        &self,
+        name: &ShellSafeName,
+        _lines: &[TextLine],
+    ) -> Result<FileHandle> {
+        bail!(
+            "Can't save metadata file {} in a backup stream being read.",
+            name.as_ref()
+        )
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+use super::*;
+use crate::storage::test_util::{arb_backups, arb_metadata_files, to_file_name};
+use aptos_temppath::TempPath;
+use itertools::Itertools;
+use proptest::prelude::*;
+use tokio::runtime::Runtime;
+
+async fn read_file(reader: &StreamReader, file_handle: &FileHandleRef) -> Vec<u8> {
+    let mut buf = Vec::new();
+    reader
+        .open_for_read(file_handle)
+        .await
+        .unwrap()
+        .read_to_end(&mut buf)
+        .await
+        .unwrap();
+    buf
+}
+
+fn indexed_files(reader: &StreamReader) -> &HashMap<FileHandle, Vec<(u64, u32)>> {
+    match &reader.source {
+        StreamSource::Indexed { files, .. } => files,
+        StreamSource::Forward(_) => panic!("Expecting an indexed stream."),
+    }
+}
+
+async fn write_two_file_backup(writer: &StreamWriter, contents: &[Vec<u8>]) -> Vec<FileHandle> {
+    let backup_handle = writer
+        .create_backup(&ShellSafeName::try_from("backup".to_string()).unwrap())
+        .await
+        .unwrap();
+    let mut handles = Vec::new();
+    for (i, content) in contents.iter().enumerate() {
+        let name = ShellSafeName::try_from(format!("file{}", i)).unwrap();
+        let (handle, mut file) = writer
+            .create_for_write(&backup_handle, &name)
+            .await
+            .unwrap();
+        file.write_all(content).await.unwrap();
+        file.shutdown().await.unwrap();
+        handles.push(handle);
+    }
+    handles
+}
+
+async fn test_write_and_read_impl(
+    backups: HashMap<ShellSafeName, HashMap<ShellSafeName, Vec<u8>>>,
+    metadata_files: Vec<(ShellSafeName, TextLine)>,
+) {
+    let stream_path = TempPath::new();
+    let writer = StreamWriter::new(File::create(stream_path.path()).await.unwrap());
+    for (backup_name, files) in &backups {
+        let backup_handle = writer.create_backup(backup_name).await.unwrap();
+        for (name, content) in files {
+            let (handle, mut file) = writer.create_for_write(&backup_handle, name).await.unwrap();
+            assert_eq!(handle, to_file_name(backup_name, name));
+            file.write_all(content).await.unwrap();
+            file.shutdown().await.unwrap();
+        }
+    }
+    for (name, content) in &metadata_files {
+        writer.save_metadata_line(name, content).await.unwrap();
+    }
+    assert!(writer.list_metadata_files().await.is_err());
+
+    let reader = StreamReader::open(stream_path.path().to_path_buf())
+        .await
+        .unwrap();
+    for (backup_name, files) in &backups {
+        for (name, content) in files {
+            let buf = read_file(&reader, &to_file_name(backup_name, name)).await;
+            assert_eq!(content, &buf);
+        }
+    }
+
+    let mut read_back = Vec::new();
+    for file_handle in reader.list_metadata_files().await.unwrap() {
+        let buf = String::from_utf8(read_file(&reader, &file_handle).await).unwrap();
+        read_back.extend(buf.lines().map(|line| TextLine::new(line).unwrap()));
+    }
+    read_back.sort();
+    let expected = metadata_files
+        .into_iter()
+        .map(|(_name, content)| content)
+        .sorted()
+        .collect::<Vec<_>>();
+    assert_eq!(read_back, expected);
+
+    // The same read forward only, with the metadata files, which are at the end, listed last.
+    let reader = StreamReader::from_reader(File::open(stream_path.path()).await.unwrap())
+        .await
+        .unwrap();
+    for (backup_name, files) in &backups {
+        for (name, content) in files {
+            let buf = read_file(&reader, &to_file_name(backup_name, name)).await;
+            assert_eq!(content, &buf);
+        }
+    }
+    let mut num_metadata_lines = 0;
+    for file_handle in reader.list_metadata_files().await.unwrap() {
+        let buf = String::from_utf8(read_file(&reader, &file_handle).await).unwrap();
+        num_metadata_lines += buf.lines().count();
+    }
+    assert_eq!(num_metadata_lines, expected.len());
+
+    // A stream cut short is rejected rather than restored from partially.
+    let stream = std::fs::read(stream_path.path()).unwrap();
+    if stream.len() > MAGIC.len() {
+        let truncated_path = TempPath::new();
+        std::fs::write(truncated_path.path(), &stream[..stream.len() - 1]).unwrap();
+        assert!(StreamReader::open(truncated_path.path().to_path_buf())
+            .await
+            .is_err());
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_write_and_read(
+        backups in arb_backups(),
+        metadata_files in arb_metadata_files(),
+    ) {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(test_write_and_read_impl(backups, metadata_files));
+    }
+}
+
+#[tokio::test]
+async fn test_interleaved_files() {
+    let stream_path = TempPath::new();
+    let writer = StreamWriter::new(File::create(stream_path.path()).await.unwrap());
+    let backup_handle = writer
+        .create_backup(&ShellSafeName::try_from("backup".to_string()).unwrap())
+        .await
+        .unwrap();
+
+    // Files spanning several chunks, written concurrently.
+    let contents = [vec![1u8; CHUNK_SIZE * 2 + 1], vec![2u8; CHUNK_SIZE + 1]];
+    let mut files = Vec::new();
+    for i in 0..contents.len() {
+        let name = ShellSafeName::try_from(format!("file{}", i)).unwrap();
+        files.push(
+            writer
+                .create_for_write(&backup_handle, &name)
+                .await
+                .unwrap(),
+        );
+    }
+    for offset in (0..CHUNK_SIZE * 3).step_by(CHUNK_SIZE / 2) {
+        for ((_handle, file), content) in files.iter_mut().zip(&contents) {
+            if offset < content.len() {
+                let end = min(offset + CHUNK_SIZE / 2, content.len());
+                file.write_all(&content[offset..end]).await.unwrap();
+            }
+        }
+    }
+    for (_handle, file) in files.iter_mut() {
+        file.shutdown().await.unwrap();
+    }
+
+    let reader = StreamReader::open(stream_path.path().to_path_buf())
+        .await
+        .unwrap();
+    for ((handle, _file), content) in files.iter().zip(&contents) {
+        assert!(indexed_files(&reader)[handle].len() > 1);
+        assert_eq!(&read_file(&reader, handle).await, content);
+    }
+}
+
+#[tokio::test]
+async fn test_not_a_backup_stream() {
+    let stream_path = TempPath::new();
+    for content in [&b""[..], &b"not a backup stream"[..]] {
+        std::fs::write(stream_path.path(), content).unwrap();
+        assert!(StreamReader::open(stream_path.path().to_path_buf())
+            .await
+            .is_err());
+    }
+
+    std::fs::write(stream_path.path(), MAGIC).unwrap();
+    assert!(indexed_files(
+        &StreamReader::open(stream_path.path().to_path_buf())
+            .await
+            .unwrap()
+    )
+    .is_empty());
+
+    // Nor is a directory a backup stream.
+    let dir = TempPath::new();
+    dir.create_as_dir().unwrap();
+    assert!(StreamReader::open(dir.path().to_path_buf()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_read_from_pipe() {
+    let (pipe_writer, pipe_reader) = tokio::io::duplex(CHUNK_SIZE / 4);
+    let contents = vec![vec![1u8; CHUNK_SIZE * 2 + 1], vec![2u8; 10]];
+
+    let writer_contents = contents.clone();
+    let write = tokio::spawn(async move {
+        let writer = StreamWriter::new(pipe_writer);
+        write_two_file_backup(&writer, &writer_contents).await
+    });
+    // The pipe holds less than a file, so the files are read while being written.
+    let reader = StreamReader::from_reader(pipe_reader).await.unwrap();
+    for (i, content) in contents.iter().enumerate() {
+        assert_eq!(
+            &read_file(&reader, &to_file_name("backup", &format!("file{}", i))).await,
+            content
+        );
+    }
+    write.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_read_forward_only() {
+    let stream_path = TempPath::new();
+    let contents = vec![vec![1u8; CHUNK_SIZE * 2 + 1], vec![2u8; 10]];
+    let handles = write_two_file_backup(
+        &StreamWriter::new(File::create(stream_path.path()).await.unwrap()),
+        &contents,
+    )
+    .await;
+    let open = || async {
+        StreamReader::from_reader_impl(
+            File::open(stream_path.path()).await.unwrap(),
+            /* max_buffered_bytes = */ CHUNK_SIZE,
+        )
+        .await
+        .unwrap()
+    };
+
+    // In stream order, no more than a chunk is held at a time.
+    let reader = open().await;
+    for (handle, content) in handles.iter().zip(&contents) {
+        assert_eq!(&read_file(&reader, handle).await, content);
+    }
+    // A file can only be read once.
+    assert!(reader.open_for_read(&handles[0]).await.is_err());
+
+    // Reading the second file first holds the first one in memory, more than allowed.
+    let reader = open().await;
+    let mut buf = Vec::new();
+    assert!(reader
+        .open_for_read(&handles[1])
+        .await
+        .unwrap()
+        .read_to_end(&mut buf)
+        .await
+        .is_err());
+
+    // A file not in the stream.
+    let reader = open().await;
+    let mut buf = Vec::new();
+    assert!(reader
+        .open_for_read("backup/no_such_file")
+        .await
+        .unwrap()
+        .read_to_end(&mut buf)
+        .await
+        .is_err());
+}
@@ -14,7 +14,7 @@ use proptest::{
 use std::{collections::HashMap, path::Path};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-fn to_file_name(backup_name: &str, file_name: &str) -> String {
+pub fn to_file_name(backup_name: &str, file_name: &str) -> String {
     Path::new(backup_name)
         .join(file_name)
         .path_to_string()
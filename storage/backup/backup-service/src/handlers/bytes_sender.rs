@@ -1,11 +1,12 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
-use crate::handlers::utils::THROUGHPUT_COUNTER;
+use crate::handlers::{throttle::Throttle, utils::THROUGHPUT_COUNTER};
 use aptos_metrics_core::IntCounterVecHelper;
 use aptos_storage_interface::{AptosDbError, Result as DbResult};
 use bytes::{BufMut, Bytes, BytesMut};
 use serde::Serialize;
+use std::sync::Arc;
 
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
 type BytesResult = Result<Bytes, BoxError>;
@@ -16,6 +17,7 @@ pub(super) struct BytesSender {
     buffer: BytesMut,
     bytes_tx: tokio::sync::mpsc::Sender<BytesResult>,
     endpoint: &'static str,
+    throttle: Option<Arc<Throttle>>,
 }
 
 impl BytesSender {
@@ -27,6 +29,7 @@ impl BytesSender {
 
     pub fn new(
         endpoint: &'static str,
+        throttle: Option<Arc<Throttle>>,
     ) -> (Self, tokio_stream::wrappers::ReceiverStream<BytesResult>) {
         let (bytes_tx, bytes_rx) = tokio::sync::mpsc::channel(Self::MAX_BATCHES);
 
@@ -34,6 +37,7 @@ impl BytesSender {
             buffer: BytesMut::new(),
             bytes_tx,
             endpoint,
+            throttle,
         };
 
         let stream = tokio_stream::wrappers::ReceiverStream::new(bytes_rx);
@@ -67,6 +71,10 @@ impl BytesSender {
 
     pub fn flush_buffer(&mut self) -> DbResult<()> {
         let bytes = self.buffer.split().freeze();
+        // Sleeping here also holds off reading the DB for the next batch.
+        if let Some(throttle) = &self.throttle {
+            throttle.acquire(bytes.len());
+        }
         THROUGHPUT_COUNTER.inc_with_by(&[self.endpoint], bytes.len() as u64);
 
         self.send_res(Ok(bytes))
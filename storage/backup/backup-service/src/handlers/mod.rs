@@ -2,16 +2,21 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 mod bytes_sender;
+mod throttle;
 mod utils;
 
-use crate::handlers::utils::{
-    handle_rejection, reply_with_bcs_bytes, reply_with_bytes_sender, unwrap_or_500,
-    LATENCY_HISTOGRAM,
+use crate::handlers::{
+    throttle::Throttle,
+    utils::{
+        handle_rejection, reply_with_bcs_bytes, reply_with_bytes_sender, unwrap_or_500,
+        LATENCY_HISTOGRAM,
+    },
 };
 use aptos_crypto::hash::HashValue;
 use aptos_db::backup::backup_handler::BackupHandler;
 use aptos_metrics_core::TimerHelper;
 use aptos_types::transaction::Version;
+use std::{num::NonZeroU64, sync::Arc};
 use warp::{filters::BoxedFilter, reply::Reply, Filter};
 
 static DB_STATE: &str = "db_state";
@@ -24,7 +29,15 @@ static EPOCH_ENDING_LEDGER_INFOS: &str = "epoch_ending_ledger_infos";
 static TRANSACTIONS: &str = "transactions";
 static TRANSACTION_RANGE_PROOF: &str = "transaction_range_proof";
 
-pub(crate) fn get_routes(backup_handler: BackupHandler) -> BoxedFilter<(impl Reply,)> {
+/// With `max_bytes_per_sec` set, the endpoints streaming DB records share a throttle capping their
+/// total throughput.
+pub(crate) fn get_routes(
+    backup_handler: BackupHandler,
+    max_bytes_per_sec: Option<NonZeroU64>,
+) -> BoxedFilter<(impl Reply,)> {
+    let shared_throttle =
+        max_bytes_per_sec.map(|max_bytes_per_sec| Arc::new(Throttle::new(max_bytes_per_sec)));
+
     // GET db_state
     let bh = backup_handler.clone();
     let db_state = warp::path::end()
@@ -46,9 +59,10 @@ pub(crate) fn get_routes(backup_handler: BackupHandler) -> BoxedFilter<(impl Rep
 
     // GET state_snapshot/<version>
     let bh = backup_handler.clone();
+    let throttle = shared_throttle.clone();
     let state_snapshot = warp::path!(Version)
         .map(move |version| {
-            reply_with_bytes_sender(&bh, STATE_SNAPSHOT, move |bh, sender| {
+            reply_with_bytes_sender(&bh, &throttle, STATE_SNAPSHOT, move |bh, sender| {
                 bh.get_state_item_iter(version, 0, usize::MAX)?
                     .try_for_each(|record_res| sender.send_size_prefixed_bcs_bytes(record_res?))
             })
@@ -69,9 +83,10 @@ pub(crate) fn get_routes(backup_handler: BackupHandler) -> BoxedFilter<(impl Rep
 
     // GET state_snapshot_chunk/<version>/<start_idx>/<limit>
     let bh = backup_handler.clone();
+    let throttle = shared_throttle.clone();
     let state_snapshot_chunk = warp::path!(Version / usize / usize)
         .map(move |version, start_idx, limit| {
-            reply_with_bytes_sender(&bh, STATE_SNAPSHOT_CHUNK, move |bh, sender| {
+            reply_with_bytes_sender(&bh, &throttle, STATE_SNAPSHOT_CHUNK, move |bh, sender| {
                 bh.get_state_item_iter(version, start_idx, limit)?
                     .try_for_each(|record_res| sender.send_size_prefixed_bcs_bytes(record_res?))
             })
@@ -89,20 +104,27 @@ pub(crate) fn get_routes(backup_handler: BackupHandler) -> BoxedFilter<(impl Rep
 
     // GET epoch_ending_ledger_infos/<start_epoch>/<end_epoch>/
     let bh = backup_handler.clone();
+    let throttle = shared_throttle.clone();
     let epoch_ending_ledger_infos = warp::path!(u64 / u64)
         .map(move |start_epoch, end_epoch| {
-            reply_with_bytes_sender(&bh, EPOCH_ENDING_LEDGER_INFOS, move |bh, sender| {
-                bh.get_epoch_ending_ledger_info_iter(start_epoch, end_epoch)?
-                    .try_for_each(|record_res| sender.send_size_prefixed_bcs_bytes(record_res?))
-            })
+            reply_with_bytes_sender(
+                &bh,
+                &throttle,
+                EPOCH_ENDING_LEDGER_INFOS,
+                move |bh, sender| {
+                    bh.get_epoch_ending_ledger_info_iter(start_epoch, end_epoch)?
+                        .try_for_each(|record_res| sender.send_size_prefixed_bcs_bytes(record_res?))
+                },
+            )
         })
         .recover(handle_rejection);
 
     // GET transactions/<start_version>/<num_transactions>
     let bh = backup_handler.clone();
+    let throttle = shared_throttle.clone();
     let transactions = warp::path!(Version / usize)
         .map(move |start_version, num_transactions| {
-            reply_with_bytes_sender(&bh, TRANSACTIONS, move |bh, sender| {
+            reply_with_bytes_sender(&bh, &throttle, TRANSACTIONS, move |bh, sender| {
                 bh.get_transaction_iter(start_version, num_transactions)?
                     .try_for_each(|record_res| sender.send_size_prefixed_bcs_bytes(record_res?))
             })
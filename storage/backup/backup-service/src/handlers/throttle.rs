@@ -0,0 +1,78 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+use aptos_infallible::Mutex;
+use std::{
+    num::NonZeroU64,
+    time::{Duration, Instant},
+};
+
+/// Token bucket capping the bytes per second sent by all the streaming endpoints together, so that
+/// a backup doesn't steal too much IO from the node. The bucket holds up to a second worth of
+/// bytes, and a sender going over it sleeps until the bytes it took are refilled.
+pub(super) struct Throttle {
+    max_bytes_per_sec: NonZeroU64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Negative when senders have taken more than available and are sleeping it off.
+    available_bytes: f64,
+    refilled_at: Instant,
+}
+
+impl Throttle {
+    pub fn new(max_bytes_per_sec: NonZeroU64) -> Self {
+        Self {
+            max_bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                available_bytes: max_bytes_per_sec.get() as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `num_bytes` out of the bucket, blocking the thread until they are refilled if they
+    /// were not available.
+    pub fn acquire(&self, num_bytes: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock();
+            let rate = self.max_bytes_per_sec.get() as f64;
+            let now = Instant::now();
+            let refilled_bytes = now.duration_since(bucket.refilled_at).as_secs_f64() * rate;
+            bucket.available_bytes = (bucket.available_bytes + refilled_bytes).min(rate);
+            bucket.refilled_at = now;
+
+            bucket.available_bytes -= num_bytes as f64;
+            if bucket.available_bytes < 0.0 {
+                Duration::from_secs_f64(-bucket.available_bytes / rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle() {
+        let throttle = Throttle::new(NonZeroU64::new(1000).unwrap());
+
+        // A second worth of bytes is available right away.
+        let start = Instant::now();
+        throttle.acquire(1000);
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // Then it's a second per 1000 bytes.
+        let start = Instant::now();
+        throttle.acquire(200);
+        throttle.acquire(200);
+        assert!(start.elapsed() >= Duration::from_millis(350));
+    }
+}
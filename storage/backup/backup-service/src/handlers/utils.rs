@@ -1,7 +1,7 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
-use crate::handlers::bytes_sender;
+use crate::handlers::{bytes_sender, throttle::Throttle};
 use aptos_db::{backup::backup_handler::BackupHandler, metrics::BACKUP_TIMER};
 use aptos_logger::prelude::*;
 use aptos_metrics_core::{
@@ -11,7 +11,7 @@ use aptos_storage_interface::Result as DbResult;
 use hyper::Body;
 use once_cell::sync::Lazy;
 use serde::Serialize;
-use std::convert::Infallible;
+use std::{convert::Infallible, sync::Arc};
 use warp::{reply::Response, Rejection, Reply};
 
 pub(super) static LATENCY_HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
@@ -45,13 +45,14 @@ pub(super) fn reply_with_bcs_bytes<R: Serialize>(
 
 pub(super) fn reply_with_bytes_sender<F>(
     backup_handler: &BackupHandler,
+    throttle: &Option<Arc<Throttle>>,
     endpoint: &'static str,
     f: F,
 ) -> Box<dyn Reply>
 where
     F: FnOnce(BackupHandler, &mut bytes_sender::BytesSender) -> DbResult<()> + Send + 'static,
 {
-    let (sender, stream) = bytes_sender::BytesSender::new(endpoint);
+    let (sender, stream) = bytes_sender::BytesSender::new(endpoint, throttle.clone());
 
     // spawn and forget, error propagates through the `stream: TryStream<_>`
     let bh = backup_handler.clone();
@@ -6,12 +6,23 @@ mod handlers;
 use crate::handlers::get_routes;
 use aptos_db::AptosDB;
 use aptos_logger::prelude::*;
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, num::NonZeroU64, sync::Arc};
 use tokio::runtime::Runtime;
 
 pub fn start_backup_service(address: SocketAddr, db: Arc<AptosDB>) -> Runtime {
+    start_backup_service_with_max_bytes_per_sec(address, db, None)
+}
+
+/// Like `start_backup_service`, but with `max_bytes_per_sec` set, the backup service sleeps
+/// between reading chunks of DB records to keep its total throughput under it, so that running a
+/// backup against a live node doesn't degrade it.
+pub fn start_backup_service_with_max_bytes_per_sec(
+    address: SocketAddr,
+    db: Arc<AptosDB>,
+    max_bytes_per_sec: Option<NonZeroU64>,
+) -> Runtime {
     let backup_handler = db.get_backup_handler();
-    let routes = get_routes(backup_handler);
+    let routes = get_routes(backup_handler, max_bytes_per_sec);
 
     let runtime = aptos_runtimes::spawn_named_runtime("backup".into(), None);
 
@@ -166,6 +166,18 @@ pub struct VerifyOpt {
 }
 
 impl Command {
+    /// Whether the backup is written to stdout, which the logs must then stay off.
+    pub fn writes_to_stdout(&self) -> bool {
+        match self {
+            Command::Oneoff(opt) => match &opt.backup_type {
+                BackupType::EpochEnding { storage, .. }
+                | BackupType::StateSnapshot { storage, .. }
+                | BackupType::Transaction { storage, .. } => storage.writes_to_stdout(),
+            },
+            Command::Continuously(_) | Command::Query(_) | Command::Verify(_) => false,
+        }
+    }
+
     pub async fn run(self) -> Result<()> {
         match self {
             Command::Oneoff(opt) => {
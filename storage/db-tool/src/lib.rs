@@ -44,6 +44,13 @@ pub enum DBTool {
 }
 
 impl DBTool {
+    pub fn writes_to_stdout(&self) -> bool {
+        match self {
+            DBTool::Backup(cmd) => cmd.writes_to_stdout(),
+            _ => false,
+        }
+    }
+
     pub async fn run(self) -> Result<()> {
         match self {
             DBTool::Backup(cmd) => cmd.run().await,
@@ -121,6 +121,22 @@ fn run_tests<K>(
         );
     }
 
+    {
+        let leaves = JellyfishMerkleIterator::new(Arc::clone(&db), version, HashValue::zero())
+            .unwrap()
+            .into_leaves();
+        assert_eq!(
+            leaves
+                .map(|leaf| leaf.map(|leaf| (*leaf.account_key(), leaf.value_hash())))
+                .collect::<Result<Vec<_>>>()
+                .unwrap(),
+            btree
+                .iter()
+                .map(|(key, (value_hash, _))| (*key, *value_hash))
+                .collect::<Vec<_>>(),
+        );
+    }
+
     for i in 0..btree.len() {
         {
             let iter = JellyfishMerkleIterator::new_by_index(Arc::clone(&db), version, i).unwrap();
@@ -10,7 +10,7 @@
 mod iterator_test;
 
 use crate::{
-    node_type::{Child, InternalNode, Node, NodeKey},
+    node_type::{Child, InternalNode, LeafNode, Node, NodeKey},
     TreeReader,
 };
 use aptos_crypto::HashValue;
@@ -273,14 +273,18 @@ where
     }
 }
 
-impl<R, K> Iterator for JellyfishMerkleIterator<R, K>
+impl<R, K> JellyfishMerkleIterator<R, K>
 where
     R: TreeReader<K>,
     K: crate::Key,
 {
-    type Item = Result<(HashValue, (K, Version))>;
+    /// Turns the iterator into one over the leaf nodes themselves, e.g. to compare the hashes of
+    /// the values without reading the values.
+    pub fn into_leaves(mut self) -> impl Iterator<Item = Result<LeafNode<K>>> {
+        std::iter::from_fn(move || self.next_leaf())
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_leaf(&mut self) -> Option<Result<LeafNode<K>>> {
         if self.done {
             return None;
         }
@@ -294,10 +298,7 @@ where
                     // true in `new`). Return the node and mark `self.done` so next time we return
                     // None.
                     self.done = true;
-                    return Some(Ok((
-                        *leaf_node.account_key(),
-                        leaf_node.value_index().clone(),
-                    )));
+                    return Some(Ok(leaf_node));
                 },
                 Ok(Node::Internal(_)) => {
                     // This means `starting_key` is bigger than every key in this tree, or we have
@@ -332,9 +333,8 @@ where
                     self.parent_stack.push(visit_info);
                 },
                 Ok(Node::Leaf(leaf_node)) => {
-                    let ret = (*leaf_node.account_key(), leaf_node.value_index().clone());
                     Self::cleanup_stack(&mut self.parent_stack);
-                    return Some(Ok(ret));
+                    return Some(Ok(leaf_node));
                 },
                 Ok(Node::Null) => {
                     unreachable!("When tree is empty, done should be already set to true")
@@ -344,3 +344,17 @@ where
         }
     }
 }
+
+impl<R, K> Iterator for JellyfishMerkleIterator<R, K>
+where
+    R: TreeReader<K>,
+    K: crate::Key,
+{
+    type Item = Result<(HashValue, (K, Version))>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_leaf().map(|res| {
+            res.map(|leaf_node| (*leaf_node.account_key(), leaf_node.value_index().clone()))
+        })
+    }
+}
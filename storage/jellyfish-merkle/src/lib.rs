@@ -753,14 +753,20 @@ where
                     let queried_child_index = nibble_iter
                         .next()
                         .ok_or_else(|| AptosDbError::Other("ran out of nibbles".to_string()))?;
-                    let child_node_key = internal_node.get_child_with_siblings(
-                        &next_node_key,
-                        queried_child_index,
-                        Some(self.reader),
-                        &mut out_siblings,
-                        nibble_depth * 4,
-                        target_root_depth,
-                    )?;
+                    let child_node_key = internal_node
+                        .get_child_with_siblings(
+                            &next_node_key,
+                            queried_child_index,
+                            Some(self.reader),
+                            &mut out_siblings,
+                            nibble_depth * 4,
+                            target_root_depth,
+                        )
+                        // Keeps e.g. a `NotFound` from reading a sibling leaf as is.
+                        .map_err(|err| match err.downcast::<AptosDbError>() {
+                            Ok(err) => err,
+                            Err(err) => err.into(),
+                        })?;
                     next_node_key = match child_node_key {
                         Some(node_key) => node_key,
                         None => {
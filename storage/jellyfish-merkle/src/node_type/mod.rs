@@ -385,6 +385,24 @@ impl InternalNode {
         Ok(())
     }
 
+    /// Returns the size of what `serialize` outputs, without serializing.
+    pub fn serialized_len(&self) -> usize {
+        let bitmaps_len = 2 * size_of::<u16>();
+        let children_len: usize = self
+            .children
+            .iter()
+            .map(|(_, child)| {
+                let leaf_count_len = match child.node_type {
+                    NodeType::Leaf => 0,
+                    NodeType::Internal { leaf_count } => u64_varint_len(leaf_count as u64),
+                    NodeType::Null => unreachable!("Child cannot be Null"),
+                };
+                u64_varint_len(child.version) + HashValue::LENGTH + leaf_count_len
+            })
+            .sum();
+        bitmaps_len + children_len
+    }
+
     pub fn deserialize(data: &[u8]) -> Result<Self> {
         let mut reader = Cursor::new(data);
         let len = data.len();
@@ -846,6 +864,16 @@ where
         Ok(out)
     }
 
+    /// Returns the size of what `encode` outputs, without encoding the node.
+    pub fn encoded_len(&self) -> Result<usize> {
+        let tag_len = size_of::<u8>();
+        Ok(match self {
+            Node::Internal(internal_node) => tag_len + internal_node.serialized_len(),
+            Node::Leaf(leaf_node) => tag_len + bcs::serialized_size(leaf_node)?,
+            Node::Null => tag_len,
+        })
+    }
+
     /// Computes the hash of nodes.
     pub fn hash(&self) -> HashValue {
         match self {
@@ -917,6 +945,18 @@ fn serialize_u64_varint(mut num: u64, binary: &mut Vec<u8>) {
     binary.push(num as u8);
 }
 
+/// Helper function to get the number of bytes of the above encoding.
+fn u64_varint_len(mut num: u64) -> usize {
+    for len in 1..=8 {
+        num >>= 7;
+        if num == 0 {
+            return len;
+        }
+    }
+    // Last byte is encoded raw.
+    9
+}
+
 /// Helper function to deserialize versions from above encoding.
 fn deserialize_u64_varint<T>(reader: &mut T) -> Result<u64>
 where
@@ -2,8 +2,8 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use super::{
-    deserialize_u64_varint, serialize_u64_varint, Child, Children, InternalNode, NodeDecodeError,
-    NodeKey,
+    deserialize_u64_varint, serialize_u64_varint, u64_varint_len, Child, Children, InternalNode,
+    NodeDecodeError, NodeKey,
 };
 use crate::{node_type::NodeType, test_helper::ValueBlob, LeafNode, StateKey, TreeReader};
 use aptos_crypto::{
@@ -106,6 +106,7 @@ fn test_encode_decode() {
     ];
     for n in &nodes {
         let v = n.encode().unwrap();
+        assert_eq!(n.encoded_len().unwrap(), v.len());
         assert_eq!(*n, Node::decode(&v).unwrap());
     }
     // Error cases
@@ -128,6 +129,7 @@ proptest! {
     fn test_u64_varint_roundtrip(input in any::<u64>()) {
         let mut vec = vec![];
         serialize_u64_varint(input, &mut vec);
+        assert_eq!(u64_varint_len(input), vec.len());
         assert_eq!(deserialize_u64_varint(&mut Cursor::new(vec)).unwrap(), input);
     }
 
@@ -135,6 +137,7 @@ proptest! {
     fn test_internal_node_roundtrip(input in any::<InternalNode>()) {
         let mut vec = vec![];
         input.serialize(&mut vec).unwrap();
+        assert_eq!(input.serialized_len(), vec.len());
         let deserialized = InternalNode::deserialize(&vec).unwrap();
         assert_eq!(deserialized, input);
     }
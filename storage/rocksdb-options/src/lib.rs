@@ -1,8 +1,8 @@
 // Copyright (c) Aptos Foundation
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
-use aptos_config::config::{RocksDBStatsLevel, RocksdbConfig};
-use rocksdb::{statistics::StatsLevel, Env, Options};
+use aptos_config::config::{RocksDBStatsLevel, RocksdbConfig, WalRecoveryMode};
+use rocksdb::{statistics::StatsLevel, DBRecoveryMode, Env, Options};
 
 // TODO: Clean this up. It is currently separated into its own crate
 // to avoid circular dependencies, because it depends on aptos-config (which
@@ -19,6 +19,16 @@ fn convert_stats_level(level: RocksDBStatsLevel) -> StatsLevel {
     }
 }
 
+fn convert_wal_recovery_mode(mode: WalRecoveryMode) -> DBRecoveryMode {
+    use WalRecoveryMode::*;
+    match mode {
+        TolerateCorruptedTailRecords => DBRecoveryMode::TolerateCorruptedTailRecords,
+        AbsoluteConsistency => DBRecoveryMode::AbsoluteConsistency,
+        PointInTimeRecovery => DBRecoveryMode::PointInTime,
+        SkipAnyCorruptedRecords => DBRecoveryMode::SkipAnyCorruptedRecord,
+    }
+}
+
 pub fn gen_rocksdb_options(config: &RocksdbConfig, env: Option<&Env>, readonly: bool) -> Options {
     let mut db_opts = Options::default();
     if let Some(env) = env {
@@ -35,6 +45,10 @@ pub fn gen_rocksdb_options(config: &RocksdbConfig, env: Option<&Env>, readonly:
         db_opts.set_stats_dump_period_sec(stats_dump_period_sec);
     }
 
+    if let Some(mode) = config.wal_recovery_mode {
+        db_opts.set_wal_recovery_mode(convert_wal_recovery_mode(mode));
+    }
+
     if !readonly {
         db_opts.create_if_missing(true);
         db_opts.create_missing_column_families(true);
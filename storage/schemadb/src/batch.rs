@@ -146,6 +146,21 @@ impl SchemaBatch {
     pub fn delete<S: Schema>(&mut self, key: &S::Key) -> DbResult<()> {
         <Self as WriteBatch>::delete::<S>(self, key)
     }
+
+    /// Returns the number of deletes in the batch by column family, omitting the column families
+    /// without any.
+    pub fn num_deletes_by_cf(&self) -> HashMap<ColumnFamilyName, usize> {
+        self.rows
+            .iter()
+            .filter_map(|(cf_name, rows)| {
+                let num_deletes = rows
+                    .iter()
+                    .filter(|write_op| matches!(write_op, WriteOp::Deletion { .. }))
+                    .count();
+                (num_deletes > 0).then_some((*cf_name, num_deletes))
+            })
+            .collect()
+    }
 }
 
 impl WriteBatch for SchemaBatch {
@@ -182,9 +197,15 @@ impl IntoRawBatch for SchemaBatch {
         let mut db_batch = rocksdb::WriteBatch::default();
         for (cf_name, rows) in rows.iter() {
             let cf_handle = db.get_cf_handle(cf_name)?;
+            let cipher = db.value_cipher(cf_name);
             for write_op in rows {
                 match write_op {
-                    WriteOp::Value { key, value } => db_batch.put_cf(cf_handle, key, value),
+                    WriteOp::Value { key, value } => match cipher {
+                        Some(cipher) => {
+                            db_batch.put_cf(cf_handle, key, cipher.encrypt(cf_name, key, value)?)
+                        },
+                        None => db_batch.put_cf(cf_handle, key, value),
+                    },
                     WriteOp::Deletion { key } => db_batch.delete_cf(cf_handle, key),
                 }
             }
@@ -226,6 +247,10 @@ impl WriteBatch for NativeBatch<'_> {
     }
 
     fn raw_put(&mut self, cf_name: ColumnFamilyName, key: Vec<u8>, value: Vec<u8>) -> DbResult<()> {
+        let value = match self.db.value_cipher(cf_name) {
+            Some(cipher) => cipher.encrypt(cf_name, &key, &value)?,
+            None => value,
+        };
         self.raw_batch
             .inner
             .put_cf(&self.db.get_cf_handle(cf_name)?, &key, &value);
@@ -0,0 +1,26 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Verification of the block checksums on every read of a DB, for machines suspected of flaky
+//! memory, see `DB::set_verify_checksums_on_every_read`.
+//!
+//! RocksDB verifies the checksum of a block when it reads the block from its SST file, but not on
+//! a block cache hit: cached blocks are kept decompressed and without their checksums. Once the
+//! verification is on, reads stop filling the block cache, so every data block is read, and
+//! verified, from its file each time, at the cost of more IO and CPU. Index and filter blocks can
+//! still be served from the cache. Compactions always verify the checksums of the blocks they
+//! read, there's no switch for it in RocksDB anymore.
+//!
+//! The verification is best turned on right after the DB is opened, so that the block cache
+//! holds no data blocks of it to begin with.
+
+use rocksdb::ReadOptions;
+
+/// Adjusts the options of a read to verify the checksums of all the data blocks it reads, if
+/// `enabled`.
+pub(crate) fn apply(opts: &mut ReadOptions, enabled: bool) {
+    if enabled {
+        opts.set_verify_checksums(true);
+        opts.fill_cache(false);
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Accounting of the bytes read from and written to a set of DBs.
+//!
+//! Each [`DB`] keeps its own counters, and only counts while at least one [`IoAccountingHandle`]
+//! covering it is alive, so there's no cost on the hot path otherwise. A handle only sees the IO
+//! of the DBs it was begun on, e.g. all the DBs of an `AptosDB`, not that of the other DBs in the
+//! process. Reads and writes on those DBs done on any thread (e.g. the IO thread pools) are
+//! attributed to every handle covering them, so to attribute IO to a specific workload, run it in
+//! isolation from other workloads on the same DBs while the handle is alive.
+
+use crate::DB;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// The IO counters of a single [`DB`].
+#[derive(Debug, Default)]
+pub(crate) struct IoCounters {
+    num_active_handles: AtomicUsize,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl IoCounters {
+    pub(crate) fn record_read(&self, bytes: usize) {
+        if self.num_active_handles.load(Ordering::Relaxed) > 0 {
+            self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_write(&self, bytes: usize) {
+        if self.num_active_handles.load(Ordering::Relaxed) > 0 {
+            self.bytes_written
+                .fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Bytes read and written since an [`IoAccountingHandle`] was created.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct IoAccountingReport {
+    /// Keys and values returned by point reads and iterators.
+    pub bytes_read: u64,
+    /// Serialized sizes of the committed write batches.
+    pub bytes_written: u64,
+}
+
+/// Keeps IO accounting on for the DBs it was begun on while alive, see the module level doc.
+#[derive(Debug)]
+pub struct IoAccountingHandle {
+    /// The counters of each DB covered, with their values when the handle was created.
+    counters: Vec<(Arc<IoCounters>, IoAccountingReport)>,
+}
+
+impl IoAccountingHandle {
+    /// Starts accounting the IO of `dbs`. A DB listed more than once is only counted once.
+    pub fn begin<'a>(dbs: impl IntoIterator<Item = &'a DB>) -> Self {
+        let mut counters: Vec<(Arc<IoCounters>, IoAccountingReport)> = Vec::new();
+        for db in dbs {
+            let db_counters = db.io_counters();
+            if counters
+                .iter()
+                .any(|(counters, _start)| Arc::ptr_eq(counters, db_counters))
+            {
+                continue;
+            }
+            db_counters
+                .num_active_handles
+                .fetch_add(1, Ordering::SeqCst);
+            let start = IoAccountingReport {
+                bytes_read: db_counters.bytes_read.load(Ordering::SeqCst),
+                bytes_written: db_counters.bytes_written.load(Ordering::SeqCst),
+            };
+            counters.push((Arc::clone(db_counters), start));
+        }
+        Self { counters }
+    }
+
+    /// Returns the bytes read and written on the DBs covered since the handle was created.
+    pub fn report(&self) -> IoAccountingReport {
+        let mut report = IoAccountingReport::default();
+        for (counters, start) in &self.counters {
+            report.bytes_read += counters
+                .bytes_read
+                .load(Ordering::SeqCst)
+                .saturating_sub(start.bytes_read);
+            report.bytes_written += counters
+                .bytes_written
+                .load(Ordering::SeqCst)
+                .saturating_sub(start.bytes_written);
+        }
+        report
+    }
+}
+
+impl Drop for IoAccountingHandle {
+    fn drop(&mut self) {
+        for (counters, _start) in &self.counters {
+            counters.num_active_handles.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
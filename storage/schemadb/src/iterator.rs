@@ -2,8 +2,9 @@
 // Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
 
 use crate::{
-    IntoDbResult, KeyCodec, Schema, SeekKeyCodec, ValueCodec, APTOS_SCHEMADB_ITER_BYTES,
-    APTOS_SCHEMADB_ITER_LATENCY_SECONDS, APTOS_SCHEMADB_SEEK_LATENCY_SECONDS,
+    io_accounting::IoCounters, value_cipher::ValueCipher, IntoDbResult, KeyCodec, Schema,
+    SeekKeyCodec, ValueCodec, APTOS_SCHEMADB_ITER_BYTES, APTOS_SCHEMADB_ITER_LATENCY_SECONDS,
+    APTOS_SCHEMADB_SEEK_LATENCY_SECONDS,
 };
 use aptos_metrics_core::TimerHelper;
 use std::marker::PhantomData;
@@ -27,6 +28,10 @@ pub struct SchemaIterator<'a, S> {
     db_iter: rocksdb::DBRawIterator<'a>,
     direction: ScanDirection,
     status: Status,
+    cipher: Option<&'a dyn ValueCipher>,
+    io_counters: &'a IoCounters,
+    /// Holds the decrypted value returned by `next_with_raw_value()`, if there's a cipher.
+    value_buf: Vec<u8>,
     phantom: PhantomData<S>,
 }
 
@@ -34,11 +39,19 @@ impl<'a, S> SchemaIterator<'a, S>
 where
     S: Schema,
 {
-    pub(crate) fn new(db_iter: rocksdb::DBRawIterator<'a>, direction: ScanDirection) -> Self {
+    pub(crate) fn new(
+        db_iter: rocksdb::DBRawIterator<'a>,
+        direction: ScanDirection,
+        cipher: Option<&'a dyn ValueCipher>,
+        io_counters: &'a IoCounters,
+    ) -> Self {
         SchemaIterator {
             db_iter,
             direction,
             status: Status::Initialized,
+            cipher,
+            io_counters,
+            value_buf: Vec::new(),
             phantom: PhantomData,
         }
     }
@@ -89,9 +102,9 @@ where
         Ok(())
     }
 
-    fn next_impl(&mut self) -> aptos_storage_interface::Result<Option<(S::Key, S::Value)>> {
-        let _timer = APTOS_SCHEMADB_ITER_LATENCY_SECONDS.timer_with(&[S::COLUMN_FAMILY_NAME]);
-
+    /// Advances the iterator, returning whether it lands on a record, whose raw key and value are
+    /// then those of `db_iter`.
+    fn advance(&mut self) -> aptos_storage_interface::Result<bool> {
         if let Status::Advancing = self.status {
             match self.direction {
                 ScanDirection::Forward => self.db_iter.next(),
@@ -105,21 +118,88 @@ where
             self.db_iter.status().into_db_res()?;
             // advancing an invalid raw iter results in seg fault
             self.status = Status::Invalid;
-            return Ok(None);
+            return Ok(false);
         }
 
-        let raw_key = self.db_iter.key().expect("db_iter.key() failed.");
-        let raw_value = self.db_iter.value().expect("db_iter.value(0 failed.");
-        APTOS_SCHEMADB_ITER_BYTES.observe_with(
-            &[S::COLUMN_FAMILY_NAME],
-            (raw_key.len() + raw_value.len()) as f64,
-        );
+        let num_bytes = self.raw_key().len() + self.raw_value().len();
+        APTOS_SCHEMADB_ITER_BYTES.observe_with(&[S::COLUMN_FAMILY_NAME], num_bytes as f64);
+        self.io_counters.record_read(num_bytes);
+
+        Ok(true)
+    }
+
+    fn raw_key(&self) -> &[u8] {
+        self.db_iter.key().expect("db_iter.key() failed.")
+    }
+
+    fn raw_value(&self) -> &[u8] {
+        self.db_iter.value().expect("db_iter.value() failed.")
+    }
 
-        let key = <S::Key as KeyCodec<S>>::decode_key(raw_key);
-        let value = <S::Value as ValueCodec<S>>::decode_value(raw_value);
+    fn next_impl(&mut self) -> aptos_storage_interface::Result<Option<(S::Key, S::Value)>> {
+        let _timer = APTOS_SCHEMADB_ITER_LATENCY_SECONDS.timer_with(&[S::COLUMN_FAMILY_NAME]);
+
+        if !self.advance()? {
+            return Ok(None);
+        }
+        let key = <S::Key as KeyCodec<S>>::decode_key(self.raw_key());
+        let value = match self.cipher {
+            Some(cipher) => cipher
+                .decrypt(S::COLUMN_FAMILY_NAME, self.raw_key(), self.raw_value())
+                .and_then(|value| <S::Value as ValueCodec<S>>::decode_value(&value)),
+            None => <S::Value as ValueCodec<S>>::decode_value(self.raw_value()),
+        };
 
         Ok(Some((key?, value?)))
     }
+
+    /// Like `next()`, but yields the size of the encoded value instead of decoding it, for when
+    /// only the sizes are of interest. If there's a value cipher, it's the size of the plaintext,
+    /// which is known without decrypting.
+    pub fn next_with_value_size(
+        &mut self,
+    ) -> aptos_storage_interface::Result<Option<(S::Key, usize)>> {
+        let _timer = APTOS_SCHEMADB_ITER_LATENCY_SECONDS.timer_with(&[S::COLUMN_FAMILY_NAME]);
+
+        if !self.advance()? {
+            return Ok(None);
+        }
+        let key = <S::Key as KeyCodec<S>>::decode_key(self.raw_key())?;
+        let size = match self.cipher {
+            Some(cipher) => cipher.plaintext_len(S::COLUMN_FAMILY_NAME, self.raw_value())?,
+            None => self.raw_value().len(),
+        };
+
+        Ok(Some((key, size)))
+    }
+
+    /// Like `next()`, but yields the encoded value as it is in the iterator instead of decoding
+    /// it, for callers decoding only the parts they need, without allocating. The bytes are valid
+    /// until the iterator moves.
+    pub fn next_with_raw_value(
+        &mut self,
+    ) -> aptos_storage_interface::Result<Option<(S::Key, &[u8])>> {
+        let _timer = APTOS_SCHEMADB_ITER_LATENCY_SECONDS.timer_with(&[S::COLUMN_FAMILY_NAME]);
+
+        if !self.advance()? {
+            return Ok(None);
+        }
+        let key = <S::Key as KeyCodec<S>>::decode_key(self.raw_key())?;
+        let raw_value = self.db_iter.value().expect("db_iter.value() failed.");
+        let raw_value = match self.cipher {
+            Some(cipher) => {
+                self.value_buf = cipher.decrypt(
+                    S::COLUMN_FAMILY_NAME,
+                    self.db_iter.key().expect("db_iter.key() failed."),
+                    raw_value,
+                )?;
+                self.value_buf.as_slice()
+            },
+            None => raw_value,
+        };
+
+        Ok(Some((key, raw_value)))
+    }
 }
 
 impl<S> Iterator for SchemaIterator<'_, S>
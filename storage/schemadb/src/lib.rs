@@ -17,7 +17,10 @@ mod metrics;
 #[macro_use]
 pub mod schema;
 pub mod batch;
+pub mod checksum_verification;
+pub mod io_accounting;
 pub mod iterator;
+pub mod value_cipher;
 
 use crate::{
     metrics::{
@@ -32,14 +35,25 @@ use aptos_logger::prelude::*;
 use aptos_metrics_core::TimerHelper;
 use aptos_storage_interface::{AptosDbError, Result as DbResult};
 use batch::{IntoRawBatch, NativeBatch, WriteBatch};
+use io_accounting::IoCounters;
 use iterator::{ScanDirection, SchemaIterator};
 /// Type alias to `rocksdb::ReadOptions`. See [`rocksdb doc`](https://github.com/pingcap/rust-rocksdb/blob/master/src/rocksdb_options.rs)
 pub use rocksdb::{
     BlockBasedIndexType, BlockBasedOptions, Cache, ColumnFamilyDescriptor, DBCompressionType, Env,
-    Options, ReadOptions, SliceTransform, DEFAULT_COLUMN_FAMILY_NAME,
+    LiveFile, Options, ReadOptions, SliceTransform, WriteBufferManager, DEFAULT_COLUMN_FAMILY_NAME,
 };
-use rocksdb::{ErrorKind, WriteOptions};
-use std::{collections::HashSet, fmt::Debug, iter::Iterator, path::Path};
+use rocksdb::{ErrorKind, FlushOptions, WriteOptions};
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    iter::Iterator,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+};
+use value_cipher::ValueCipher;
 
 pub type ColumnFamilyName = &'static str;
 
@@ -56,6 +70,12 @@ enum OpenMode<'a> {
 pub struct DB {
     name: String, // for logging
     inner: rocksdb::DB,
+    /// Set by `set_value_cipher()`, with the column families it's for.
+    value_cipher: OnceLock<(Arc<dyn ValueCipher>, HashSet<ColumnFamilyName>)>,
+    /// See [`crate::io_accounting`].
+    io_counters: Arc<IoCounters>,
+    /// See `set_verify_checksums_on_every_read()`.
+    verify_checksums_on_every_read: AtomicBool,
 }
 
 impl DB {
@@ -87,8 +107,8 @@ impl DB {
     }
 
     /// Open db in readonly mode
-    /// Note that this still assumes there's only one process that opens the same DB.
-    /// See `open_as_secondary`
+    /// RocksDB doesn't lock the DB in this mode, so multiple processes can open the same DB as long
+    /// as none of them writes to it. To follow a live primary, see `open_as_secondary`.
     pub fn open_cf_readonly(
         opts: &Options,
         path: impl AsRef<Path>,
@@ -209,9 +229,48 @@ impl DB {
         DB {
             name: name.to_string(),
             inner,
+            value_cipher: OnceLock::new(),
+            io_counters: Arc::new(IoCounters::default()),
+            verify_checksums_on_every_read: AtomicBool::new(false),
         }
     }
 
+    /// Encrypts the values of the column families `cf_names` with `cipher` from now on, see
+    /// [`crate::value_cipher`]. Can only be set once, right after opening the DB.
+    pub fn set_value_cipher(
+        &self,
+        cipher: Arc<dyn ValueCipher>,
+        cf_names: &[ColumnFamilyName],
+    ) -> DbResult<()> {
+        self.value_cipher
+            .set((cipher, cf_names.iter().copied().collect()))
+            .map_err(|_| {
+                AptosDbError::Other(format!("Value cipher of DB {} already set.", self.name))
+            })
+    }
+
+    fn value_cipher(&self, cf_name: &str) -> Option<&dyn ValueCipher> {
+        self.value_cipher
+            .get()
+            .filter(|(_, cf_names)| cf_names.contains(cf_name))
+            .map(|(cipher, _)| cipher.as_ref())
+    }
+
+    /// Turns the verification of the block checksums on every read of this DB on or off, see
+    /// [`crate::checksum_verification`].
+    pub fn set_verify_checksums_on_every_read(&self, enabled: bool) {
+        self.verify_checksums_on_every_read
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn verify_checksums_on_every_read(&self) -> bool {
+        self.verify_checksums_on_every_read.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn io_counters(&self) -> &Arc<IoCounters> {
+        &self.io_counters
+    }
+
     /// Reads single record by key.
     pub fn get<S: Schema>(&self, schema_key: &S::Key) -> DbResult<Option<S::Value>> {
         let _timer = APTOS_SCHEMADB_GET_LATENCY_SECONDS.timer_with(&[S::COLUMN_FAMILY_NAME]);
@@ -219,14 +278,22 @@ impl DB {
         let k = <S::Key as KeyCodec<S>>::encode_key(schema_key)?;
         let cf_handle = self.get_cf_handle(S::COLUMN_FAMILY_NAME)?;
 
-        let result = self.inner.get_cf(cf_handle, k).into_db_res()?;
-        APTOS_SCHEMADB_GET_BYTES.observe_with(
-            &[S::COLUMN_FAMILY_NAME],
-            result.as_ref().map_or(0.0, |v| v.len() as f64),
-        );
+        let mut opts = ReadOptions::default();
+        checksum_verification::apply(&mut opts, self.verify_checksums_on_every_read());
+        let result = self.inner.get_cf_opt(cf_handle, &k, &opts).into_db_res()?;
+        let num_bytes = result.as_ref().map_or(0, |v| v.len());
+        APTOS_SCHEMADB_GET_BYTES.observe_with(&[S::COLUMN_FAMILY_NAME], num_bytes as f64);
+        self.io_counters.record_read(num_bytes);
 
         result
-            .map(|raw_value| <S::Value as ValueCodec<S>>::decode_value(&raw_value))
+            .map(|raw_value| match self.value_cipher(S::COLUMN_FAMILY_NAME) {
+                Some(cipher) => <S::Value as ValueCodec<S>>::decode_value(&cipher.decrypt(
+                    S::COLUMN_FAMILY_NAME,
+                    &k,
+                    &raw_value,
+                )?),
+                None => <S::Value as ValueCodec<S>>::decode_value(&raw_value),
+            })
             .transpose()
             .map_err(Into::into)
     }
@@ -253,13 +320,16 @@ impl DB {
 
     fn iter_with_direction<S: Schema>(
         &self,
-        opts: ReadOptions,
+        mut opts: ReadOptions,
         direction: ScanDirection,
     ) -> DbResult<SchemaIterator<'_, S>> {
+        checksum_verification::apply(&mut opts, self.verify_checksums_on_every_read());
         let cf_handle = self.get_cf_handle(S::COLUMN_FAMILY_NAME)?;
         Ok(SchemaIterator::new(
             self.inner.raw_iterator_cf_opt(cf_handle, opts),
             direction,
+            self.value_cipher(S::COLUMN_FAMILY_NAME),
+            &self.io_counters,
         ))
     }
 
@@ -299,6 +369,7 @@ impl DB {
 
         raw_batch.stats.commit();
         APTOS_SCHEMADB_BATCH_COMMIT_BYTES.observe_with(&[&self.name], serialized_size as f64);
+        self.io_counters.record_write(serialized_size);
 
         Ok(())
     }
@@ -337,6 +408,30 @@ impl DB {
             .into_db_res()
     }
 
+    /// Flushes the memtables of the given column families, blocking until it's done.
+    pub fn flush_cfs(&self, cf_names: &[ColumnFamilyName]) -> DbResult<()> {
+        let cf_handles = cf_names
+            .iter()
+            .map(|cf_name| self.get_cf_handle(cf_name))
+            .collect::<DbResult<Vec<_>>>()?;
+        self.inner
+            .flush_cfs_opt(&cf_handles, &FlushOptions::default())
+            .into_db_res()
+    }
+
+    /// Compacts the keys in `[begin, end]` of the column family, blocking until it's done.
+    pub fn compact_range_cf(&self, cf_name: &str, begin: &[u8], end: &[u8]) -> DbResult<()> {
+        self.inner
+            .compact_range_cf(self.get_cf_handle(cf_name)?, Some(begin), Some(end));
+        Ok(())
+    }
+
+    /// Returns the metadata of the live SST files of all the column families, e.g. their key
+    /// ranges and their numbers of entries and deletions.
+    pub fn live_files(&self) -> DbResult<Vec<LiveFile>> {
+        self.inner.live_files().into_db_res()
+    }
+
     pub fn get_property(&self, cf_name: &str, property_name: &str) -> DbResult<u64> {
         self.inner
             .property_int_value_cf(self.get_cf_handle(cf_name)?, property_name)
@@ -352,6 +447,108 @@ impl DB {
             })
     }
 
+    /// Gets a DB-wide integer property, as opposed to [`DB::get_property`] which reads a column
+    /// family specific one.
+    pub fn get_db_property(&self, property_name: &str) -> DbResult<u64> {
+        self.inner
+            .property_int_value(property_name)
+            .into_db_res()?
+            .ok_or_else(|| {
+                AptosDbError::Other(format!(
+                    "Unable to get property \"{}\" of DB \"{}\".",
+                    property_name, self.name,
+                ))
+            })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Writes records of schema `S` into a new SST file at `path`, which can later be ingested into
+    /// this DB with [`DB::ingest_sst_files`]. Records are sorted by their encoded keys before being
+    /// written, and there must be at least one record with no duplicated keys.
+    ///
+    /// `cf_opts` are the options the column family of `S` is opened with, for the file to be built
+    /// like the ones RocksDB writes itself, i.e. with the same filter, prefix extractor,
+    /// compression and block size.
+    pub fn write_sst_file<'a, S: Schema>(
+        &self,
+        path: &Path,
+        cf_opts: &Options,
+        records: impl IntoIterator<Item = (&'a S::Key, &'a S::Value)>,
+    ) -> DbResult<()>
+    where
+        S::Key: 'a,
+        S::Value: 'a,
+    {
+        let mut encoded = records
+            .into_iter()
+            .map(|(key, value)| {
+                let key = <S::Key as KeyCodec<S>>::encode_key(key)?;
+                let value = <S::Value as ValueCodec<S>>::encode_value(value)?;
+                let value = match self.value_cipher(S::COLUMN_FAMILY_NAME) {
+                    Some(cipher) => cipher.encrypt(S::COLUMN_FAMILY_NAME, &key, &value)?,
+                    None => value,
+                };
+                Ok((key, value))
+            })
+            .collect::<DbResult<Vec<_>>>()?;
+        encoded.sort_unstable_by(|(k1, _), (k2, _)| k1.cmp(k2));
+
+        let mut writer = rocksdb::SstFileWriter::create(cf_opts);
+        writer.open(path).into_db_res()?;
+        for (key, value) in encoded {
+            writer.put(key, value).into_db_res()?;
+        }
+        writer.finish().into_db_res()
+    }
+
+    /// Writes deletions of the keys of schema `S` into a new SST file at `path`, like
+    /// [`DB::write_sst_file`] does with records. Ingested, they delete the keys from the DB.
+    pub fn write_deletion_sst_file<'a, S: Schema>(
+        &self,
+        path: &Path,
+        cf_opts: &Options,
+        keys: impl IntoIterator<Item = &'a S::Key>,
+    ) -> DbResult<()>
+    where
+        S::Key: 'a,
+    {
+        let mut encoded = keys
+            .into_iter()
+            .map(|key| Ok(<S::Key as KeyCodec<S>>::encode_key(key)?))
+            .collect::<DbResult<Vec<_>>>()?;
+        encoded.sort_unstable();
+
+        let mut writer = rocksdb::SstFileWriter::create(cf_opts);
+        writer.open(path).into_db_res()?;
+        for key in encoded {
+            writer.delete(key).into_db_res()?;
+        }
+        writer.finish().into_db_res()
+    }
+
+    /// Ingests SST files created by [`DB::write_sst_file`] into the column family of schema `S`,
+    /// bypassing the memtable and the WAL. The files are moved into the DB instead of copied.
+    /// They're ingested atomically, and where they overlap, the later files take precedence.
+    pub fn ingest_sst_files<S: Schema>(&self, paths: Vec<PathBuf>) -> DbResult<()> {
+        let mut opts = rocksdb::IngestExternalFileOptions::default();
+        opts.set_move_files(true);
+        self.inner
+            .ingest_external_file_cf_opts(self.get_cf_handle(S::COLUMN_FAMILY_NAME)?, &opts, paths)
+            .into_db_res()
+    }
+
+    /// Same as [`DB::ingest_sst_files`], but the files are copied instead of moved, so that they
+    /// can be ingested again, e.g. when resuming an interrupted migration.
+    pub fn ingest_sst_file_copies<S: Schema>(&self, paths: Vec<PathBuf>) -> DbResult<()> {
+        let opts = rocksdb::IngestExternalFileOptions::default();
+        self.inner
+            .ingest_external_file_cf_opts(self.get_cf_handle(S::COLUMN_FAMILY_NAME)?, &opts, paths)
+            .into_db_res()
+    }
+
     /// Creates new physical DB checkpoint in directory specified by `path`.
     pub fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> DbResult<()> {
         rocksdb::checkpoint::Checkpoint::new(&self.inner)
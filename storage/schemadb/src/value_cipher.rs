@@ -0,0 +1,197 @@
+// Copyright (c) Aptos Foundation
+// Licensed pursuant to the Innovation-Enabling Source Code License, available at https://github.com/aptos-labs/aptos-core/blob/main/LICENSE
+
+//! Encryption at rest of the values of chosen column families, transparent to the users of the DB.
+//!
+//! Once a cipher is set on a [`crate::DB`] with [`crate::DB::set_value_cipher`], the encoded
+//! values of the column families it's set for are encrypted right before they're written, and
+//! decrypted right after they're read, so the [`crate::schema::ValueCodec`]s only ever see the
+//! plaintext. Keys are stored in the clear, since RocksDB orders the records by them, but each
+//! value is bound to its column family and key.
+//!
+//! The cipher isn't recorded in the DB: it has to be set every time the DB is opened, before
+//! anything is read from or written to these column families, with the keys the values were
+//! encrypted with. A value that isn't encrypted fails to decrypt, rather than being decoded as is.
+
+use aes_gcm::{
+    aead::{Aead, Nonce, OsRng, Payload},
+    AeadCore, Aes256Gcm, Key, KeyInit,
+};
+use anyhow::{anyhow, ensure, Result};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+};
+
+pub trait ValueCipher: Debug + Send + Sync {
+    /// Encrypts an encoded value before it's written under the encoded `key` in the column family
+    /// `cf_name`, binding it to both, so that it can't be moved elsewhere in the DB unnoticed.
+    fn encrypt(&self, cf_name: &str, key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decrypts what `encrypt()` returned for the same `cf_name` and `key`, failing if it was
+    /// tampered with, moved, or isn't encrypted at all.
+    fn decrypt(&self, cf_name: &str, key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>>;
+
+    /// Returns the length of the plaintext `ciphertext` decrypts to, without decrypting it.
+    fn plaintext_len(&self, cf_name: &str, ciphertext: &[u8]) -> Result<usize>;
+}
+
+/// AES-256-GCM, with a random nonce for each value and the column family and key of the value
+/// as associated data. Every value is prefixed with a header made of a format byte and the id of
+/// the key it's encrypted with:
+///
+/// ```text
+/// | format (1) | key id (4, big endian) | nonce (12) | ciphertext and tag |
+/// ```
+///
+/// The keys are expected to come from a KMS. Since the nonces are random, a key must be rotated
+/// well before 2^32 values are encrypted with it: the new key encrypts from then on, and the old
+/// one is kept with `with_retired_key()` to decrypt the values written before.
+pub struct Aes256GcmValueCipher {
+    key_id: u32,
+    ciphers: HashMap<u32, Aes256Gcm>,
+}
+
+impl Aes256GcmValueCipher {
+    const FORMAT: u8 = 1;
+    const HEADER_LEN: usize = 1 + 4 + Self::NONCE_LEN;
+    const NONCE_LEN: usize = 12;
+    const TAG_LEN: usize = 16;
+
+    /// Encrypts with `key`, recorded as `key_id` in the values.
+    pub fn new(key_id: u32, key: &[u8; 32]) -> Self {
+        Self {
+            key_id,
+            ciphers: HashMap::from([(key_id, Self::cipher(key))]),
+        }
+    }
+
+    /// Also decrypts the values encrypted with a key rotated out, never encrypting with it.
+    pub fn with_retired_key(mut self, key_id: u32, key: &[u8; 32]) -> Self {
+        assert!(
+            self.ciphers.insert(key_id, Self::cipher(key)).is_none(),
+            "Key id {key_id} used twice."
+        );
+        self
+    }
+
+    fn cipher(key: &[u8; 32]) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+    }
+
+    fn ensure_encrypted(cf_name: &str, ciphertext: &[u8]) -> Result<()> {
+        ensure!(
+            ciphertext.len() >= Self::HEADER_LEN + Self::TAG_LEN && ciphertext[0] == Self::FORMAT,
+            "Value in {cf_name} not encrypted, plaintext and encrypted values mixed?",
+        );
+        Ok(())
+    }
+
+    /// The column family names have no NUL, so the separator keeps the pairs apart.
+    fn associated_data(cf_name: &str, key: &[u8]) -> Vec<u8> {
+        [cf_name.as_bytes(), &[0], key].concat()
+    }
+}
+
+impl Debug for Aes256GcmValueCipher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Leaves the keys out.
+        write!(f, "Aes256GcmValueCipher {{ key_id: {} }}", self.key_id)
+    }
+}
+
+impl ValueCipher for Aes256GcmValueCipher {
+    fn encrypt(&self, cf_name: &str, key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.ciphers[&self.key_id]
+            .encrypt(&nonce, Payload {
+                msg: plaintext,
+                aad: &Self::associated_data(cf_name, key),
+            })
+            .map_err(|e| anyhow!("Failed to encrypt value: {e}"))?;
+        Ok([
+            &[Self::FORMAT][..],
+            &self.key_id.to_be_bytes(),
+            nonce.as_slice(),
+            &ciphertext,
+        ]
+        .concat())
+    }
+
+    fn decrypt(&self, cf_name: &str, key: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        Self::ensure_encrypted(cf_name, ciphertext)?;
+        let (header, ciphertext) = ciphertext.split_at(Self::HEADER_LEN);
+        let key_id = u32::from_be_bytes(header[1..5].try_into().expect("4 bytes."));
+        let cipher = self
+            .ciphers
+            .get(&key_id)
+            .ok_or_else(|| anyhow!("Value in {cf_name} encrypted with unknown key id {key_id}."))?;
+        cipher
+            .decrypt(Nonce::<Aes256Gcm>::from_slice(&header[5..]), Payload {
+                msg: ciphertext,
+                aad: &Self::associated_data(cf_name, key),
+            })
+            .map_err(|e| anyhow!("Failed to decrypt value, wrong key or corrupted: {e}"))
+    }
+
+    fn plaintext_len(&self, cf_name: &str, ciphertext: &[u8]) -> Result<usize> {
+        Self::ensure_encrypted(cf_name, ciphertext)?;
+        Ok(ciphertext.len() - Self::HEADER_LEN - Self::TAG_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aes_256_gcm_value_cipher() {
+        let cipher = Aes256GcmValueCipher::new(1, &[1; 32]);
+        for plaintext in [&b""[..], b"value"] {
+            let ciphertext = cipher.encrypt("cf", b"key", plaintext).unwrap();
+            assert_eq!(
+                cipher.decrypt("cf", b"key", &ciphertext).unwrap(),
+                plaintext
+            );
+            assert_eq!(
+                cipher.plaintext_len("cf", &ciphertext).unwrap(),
+                plaintext.len()
+            );
+            // Fresh nonce each time.
+            assert_ne!(cipher.encrypt("cf", b"key", plaintext).unwrap(), ciphertext);
+
+            let mut tampered = ciphertext.clone();
+            *tampered.last_mut().unwrap() ^= 1;
+            assert!(cipher.decrypt("cf", b"key", &tampered).is_err());
+            // Moved to another key or column family.
+            assert!(cipher.decrypt("cf", b"key2", &ciphertext).is_err());
+            assert!(cipher.decrypt("cf2", b"key", &ciphertext).is_err());
+            assert!(Aes256GcmValueCipher::new(1, &[2; 32])
+                .decrypt("cf", b"key", &ciphertext)
+                .is_err());
+            // Plaintext.
+            assert!(cipher.decrypt("cf", b"key", plaintext).is_err());
+        }
+        assert!(cipher.decrypt("cf", b"key", &[0; 4]).is_err());
+        assert!(cipher.plaintext_len("cf", &[0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_aes_256_gcm_value_cipher_key_rotation() {
+        let old_cipher = Aes256GcmValueCipher::new(1, &[1; 32]);
+        let old_ciphertext = old_cipher.encrypt("cf", b"key", b"old").unwrap();
+
+        let cipher = Aes256GcmValueCipher::new(2, &[2; 32]).with_retired_key(1, &[1; 32]);
+        assert_eq!(
+            cipher.decrypt("cf", b"key", &old_ciphertext).unwrap(),
+            b"old"
+        );
+        let ciphertext = cipher.encrypt("cf", b"key", b"new").unwrap();
+        assert_eq!(cipher.decrypt("cf", b"key", &ciphertext).unwrap(), b"new");
+        // Encrypted with the new key only.
+        assert!(old_cipher.decrypt("cf", b"key", &ciphertext).is_err());
+        assert!(Aes256GcmValueCipher::new(2, &[2; 32])
+            .decrypt("cf", b"key", &old_ciphertext)
+            .is_err());
+    }
+}
@@ -5,12 +5,15 @@ use anyhow::Result;
 use aptos_schemadb::{
     batch::SchemaBatch,
     define_schema,
+    io_accounting::{IoAccountingHandle, IoAccountingReport},
     schema::{KeyCodec, Schema, ValueCodec},
+    value_cipher::Aes256GcmValueCipher,
     ColumnFamilyName, DB,
 };
 use aptos_storage_interface::AptosDbError;
 use byteorder::{LittleEndian, ReadBytesExt};
 use rocksdb::{ColumnFamilyDescriptor, DEFAULT_COLUMN_FAMILY_NAME};
+use std::sync::Arc;
 
 // Creating two schemas that share exactly the same structure but are stored in different column
 // families. Also note that the key and value are of the same type `TestField`. By implementing
@@ -390,6 +393,227 @@ fn test_checkpoint() {
     }
 }
 
+#[test]
+fn test_ingest_sst_files() {
+    let db = TestDB::new();
+    let sst_dir = aptos_temppath::TempPath::new();
+    sst_dir.create_as_dir().unwrap();
+    let sst_path = sst_dir.path().join("test.sst");
+
+    // Records don't need to be passed in in order.
+    let records = [(TestField(2), TestField(2)), (TestField(1), TestField(1))];
+    db.write_sst_file::<TestSchema1>(
+        &sst_path,
+        &rocksdb::Options::default(),
+        records.iter().map(|(k, v)| (k, v)),
+    )
+    .unwrap();
+    db.ingest_sst_files::<TestSchema1>(vec![sst_path.clone()])
+        .unwrap();
+
+    assert_eq!(
+        db.get::<TestSchema1>(&TestField(1)).unwrap(),
+        Some(TestField(1)),
+    );
+    assert_eq!(
+        db.get::<TestSchema1>(&TestField(2)).unwrap(),
+        Some(TestField(2)),
+    );
+    assert_eq!(db.get::<TestSchema2>(&TestField(1)).unwrap(), None);
+    // The file is moved into the DB.
+    assert!(!sst_path.exists());
+}
+
+#[test]
+fn test_ingest_sst_file_copies() {
+    let db = TestDB::new();
+    let sst_dir = aptos_temppath::TempPath::new();
+    sst_dir.create_as_dir().unwrap();
+    let sst_path = sst_dir.path().join("test.sst");
+    db.write_sst_file::<TestSchema1>(&sst_path, &rocksdb::Options::default(), [(
+        &TestField(1),
+        &TestField(1),
+    )])
+    .unwrap();
+
+    db.ingest_sst_file_copies::<TestSchema1>(vec![sst_path.clone()])
+        .unwrap();
+    assert!(sst_path.exists());
+    db.delete::<TestSchema1>(&TestField(1)).unwrap();
+    // The same file can be ingested again.
+    db.ingest_sst_file_copies::<TestSchema1>(vec![sst_path.clone()])
+        .unwrap();
+    assert_eq!(
+        db.get::<TestSchema1>(&TestField(1)).unwrap(),
+        Some(TestField(1)),
+    );
+}
+
+#[test]
+fn test_write_sst_file_with_cf_opts() {
+    let mut cf_opts = rocksdb::Options::default();
+    let mut table_options = rocksdb::BlockBasedOptions::default();
+    table_options.set_bloom_filter(10.0, /* block_based = */ false);
+    cf_opts.set_block_based_table_factory(&table_options);
+    cf_opts.set_prefix_extractor(rocksdb::SliceTransform::create(
+        "test_prefix_extractor",
+        |key| &key[..2],
+        None,
+    ));
+    let cfds = get_column_families()
+        .iter()
+        .map(|cf_name| ColumnFamilyDescriptor::new(*cf_name, cf_opts.clone()))
+        .collect();
+    let tmpdir = aptos_temppath::TempPath::new();
+    let mut db_opts = rocksdb::Options::default();
+    db_opts.create_if_missing(true);
+    db_opts.create_missing_column_families(true);
+    let db = DB::open_cf(&db_opts, tmpdir.path(), "test", cfds).unwrap();
+
+    let sst_dir = aptos_temppath::TempPath::new();
+    sst_dir.create_as_dir().unwrap();
+    let sst_path = sst_dir.path().join("test.sst");
+    db.write_sst_file::<TestSchema1>(&sst_path, &cf_opts, [(&TestField(1), &TestField(1))])
+        .unwrap();
+    db.ingest_sst_files::<TestSchema1>(vec![sst_path]).unwrap();
+
+    // Nothing was flushed, so the ingested file is the only SST file of the DB.
+    let ingested = std::fs::read_dir(tmpdir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sst"))
+        .collect::<Vec<_>>();
+    assert_eq!(ingested.len(), 1);
+    // The table properties block is stored uncompressed, with the names of the filter policy and
+    // of the prefix extractor the file was built with, which are empty and "nullptr" with the
+    // default options.
+    let file = std::fs::read(&ingested[0]).unwrap();
+    let contains = |needle: &[u8]| file.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(b"rocksdb.BuiltinBloomFilter"));
+    assert!(contains(b"test_prefix_extractor"));
+}
+
+#[test]
+fn test_io_accounting() {
+    let db = TestDB::new();
+    let other_db = TestDB::new();
+    let handle = IoAccountingHandle::begin([&*db, &*db]);
+    let other_handle = IoAccountingHandle::begin([&*other_db]);
+
+    db.put::<TestSchema1>(&TestField(0), &TestField(0)).unwrap();
+    db.get::<TestSchema1>(&TestField(0)).unwrap();
+
+    let report = handle.report();
+    assert!(report.bytes_written > 0);
+    // The key and the value, counted once even though the DB was listed twice.
+    assert_eq!(report.bytes_read, 4);
+    // IO on another DB isn't attributed to the handle.
+    assert_eq!(other_handle.report(), IoAccountingReport::default());
+    drop(other_handle);
+    other_db
+        .put::<TestSchema1>(&TestField(0), &TestField(0))
+        .unwrap();
+    assert_eq!(handle.report(), report);
+}
+
+#[test]
+fn test_verify_checksums_on_every_read() {
+    let db = TestDB::new();
+    db.put::<TestSchema1>(&TestField(0), &TestField(0)).unwrap();
+    db.put::<TestSchema1>(&TestField(1), &TestField(1)).unwrap();
+    db.flush_cf("TestCF1").unwrap();
+
+    db.set_verify_checksums_on_every_read(true);
+    assert!(db.verify_checksums_on_every_read());
+    // Only for the DB it's turned on for.
+    assert!(!TestDB::new().verify_checksums_on_every_read());
+    assert_eq!(
+        db.get::<TestSchema1>(&TestField(1)).unwrap(),
+        Some(TestField(1))
+    );
+    assert_eq!(
+        collect_values::<TestSchema1>(&db),
+        gen_expected_values(&[(0, 0), (1, 1)])
+    );
+}
+
+#[test]
+fn test_value_cipher() {
+    let tmpdir = aptos_temppath::TempPath::new();
+    let cipher = Arc::new(Aes256GcmValueCipher::new(1, &[1; 32]));
+    {
+        let db = open_db(&tmpdir);
+        db.set_value_cipher(cipher.clone(), &[TestSchema1::COLUMN_FAMILY_NAME])
+            .unwrap();
+        assert!(db
+            .set_value_cipher(cipher.clone(), &[TestSchema1::COLUMN_FAMILY_NAME])
+            .is_err());
+
+        db.put::<TestSchema1>(&TestField(0), &TestField(0)).unwrap();
+        let mut batch = SchemaBatch::new();
+        batch
+            .put::<TestSchema1>(&TestField(1), &TestField(1))
+            .unwrap();
+        batch
+            .put::<TestSchema2>(&TestField(1), &TestField(1))
+            .unwrap();
+        db.write_schemas(batch).unwrap();
+
+        assert_eq!(
+            db.get::<TestSchema1>(&TestField(1)).unwrap(),
+            Some(TestField(1)),
+        );
+        let mut iter = db.iter::<TestSchema1>().unwrap();
+        iter.seek_to_first();
+        assert_eq!(
+            iter.collect::<Result<Vec<_>, AptosDbError>>().unwrap(),
+            gen_expected_values(&[(0, 0), (1, 1)]),
+        );
+        let mut iter = db.iter::<TestSchema1>().unwrap();
+        iter.seek_to_first();
+        let (key, raw_value) = iter.next_with_raw_value().unwrap().unwrap();
+        assert_eq!((key, raw_value), (TestField(0), &0u32.to_le_bytes()[..]));
+        // The size of the plaintext.
+        assert_eq!(
+            iter.next_with_value_size().unwrap(),
+            Some((TestField(1), 4))
+        );
+    }
+    {
+        // Only the values of the column families the cipher is set for are encrypted on disk.
+        let db = open_db(&tmpdir);
+        let mut iter = db.iter::<TestSchema1>().unwrap();
+        iter.seek_to_first();
+        let (key, size) = iter.next_with_value_size().unwrap().unwrap();
+        assert_eq!(key, TestField(0));
+        assert!(size > 4);
+        assert_eq!(
+            db.get::<TestSchema2>(&TestField(1)).unwrap(),
+            Some(TestField(1)),
+        );
+        // Written in plaintext.
+        db.put::<TestSchema1>(&TestField(2), &TestField(2)).unwrap();
+    }
+    {
+        let db = open_db(&tmpdir);
+        db.set_value_cipher(cipher.clone(), &[TestSchema1::COLUMN_FAMILY_NAME])
+            .unwrap();
+        assert_eq!(
+            db.get::<TestSchema1>(&TestField(1)).unwrap(),
+            Some(TestField(1)),
+        );
+        assert!(db.get::<TestSchema1>(&TestField(2)).is_err());
+    }
+    {
+        let db = open_db(&tmpdir);
+        db.set_value_cipher(Arc::new(Aes256GcmValueCipher::new(1, &[2; 32])), &[
+            TestSchema1::COLUMN_FAMILY_NAME,
+        ])
+        .unwrap();
+        assert!(db.get::<TestSchema1>(&TestField(1)).is_err());
+    }
+}
+
 #[test]
 fn test_unrecognised_column_family() {
     let tmpdir = aptos_temppath::TempPath::new();
@@ -184,6 +184,23 @@ fn test_seek_to_last() {
     ]);
 }
 
+#[test]
+fn test_next_with_value_size() {
+    let db = TestDB::new();
+
+    let mut iter = db.iter();
+    iter.seek(&KeyPrefix1(2)).unwrap();
+    assert_eq!(
+        iter.next_with_value_size().unwrap(),
+        Some((TestKey(2, 0, 0), 4))
+    );
+    assert_eq!(
+        iter.next().transpose().unwrap(),
+        Some((TestKey(2, 0, 2), TestValue(202)))
+    );
+    assert_eq!(iter.next_with_value_size().unwrap(), None);
+}
+
 #[test]
 fn test_seek_by_existing_key() {
     let db = TestDB::new();
@@ -34,6 +34,11 @@ pub enum AptosDbError {
     ParseIntError(String),
     #[error("Hot state not configured properly")]
     HotStateError,
+    /// The DB is opened with a shard config other than the one it was created with.
+    #[error(
+        "Shard config mismatch, the DB was created with {stored}, but is opened with {opening}."
+    )]
+    ShardConfigMismatch { stored: String, opening: String },
 }
 
 impl From<anyhow::Error> for AptosDbError {
@@ -26,12 +26,13 @@ use aptos_types::{
     transaction::{
         AccountOrderedTransactionsWithProof, IndexedTransactionSummary, PersistedAuxiliaryInfo,
         Transaction, TransactionAuxiliaryData, TransactionInfo, TransactionListWithProofV2,
-        TransactionOutputListWithProofV2, TransactionToCommit, TransactionWithProof, Version,
+        TransactionOutput, TransactionOutputListWithProofV2, TransactionToCommit,
+        TransactionWithProof, Version,
     },
     write_set::WriteSet,
 };
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 use thiserror::Error;
 
 pub mod block_info;
@@ -165,6 +166,10 @@ pub trait DbReader: Send + Sync {
             version: Version,
         ) -> Result<Option<TransactionAuxiliaryData>>;
 
+        /// Returns the output (write set, events, gas used, status and auxiliary data) of the
+        /// transaction at `version`.
+        fn get_transaction_output(&self, version: Version) -> Result<TransactionOutput>;
+
         fn get_persisted_auxiliary_info_by_version(
             &self,
             version: Version,
@@ -232,6 +237,14 @@ pub trait DbReader: Send + Sync {
             limit: u64,
         ) -> Result<Box<dyn Iterator<Item = Result<Vec<ContractEvent>>> + '_>>;
 
+        /// Returns the events emitted by the transactions in `[start_version, end_version)`,
+        /// grouped by version. Versions whose transaction emitted no event are not in the map.
+        fn get_events_grouped_by_version(
+            &self,
+            start_version: Version,
+            end_version: Version,
+        ) -> Result<BTreeMap<Version, Vec<ContractEvent>>>;
+
         fn get_write_set_iterator(
             &self,
             start_version: Version,
@@ -290,6 +303,13 @@ pub trait DbReader: Send + Sync {
             version: Version,
         ) -> Result<Box<dyn Iterator<Item = Result<(StateKey, StateValue)>> + '_>>;
 
+        /// Returns all the resources and modules under an account at desired version.
+        fn get_account_state(
+            &self,
+            address: AccountAddress,
+            version: Version,
+        ) -> Result<BTreeMap<StateKey, StateValue>>;
+
         /// Returns the latest ledger info, if any.
         fn get_latest_ledger_info_option(&self) -> Result<Option<LedgerInfoWithSignatures>>;
 
@@ -94,6 +94,22 @@ impl StateKey {
         Ok(myself)
     }
 
+    /// Recovers from a raw key of the state value column family in physical storage, i.e. the
+    /// serialized state key followed by the version, e.g. one from scanning RocksDB directly while
+    /// debugging. The raw keys of the sharded layout hold the hash of the state key instead, which
+    /// can't be decoded.
+    pub fn decode_stored(val: &[u8]) -> Result<StateKey, StateKeyDecodeErr> {
+        const VERSION_SIZE: usize = std::mem::size_of::<u64>();
+
+        let state_key_len = val.len().checked_sub(VERSION_SIZE).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Stored key of {} bytes is too short to hold a version.",
+                val.len()
+            )
+        })?;
+        Self::decode(&val[..state_key_len])
+    }
+
     pub fn crypto_hash_ref(&self) -> &HashValue {
         &self.0.hash_value
     }
@@ -89,6 +89,18 @@ fn test_debug() {
     assert_eq!(&format!("{:?}", key), "StateKey::Raw(010203)",);
 }
 
+#[test]
+fn test_decode_stored() {
+    let key = StateKey::table_item(&"0x123".parse().unwrap(), &[1]);
+    let mut stored = key.encoded().to_vec();
+    stored.extend_from_slice(&(!7u64).to_be_bytes());
+    assert_eq!(StateKey::decode_stored(&stored).unwrap(), key);
+
+    assert!(StateKey::decode_stored(&[1, 2, 3]).is_err());
+    // Just the version.
+    assert!(StateKey::decode_stored(&[0; 8]).is_err());
+}
+
 proptest! {
     #[test]
     fn test_shard_order(